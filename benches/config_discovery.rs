@@ -0,0 +1,43 @@
+use assert_fs::TempDir;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs::{create_dir_all, File};
+use std::path::PathBuf;
+use yamis::config_files::ConfigFilePaths;
+
+/// Depth of the directory tree to search config files in
+const DEPTH: usize = 30;
+
+/// Creates a deep directory tree with a few config files, returning the deepest directory
+fn create_tree(tmp_dir: &TempDir) -> PathBuf {
+    let mut dir = tmp_dir.to_path_buf();
+    File::create(dir.join("project.yamis.yml")).unwrap();
+    for i in 0..DEPTH {
+        dir = dir.join(format!("dir{}", i));
+        create_dir_all(&dir).unwrap();
+        // Some unrelated files, which are usually present in a project
+        for j in 0..20 {
+            File::create(dir.join(format!("file{}.txt", j))).unwrap();
+        }
+        if i % 10 == 0 {
+            File::create(dir.join("yamis.toml")).unwrap();
+        }
+    }
+    File::create(dir.join("local.yamis.yaml")).unwrap();
+    dir
+}
+
+fn bench_config_file_paths(c: &mut Criterion) {
+    let tmp_dir = TempDir::new().unwrap();
+    let deepest_dir = create_tree(&tmp_dir);
+
+    c.bench_function("config file discovery", |b| {
+        b.iter(|| {
+            ConfigFilePaths::new(&deepest_dir)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_config_file_paths);
+criterion_main!(benches);
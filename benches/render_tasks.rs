@@ -0,0 +1,53 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use yamis::testing::{config_file_from_str, render_task};
+
+/// Number of tasks run by the serial task
+const TASKS: usize = 100;
+
+/// Returns the contents of a config file with a serial task that runs many script tasks
+///
+/// # Arguments
+///
+/// * `script`: Script of the tasks, followed by the number of the task
+///
+/// returns: String
+fn create_config_file_contents(script: &str) -> String {
+    let mut contents = String::from("env:\n");
+    for i in 0..20 {
+        contents.push_str(&format!("  VAR_{}: value {}\n", i, i));
+    }
+    contents.push_str("vars:\n");
+    for i in 0..20 {
+        contents.push_str(&format!("  var_{}: value {}\n", i, i));
+    }
+    contents.push_str("tasks:\n  all:\n    serial:\n");
+    for i in 0..TASKS {
+        contents.push_str(&format!("      - cmd_{}\n", i));
+    }
+    for i in 0..TASKS {
+        contents.push_str(&format!(
+            "  cmd_{}:\n    private: true\n    env:\n      TASK_VAR: {}\n    script: {} {}\n",
+            i, i, script, i
+        ));
+    }
+    contents
+}
+
+fn bench_render_serial_task(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render serial task");
+    for (name, script) in [
+        ("tags", "echo {$VAR_1} {vars.var_2} {name} {$@}"),
+        ("plain", "echo hello world"),
+    ] {
+        let config_file =
+            config_file_from_str(&create_config_file_contents(script), "project.yamis.yml")
+                .unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| render_task(&config_file, "all", &["--name", "world", "extra"]).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_render_serial_task);
+criterion_main!(benches);
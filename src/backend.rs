@@ -0,0 +1,808 @@
+use crate::highlight::highlight_script;
+use crate::hints::{Hints, OutputTail};
+use crate::print_utils::YamisOutput;
+use crate::runs::RunTracker;
+use crate::tasks::{RenderedCommand, TaskError};
+use crate::types::DynErrResult;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Executes the commands rendered from tasks
+pub trait ExecutionBackend {
+    /// Executes the given command, returning an error if it fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `command`: Command rendered from a task
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    fn execute(&self, command: &RenderedCommand) -> DynErrResult<()>;
+
+    /// Returns whether the commands are only displayed or stored instead of being run
+    fn is_dry_run(&self) -> bool {
+        false
+    }
+
+    /// Returns the exit code of the last command executed, if it ran and exited with one
+    fn last_exit_code(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// Time the lines of the output of the commands are prefixed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampKind {
+    /// Time since the backend was created, i.e. `[   1.234s]`
+    Elapsed,
+    /// Wall-clock time in UTC, i.e. `[14:03:12.345]`
+    Wall,
+}
+
+impl TimestampKind {
+    /// Names of the kinds, as given in the command line
+    pub const NAMES: [&'static str; 2] = ["elapsed", "wall"];
+}
+
+impl FromStr for TimestampKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "elapsed" => Ok(TimestampKind::Elapsed),
+            "wall" => Ok(TimestampKind::Wall),
+            _ => Err(format!(
+                "Unknown timestamp kind `{}`, expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Prefixes the lines of the output of the commands with timestamps
+#[derive(Debug, Clone, Copy)]
+struct Timestamper {
+    /// Time the lines are prefixed with
+    kind: TimestampKind,
+    /// When the backend was created, to compute the elapsed time
+    started: Instant,
+}
+
+impl Timestamper {
+    /// Returns the timestamp of a line printed now
+    fn timestamp(&self) -> String {
+        match self.kind {
+            TimestampKind::Elapsed => format_elapsed(self.started.elapsed()),
+            TimestampKind::Wall => format_wall_clock(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+/// Receives the lines of the output of the commands, instead of them being written to the
+/// standard output and error. Useful to stream the output somewhere else, i.e. to the clients
+/// of the daemon.
+pub(crate) trait OutputSink: fmt::Debug + Send + Sync {
+    /// Called with each line of the output of a command, without the line break
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Content of the line
+    /// * `is_stderr`: Whether the line was written to the standard error
+    fn write_line(&self, line: &str, is_stderr: bool);
+
+    /// Called with the pid of the process when it is spawned, and with None when it exits
+    fn set_pid(&self, _pid: Option<u32>) {}
+}
+
+/// Calls `on_line` with each line of the reader, always ending with a line break, and keeps the
+/// last ones in the tail, if any. Lines are passed as they are, even if they are not valid UTF-8.
+///
+/// # Arguments
+///
+/// * `reader`: Output of a command
+/// * `tail`: Keeps the last lines, to find hints if the command fails
+/// * `on_line`: Called with each line
+fn read_lines<R: Read, F: FnMut(&[u8])>(reader: R, tail: Option<&OutputTail>, mut on_line: F) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        if let Some(tail) = tail {
+            tail.push(&line);
+        }
+        on_line(&line);
+    }
+}
+
+/// Copies the lines of the reader to the writer, each one indented and prefixed with the time
+/// it was read if a timestamper is given, and keeps the last ones in the tail, if any. Lines are
+/// written as they are, even if they are not valid UTF-8.
+///
+/// # Arguments
+///
+/// * `reader`: Output of a command
+/// * `writer`: Where the lines are written to
+/// * `indent`: Indentation of the lines, i.e. in nested yamis invocations
+/// * `timestamper`: Prefixes the lines with timestamps, if given
+/// * `tail`: Keeps the last lines, to find hints if the command fails
+fn forward_lines<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    indent: &str,
+    timestamper: Option<&Timestamper>,
+    tail: Option<&OutputTail>,
+) {
+    read_lines(reader, tail, |line| {
+        let prefix = match timestamper {
+            Some(timestamper) => format!("{}{} ", indent, timestamper.timestamp()),
+            None => String::from(indent),
+        };
+        // The output is lost anyway if it cannot be written
+        let _ = writer
+            .write_all(prefix.as_bytes())
+            .and_then(|_| writer.write_all(line))
+            .and_then(|_| writer.flush());
+    });
+}
+
+/// Sends the lines of the reader to the sink, and keeps the last ones in the tail, if any.
+/// Invalid UTF-8 is replaced.
+///
+/// # Arguments
+///
+/// * `reader`: Output of a command
+/// * `sink`: Where the lines are sent to
+/// * `is_stderr`: Whether the reader is the standard error of the command
+/// * `tail`: Keeps the last lines, to find hints if the command fails
+fn sink_lines<R: Read>(
+    reader: R,
+    sink: &dyn OutputSink,
+    is_stderr: bool,
+    tail: Option<&OutputTail>,
+) {
+    read_lines(reader, tail, |line| {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches('\n');
+        sink.write_line(line.strip_suffix('\r').unwrap_or(line), is_stderr);
+    });
+}
+
+/// Formats the elapsed time as seconds with milliseconds, i.e. `[   1.234s]`
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("[{:>4}.{:03}s]", elapsed.as_secs(), elapsed.subsec_millis())
+}
+
+/// Formats the time since the UNIX epoch as the UTC time of the day, i.e. `[14:03:12.345]`
+fn format_wall_clock(since_epoch: Duration) -> String {
+    let secs = since_epoch.as_secs() % (24 * 60 * 60);
+    format!(
+        "[{:02}:{:02}:{:02}.{:03}]",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Backend that spawns the commands as processes and waits for them to finish
+#[derive(Debug, Default)]
+pub struct ProcessBackend {
+    /// Tracks the spawned processes, so that they can be stopped with `yamis kill`
+    run: Option<RefCell<RunTracker>>,
+    /// Environment the commands run with instead of the current one, if any
+    base_env: Option<BTreeMap<String, String>>,
+    /// Prefixes the lines of the output with timestamps, if set
+    timestamper: Option<Timestamper>,
+    /// Indentation of the lines of the output, i.e. in nested yamis invocations
+    indent: String,
+    /// Hints printed below the error when a command fails, if set
+    hints: Option<Hints>,
+    /// Receives the output of the commands instead of the standard output and error, if set
+    output: Option<Arc<dyn OutputSink>>,
+    /// Exit code of the last command executed
+    last_exit_code: Cell<Option<i32>>,
+}
+
+impl ProcessBackend {
+    /// Returns a new backend that does not track the spawned processes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a new backend that records the pid of each spawned process in the given tracker
+    pub fn tracked(run: RunTracker) -> Self {
+        ProcessBackend {
+            run: Some(RefCell::new(run)),
+            ..Self::default()
+        }
+    }
+
+    /// Returns a new backend that runs the commands with the given environment, instead of
+    /// inheriting the current one. The environment variables of each command still take
+    /// precedence.
+    pub fn with_env(base_env: BTreeMap<String, String>) -> Self {
+        ProcessBackend {
+            base_env: Some(base_env),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the same backend, but prefixing each line of the output of the commands with
+    /// the given kind of timestamp. The output of the commands is piped to do so, so programs
+    /// that behave differently when writing to a terminal, i.e. disabling colors, will do so.
+    pub fn with_timestamps(self, kind: TimestampKind) -> Self {
+        ProcessBackend {
+            timestamper: Some(Timestamper {
+                kind,
+                started: Instant::now(),
+            }),
+            ..self
+        }
+    }
+
+    /// Returns the same backend, but indenting each line of the output of the commands with the
+    /// given string. The output of the commands is piped to do so, like with
+    /// [ProcessBackend::with_timestamps].
+    pub(crate) fn with_indent(self, indent: String) -> Self {
+        ProcessBackend { indent, ..self }
+    }
+
+    /// Returns the same backend, but adding a hint below the error when a command fails and
+    /// the last lines of its output match one of the given hints. The output of the commands
+    /// is piped to do so, like with [ProcessBackend::with_timestamps].
+    pub(crate) fn with_hints(self, hints: Hints) -> Self {
+        ProcessBackend {
+            hints: Some(hints),
+            ..self
+        }
+    }
+
+    /// Returns the same backend, but sending the output of the commands to the given sink. The
+    /// commands do not read from the standard input, as they are not run from a terminal.
+    pub(crate) fn with_output(self, output: Arc<dyn OutputSink>) -> Self {
+        ProcessBackend {
+            output: Some(output),
+            ..self
+        }
+    }
+
+    /// Returns whether the output of the commands is piped through yamis
+    fn pipes_output(&self) -> bool {
+        self.timestamper.is_some()
+            || self.hints.is_some()
+            || !self.indent.is_empty()
+            || self.output.is_some()
+    }
+
+    /// Adds the hint that matches the output of the failed command, or the error it raised, to
+    /// the reason of the error. Other errors are returned as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `error`: Error raised by the command
+    /// * `output`: Last lines of the output of the command
+    ///
+    /// returns: Box<dyn Error, Global>
+    fn add_hint(
+        &self,
+        error: Box<dyn std::error::Error>,
+        output: &str,
+    ) -> Box<dyn std::error::Error> {
+        let Some(hints) = &self.hints else {
+            return error;
+        };
+        match error.downcast_ref::<TaskError>() {
+            Some(TaskError::RuntimeError(name, reason)) => {
+                match hints.find(output).or_else(|| hints.find(reason)) {
+                    Some(hint) => {
+                        TaskError::RuntimeError(name.clone(), format!("{}\nHint: {}", reason, hint))
+                            .into()
+                    }
+                    None => error,
+                }
+            }
+            _ => error,
+        }
+    }
+
+    /// Sets the config file of the command in the tracker, if any, so that other runs know
+    /// which task is running. Errors are ignored, as tracking is not essential to run the task.
+    fn set_tracked_config_file(&self, config_file: &Path) {
+        if let Some(run) = &self.run {
+            let _ = run.borrow_mut().set_config_file(config_file);
+        }
+    }
+
+    /// Sets the pid of the running process in the tracker and the output sink, if any. Errors
+    /// are ignored, as tracking is not essential to run the task.
+    fn set_tracked_pid(&self, pid: Option<u32>) {
+        if let Some(run) = &self.run {
+            let _ = run.borrow_mut().set_pid(pid);
+        }
+        if let Some(output) = &self.output {
+            output.set_pid(pid);
+        }
+    }
+}
+
+impl ExecutionBackend for ProcessBackend {
+    fn execute(&self, rendered_command: &RenderedCommand) -> DynErrResult<()> {
+        let task_name = rendered_command.task_name();
+        self.set_tracked_config_file(rendered_command.config_file_path());
+        self.last_exit_code.set(None);
+        if let Some(builtin) = rendered_command.builtin() {
+            let result = if let Some(sink) = &self.output {
+                let mut output = Vec::new();
+                let result = builtin.run(rendered_command.args(), &mut output);
+                sink_lines(output.as_slice(), sink.as_ref(), false, None);
+                result
+            } else if self.timestamper.is_some() || !self.indent.is_empty() {
+                let mut output = Vec::new();
+                let result = builtin.run(rendered_command.args(), &mut output);
+                forward_lines(
+                    output.as_slice(),
+                    std::io::stdout().lock(),
+                    &self.indent,
+                    self.timestamper.as_ref(),
+                    None,
+                );
+                result
+            } else {
+                builtin.run(rendered_command.args(), &mut std::io::stdout())
+            };
+            // Built-in commands behave like programs that exit with 1 when they fail
+            return match result {
+                Ok(_) => {
+                    self.last_exit_code.set(Some(0));
+                    Ok(())
+                }
+                Err(reason) => {
+                    self.last_exit_code.set(Some(1));
+                    let error = TaskError::RuntimeError(task_name.to_string(), reason).into();
+                    Err(self.add_hint(error, ""))
+                }
+            };
+        }
+        let mut command = rendered_command.to_command()?;
+        if let Some(base_env) = &self.base_env {
+            command.env_clear();
+            command.envs(base_env);
+            command.envs(rendered_command.get_exported_env());
+            command.envs(rendered_command.env());
+        }
+        if self.pipes_output() {
+            command.stdout(Stdio::piped());
+            command.stderr(Stdio::piped());
+        } else {
+            command.stdout(Stdio::inherit());
+            command.stderr(Stdio::inherit());
+        }
+        if self.output.is_some() {
+            command.stdin(Stdio::null());
+        } else {
+            command.stdin(Stdio::inherit());
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                let error = TaskError::RuntimeError(task_name.to_string(), e.to_string()).into();
+                return Err(self.add_hint(error, ""));
+            }
+        };
+
+        // let child handle ctrl-c to prevent dropping the parent and leaving the child running.
+        // Commands whose output goes to a sink are not run from the terminal
+        if self.output.is_none() {
+            ctrlc::set_handler(move || {}).unwrap_or(());
+        }
+
+        self.set_tracked_pid(Some(child.id()));
+        let tail = self.hints.as_ref().map(|_| Arc::new(OutputTail::default()));
+        let forwarders = if let Some(sink) = &self.output {
+            let stdout = child.stdout.take().unwrap();
+            let stderr = child.stderr.take().unwrap();
+            let (stdout_sink, stderr_sink) = (Arc::clone(sink), Arc::clone(sink));
+            let (stdout_tail, stderr_tail) = (tail.clone(), tail.clone());
+            vec![
+                thread::spawn(move || {
+                    sink_lines(stdout, stdout_sink.as_ref(), false, stdout_tail.as_deref());
+                }),
+                thread::spawn(move || {
+                    sink_lines(stderr, stderr_sink.as_ref(), true, stderr_tail.as_deref());
+                }),
+            ]
+        } else if self.pipes_output() {
+            let stdout = child.stdout.take().unwrap();
+            let stderr = child.stderr.take().unwrap();
+            let timestamper = self.timestamper;
+            let (stdout_tail, stderr_tail) = (tail.clone(), tail.clone());
+            let (stdout_indent, stderr_indent) = (self.indent.clone(), self.indent.clone());
+            vec![
+                thread::spawn(move || {
+                    forward_lines(
+                        stdout,
+                        std::io::stdout(),
+                        &stdout_indent,
+                        timestamper.as_ref(),
+                        stdout_tail.as_deref(),
+                    );
+                }),
+                thread::spawn(move || {
+                    forward_lines(
+                        stderr,
+                        std::io::stderr(),
+                        &stderr_indent,
+                        timestamper.as_ref(),
+                        stderr_tail.as_deref(),
+                    );
+                }),
+            ]
+        } else {
+            Vec::new()
+        };
+        let result = child.wait();
+        // Cleared before waiting for the output, as the pid can be reused once the process exits
+        self.set_tracked_pid(None);
+        for forwarder in forwarders {
+            let _ = forwarder.join();
+        }
+        let result = result?;
+        match result.code() {
+            None => Err(TaskError::RuntimeError(
+                task_name.to_string(),
+                String::from("Process did not terminate correctly"),
+            )
+            .into()),
+            Some(code) => {
+                self.last_exit_code.set(Some(code));
+                let message = rendered_command.check_exit_code(code).map_err(|e| {
+                    let output = tail.map(|tail| tail.contents()).unwrap_or_default();
+                    self.add_hint(e, &output)
+                })?;
+                match (message, &self.output) {
+                    (Some(message), Some(sink)) => sink.write_line(message, false),
+                    (Some(message), None) => println!("{}", message.yamis_info()),
+                    (None, _) => {}
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn last_exit_code(&self) -> Option<i32> {
+        self.last_exit_code.get()
+    }
+}
+
+/// How the scripts of the commands are printed by [RecordBackend]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptEcho {
+    /// Prints the script as it is
+    #[default]
+    Plain,
+    /// Prints the script with its syntax highlighted, if the output is colored
+    Highlighted,
+    /// Only prints the number of lines of the script, useful for large scripts
+    Hidden,
+}
+
+/// Backend that records the commands instead of running them. Useful to check what a task
+/// would do, or to compare the commands of entire task trees against golden files.
+#[derive(Debug, Default)]
+pub struct RecordBackend {
+    /// Commands recorded so far
+    commands: RefCell<Vec<RenderedCommand>>,
+    /// Whether to print the commands as they are recorded
+    print: bool,
+    /// How the scripts are printed
+    script_echo: ScriptEcho,
+}
+
+impl RecordBackend {
+    /// Returns a new backend that only stores the commands
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a new backend that also prints the commands as they are recorded
+    ///
+    /// # Arguments
+    ///
+    /// * `script_echo`: How the scripts are printed
+    ///
+    /// returns: RecordBackend
+    pub fn printing(script_echo: ScriptEcho) -> Self {
+        RecordBackend {
+            commands: RefCell::new(Vec::new()),
+            print: true,
+            script_echo,
+        }
+    }
+
+    /// Returns the recorded commands
+    pub fn into_commands(self) -> Vec<RenderedCommand> {
+        self.commands.into_inner()
+    }
+}
+
+impl ExecutionBackend for RecordBackend {
+    fn execute(&self, command: &RenderedCommand) -> DynErrResult<()> {
+        if self.print {
+            let mut output = String::new();
+            write_command(&mut output, command, self.script_echo)?;
+            print!("{}", output);
+        }
+        self.commands.borrow_mut().push(command.clone());
+        Ok(())
+    }
+
+    fn is_dry_run(&self) -> bool {
+        true
+    }
+}
+
+/// Backends that can be selected from the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionBackendKind {
+    /// Runs the commands, see [ProcessBackend]
+    Process,
+    /// Prints the commands instead of running them, see [RecordBackend]
+    Record,
+}
+
+impl ExecutionBackendKind {
+    /// Names of the backends, as given in the command line
+    pub const NAMES: [&'static str; 2] = ["process", "record"];
+
+    /// Returns a new backend of this kind
+    ///
+    /// # Arguments
+    ///
+    /// * `script_echo`: How the scripts are printed, if the backend prints the commands
+    ///
+    /// returns: Box<dyn ExecutionBackend, Global>
+    pub fn create(&self, script_echo: ScriptEcho) -> Box<dyn ExecutionBackend> {
+        match self {
+            ExecutionBackendKind::Process => Box::new(ProcessBackend::new()),
+            ExecutionBackendKind::Record => Box::new(RecordBackend::printing(script_echo)),
+        }
+    }
+}
+
+impl FromStr for ExecutionBackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "process" => Ok(ExecutionBackendKind::Process),
+            "record" => Ok(ExecutionBackendKind::Record),
+            _ => Err(format!(
+                "Unknown execution backend `{}`, expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Writes the command in a stable way, one field per line, so that it can be compared against
+/// golden files. Environment variables are sorted by name.
+///
+/// # Arguments
+///
+/// * `f`: Where the command is written to
+/// * `command`: Command to write
+/// * `script_echo`: How the script is written
+///
+/// returns: Result<(), Error>
+fn write_command(
+    f: &mut dyn fmt::Write,
+    command: &RenderedCommand,
+    script_echo: ScriptEcho,
+) -> fmt::Result {
+    writeln!(f, "Task: {}", command.task_name())?;
+    if !command.echo() {
+        return writeln!(f, "  <not echoed, echo_command is false>");
+    }
+    if let Some(wd) = command.wd() {
+        writeln!(f, "  wd: {}", wd.to_string_lossy())?;
+    }
+    if let Some(sandbox) = command.sandbox() {
+        writeln!(f, "  sandbox: {}", sandbox)?;
+    }
+    let mut env: Vec<(&String, &String)> = command.env().iter().collect();
+    env.sort();
+    for (key, val) in env {
+        writeln!(f, "  env: {}={}", key, val)?;
+    }
+    write!(f, "  argv: {:?}", command.program())?;
+    for arg in command.args() {
+        write!(f, " {:?}", arg)?;
+    }
+    if command.script().is_some() {
+        write!(f, " <script>")?;
+    }
+    writeln!(f)?;
+    if let Some(script) = command.script() {
+        let script = match script_echo {
+            ScriptEcho::Plain => Cow::Borrowed(script),
+            ScriptEcho::Highlighted => Cow::Owned(highlight_script(
+                script,
+                command.script_extension().unwrap_or_default(),
+            )),
+            ScriptEcho::Hidden => {
+                return writeln!(f, "  script: <{} lines>", script.lines().count());
+            }
+        };
+        writeln!(f, "  script:")?;
+        for line in script.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for RenderedCommand {
+    /// Formats the command in a stable way, one field per line, so that it can be compared
+    /// against golden files. Environment variables are sorted by name.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_command(f, self, ScriptEcho::Plain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_files::ConfigFile;
+    use crate::run_context::RunContext;
+    use crate::types::TaskArgs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_record_backend() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  hello:
+    program: echo
+    args: ["hello", "{$1}"]
+    env:
+      B: b
+      A: a
+  bye:
+    script: "echo bye\necho {$1}"
+    script_runner: bash
+    script_runner_args: ["-e"]
+  all:
+    serial: [hello, bye]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let args = TaskArgs::from([(String::from("*"), vec!["world".into()])]);
+
+        let backend = RecordBackend::new();
+        let task = config_file.get_task("all").unwrap();
+        task.run_with_backend(&args, &config_file, &RunContext::new(), &backend)
+            .unwrap();
+        let commands = backend.into_commands();
+        assert_eq!(commands.len(), 2);
+
+        let output: String = commands.iter().map(|command| command.to_string()).collect();
+        assert_eq!(
+            output,
+            r#"Task: hello
+  env: A=a
+  env: B=b
+  argv: "echo" "hello" "world"
+Task: bye
+  argv: "bash" "-e" <script>
+  script:
+    echo bye
+    echo "world"
+"#
+        );
+    }
+
+    #[test]
+    fn test_record_backend_without_echo() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  login:
+    program: login
+    args: ["--token", "{token}"]
+    echo_command: false
+  login_staging:
+    bases: [login]
+    env:
+      STAGE: staging
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let args = TaskArgs::from([(String::from("token"), vec!["secret".into()])]);
+
+        let backend = RecordBackend::new();
+        let task = config_file.get_task("login_staging").unwrap();
+        task.run_with_backend(&args, &config_file, &RunContext::new(), &backend)
+            .unwrap();
+        let commands = backend.into_commands();
+        assert!(!commands[0].echo());
+        assert_eq!(
+            commands[0].to_string(),
+            "Task: login_staging\n  <not echoed, echo_command is false>\n"
+        );
+    }
+
+    #[test]
+    fn test_execution_backend_kind_from_str() {
+        assert_eq!(
+            "process".parse::<ExecutionBackendKind>(),
+            Ok(ExecutionBackendKind::Process)
+        );
+        assert_eq!(
+            "record".parse::<ExecutionBackendKind>(),
+            Ok(ExecutionBackendKind::Record)
+        );
+        assert!("other".parse::<ExecutionBackendKind>().is_err());
+    }
+
+    #[test]
+    fn test_timestamps() {
+        assert_eq!(format_elapsed(Duration::from_millis(1234)), "[   1.234s]");
+        assert_eq!(format_elapsed(Duration::from_secs(12345)), "[12345.000s]");
+        // 2021-01-01 14:03:12.345 UTC
+        assert_eq!(
+            format_wall_clock(Duration::from_millis(1_609_509_792_345)),
+            "[14:03:12.345]"
+        );
+        assert_eq!("wall".parse::<TimestampKind>(), Ok(TimestampKind::Wall));
+        assert!("other".parse::<TimestampKind>().is_err());
+
+        let timestamper = Timestamper {
+            kind: TimestampKind::Elapsed,
+            started: Instant::now(),
+        };
+        let mut output = Vec::new();
+        let tail = OutputTail::default();
+        forward_lines(
+            &b"first\nsecond\xff"[..],
+            &mut output,
+            "",
+            Some(&timestamper),
+            Some(&tail),
+        );
+        let output = String::from_utf8_lossy(&output);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("[   0.") && lines[0].ends_with("s] first"));
+        assert!(lines[1].ends_with("s] second\u{FFFD}"));
+        assert_eq!(tail.contents(), "first\nsecond\u{FFFD}\n");
+
+        let mut output = Vec::new();
+        forward_lines(&b"first\nsecond"[..], &mut output, "  ", None, None);
+        assert_eq!(String::from_utf8_lossy(&output), "  first\n  second\n");
+    }
+}
@@ -0,0 +1,154 @@
+//! Built-in commands, used as the `program` of a task, i.e. `program: yamis::sleep`. These run
+//! inside yamis, so they behave the same on every OS, unlike the shell built-ins.
+use std::ffi::OsString;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+/// Prefix of the built-in commands
+pub(crate) const BUILTIN_PREFIX: &str = "yamis::";
+
+/// Built-in commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Builtin {
+    /// Waits the given number of seconds
+    Sleep,
+    /// Prints the arguments, separated by spaces
+    Echo,
+    /// Fails with the given reason
+    Fail,
+}
+
+impl Builtin {
+    /// Returns the built-in command for the given program, None if it is not a built-in
+    /// command, or an error if it has the prefix but is unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `program`: Program of the task
+    ///
+    /// returns: Result<Option<Builtin>, String>
+    pub(crate) fn from_program(program: &str) -> Result<Option<Builtin>, String> {
+        let name = match program.strip_prefix(BUILTIN_PREFIX) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        match name {
+            "sleep" => Ok(Some(Builtin::Sleep)),
+            "echo" => Ok(Some(Builtin::Echo)),
+            "fail" => Ok(Some(Builtin::Fail)),
+            _ => Err(format!(
+                "Unknown built-in command `{}`, expected one of: {}sleep, {}echo, {}fail",
+                program, BUILTIN_PREFIX, BUILTIN_PREFIX, BUILTIN_PREFIX
+            )),
+        }
+    }
+
+    /// Runs the command. Returns an error with the reason if it fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `args`: Arguments of the command
+    /// * `stdout`: Where the output is written to
+    ///
+    /// returns: Result<(), String>
+    pub(crate) fn run(&self, args: &[OsString], stdout: &mut dyn Write) -> Result<(), String> {
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        match self {
+            Builtin::Sleep => {
+                thread::sleep(Duration::from_secs_f64(sleep_seconds(&args)?));
+                Ok(())
+            }
+            Builtin::Echo => match writeln!(stdout, "{}", args.join(" ")) {
+                Ok(_) => Ok(()),
+                Err(e) => Err(e.to_string()),
+            },
+            Builtin::Fail if args.is_empty() => Err(String::from("Failed on purpose")),
+            Builtin::Fail => Err(args.join(" ")),
+        }
+    }
+}
+
+/// Returns the number of seconds given to `yamis::sleep`, or an error if the arguments are not
+/// a single non negative number
+///
+/// # Arguments
+///
+/// * `args`: Arguments of the command
+///
+/// returns: Result<f64, String>
+pub(crate) fn sleep_seconds(args: &[String]) -> Result<f64, String> {
+    let seconds = match args {
+        [seconds] => seconds.parse::<f64>().ok(),
+        _ => None,
+    };
+    match seconds {
+        Some(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(seconds),
+        _ => Err(format!(
+            "`{}sleep` expects a number of seconds, got `{}`",
+            BUILTIN_PREFIX,
+            args.join(" ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_program() {
+        assert_eq!(Builtin::from_program("echo"), Ok(None));
+        assert_eq!(
+            Builtin::from_program("yamis::echo"),
+            Ok(Some(Builtin::Echo))
+        );
+        assert_eq!(
+            Builtin::from_program("yamis::sleep"),
+            Ok(Some(Builtin::Sleep))
+        );
+        assert_eq!(
+            Builtin::from_program("yamis::fail"),
+            Ok(Some(Builtin::Fail))
+        );
+        assert_eq!(
+            Builtin::from_program("yamis::other"),
+            Err(String::from(
+                "Unknown built-in command `yamis::other`, expected one of: yamis::sleep, yamis::echo, yamis::fail"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let mut stdout = Vec::new();
+        Builtin::Echo
+            .run(&["hello".into(), "world".into()], &mut stdout)
+            .unwrap();
+        assert_eq!(String::from_utf8(stdout).unwrap(), "hello world\n");
+
+        let mut stdout = Vec::new();
+        assert_eq!(Builtin::Sleep.run(&["0.01".into()], &mut stdout), Ok(()));
+        assert_eq!(
+            Builtin::Sleep.run(&["soon".into()], &mut stdout),
+            Err(String::from(
+                "`yamis::sleep` expects a number of seconds, got `soon`"
+            ))
+        );
+        assert!(Builtin::Sleep.run(&["-1".into()], &mut stdout).is_err());
+        assert!(Builtin::Sleep.run(&[], &mut stdout).is_err());
+
+        assert_eq!(
+            Builtin::Fail.run(&["not".into(), "ready".into()], &mut stdout),
+            Err(String::from("not ready"))
+        );
+        assert_eq!(
+            Builtin::Fail.run(&[], &mut stdout),
+            Err(String::from("Failed on purpose"))
+        );
+        assert!(stdout.is_empty());
+    }
+}
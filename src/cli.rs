@@ -1,32 +1,109 @@
 use clap::ArgAction;
 use colored::{ColoredString, Colorize};
+use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use serde_derive::Deserialize;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::ffi::{OsStr, OsString};
 use std::fs::File;
-use std::path::Path;
+#[cfg(feature = "updater")]
+use std::io::IsTerminal;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "watch")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "watch")]
+use std::sync::Arc;
 use std::{env, fmt, fs};
 
-use regex::Regex;
+use regex::bytes::Regex;
 
-use crate::config_files::{ConfigFilePaths, ConfigFilesContainer};
-use crate::print_utils::YamisOutput;
+use crate::backend::{
+    ExecutionBackend, ExecutionBackendKind, ProcessBackend, RecordBackend, ScriptEcho,
+    TimestampKind,
+};
+use crate::completion;
+use crate::config_files::{
+    ConfigFile, ConfigFilePaths, ConfigFileSharedPtr, ConfigFilesContainer, InlineFilesCleanup,
+};
+#[cfg(all(feature = "daemon", unix))]
+use crate::daemon;
+use crate::env_check;
+use crate::escape::ShellKind;
+use crate::formatter::format_config_file;
+use crate::hints::Hints;
+use crate::nesting;
+#[cfg(feature = "otel")]
+use crate::otel;
+use crate::panic_hook;
+use crate::plugins;
+use crate::print_utils;
+use crate::print_utils::{format_markdown, YamisOutput};
+use crate::run_context::RunContext;
+use crate::runs;
+use crate::runs::RunTracker;
+use crate::script_export::{export_script, ExportShell};
+use crate::script_lint::{get_template_line, is_not_found, LinterSettings};
+#[cfg(feature = "updater")]
+use crate::settings::UpdateCheck;
+use crate::settings::{GlobalTasks, Settings};
+use crate::snapshots::{Snapshot, SnapshotBackend};
+use crate::status::StatusBackend;
+use crate::task_index::TaskIndex;
+use crate::tasks::{EnvOrigin, Task};
 use crate::types::{DynErrResult, TaskArgs};
+#[cfg(feature = "updater")]
 use crate::updater;
+use crate::utils::{fuzzy_match, glob_match, os_string_from_bytes, read_env_file};
+#[cfg(feature = "watch")]
+use crate::watcher::FileWatcher;
 
 const HELP: &str = "The appropriate YAML or TOML config files need to exist \
 in the directory or parents, or a file is specified with the `-f` or `--file` \
 options. For help about the config files check https://github.com/adrianmrit/yamis";
 
+/// Default format of the output of `yamis current`
+const DEFAULT_CURRENT_FORMAT: &str = "yamis: {tasks} tasks";
+
+/// Operator that chains a task to the previous one in the command line, i.e. `yamis build && test`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChainOperator {
+    /// Runs the task if the previous one succeeded, given as `&&`
+    And,
+    /// Runs the task if the previous one failed, given as `||`
+    Or,
+}
+
+impl ChainOperator {
+    /// Returns the operator given as the argument, if it is one
+    fn from_arg(arg: &OsString) -> Option<ChainOperator> {
+        match arg.to_str() {
+            Some("&&") => Some(ChainOperator::And),
+            Some("||") => Some(ChainOperator::Or),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ChainOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainOperator::And => write!(f, "&&"),
+            ChainOperator::Or => write!(f, "||"),
+        }
+    }
+}
+
 /// Holds the data for running the given task.
-struct TaskSubcommand {
+pub(crate) struct TaskSubcommand {
     /// Task to run, if given
     pub task: String,
     /// Args to run the command with
     pub args: TaskArgs,
+    /// Tasks chained after this one, along with the operator they are chained with
+    pub chain: Vec<(ChainOperator, TaskSubcommand)>,
 }
 
 /// Enum of config file containers by version
@@ -47,7 +124,8 @@ pub struct ConfigFileVersionSerializer {
 /// Enum of available config file versions
 #[derive(Hash, Eq, PartialEq, Debug, Deserialize)]
 enum Version {
-    #[serde(alias = "v1")]
+    /// Version 2 files are handled by the same container, only gating the fields they can use
+    #[serde(alias = "v1", alias = "2", alias = "v2")]
     #[serde(rename = "1")]
     V1,
 }
@@ -56,19 +134,34 @@ enum Version {
 struct ConfigFileContainers {
     /// Holds the config file containers for each version
     containers: HashMap<Version, ConfigFileContainerVersion>,
+    /// Whether to notify when a task is run from the global config file
+    global_tasks: GlobalTasks,
+    /// Whether to notify when a task shadows a different one in another directory
+    warn_shadowed_tasks: bool,
+    /// Whether to run tasks even if another yamis process is running them
+    force: bool,
+    /// Variables given with `--var`, which take precedence over the ones of the config files
+    var_overrides: IndexMap<String, String>,
+    /// Whether to print each tag of the templates and the value it rendered to
+    trace_templates: bool,
 }
 
 /// Argument errors
 #[derive(Debug, PartialEq, Eq)]
-enum ArgsError {
+pub(crate) enum ArgsError {
     /// Raised when no task to run is given
     MissingTaskArg,
+    /// Raised when no task is given after a chain operator
+    MissingChainedTask(ChainOperator),
 }
 
 impl fmt::Display for ArgsError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ArgsError::MissingTaskArg => write!(f, "No task was given."),
+            ArgsError::MissingChainedTask(operator) => {
+                write!(f, "No task was given after `{}`.", operator)
+            }
         }
     }
 }
@@ -77,6 +170,7 @@ impl Error for ArgsError {
     fn description(&self) -> &str {
         match *self {
             ArgsError::MissingTaskArg => "no task given",
+            ArgsError::MissingChainedTask(_) => "no task given after chain operator",
         }
     }
 
@@ -97,13 +191,27 @@ fn colorize_config_file_path(val: &str) -> ColoredString {
 
 impl ConfigFileContainers {
     /// Creates a new instance of `ConfigFileContainers`
-    fn new() -> Self {
+    ///
+    /// # Arguments
+    ///
+    /// * `settings`: Settings of yamis
+    /// * `force`: Whether to run tasks even if another yamis process is running them
+    ///
+    /// returns: ConfigFileContainers
+    fn new(settings: &Settings, force: bool) -> Self {
         let mut containers = HashMap::new();
         containers.insert(
             Version::V1,
             ConfigFileContainerVersion::V1(ConfigFilesContainer::new()),
         );
-        Self { containers }
+        Self {
+            containers,
+            global_tasks: settings.global_tasks,
+            warn_shadowed_tasks: settings.warn_shadowed_tasks,
+            force,
+            var_overrides: IndexMap::new(),
+            trace_templates: false,
+        }
     }
 
     /// Peeks at the file and returns the version of the config file.
@@ -144,6 +252,21 @@ impl ConfigFileContainers {
         Ok(result.version)
     }
 
+    /// Prints the given task names and their summaries, or a message if there are none
+    fn print_task_summaries(tasks: Vec<(&str, &str)>) {
+        if tasks.is_empty() {
+            println!("  {}", "No tasks found.".red());
+        } else {
+            for (task, summary) in tasks {
+                if summary.is_empty() {
+                    println!(" - {}", colorize_task_name(task));
+                } else {
+                    println!(" - {}: {}", colorize_task_name(task), summary);
+                }
+            }
+        }
+    }
+
     /// prints config file paths and their tasks
     fn print_tasks_list(&mut self, paths: ConfigFilePaths) -> DynErrResult<()> {
         for path in paths {
@@ -152,70 +275,213 @@ impl ConfigFileContainers {
             match version {
                 Version::V1 => {
                     println!("{}:", colorize_config_file_path(&path.to_string_lossy()));
-                    let container = self.containers.get_mut(&Version::V1).unwrap();
+                    if let Some(index) = TaskIndex::load(&path) {
+                        Self::print_task_summaries(index.get_public_task_summaries());
+                        continue;
+                    }
+                    let container = self.containers.get(&Version::V1).unwrap();
                     let ConfigFileContainerVersion::V1(container) = container;
                     let config_file_ptr = container.read_config_file(path.clone())?;
-                    let config_file_lock = config_file_ptr.lock().unwrap();
-                    let task_names = config_file_lock.get_public_task_names();
-                    if task_names.is_empty() {
-                        println!("  {}", "No tasks found.".red());
-                    } else {
-                        for task in task_names {
-                            println!(" - {}", colorize_task_name(task));
-                        }
-                    }
+                    let config_file_lock = config_file_ptr.read().unwrap();
+                    Self::print_task_summaries(config_file_lock.get_public_task_summaries());
                 }
             }
         }
         Ok(())
     }
 
-    /// Prints help for the given task
-    fn print_task_info(&mut self, paths: ConfigFilePaths, task: &str) -> DynErrResult<()> {
+    /// Prints the information of the given task
+    ///
+    /// # Arguments
+    ///
+    /// * `task`: Task to print
+    /// * `config_file`: Config file of the task
+    /// * `show_env`: State of the run, with the environment variables given from the command
+    ///   line, if the environment variables the task sets, and where they come from, should be
+    ///   printed
+    fn print_task_details(task: &Task, config_file: &ConfigFile, show_env: Option<&RunContext>) {
+        print!(" - {}", colorize_task_name(task.get_name()));
+        if task.is_private() {
+            print!(" {}", "(private)".red());
+        }
+        println!();
+        let prefix = "     ";
+        match task.get_help().trim() {
+            "" => println!("{}{}", prefix, "No help to display".yellow()),
+            help => {
+                //                 " -   "  Two spaces after the dash
+                let help = format_markdown(help);
+                let help_lines: Vec<&str> = help.lines().collect();
+                println!("{}{}", prefix, help_lines.join(&format!("\n{}", prefix)))
+            }
+        }
+        let ancestors = task.get_ancestors();
+        if !ancestors.is_empty() {
+            let ancestors: Vec<String> = ancestors
+                .iter()
+                .map(|name| colorize_task_name(name).to_string())
+                .collect();
+            println!("{}Bases: {}", prefix, ancestors.join(", "));
+        }
+        if let Some(params) = task.get_params() {
+            println!("{}Parameters:", prefix);
+            for (name, param_type) in params {
+                println!("{}  --{}: {}", prefix, name.cyan(), param_type);
+            }
+        }
+        let env_docs = task.get_env_docs();
+        if !env_docs.is_empty() {
+            println!("{}Environment variables:", prefix);
+            for (name, doc) in env_docs {
+                println!("{}  {}: {}", prefix, name.cyan(), doc.trim());
+            }
+        }
+        if let Some(context) = show_env {
+            let env = task.get_env_with_origins(config_file, context);
+            if env.is_empty() {
+                println!(
+                    "{}Environment: {}",
+                    prefix,
+                    "Inherited from the system".yellow()
+                );
+            } else {
+                println!("{}Environment:", prefix);
+            }
+            for (name, val, origin) in env {
+                let overrides_system =
+                    origin != EnvOrigin::FilePath && env::var_os(&name).is_some();
+                let origin = match overrides_system {
+                    true => format!("({}, overrides system)", origin),
+                    false => format!("({})", origin),
+                };
+                println!("{}  {}={} {}", prefix, name.cyan(), val, origin.dimmed());
+            }
+        }
+    }
+
+    /// Prints the information of the given tasks of the config file, under its path
+    fn print_config_file_tasks_details(
+        path: &Path,
+        config_file: &ConfigFile,
+        tasks: &[String],
+        show_env: Option<&RunContext>,
+    ) {
+        println!("{}:", colorize_config_file_path(&path.to_string_lossy()));
+        for task in tasks {
+            if let Some(task) = config_file.get_task(task) {
+                Self::print_task_details(&task, config_file, show_env);
+            }
+        }
+    }
+
+    /// Prints help for the tasks matching the given pattern, in all the config files. Patterns
+    /// with `*` or `?` are matched as globs, otherwise tasks with that exact name are printed,
+    /// or tasks with a similar name if there are none.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths`: Config files to search the tasks in
+    /// * `pattern`: Name of the tasks, or glob pattern
+    /// * `show_env`: Environment variables given from the command line, if the environment
+    ///   variables of the tasks should be printed
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    fn print_task_info(
+        &mut self,
+        paths: ConfigFilePaths,
+        pattern: &str,
+        show_env: Option<&HashMap<String, String>>,
+    ) -> DynErrResult<()> {
+        let is_glob = pattern.contains(['*', '?']);
+        let context = show_env.map(|env| RunContext::new().with_env_overrides(env.clone()));
+        let mut found = false;
+        // Tasks with a similar name in each config file, printed if none matches exactly
+        let mut similar_tasks: Vec<(PathBuf, ConfigFileSharedPtr, Vec<String>)> = Vec::new();
         for path in paths {
             let path = path?;
             let version = ConfigFileContainers::get_file_version(&path)?;
             match version {
                 Version::V1 => {
-                    let container = self.containers.get_mut(&Version::V1).unwrap();
+                    let container = self.containers.get(&Version::V1).unwrap();
                     let ConfigFileContainerVersion::V1(container) = container;
                     let config_file_ptr = container.read_config_file(path.clone())?;
-                    let config_file_lock = config_file_ptr.lock().unwrap();
-                    let task = config_file_lock.get_task(task);
-                    match task {
-                        Some(task) => {
-                            println!("{}:", colorize_config_file_path(&path.to_string_lossy()));
-                            print!(" - {}", colorize_task_name(task.get_name()));
-                            if task.is_private() {
-                                print!(" {}", "(private)".red());
-                            }
-                            println!();
-                            let prefix = "     ";
-                            match task.get_help().trim() {
-                                "" => println!("{}{}", prefix, "No help to display".yellow()),
-                                help => {
-                                    //                 " -   "  Two spaces after the dash
-                                    let help_lines: Vec<&str> = help.lines().collect();
-                                    println!(
-                                        "{}{}",
-                                        prefix,
-                                        help_lines.join(&format!("\n{}", prefix)).green()
-                                    )
-                                }
-                            }
-                            return Ok(());
+                    let config_file_lock = config_file_ptr.read().unwrap();
+                    let task_names = config_file_lock.get_task_base_names();
+                    let matching: Vec<String> = if is_glob {
+                        task_names
+                            .iter()
+                            .filter(|name| glob_match(pattern, name))
+                            .map(|name| String::from(*name))
+                            .collect()
+                    } else if config_file_lock.get_task(pattern).is_some() {
+                        vec![String::from(pattern)]
+                    } else {
+                        Vec::new()
+                    };
+                    if !matching.is_empty() {
+                        found = true;
+                        Self::print_config_file_tasks_details(
+                            &path,
+                            &config_file_lock,
+                            &matching,
+                            context.as_ref(),
+                        );
+                    } else if !is_glob && !found {
+                        let similar: Vec<String> = task_names
+                            .iter()
+                            .filter(|name| fuzzy_match(pattern, name))
+                            .map(|name| String::from(*name))
+                            .collect();
+                        if !similar.is_empty() {
+                            drop(config_file_lock);
+                            similar_tasks.push((path, config_file_ptr, similar));
                         }
-                        None => continue,
                     }
                 }
             }
         }
-        Err(format!("Task {} not found", task).into())
+        if found {
+            return Ok(());
+        }
+        if similar_tasks.is_empty() {
+            return Err(format!("Task {} not found", pattern).into());
+        }
+        let msg = format!("Task {} not found, showing similar tasks", pattern);
+        eprintln!("{}", msg.yamis_warn());
+        for (path, config_file_ptr, tasks) in similar_tasks {
+            let config_file_lock = config_file_ptr.read().unwrap();
+            Self::print_config_file_tasks_details(
+                &path,
+                &config_file_lock,
+                &tasks,
+                context.as_ref(),
+            );
+        }
+        Ok(())
     }
 
-    /// Runs the given task
-    fn run_task(&mut self, paths: ConfigFilePaths, task: &str, args: TaskArgs) -> DynErrResult<()> {
-        for path in paths {
+    /// Runs the given task, executing its commands with the given backend
+    ///
+    /// # Arguments
+    ///
+    /// * `paths`: Config files to search the task in
+    /// * `task`: Name of the task
+    /// * `args`: Arguments to run the task with
+    /// * `env_overrides`: Environment variables that take precedence over the ones of the
+    ///   config file and task
+    /// * `backend`: Backend that executes the commands
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    fn run_task(
+        &mut self,
+        paths: impl IntoIterator<Item = DynErrResult<PathBuf>>,
+        task: &str,
+        args: TaskArgs,
+        env_overrides: &HashMap<String, String>,
+        backend: &dyn ExecutionBackend,
+    ) -> DynErrResult<()> {
+        let mut paths = paths.into_iter();
+        while let Some(path) = paths.next() {
             let path = path?;
             let version = match ConfigFileContainers::get_file_version(&path) {
                 Ok(version) => version,
@@ -227,7 +493,11 @@ impl ConfigFileContainers {
             };
             match version {
                 Version::V1 => {
-                    let container = self.containers.get_mut(&Version::V1).unwrap();
+                    // Avoid fully loading files that do not have the task
+                    if !may_have_public_task(&path, task) {
+                        continue;
+                    }
+                    let container = self.containers.get(&Version::V1).unwrap();
                     let ConfigFileContainerVersion::V1(container) = container;
                     let config_file_ptr = match container.read_config_file(path.clone()) {
                         Ok(val) => val,
@@ -236,14 +506,55 @@ impl ConfigFileContainers {
                             return Err(e.into());
                         }
                     };
-                    let config_file_lock = config_file_ptr.lock().unwrap();
-                    let task = config_file_lock.get_public_task(task);
-                    match task {
-                        Some(task) => {
+                    // The config file is only read, the state of the run is kept apart, so that
+                    // other threads can run tasks of the same config file at the same time
+                    let config_file_lock = config_file_ptr.read().unwrap();
+                    if let Err(e) = config_file_lock
+                        .check_constants_not_redefined(&self.var_overrides, "--var ")
+                    {
+                        let e = format!("{}:\n{}", &path.to_string_lossy().red(), e);
+                        return Err(e.into());
+                    }
+                    // Each run starts from scratch, i.e. for each environment when fanning out,
+                    // except for the dependencies that already ran if yamis was invoked by one
+                    // of its tasks
+                    let context = RunContext::new()
+                        .with_dry_run(backend.is_dry_run())
+                        .with_env_overrides(env_overrides.clone())
+                        .with_var_overrides(self.var_overrides.clone())
+                        .with_trace_templates(self.trace_templates)
+                        .with_executed_deps(nesting::get_inherited_deps(&path));
+                    match config_file_lock.get_public_task(task) {
+                        Some(found_task) => {
                             if config_file_lock.debug_config.print_file_path {
                                 println!("{}", &path.to_string_lossy().yamis_info());
                             }
-                            return match task.run(&args, &config_file_lock) {
+                            if self.global_tasks == GlobalTasks::Notify
+                                && path.starts_with(ConfigFilePaths::get_global_config_file_dir())
+                            {
+                                let notice = format!(
+                                    "Task {} not found in the project, running it from {}",
+                                    task,
+                                    path.to_string_lossy()
+                                );
+                                eprintln!("{}", notice.yamis_info());
+                            }
+                            if self.warn_shadowed_tasks {
+                                print_shadowed_tasks(&path, task, paths.by_ref());
+                            }
+                            let exclusive =
+                                !self.force && !found_task.is_reentrant() && !backend.is_dry_run();
+                            let _run = runs::register_run(task, &path, exclusive)?;
+                            return match found_task
+                                .check_strict_args(&args, &config_file_lock, &context)
+                                .and_then(|_| {
+                                    found_task.run_with_backend(
+                                        &args,
+                                        &config_file_lock,
+                                        &context,
+                                        backend,
+                                    )
+                                }) {
                                 Ok(val) => Ok(val),
                                 Err(e) => {
                                     let e = format!("{}:\n{}", &path.to_string_lossy().red(), e);
@@ -258,135 +569,782 @@ impl ConfigFileContainers {
         }
         Err(format!("Task {} not found", task).into())
     }
+
+    /// Returns the path of the config file the given task would run from, and the glob patterns
+    /// of the files that run it again when they change
+    ///
+    /// # Arguments
+    ///
+    /// * `paths`: Config files to search the task in
+    /// * `task`: Name of the task
+    ///
+    /// returns: Result<(PathBuf, Vec<String, Global>), Box<dyn Error, Global>>
+    #[cfg(feature = "watch")]
+    fn get_watch_patterns(
+        &mut self,
+        paths: impl IntoIterator<Item = DynErrResult<PathBuf>>,
+        task: &str,
+    ) -> DynErrResult<(PathBuf, Vec<String>)> {
+        for path in paths {
+            let path = path?;
+            match ConfigFileContainers::get_file_version(&path)? {
+                Version::V1 => {
+                    if !may_have_public_task(&path, task) {
+                        continue;
+                    }
+                    let container = self.containers.get(&Version::V1).unwrap();
+                    let ConfigFileContainerVersion::V1(container) = container;
+                    let config_file_ptr = match container.read_config_file(path.clone()) {
+                        Ok(val) => val,
+                        Err(e) => {
+                            let e = format!("{}:\n{}", &path.to_string_lossy().red(), e);
+                            return Err(e.into());
+                        }
+                    };
+                    let config_file_lock = config_file_ptr.read().unwrap();
+                    if let Some(found_task) = config_file_lock.get_public_task(task) {
+                        return match found_task.get_watch_patterns() {
+                            Some(patterns) => Ok((path.clone(), patterns.to_vec())),
+                            None => Err(format!(
+                                "Task {} cannot be watched, as it does not set `watch` or `sources`",
+                                task
+                            )
+                            .into()),
+                        };
+                    }
+                }
+            }
+        }
+        Err(format!("Task {} not found", task).into())
+    }
+
+    /// Forgets the config files read so far, so that changes to them are picked up
+    #[cfg(feature = "watch")]
+    fn clear(&self) {
+        for container in self.containers.values() {
+            let ConfigFileContainerVersion::V1(container) = container;
+            container.clear();
+        }
+    }
+}
+
+/// Prints a message for each config file in another directory that defines a different task
+/// with the same name, which is shadowed by the task of the given config file.
+///
+/// # Arguments
+///
+/// * `path`: Path of the config file the task is run from
+/// * `task`: Name of the task
+/// * `other_paths`: Paths of the config files with lower priority
+fn print_shadowed_tasks(
+    path: &Path,
+    task: &str,
+    other_paths: impl Iterator<Item = DynErrResult<PathBuf>>,
+) {
+    let definition = ConfigFile::task_definition(path, task);
+    for other_path in other_paths.map_while(Result::ok) {
+        // Files in the same directory are meant to override each other
+        if other_path.parent() == path.parent() || !may_have_public_task(&other_path, task) {
+            continue;
+        }
+        let Some(other_definition) = ConfigFile::task_definition(&other_path, task) else {
+            continue;
+        };
+        if definition.as_ref() != Some(&other_definition) {
+            let notice = format!(
+                "Task {} shadows the one in {}",
+                task,
+                other_path.to_string_lossy()
+            );
+            eprintln!("{}", notice.yamis_info());
+        }
+    }
+}
+
+/// Returns whether the config file in the given path might have the given public task, without
+/// fully loading it
+fn may_have_public_task(path: &Path, task: &str) -> bool {
+    match TaskIndex::load(path) {
+        Some(index) => index.has_public_task(task),
+        None => ConfigFile::defines_task(path, task),
+    }
 }
 
 // TODO: Handle
 impl TaskSubcommand {
     /// Returns a new TaskSubcommand
     pub(crate) fn new(args: &clap::ArgMatches) -> Result<TaskSubcommand, ArgsError> {
+        let (task_name, task_args, stop_at_separator) = match args.subcommand() {
+            None => return Err(ArgsError::MissingTaskArg),
+            Some(("run", run_args)) => {
+                let mut run_args = Self::get_args_vec(run_args.get_many::<OsString>("args"));
+                if run_args.is_empty() {
+                    return Err(ArgsError::MissingTaskArg);
+                }
+                let task_name = run_args.remove(0).to_string_lossy().to_string();
+                (task_name, run_args, true)
+            }
+            Some((task_name, task_args)) => (
+                String::from(task_name),
+                Self::get_args_vec(task_args.get_many::<OsString>("")),
+                false,
+            ),
+        };
+
+        let (task_args, chained) = Self::split_chain(task_args, stop_at_separator);
+        let mut chain = Vec::with_capacity(chained.len());
+        for (operator, mut chained_args) in chained {
+            if chained_args.is_empty() {
+                return Err(ArgsError::MissingChainedTask(operator));
+            }
+            let chained_task = chained_args.remove(0).to_string_lossy().to_string();
+            chain.push((
+                operator,
+                TaskSubcommand {
+                    task: chained_task,
+                    args: Self::get_task_args(chained_args, stop_at_separator),
+                    chain: vec![],
+                },
+            ));
+        }
+
+        Ok(TaskSubcommand {
+            task: task_name,
+            args: Self::get_task_args(task_args, stop_at_separator),
+            chain,
+        })
+    }
+
+    /// Splits the arguments at the `&&` and `||` operators. Returns the arguments of the first
+    /// task, and the operator and arguments, including the task name, of each chained task.
+    ///
+    /// # Arguments
+    ///
+    /// * `args`: Arguments given after the task name
+    /// * `stop_at_separator`: If true, operators given after `--` are kept as arguments
+    ///
+    /// returns: (Vec<OsString, Global>, Vec<(ChainOperator, Vec<OsString, Global>), Global>)
+    fn split_chain(
+        args: Vec<OsString>,
+        stop_at_separator: bool,
+    ) -> (Vec<OsString>, Vec<(ChainOperator, Vec<OsString>)>) {
+        let mut first = Vec::new();
+        let mut chained: Vec<(ChainOperator, Vec<OsString>)> = Vec::new();
+        let mut after_separator = false;
+        for arg in args {
+            let operator = match after_separator {
+                true => None,
+                false => ChainOperator::from_arg(&arg),
+            };
+            if let Some(operator) = operator {
+                chained.push((operator, Vec::new()));
+                continue;
+            }
+            // The first value of a chained task is its name, not a separator
+            let is_task_name =
+                matches!(chained.last(), Some((_, chained_args)) if chained_args.is_empty());
+            if stop_at_separator && arg == "--" && !is_task_name {
+                after_separator = true;
+            }
+            match chained.last_mut() {
+                Some((_, chained_args)) => chained_args.push(arg),
+                None => first.push(arg),
+            }
+        }
+        (first, chained)
+    }
+
+    /// Returns the raw values given by clap. These are kept as OS strings so that
+    /// arguments that are not valid unicode, i.e. some file names, are not mangled.
+    fn get_args_vec(values: Option<clap::parser::ValuesRef<OsString>>) -> Vec<OsString> {
+        match values {
+            None => vec![],
+            Some(values) => values.cloned().collect::<Vec<OsString>>(),
+        }
+    }
+
+    /// Maps the arguments given after the task name into positional and named arguments.
+    /// The values of named arguments given multiple times keep the order of the command line.
+    ///
+    /// # Arguments
+    ///
+    /// * `args`: Arguments given after the task name
+    /// * `stop_at_separator`: If true, a `--` argument marks the end of named arguments, and
+    ///   anything after it is taken as a positional argument. The separator itself is dropped.
+    ///
+    /// returns: HashMap<String, Vec<OsString>, RandomState>
+    pub(crate) fn get_task_args(args: Vec<OsString>, stop_at_separator: bool) -> TaskArgs {
         let mut kwargs = TaskArgs::new();
 
-        let (task_name, task_args) = match args.subcommand() {
-            None => return Err(ArgsError::MissingTaskArg),
-            Some(command) => command,
+        let (args, positional_only) = match args.iter().position(|arg| arg == "--") {
+            Some(separator_index) if stop_at_separator => {
+                let mut args = args;
+                let positional_only = args.split_off(separator_index + 1);
+                args.pop(); // removes the separator
+                (args, positional_only)
+            }
+            _ => (args, vec![]),
         };
 
-        if let Some(args) = task_args.get_many::<OsString>("") {
-            // All args are pushed into a vector as they are
-            let all_args = args
-                .clone()
-                .map(|s| s.to_string_lossy().to_string())
-                .collect::<Vec<String>>();
-            kwargs.insert(String::from("*"), all_args);
-
-            // kwarg found that could be a key
-            let mut possible_kwarg_key = None;
-
-            // looping over the args to find kwargs
-            for arg in args {
-                let arg = arg.to_string_lossy().to_string();
-                // if a kwarg key was previously found, assume this is the value, even if
-                // it starts with - or --
-                if let Some(possible_kwarg) = possible_kwarg_key {
-                    match kwargs.entry(possible_kwarg) {
-                        Entry::Occupied(mut e) => {
-                            e.get_mut().push(arg);
-                        }
-                        Entry::Vacant(e) => {
-                            let args_vec: Vec<String> = vec![arg];
-                            e.insert(args_vec);
-                        }
+        // kwarg found that could be a key
+        let mut possible_kwarg_key = None;
+
+        // looping over the args to find kwargs
+        for arg in &args {
+            // if a kwarg key was previously found, assume this is the value, even if
+            // it starts with - or --
+            if let Some(possible_kwarg) = possible_kwarg_key {
+                match kwargs.entry(possible_kwarg) {
+                    Entry::Occupied(mut e) => {
+                        e.get_mut().push(arg.clone());
+                    }
+                    Entry::Vacant(e) => {
+                        let args_vec: Vec<OsString> = vec![arg.clone()];
+                        e.insert(args_vec);
                     }
-                    possible_kwarg_key = None;
-                    continue;
                 }
+                possible_kwarg_key = None;
+                continue;
+            }
 
-                // Quick check to see if the arg is a kwarg key or key-value pair
-                // if it is a positional value, we just continue
-                if !arg.starts_with('-') {
-                    continue;
-                }
+            // Quick check to see if the arg is a kwarg key or key-value pair
+            // if it is a positional value, we just continue
+            let arg = arg.as_encoded_bytes();
+            if !arg.starts_with(b"-") {
+                continue;
+            }
 
-                // Check if this is a kwarg key-value pair
-                if let Some((key, val)) = Self::get_kwarg(&arg) {
-                    match kwargs.entry(key) {
-                        Entry::Occupied(mut e) => {
-                            e.get_mut().push(val);
-                        }
-                        Entry::Vacant(e) => {
-                            let args_vec: Vec<String> = vec![val];
-                            e.insert(args_vec);
-                        }
+            // Check if this is a kwarg key-value pair
+            if let Some((key, val)) = Self::get_kwarg(arg) {
+                match kwargs.entry(key) {
+                    Entry::Occupied(mut e) => {
+                        e.get_mut().push(val);
+                    }
+                    Entry::Vacant(e) => {
+                        let args_vec: Vec<OsString> = vec![val];
+                        e.insert(args_vec);
                     }
-                    continue;
-                }
-
-                // Otherwise it could be a kwarg key, for which we need to check the next arg
-                if let Some(key) = Self::get_kwarg_key(&arg) {
-                    possible_kwarg_key = Some(key);
-                    continue;
                 }
+                continue;
+            }
 
-                // Finally if it is not a kwarg key or key-value pair, it is a positional arg,
-                // i.e. -0
+            // Otherwise it could be a kwarg key, for which we need to check the next arg
+            if let Some(key) = Self::get_kwarg_key(arg) {
+                possible_kwarg_key = Some(key);
+                continue;
             }
-        } else {
-            kwargs.insert(String::from("*"), vec![]);
+
+            // Finally if it is not a kwarg key or key-value pair, it is a positional arg,
+            // i.e. -0
         }
 
-        Ok(TaskSubcommand {
-            task: String::from(task_name),
-            args: kwargs,
-        })
+        // All args are pushed into a vector as they are
+        let mut all_args = args;
+        all_args.extend(positional_only);
+        kwargs.insert(String::from("*"), all_args);
+
+        kwargs
     }
 
     /// Returns the key if the arg represents a kwarg key, otherwise None
-    fn get_kwarg_key(arg: &str) -> Option<String> {
+    fn get_kwarg_key(arg: &[u8]) -> Option<String> {
         lazy_static! {
             static ref KWARG_KEY_REGEX: Regex = Regex::new(r"-{1,2}(?P<key>[a-zA-Z]+\w*)").unwrap();
         }
         let kwarg_match = KWARG_KEY_REGEX.captures(arg);
         if let Some(arg_match) = kwarg_match {
-            let key = String::from(arg_match.name("key").unwrap().as_str());
-            Some(key)
+            let key = String::from_utf8_lossy(arg_match.name("key").unwrap().as_bytes());
+            Some(key.to_string())
         } else {
             None
         }
     }
 
     /// Returns the key and value if the arg represents a kwarg key-value pair, otherwise None
-    fn get_kwarg(arg: &str) -> Option<(String, String)> {
+    fn get_kwarg(arg: &[u8]) -> Option<(String, OsString)> {
         lazy_static! {
+            // The value can be anything, including bytes that are not valid unicode
             static ref KWARG_REGEX: Regex =
-                Regex::new(r"-{1,2}(?P<key>[a-zA-Z]+\w*)=(?P<val>[\s\S]*)").unwrap();
+                Regex::new(r"-{1,2}(?P<key>[a-zA-Z]+\w*)=(?P<val>(?s-u:.*))").unwrap();
         }
         let kwarg_match = KWARG_REGEX.captures(arg);
         if let Some(arg_match) = kwarg_match {
-            let key = String::from(arg_match.name("key").unwrap().as_str());
-            let val = String::from(arg_match.name("val").unwrap().as_str());
-            Some((key, val))
+            let key = String::from_utf8_lossy(arg_match.name("key").unwrap().as_bytes());
+            let val = os_string_from_bytes(arg_match.name("val").unwrap().as_bytes());
+            Some((key.to_string(), val))
         } else {
             None
         }
     }
 }
 
+/// Formats the given config files, printing the result or overwriting them.
+///
+/// # Arguments
+///
+/// * `paths`: Paths of the config files to format
+/// * `write`: Whether to overwrite the files instead of printing the result
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+fn format_config_files(paths: Vec<PathBuf>, write: bool) -> DynErrResult<()> {
+    for path in paths {
+        let path_str = path.to_string_lossy();
+        let formatted = match format_config_file(&path) {
+            Ok(formatted) => formatted,
+            Err(e) => return Err(format!("{}:\n{}", path_str.red(), e).into()),
+        };
+        if !write {
+            println!("{}:", colorize_config_file_path(&path_str));
+            print!("{}", formatted.contents);
+        } else if !formatted.changed {
+            println!(
+                "{}",
+                format!("{} is already formatted", path_str).yamis_info()
+            );
+        } else if formatted.has_comments {
+            let msg = format!(
+                "Skipping {}, it has comments that would be lost after formatting",
+                path_str
+            );
+            eprintln!("{}", msg.yamis_warn());
+        } else {
+            fs::write(&path, formatted.contents)?;
+            println!("{}", format!("Formatted {}", path_str).yamis_info());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the given config file paths, excluding the global config file
+fn get_project_config_file_paths(config_file_paths: ConfigFilePaths) -> DynErrResult<Vec<PathBuf>> {
+    let global_config_dir = ConfigFilePaths::get_global_config_file_dir();
+    let mut paths = Vec::new();
+    for path in config_file_paths {
+        let path = path?;
+        if !path.starts_with(&global_config_dir) {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Checks that the env files used by the given config files declare the same keys as their
+/// example, printing the differences. Env files without an example are skipped with a warning.
+fn check_env_files(paths: Vec<PathBuf>, example: Option<&Path>) -> DynErrResult<()> {
+    let mut checked_env_files: Vec<PathBuf> = Vec::new();
+    let mut all_ok = true;
+    for path in paths {
+        let env_files = match ConfigFile::env_file_paths(&path) {
+            Ok(env_files) => env_files,
+            Err(e) => return Err(format!("{}:\n{}", path.to_string_lossy().red(), e).into()),
+        };
+        for env_file in env_files {
+            if checked_env_files.contains(&env_file) {
+                continue;
+            }
+            let example_path = env_check::get_example_path(&env_file, example);
+            if !example_path.is_file() {
+                let msg = format!(
+                    "Skipping {}, example file {} not found",
+                    env_file.to_string_lossy(),
+                    example_path.to_string_lossy()
+                );
+                eprintln!("{}", msg.yamis_warn());
+                checked_env_files.push(env_file);
+                continue;
+            }
+            let check = env_check::check_env_file(&env_file, &example_path)?;
+            if check.is_ok() {
+                println!(
+                    "{}: {}",
+                    colorize_config_file_path(&env_file.to_string_lossy()),
+                    "OK".green()
+                );
+            } else {
+                all_ok = false;
+                let example_str = check.example.to_string_lossy();
+                println!(
+                    "{}:",
+                    colorize_config_file_path(&env_file.to_string_lossy())
+                );
+                if !check.missing.is_empty() {
+                    println!(
+                        "  Missing keys declared in {}: {}",
+                        example_str,
+                        check.missing.join(", ").red()
+                    );
+                }
+                if !check.extra.is_empty() {
+                    println!(
+                        "  Keys not declared in {}: {}",
+                        example_str,
+                        check.extra.join(", ").yellow()
+                    );
+                }
+            }
+            checked_env_files.push(env_file);
+        }
+    }
+    if !all_ok {
+        return Err("Some env files do not match their example".into());
+    }
+    Ok(())
+}
+
+/// Runs the tests declared in the given config files, printing the result of each of them.
+///
+/// # Arguments
+///
+/// * `paths`: Config files to run the tests of
+/// * `names`: Names of the tests to run. All of them run if empty
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+fn run_config_tests(paths: Vec<PathBuf>, names: &[String]) -> DynErrResult<()> {
+    let mut passed = 0;
+    let mut failed = 0;
+    for path in paths {
+        let mut config_file = match ConfigFile::load(path.clone()) {
+            Ok(config_file) => config_file,
+            Err(e) => return Err(format!("{}:\n{}", path.to_string_lossy().red(), e).into()),
+        };
+        // The result of each test is printed instead of the tasks it runs
+        config_file.debug_config.print_task_name = false;
+        let tests = std::mem::take(&mut config_file.tests);
+        let tests: Vec<_> = tests
+            .iter()
+            .filter(|(name, _)| names.is_empty() || names.contains(name))
+            .collect();
+        if tests.is_empty() {
+            continue;
+        }
+        println!("{}", colorize_config_file_path(&path.to_string_lossy()));
+        for (name, test) in tests {
+            let args = TaskSubcommand::get_task_args(
+                test.get_args().iter().map(OsString::from).collect(),
+                true,
+            );
+            match test.run(&config_file, &args) {
+                Ok(_) => {
+                    passed += 1;
+                    println!("  {} ... {}", name, "ok".green());
+                }
+                Err(reason) => {
+                    failed += 1;
+                    println!("  {} ... {}", name, "FAILED".red());
+                    for line in reason.lines() {
+                        println!("    {}", line);
+                    }
+                }
+            }
+        }
+    }
+    println!("{} passed, {} failed", passed, failed);
+    if failed > 0 {
+        return Err(format!("{} tests failed", failed).into());
+    }
+    Ok(())
+}
+
+/// Lints the scripts of the public tasks of the given config files, or of the given task only,
+/// printing the problems found next to the line of the task script they come from. Tasks that
+/// cannot be rendered, i.e. because they require arguments, are skipped.
+///
+/// # Arguments
+///
+/// * `paths`: Config files to lint the tasks of
+/// * `matches`: Matches of the `lint-scripts` subcommand
+/// * `env_overrides`: Environment variables given with `--env-file`
+/// * `linters`: Linters of the scripts of each shell
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+fn lint_scripts(
+    paths: Vec<PathBuf>,
+    matches: &clap::ArgMatches,
+    env_overrides: &HashMap<String, String>,
+    linters: &LinterSettings,
+) -> DynErrResult<()> {
+    let task_name = matches.get_one::<String>("task");
+    let args = TaskSubcommand::get_task_args(
+        TaskSubcommand::get_args_vec(matches.get_many::<OsString>("args")),
+        true,
+    );
+    let mut linted = 0;
+    let mut problems = 0;
+    let mut found_task = false;
+    let mut missing_linters: Vec<String> = Vec::new();
+    for path in paths {
+        let config_file = match ConfigFile::load(path.clone()) {
+            Ok(config_file) => config_file,
+            Err(e) => return Err(format!("{}:\n{}", path.to_string_lossy().red(), e).into()),
+        };
+        let context = RunContext::new()
+            .with_dry_run(true)
+            .with_env_overrides(env_overrides.clone());
+        let names: Vec<String> = match task_name {
+            Some(name) if config_file.get_public_task(name).is_some() => vec![name.clone()],
+            Some(_) => continue,
+            None => config_file
+                .get_public_task_names()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        };
+        found_task = true;
+        println!("{}", colorize_config_file_path(&path.to_string_lossy()));
+        // Tasks that run other tasks render their scripts too, which are linted only once
+        let mut seen = HashSet::new();
+        for name in names {
+            let task = config_file.get_public_task(&name).unwrap();
+            let commands = match task.render_in(&args, &config_file, &context) {
+                Ok(commands) => commands,
+                Err(e) => {
+                    println!("  {} ... {}", name, "skipped".yellow());
+                    for line in e.to_string().lines() {
+                        println!("    {}", line);
+                    }
+                    continue;
+                }
+            };
+            for command in commands {
+                let Some(script) = command.script() else {
+                    continue;
+                };
+                if !seen.insert((command.task_name().to_string(), script.to_string())) {
+                    continue;
+                }
+                let extension = command.script_extension().unwrap_or_default();
+                let shell_kind = ShellKind::from_script_runner(command.program(), extension);
+                let Some(linter) = linters.get(shell_kind) else {
+                    continue;
+                };
+                if missing_linters.iter().any(|name| name == linter.program()) {
+                    continue;
+                }
+                let findings = match linter.lint(script, command.program(), extension) {
+                    Ok(findings) => findings,
+                    Err(e) if is_not_found(e.as_ref()) => {
+                        let msg = format!(
+                            "Linter `{}` not found, the scripts it lints are skipped",
+                            linter.program()
+                        );
+                        eprintln!("{}", msg.yamis_warn());
+                        missing_linters.push(linter.program().to_string());
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                linted += 1;
+                problems += findings.len();
+                let template = config_file
+                    .get_task_ref(command.task_name())
+                    .and_then(|task| task.get_script());
+                let rendered_lines: Vec<&str> = script.lines().collect();
+                for finding in findings {
+                    let template_line = template
+                        .and_then(|template| get_template_line(template, script, finding.line));
+                    let (location, source) = match (template, template_line) {
+                        (Some(template), Some(line)) => (
+                            format!("{}:{}:{}", command.task_name(), line, finding.column),
+                            template.lines().nth(line - 1),
+                        ),
+                        _ => (
+                            format!(
+                                "{} (rendered):{}:{}",
+                                command.task_name(),
+                                finding.line,
+                                finding.column
+                            ),
+                            rendered_lines.get(finding.line.wrapping_sub(1)).copied(),
+                        ),
+                    };
+                    println!("  {}: {}", colorize_task_name(&location), finding.message);
+                    if let Some(source) = source {
+                        println!("    {}", source.trim().dimmed());
+                    }
+                }
+            }
+        }
+    }
+    if let (Some(task_name), false) = (task_name, found_task) {
+        return Err(format!("Task {} not found", task_name).into());
+    }
+    println!("{} scripts linted, {} problems found", linted, problems);
+    if problems > 0 {
+        return Err(format!("Found {} problems in the scripts", problems).into());
+    }
+    Ok(())
+}
+
 /// Executes the program. If errors are encountered during the execution these
 /// are returned immediately. The wrapping method needs to take care of formatting
 /// and displaying these errors appropriately.
 pub fn exec() -> DynErrResult<()> {
+    // Removes the config files given with `-f -` or `--inline` once the run finishes
+    let _inline_files = InlineFilesCleanup;
     let app = clap::Command::new(clap::crate_name!())
         .version(clap::crate_version!())
         .about(clap::crate_description!())
         .author(clap::crate_authors!())
         .after_help(HELP)
         .allow_external_subcommands(true)
+        // Otherwise `help` could not be used as a task name
+        .disable_help_subcommand(true)
+        .subcommand(run_command())
+        .subcommand(
+            clap::Command::new("fmt")
+                .about("Formats config files. Prints the result unless `--write` is given")
+                .arg(
+                    clap::Arg::new("write")
+                        .long("write")
+                        .help("Overwrites the files with the formatted content. Files with comments are skipped, as these would be lost")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("files")
+                        .action(ArgAction::Append)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Files to format. Defaults to the config files reachable from the current directory, excluding the global one")
+                        .value_name("FILES"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("env")
+                .about("Commands to work with env files")
+                .subcommand_required(true)
+                .subcommand(
+                    clap::Command::new("check")
+                        .about("Checks that the env files used by the config files declare the same keys as their example")
+                        .arg(
+                            clap::Arg::new("example")
+                                .long("example")
+                                .action(ArgAction::Set)
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .help("Example env file to compare against. Defaults to `.env.example` next to each env file")
+                                .value_name("FILE"),
+                        ),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("ws")
+                .about("Commands to work with workspaces")
+                .subcommand_required(true)
+                .subcommand(
+                    run_command()
+                        .about("Runs the given task in each member of the workspace that defines it, after the members they depend on")
+                        .arg(
+                            clap::Arg::new("member")
+                                .long("member")
+                                .action(ArgAction::Append)
+                                .help("Only runs the task in the given member. Can be given multiple times")
+                                .value_name("NAME"),
+                        ),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("current")
+                .about("Prints the nearest config file and its number of tasks, i.e. for shell prompts. Prints nothing if there is none")
+                .arg(
+                    clap::Arg::new("format")
+                        .long("format")
+                        .action(ArgAction::Set)
+                        .default_value(DEFAULT_CURRENT_FORMAT)
+                        .help("Format of the output. `{name}` is replaced by the name of the config file, `{dir}` by its directory and `{tasks}` by its number of public tasks")
+                        .value_name("FORMAT"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("__complete")
+                .about("Prints the completions of the last of the given words, which are the ones given after `yamis`")
+                .hide(true)
+                .arg(
+                    clap::Arg::new("words")
+                        .num_args(0..)
+                        .allow_hyphen_values(true)
+                        .trailing_var_arg(true)
+                        .value_name("WORDS"),
+                ),
+        )
+        .subcommand(clap::Command::new("ps").about("Lists the tasks being run by yamis"))
+        .subcommand(
+            clap::Command::new("kill")
+                .about("Gracefully stops a task being run by yamis, as listed by `ps`")
+                .arg(
+                    clap::Arg::new("id")
+                        .required(true)
+                        .help("Id of the run")
+                        .value_name("ID"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("replay")
+                .about("Runs the commands of a snapshot taken with `--snapshot-env` again, with the same environment")
+                .arg(
+                    clap::Arg::new("snapshot")
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Snapshot file")
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("export-script")
+                .about("Exports a task as a standalone script that runs its commands without yamis, rendered as they would run now")
+                .arg(
+                    clap::Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .required(true)
+                        .action(ArgAction::Set)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("File to write the script to")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    clap::Arg::new("shell")
+                        .long("shell")
+                        .action(ArgAction::Set)
+                        .default_value(ExportShell::default_name())
+                        .value_parser(ExportShell::NAMES)
+                        .help("Shell the script is written for")
+                        .value_name("SHELL"),
+                )
+                .arg(
+                    clap::Arg::new("task")
+                        .required(true)
+                        .help("Task to export")
+                        .value_name("TASK"),
+                )
+                .arg(
+                    clap::Arg::new("args")
+                        .action(ArgAction::Append)
+                        .last(true)
+                        .value_parser(clap::value_parser!(OsString))
+                        .help("Arguments of the task, given after `--`")
+                        .value_name("ARGS"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("lint-scripts")
+                .about("Renders the scripts of the tasks and lints them with the linter of their shell, i.e. shellcheck, printing the problems found next to the line of the task script they come from")
+                .arg(
+                    clap::Arg::new("task")
+                        .help("Task to lint. Defaults to all the public tasks of the config files reachable from the current directory, excluding the global one")
+                        .value_name("TASK"),
+                )
+                .arg(
+                    clap::Arg::new("args")
+                        .action(ArgAction::Append)
+                        .last(true)
+                        .value_parser(clap::value_parser!(OsString))
+                        .help("Arguments the scripts are rendered with, given after `--`")
+                        .value_name("ARGS"),
+                ),
+        )
         .arg(
             clap::Arg::new("list")
                 .short('l')
                 .long("list")
-                .help("Lists configuration files that can be reached from the current directory")
-                .conflicts_with_all(["file"])
+                .help("Lists the configuration files given with `-f`, the ones that can be reached from the current directory and the global one")
                 .action(ArgAction::SetTrue),
         )
         .arg(
@@ -402,50 +1360,208 @@ pub fn exec() -> DynErrResult<()> {
                 .short('i')
                 .long("task-info")
                 .action(ArgAction::Set)
-                .help("Displays information about the given task")
+                .help("Displays information about the given task in all the config files. Accepts glob patterns, i.e. `build_*`, and shows similar tasks if none has the given name")
                 .value_name("TASK"),
         )
+        .arg(
+            clap::Arg::new("show-env")
+                .long("show-env")
+                .requires("task-info")
+                .help("With `--task-info`, prints the environment variables the task sets and where each value comes from, without running it")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             clap::Arg::new("file")
                 .short('f')
                 .long("file")
+                .action(ArgAction::Append)
+                .help("Search for tasks in the given file, or in the config files of the given directory. Can be given multiple times to search in all of them, in order. `-` reads a YAML config file from stdin. Defaults to the `YAMIS_FILE` environment variable, if set")
+                .value_name("FILE"),
+        )
+        .arg(
+            clap::Arg::new("inline")
+                .long("inline")
+                .action(ArgAction::Set)
+                .conflicts_with("file")
+                .help("Search for tasks in the given YAML config file contents, i.e. `--inline 'tasks: {hi: {script: echo hi}}'`")
+                .value_name("CONFIG"),
+        )
+        .arg(
+            clap::Arg::new("walk-up")
+                .long("walk-up")
+                .help("If the file given with `-f` is a directory, also searches its parents and the global config file")
+                .requires("file")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("cwd")
+                .long("cwd")
                 .action(ArgAction::Set)
-                .help("Search for tasks in the given file")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Changes the working directory before searching the config files and running the task")
+                .value_name("DIR"),
+        )
+        .arg(
+            clap::Arg::new("env-file")
+                .long("env-file")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Loads the environment variables of the given file, which take precedence over the ones of the config file. Can be given multiple times")
                 .value_name("FILE"),
         )
         .arg(
-            clap::Arg::new("update")
-                .long("update")
-                .help("Checks for updates and updates the binary if necessary")
-                .exclusive(true)
+            clap::Arg::new("var")
+                .long("var")
+                .action(ArgAction::Append)
+                .help("Overrides the variable declared in `vars` with the given value, i.e. `--var name=John`. Can be given multiple times")
+                .value_name("KEY=VALUE"),
+        )
+        .arg(
+            clap::Arg::new("fan-out")
+                .long("fan-out")
+                .help("Runs the task once per env file, instead of merging them")
+                .requires("env-file")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("execution-backend")
+                .long("execution-backend")
+                .action(ArgAction::Set)
+                .value_parser(ExecutionBackendKind::NAMES)
+                .default_value("process")
+                .help("Backend that executes tasks. `record` prints the commands instead of running them")
+                .value_name("BACKEND"),
+        )
+        .arg(
+            clap::Arg::new("force")
+                .long("force")
+                .help("Runs the task even if another yamis process is running it")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("no-script-echo")
+                .long("no-script-echo")
+                .help("Does not print the body of the scripts when the commands are printed instead of being run")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("timestamps")
+                .long("timestamps")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("elapsed")
+                .value_parser(TimestampKind::NAMES)
+                .help("Prefixes each line of the output of the commands with the time since the task started, or with the UTC wall-clock time with `--timestamps=wall`. The output of the commands is piped to do so")
+                .value_name("KIND"),
+        )
+        .arg(
+            clap::Arg::new("hints")
+                .long("hints")
+                .action(ArgAction::SetTrue)
+                .help("Prints a hint below the error when a command fails and its output matches a common problem, i.e. a port already in use. The output of the commands is piped to do so"),
+        )
+        .arg(
+            clap::Arg::new("snapshot-env")
+                .long("snapshot-env")
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Writes the environment, rendered commands and config file hashes of the run to the given JSON file, which can be run again with `replay`")
+                .value_name("FILE"),
+        )
+        .arg(
+            clap::Arg::new("trace-templates")
+                .long("trace-templates")
+                .help("Prints each tag of the templates and the value it rendered to, masking the ones that might contain secrets")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("backtrace")
+                .long("backtrace")
+                .help("Prints a backtrace if yamis crashes unexpectedly")
+                .action(ArgAction::SetTrue),
+        )
+        // Accepted even if the binary cannot check for updates, so that scripts passing it work
+        // with any build
+        .arg(
+            clap::Arg::new("no-update-check")
+                .long("no-update-check")
+                .help("Does not check whether a new release is available in this run, i.e. in scripts or CI. Same as setting the `YAMIS_NO_UPDATE_CHECK` environment variable")
                 .action(ArgAction::SetTrue),
         );
+    #[cfg(feature = "watch")]
+    let app = app.arg(
+        clap::Arg::new("watch")
+            .long("watch")
+            .help("Runs the task again whenever the files given in its `watch` or `sources` change, until ctrl-c is pressed")
+            .action(ArgAction::SetTrue),
+    );
+    #[cfg(feature = "updater")]
+    let app = app.arg(
+        clap::Arg::new("update")
+            .long("update")
+            .help("Checks for updates and updates the binary if necessary")
+            .exclusive(true)
+            .action(ArgAction::SetTrue),
+    );
+    #[cfg(feature = "daemon")]
+    let app = app
+        .subcommand(
+            clap::Command::new("daemon")
+                .about("Starts a daemon that lists and runs tasks through a JSON API in a local socket")
+                .arg(socket_arg()),
+        )
+        .subcommand(
+            clap::Command::new("client")
+                .about("Sends a JSON request to the daemon and prints the responses")
+                .arg(socket_arg())
+                .arg(
+                    clap::Arg::new("request")
+                        .required(true)
+                        .help("Request to send, i.e. '{\"command\": \"list\", \"dir\": \".\"}'")
+                        .value_name("REQUEST"),
+                ),
+        );
     let matches = app.get_matches();
 
-    if matches.get_one::<bool>("update").cloned().unwrap_or(false) {
+    panic_hook::set_show_backtrace(matches.get_flag("backtrace"));
+
+    if let Some(dir) = matches.get_one::<PathBuf>("cwd") {
+        if let Err(e) = env::set_current_dir(dir) {
+            let e = format!(
+                "Could not change the working directory to {}: {}",
+                dir.display(),
+                e
+            );
+            return Err(e.into());
+        }
+    }
+
+    #[cfg(not(feature = "colors"))]
+    colored::control::set_override(false);
+
+    let settings = ConfigFile::load_global_settings();
+    // The output of yamis invocations made by tasks is indented under the one of their parent
+    let indent = nesting::get_indent();
+    let mut theme = settings.output.clone();
+    theme.prefix = format!("{}{}", indent, theme.prefix);
+    print_utils::set_theme(theme);
+
+    #[cfg(feature = "updater")]
+    if matches.get_flag("update") {
         updater::update()?;
         return Ok(());
     } else {
-        match updater::check_update_available() {
-            Ok(result) => {
-                if let Some(msg) = result {
-                    println!("{}", msg.yamis_prefix_info());
-                }
-            }
-            Err(e) => {
-                let err_msg = format!("Error checking for updates: {}", e);
-                eprintln!("{}", err_msg.yamis_error());
-            }
-        }
+        print_update_available(&matches, &settings);
     }
 
     let current_dir = env::current_dir()?;
-    let mut file_containers = ConfigFileContainers::new();
+    let mut file_containers = ConfigFileContainers::new(&settings, matches.get_flag("force"));
+    if let Some(vars) = matches.get_many::<String>("var") {
+        file_containers.var_overrides = parse_var_overrides(vars)?;
+    }
+    file_containers.trace_templates = matches.get_flag("trace-templates");
 
-    let config_file_paths = match matches.get_one::<String>("file") {
-        None => ConfigFilePaths::new(&current_dir),
-        Some(file_path) => ConfigFilePaths::only(file_path)?,
-    };
+    let config_file_paths = get_config_file_paths(&matches, &current_dir, settings.global_tasks)?;
 
     if matches
         .get_one::<bool>("list-tasks")
@@ -457,21 +1573,953 @@ pub fn exec() -> DynErrResult<()> {
     };
 
     if let Some(task_name) = matches.get_one::<String>("task-info") {
-        file_containers.print_task_info(config_file_paths, task_name)?;
+        let env_overrides = match matches.get_flag("show-env") {
+            true => {
+                let env_files: Vec<&PathBuf> = matches
+                    .get_many::<PathBuf>("env-file")
+                    .map(|files| files.collect())
+                    .unwrap_or_default();
+                Some(merge_env_files(&env_files)?)
+            }
+            false => None,
+        };
+        file_containers.print_task_info(config_file_paths, task_name, env_overrides.as_ref())?;
         return Ok(());
     };
 
     if matches.get_one::<bool>("list").cloned().unwrap_or(false) {
-        for path in config_file_paths {
+        return print_config_files(&matches, &current_dir, settings.global_tasks);
+    }
+
+    if let Some(("fmt", fmt_matches)) = matches.subcommand() {
+        let paths = match fmt_matches.get_many::<PathBuf>("files") {
+            Some(files) => files.cloned().collect(),
+            None => get_project_config_file_paths(config_file_paths)?,
+        };
+        return format_config_files(paths, fmt_matches.get_flag("write"));
+    }
+
+    match matches.subcommand() {
+        Some(("env", env_matches)) => {
+            let Some(("check", check_matches)) = env_matches.subcommand() else {
+                unreachable!("A subcommand is required");
+            };
+            let paths = get_project_config_file_paths(config_file_paths)?;
+            return check_env_files(
+                paths,
+                check_matches
+                    .get_one::<PathBuf>("example")
+                    .map(PathBuf::as_path),
+            );
+        }
+        #[cfg(feature = "daemon")]
+        Some(("daemon", daemon_matches)) => return run_daemon(daemon_matches),
+        #[cfg(feature = "daemon")]
+        Some(("client", client_matches)) => return run_client(client_matches),
+        Some(("__complete", complete_matches)) => {
+            let words: Vec<String> = complete_matches
+                .get_many::<String>("words")
+                .map(|words| words.cloned().collect())
+                .unwrap_or_default();
+            for candidate in completion::complete(config_file_paths, &words) {
+                println!("{}", candidate);
+            }
+            return Ok(());
+        }
+        Some(("current", current_matches)) => {
+            let format = current_matches.get_one::<String>("format").unwrap();
+            return print_current(config_file_paths, format);
+        }
+        Some(("replay", replay_matches)) => {
+            return replay_snapshot(replay_matches.get_one::<PathBuf>("snapshot").unwrap());
+        }
+        Some(("export-script", export_matches)) => {
+            let env_files: Vec<&PathBuf> = matches
+                .get_many::<PathBuf>("env-file")
+                .map(|files| files.collect())
+                .unwrap_or_default();
+            return export_task_script(
+                &mut file_containers,
+                config_file_paths,
+                export_matches,
+                &merge_env_files(&env_files)?,
+            );
+        }
+        Some(("lint-scripts", lint_matches)) => {
+            let env_files: Vec<&PathBuf> = matches
+                .get_many::<PathBuf>("env-file")
+                .map(|files| files.collect())
+                .unwrap_or_default();
+            let paths = get_project_config_file_paths(config_file_paths)?;
+            return lint_scripts(
+                paths,
+                lint_matches,
+                &merge_env_files(&env_files)?,
+                &settings.linters,
+            );
+        }
+        Some(("ps", _)) => return print_runs(),
+        Some(("kill", kill_matches)) => {
+            let id = kill_matches.get_one::<String>("id").unwrap();
+            let run = runs::stop_run(&runs::default_runs_dir()?, id)?;
+            println!(
+                "{}",
+                format!("Stopping task {} with id {}", run.task, run.id).yamis_info()
+            );
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // `test` is a common task name, so the tests of the config files only run if no task is
+    // named like it
+    if let Some(("test", sub_matches)) = matches.subcommand() {
+        let paths = get_config_file_paths(&matches, &current_dir, settings.global_tasks)?;
+        if !paths
+            .map_while(Result::ok)
+            .any(|path| may_have_public_task(&path, "test"))
+        {
+            let names: Vec<String> = TaskSubcommand::get_args_vec(sub_matches.get_many(""))
+                .iter()
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect();
+            let paths = get_project_config_file_paths(config_file_paths)?;
+            return run_config_tests(paths, &names);
+        }
+    }
+
+    // Unknown subcommands run the plugin with the same name, unless there is a task named like it.
+    // Tasks are looked up first, as they are cheaper to find than plugins in the PATH
+    if let Some((name, sub_matches)) = matches.subcommand() {
+        if name != "run" && name != "ws" {
+            let has_task =
+                match get_config_file_paths(&matches, &current_dir, settings.global_tasks) {
+                    Ok(paths) => paths
+                        .map_while(Result::ok)
+                        .any(|path| may_have_public_task(&path, name)),
+                    // Plugins do not need config files
+                    Err(_) => false,
+                };
+            if !has_task {
+                if let Some(plugin) = plugins::find_plugin(name) {
+                    let args = TaskSubcommand::get_args_vec(sub_matches.get_many::<OsString>(""));
+                    return plugins::run_plugin(&plugin, &args);
+                }
+            }
+        }
+    }
+
+    let (task_command, ws_run_matches) = match matches.subcommand() {
+        Some(("ws", ws_matches)) => (
+            TaskSubcommand::new(ws_matches)?,
+            ws_matches.subcommand_matches("run"),
+        ),
+        _ => (TaskSubcommand::new(&matches)?, None),
+    };
+
+    let backend_kind: ExecutionBackendKind = matches
+        .get_one::<String>("execution-backend")
+        .unwrap()
+        .parse()?;
+    let script_echo = if matches.get_flag("no-script-echo") {
+        ScriptEcho::Hidden
+    } else {
+        ScriptEcho::Highlighted
+    };
+
+    let timestamps: Option<TimestampKind> = match matches.get_one::<String>("timestamps") {
+        Some(kind) => Some(kind.parse()?),
+        None => None,
+    };
+
+    let backend: Box<dyn ExecutionBackend> = match backend_kind {
+        ExecutionBackendKind::Process => {
+            // Tracking is not essential, so the task runs even if the state file cannot be written
+            let backend = match runs::default_runs_dir()
+                .and_then(|runs_dir| RunTracker::start(&runs_dir, &task_command.task, &current_dir))
+            {
+                Ok(run) => ProcessBackend::tracked(run),
+                Err(_) => ProcessBackend::new(),
+            };
+            let backend = match timestamps {
+                Some(kind) => backend.with_timestamps(kind),
+                None => backend,
+            };
+            let backend = match indent.is_empty() {
+                true => backend,
+                false => backend.with_indent(indent),
+            };
+            if settings.hints.enabled || matches.get_flag("hints") {
+                Box::new(backend.with_hints(Hints::new(&settings.hints)))
+            } else {
+                Box::new(backend)
+            }
+        }
+        _ => backend_kind.create(script_echo),
+    };
+    let backend: Box<dyn ExecutionBackend> = match matches.get_one::<PathBuf>("snapshot-env") {
+        Some(path) => Box::new(SnapshotBackend::new(backend, path.clone())),
+        None => backend,
+    };
+    let backend: Box<dyn ExecutionBackend> = if backend.is_dry_run() {
+        // Printed to stderr so that the recorded commands can be compared against golden files
+        eprintln!("{}", print_utils::theme().messages.dry_run.yamis_warn());
+        backend
+    } else if nesting::get_depth() > 0 {
+        // The status and report belong to the outermost run, which runs this one as a step
+        backend
+    } else {
+        Box::new(
+            StatusBackend::new(backend, &task_command.task).with_report(settings.report.clone()),
+        )
+    };
+    #[cfg(feature = "otel")]
+    let backend: Box<dyn ExecutionBackend> = match otel::OtelConfig::from_env(env::vars()) {
+        Ok(Some(config)) if !backend.is_dry_run() => {
+            Box::new(otel::OtelBackend::new(backend, config, &task_command.task))
+        }
+        Ok(_) => backend,
+        Err(e) => {
+            eprintln!("{}", e.yamis_warn());
+            backend
+        }
+    };
+
+    let env_files: Vec<&PathBuf> = matches
+        .get_many::<PathBuf>("env-file")
+        .map(|files| files.collect())
+        .unwrap_or_default();
+
+    if let Some(ws_run_matches) = ws_run_matches {
+        if matches.get_flag("fan-out") {
+            return Err("`--fan-out` cannot be used with `ws run`".into());
+        }
+        if !task_command.chain.is_empty() {
+            return Err("Tasks cannot be chained with `&&` or `||` in `ws run`".into());
+        }
+        return run_workspace_task(
+            &mut file_containers,
+            config_file_paths,
+            ws_run_matches,
+            task_command,
+            &merge_env_files(&env_files)?,
+            backend.as_ref(),
+        );
+    }
+
+    #[cfg(feature = "watch")]
+    if matches.get_flag("watch") {
+        if matches.get_flag("fan-out") {
+            return Err("`--watch` cannot be used with `--fan-out`".into());
+        }
+        if !task_command.chain.is_empty() {
+            return Err("Tasks cannot be chained with `&&` or `||` when using `--watch`".into());
+        }
+        let env_overrides = merge_env_files(&env_files)?;
+        return run_watch(
+            &mut file_containers,
+            &task_command,
+            &env_overrides,
+            || get_config_file_paths(&matches, &current_dir, settings.global_tasks),
+            backend.as_ref(),
+        );
+    }
+
+    if !matches.get_flag("fan-out") {
+        let env_overrides = merge_env_files(&env_files)?;
+        let mut config_file_paths = config_file_paths;
+        let mut result = file_containers.run_task(
+            config_file_paths.by_ref(),
+            &task_command.task,
+            task_command.args,
+            &env_overrides,
+            backend.as_ref(),
+        );
+        if result.is_err() {
+            if let Some(e) = config_file_paths.not_found_error() {
+                return Err(e.into());
+            }
+        }
+        // Like in shells, each operator looks at the result of the last task that ran
+        for (operator, chained_task) in task_command.chain {
+            match (operator, &result) {
+                (ChainOperator::And, Ok(_)) => {}
+                (ChainOperator::Or, Err(e)) => eprintln!("{}", e.to_string().yamis_error()),
+                _ => continue,
+            }
+            result = file_containers.run_task(
+                get_config_file_paths(&matches, &current_dir, settings.global_tasks)?,
+                &chained_task.task,
+                chained_task.args,
+                &env_overrides,
+                backend.as_ref(),
+            );
+        }
+        return result;
+    }
+
+    if !task_command.chain.is_empty() {
+        return Err("Tasks cannot be chained with `&&` or `||` when using `--fan-out`".into());
+    }
+
+    // Each run consumes the config file paths, so they are searched again for each environment
+    let mut failed: Vec<&PathBuf> = Vec::new();
+    for env_file in &env_files {
+        let env_file_name = env_file.to_string_lossy();
+        println!("{}", format!("Environment: {}", env_file_name).yamis_info());
+        let result = read_env_file(env_file).and_then(|env_overrides| {
+            file_containers.run_task(
+                get_config_file_paths(&matches, &current_dir, settings.global_tasks)?,
+                &task_command.task,
+                task_command.args.clone(),
+                &HashMap::from_iter(env_overrides),
+                backend.as_ref(),
+            )
+        });
+        match result {
+            Ok(_) => println!("{}", format!("{}: OK", env_file_name).yamis_info()),
+            Err(e) => {
+                eprintln!("{}", format!("{}: {}", env_file_name, e).yamis_error());
+                failed.push(env_file);
+            }
+        }
+    }
+
+    println!(
+        "{}",
+        format!(
+            "{} of {} environments succeeded",
+            env_files.len() - failed.len(),
+            env_files.len()
+        )
+        .yamis_info()
+    );
+    if !failed.is_empty() {
+        let failed: Vec<_> = failed.iter().map(|path| path.to_string_lossy()).collect();
+        return Err(format!("Task failed for the environments: {}", failed.join(", ")).into());
+    }
+    Ok(())
+}
+
+/// Runs the task, and runs it again whenever its files change, until ctrl-c is pressed
+///
+/// # Arguments
+///
+/// * `file_containers`: Containers of the config files
+/// * `task_command`: Task to run and its arguments
+/// * `env_overrides`: Environment variables given with `--env-file`
+/// * `get_paths`: Returns the config files to search the task in, as they are searched again
+///   each time
+/// * `backend`: Backend that executes the commands
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+#[cfg(feature = "watch")]
+fn run_watch(
+    file_containers: &mut ConfigFileContainers,
+    task_command: &TaskSubcommand,
+    env_overrides: &HashMap<String, String>,
+    get_paths: impl Fn() -> DynErrResult<ConfigFilePaths>,
+    backend: &dyn ExecutionBackend,
+) -> DynErrResult<()> {
+    // Set before any command runs, so that the handler set by the process backend is ignored,
+    // and ctrl-c stops the running command, which gets the signal too, and then the whole loop
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let stop = Arc::clone(&stop);
+        ctrlc::set_handler(move || stop.store(true, Ordering::SeqCst))?;
+    }
+    // Config files found in the last search, watched while the task cannot be loaded
+    let mut config_file_paths = Vec::new();
+    // Errors loading the task the first time are returned, as nothing was watched yet
+    let mut first_run = true;
+    loop {
+        // Read again each time, as the patterns might have changed in the config file
+        let found = get_paths().and_then(|paths| {
+            let paths: Vec<DynErrResult<PathBuf>> = paths.collect();
+            config_file_paths = paths
+                .iter()
+                .filter_map(|path| path.as_ref().ok())
+                .cloned()
+                .collect();
+            file_containers.get_watch_patterns(paths, &task_command.task)
+        });
+        let (config_file_path, patterns) = match found {
+            Ok(found) => found,
+            // I.e. a syntax error in the config file, which the next change might fix
+            Err(e) if !first_run && !config_file_paths.is_empty() => {
+                eprintln!("{}", e.to_string().yamis_error());
+                println!(
+                    "{}",
+                    "Waiting for changes to the config file...".yamis_info()
+                );
+                let watcher = FileWatcher::files(&config_file_paths)?;
+                if !watcher.wait_for_change(&stop) {
+                    return Ok(());
+                }
+                file_containers.clear();
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        first_run = false;
+        let base_dir = config_file_path.parent().unwrap_or(Path::new("."));
+        let watcher =
+            FileWatcher::new(base_dir, &patterns, std::slice::from_ref(&config_file_path))?;
+        let result = get_paths().and_then(|paths| {
+            file_containers.run_task(
+                paths,
+                &task_command.task,
+                task_command.args.clone(),
+                env_overrides,
+                backend,
+            )
+        });
+        if stop.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        // Errors do not stop the loop, as the next change might fix them
+        if let Err(e) = result {
+            eprintln!("{}", e.to_string().yamis_error());
+        }
+        println!("{}", "Waiting for changes...".yamis_info());
+        if !watcher.wait_for_change(&stop) {
+            return Ok(());
+        }
+        file_containers.clear();
+    }
+}
+
+/// Returns the command that runs a task, with the task name taken as the first value so that a
+/// `--` right after it is kept as part of the arguments instead of being consumed by clap
+fn run_command() -> clap::Command {
+    clap::Command::new("run")
+        .about(
+            "Runs the given task. Arguments after `--` are always passed as positional arguments",
+        )
+        .arg(
+            clap::Arg::new("args")
+                .required(true)
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(OsString))
+                .help("Task to run, followed by the task arguments")
+                .value_names(["TASK", "ARGS"]),
+        )
+}
+
+/// Returns the variables given with `--var KEY=VALUE`, where later values take precedence
+fn parse_var_overrides<'a>(
+    values: impl IntoIterator<Item = &'a String>,
+) -> DynErrResult<IndexMap<String, String>> {
+    lazy_static! {
+        static ref VAR_NAME_REGEX: regex::Regex =
+            regex::Regex::new(r"^[_a-zA-Z][_\-a-zA-Z0-9]*$").unwrap();
+    }
+    let mut vars = IndexMap::new();
+    for value in values {
+        let Some((key, val)) = value.split_once('=') else {
+            return Err(format!("Invalid variable `{}`, expected KEY=VALUE", value).into());
+        };
+        if key.contains('.') {
+            return Err(format!(
+                "Invalid variable name `{}`, variables cannot be nested",
+                key
+            )
+            .into());
+        }
+        if !VAR_NAME_REGEX.is_match(key) {
+            return Err(format!("Invalid variable name `{}`", key).into());
+        }
+        vars.insert(String::from(key), String::from(val));
+    }
+    Ok(vars)
+}
+
+/// Returns the variables of the given env files, where later files take precedence
+fn merge_env_files(env_files: &[&PathBuf]) -> DynErrResult<HashMap<String, String>> {
+    let mut env_overrides = HashMap::new();
+    for env_file in env_files {
+        env_overrides.extend(read_env_file(env_file)?);
+    }
+    Ok(env_overrides)
+}
+
+/// Runs the task in each member of the workspace declared in the nearest config file that has
+/// one. Members that do not define the task are skipped, and it stops at the first failure.
+///
+/// # Arguments
+///
+/// * `file_containers`: Containers to load the config files with
+/// * `config_file_paths`: Config files to search the workspace in
+/// * `ws_run_matches`: Matches of the `ws run` subcommand
+/// * `task_command`: Task to run and its arguments
+/// * `env_overrides`: Environment variables that take precedence over the ones of the
+///   config files and task
+/// * `backend`: Backend that executes the commands
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+fn run_workspace_task(
+    file_containers: &mut ConfigFileContainers,
+    config_file_paths: ConfigFilePaths,
+    ws_run_matches: &clap::ArgMatches,
+    task_command: TaskSubcommand,
+    env_overrides: &HashMap<String, String>,
+    backend: &dyn ExecutionBackend,
+) -> DynErrResult<()> {
+    let mut root = None;
+    for path in get_project_config_file_paths(config_file_paths)? {
+        if let Some(workspace) = ConfigFile::workspace_of(&path)? {
+            root = Some((path, workspace));
+            break;
+        }
+    }
+    let Some((root_path, workspace)) = root else {
+        return Err(
+            "No workspace found. Declare one in the `workspace` section of a config file".into(),
+        );
+    };
+    let root_dir = root_path.parent().unwrap();
+    let only: Vec<&str> = ws_run_matches
+        .get_many::<String>("member")
+        .map(|members| members.map(String::as_str).collect())
+        .unwrap_or_default();
+
+    let current_dir = env::current_dir()?;
+    let mut found = false;
+    for member in workspace.ordered_members(&only)? {
+        let member_dir = root_dir.join(member);
+        if !member_dir.is_dir() {
+            return Err(format!(
+                "Workspace member `{}` is not a directory: {}",
+                member,
+                member_dir.to_string_lossy()
+            )
+            .into());
+        }
+        // Only the config files of the member, not the ones of the workspace or its parents
+        let mut paths = Vec::new();
+        for path in ConfigFilePaths::new(&member_dir) {
             let path = path?;
-            println!("{}", colorize_config_file_path(&path.to_string_lossy()));
+            if path.starts_with(&member_dir) {
+                paths.push(path);
+            }
         }
+        if !paths
+            .iter()
+            .any(|path| may_have_public_task(path, &task_command.task))
+        {
+            continue;
+        }
+        found = true;
+
+        println!("{}", format!("Member: {}", member).yamis_info());
+        env::set_current_dir(&member_dir)?;
+        let result = file_containers.run_task(
+            paths.into_iter().map(Ok),
+            &task_command.task,
+            task_command.args.clone(),
+            env_overrides,
+            backend,
+        );
+        env::set_current_dir(&current_dir)?;
+        if let Err(e) = result {
+            return Err(format!("Workspace member {}:\n{}", member, e).into());
+        }
+    }
+    if !found {
+        return Err(format!(
+            "Task {} not found in any workspace member",
+            task_command.task
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Returns whether the value of an environment variable used as a flag enables it, which is
+/// the case for any value except an empty one, `0` and `false`
+#[cfg(feature = "updater")]
+fn is_env_flag_set(value: Option<&str>) -> bool {
+    match value {
+        Some(value) => {
+            let value = value.trim();
+            !value.is_empty() && value != "0" && !value.eq_ignore_ascii_case("false")
+        }
+        None => false,
+    }
+}
+
+/// Prints a message if a new release of yamis is available, as set in the settings. Nothing
+/// is checked if it is disabled for this run with `--no-update-check` or
+/// `YAMIS_NO_UPDATE_CHECK`, if the output is not a terminal, or is machine readable, as the
+/// message would get mixed with it, or if yamis was invoked by a task, as the parent already
+/// checked.
+#[cfg(feature = "updater")]
+fn print_update_available(matches: &clap::ArgMatches, settings: &Settings) {
+    if settings.update_check == UpdateCheck::Off
+        || matches.get_flag("no-update-check")
+        || is_env_flag_set(env::var("YAMIS_NO_UPDATE_CHECK").ok().as_deref())
+        || !std::io::stdout().is_terminal()
+        || nesting::get_depth() > 0
+        || matches!(
+            matches.subcommand(),
+            Some(("client", _)) | Some(("current", _))
+        )
+    {
+        return;
+    }
+    match updater::check_update_available(settings.update_check_interval()) {
+        Ok(Some(msg)) => println!("{}", msg.yamis_prefix_info()),
+        Ok(None) => {}
+        Err(e) => {
+            if settings.update_check == UpdateCheck::Notify {
+                let err_msg = format!("Error checking for updates: {}", e);
+                eprintln!("{}", err_msg.yamis_error());
+            }
+        }
+    }
+}
+
+/// Returns the config file given in the `YAMIS_FILE` environment variable, if set and not empty.
+/// Yamis exports it to the commands of tasks, so that yamis invocations in them find the same
+/// config file, and it works like `-f` otherwise.
+fn get_env_config_file() -> Option<String> {
+    env::var("YAMIS_FILE").ok().filter(|file| !file.is_empty())
+}
+
+/// Returns the config file paths to search tasks in, which are the files given with `-f`,
+/// the ones found in the directories given with `-f`, the ones given in `YAMIS_FILE`, or the
+/// ones found from the current directory.
+///
+/// # Arguments
+///
+/// * `matches`: Matches of the command line arguments
+/// * `current_dir`: Directory to search the config files from
+/// * `global_tasks`: Whether the global config file is searched, when no file is given
+///
+/// returns: Result<ConfigFilePaths, Box<dyn Error, Global>>
+fn get_config_file_paths(
+    matches: &clap::ArgMatches,
+    current_dir: &Path,
+    global_tasks: GlobalTasks,
+) -> DynErrResult<ConfigFilePaths> {
+    if let Some(contents) = matches.get_one::<String>("inline") {
+        return ConfigFilePaths::inline(contents);
+    }
+    let files: Vec<&String> = match matches.get_many::<String>("file") {
+        Some(files) => files.collect(),
+        None => {
+            return match get_env_config_file() {
+                Some(file) => ConfigFilePaths::only(&file),
+                None if global_tasks == GlobalTasks::Off => {
+                    Ok(ConfigFilePaths::new(current_dir).without_global())
+                }
+                None => Ok(ConfigFilePaths::new(current_dir)),
+            }
+        }
+    };
+    let walk_up = matches.get_flag("walk-up");
+    if let [file] = files.as_slice() {
+        return given_file_config_paths(file, walk_up);
+    }
+
+    // Files are searched in the given order, each one only once
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for file in files {
+        let mut file_paths = given_file_config_paths(file, walk_up)?;
+        for path in file_paths.by_ref() {
+            let path = path?;
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        if let Some(e) = file_paths.not_found_error() {
+            return Err(e.into());
+        }
+    }
+    Ok(ConfigFilePaths::from_paths(paths))
+}
+
+/// Returns the config file paths for a file given with `-f`, which can also be a directory, or
+/// `-` to read it from stdin
+fn given_file_config_paths(file: &str, walk_up: bool) -> DynErrResult<ConfigFilePaths> {
+    if file == "-" {
+        let mut contents = String::new();
+        std::io::stdin().read_to_string(&mut contents)?;
+        ConfigFilePaths::inline(&contents)
+    } else if Path::new(file).is_dir() {
+        Ok(ConfigFilePaths::in_dir(file, walk_up))
+    } else {
+        ConfigFilePaths::only(file)
+    }
+}
+
+/// Prints the nearest project config file and its number of public tasks, with the given
+/// format. The task index is used if possible, so that unchanged files are not parsed again.
+/// Nothing is printed if there is no project config file.
+///
+/// # Arguments
+///
+/// * `config_file_paths`: Paths of the config files, nearest first
+/// * `format`: Format of the output, with `{name}`, `{dir}` and `{tasks}` placeholders
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+fn print_current(config_file_paths: ConfigFilePaths, format: &str) -> DynErrResult<()> {
+    let global_config_dir = ConfigFilePaths::get_global_config_file_dir();
+    let path = config_file_paths
+        .map_while(Result::ok)
+        .find(|path| !path.starts_with(&global_config_dir));
+    let Some(path) = path else {
         return Ok(());
+    };
+    let tasks = count_public_tasks(&path)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    println!(
+        "{}",
+        format
+            .replace("{name}", &name)
+            .replace("{dir}", &dir.to_string_lossy())
+            .replace("{tasks}", &tasks.to_string())
+    );
+    Ok(())
+}
+
+/// Returns the number of public tasks of the config file in the given path. The task index is
+/// used if possible, so that unchanged files are not parsed again.
+fn count_public_tasks(path: &Path) -> DynErrResult<usize> {
+    match TaskIndex::load(path) {
+        Some(index) => Ok(index.get_public_task_names().len()),
+        None => {
+            let config_file = ConfigFile::load(path.to_path_buf())?;
+            TaskIndex::store(&config_file);
+            Ok(config_file.get_public_task_names().len())
+        }
     }
+}
 
-    let task_command = TaskSubcommand::new(&matches)?;
+/// Prints the config files given with `-f`, the ones that can be reached from the current
+/// directory and the global config file, grouped by where they come from. Files without public
+/// tasks are dimmed.
+///
+/// # Arguments
+///
+/// * `matches`: Matches of the command line arguments
+/// * `current_dir`: Directory to search the config files from
+/// * `global_tasks`: Whether the global config file is searched
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+fn print_config_files(
+    matches: &clap::ArgMatches,
+    current_dir: &Path,
+    global_tasks: GlobalTasks,
+) -> DynErrResult<()> {
+    let mut groups: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    if matches.contains_id("file") || matches.contains_id("inline") {
+        let paths = get_config_file_paths(matches, current_dir, global_tasks)?;
+        groups.push((
+            String::from("Given with -f"),
+            paths.collect::<DynErrResult<_>>()?,
+        ));
+    } else if let Some(file) = get_env_config_file() {
+        groups.push((
+            String::from("Given with YAMIS_FILE"),
+            ConfigFilePaths::only(&file)?.collect::<DynErrResult<_>>()?,
+        ));
+    }
+    let discovered = ConfigFilePaths::new(current_dir).without_global();
+    groups.push((
+        format!("Discovered from {}", current_dir.to_string_lossy()),
+        discovered.collect::<DynErrResult<_>>()?,
+    ));
+    if global_tasks != GlobalTasks::Off {
+        let global = ConfigFilePaths::get_global_config_file_path()?;
+        groups.push((String::from("Global"), global.into_iter().collect()));
+    }
 
-    file_containers.run_task(config_file_paths, &task_command.task, task_command.args)
+    for (header, paths) in groups {
+        println!("{}:", header.bold());
+        if paths.is_empty() {
+            println!("  {}", "No config files found".dimmed());
+        }
+        for path in paths {
+            let path_str = path.to_string_lossy();
+            // Errors are displayed when running or listing the tasks of the file
+            if matches!(count_public_tasks(&path), Ok(0)) {
+                println!("  {}", path_str.dimmed());
+            } else {
+                println!("  {}", colorize_config_file_path(&path_str));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the commands of the snapshot in the given path again, warning about the config files
+/// that changed since it was taken
+fn replay_snapshot(path: &Path) -> DynErrResult<()> {
+    let snapshot = Snapshot::load(path)?;
+    for config_file in snapshot.changed_config_files() {
+        let msg = format!(
+            "{} changed since the snapshot was taken",
+            config_file.to_string_lossy()
+        );
+        eprintln!("{}", msg.yamis_warn());
+    }
+    snapshot.replay()
+}
+
+/// Exports the given task as a standalone script, warning about what the script cannot do
+/// like yamis
+///
+/// # Arguments
+///
+/// * `file_containers`: Containers to load the config files with
+/// * `config_file_paths`: Config files to search the task in
+/// * `matches`: Matches of the `export-script` subcommand
+/// * `env_overrides`: Environment variables that take precedence over the ones of the
+///   config files and task
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+fn export_task_script(
+    file_containers: &mut ConfigFileContainers,
+    config_file_paths: ConfigFilePaths,
+    matches: &clap::ArgMatches,
+    env_overrides: &HashMap<String, String>,
+) -> DynErrResult<()> {
+    let task = matches.get_one::<String>("task").unwrap();
+    let args = TaskSubcommand::get_task_args(
+        TaskSubcommand::get_args_vec(matches.get_many::<OsString>("args")),
+        true,
+    );
+    let shell: ExportShell = matches.get_one::<String>("shell").unwrap().parse()?;
+    let output = matches.get_one::<PathBuf>("output").unwrap();
+
+    // The commands are recorded as they would run, including the tasks they depend on
+    let backend = RecordBackend::new();
+    let mut config_file_paths = config_file_paths;
+    if let Err(e) = file_containers.run_task(
+        config_file_paths.by_ref(),
+        task,
+        args,
+        env_overrides,
+        &backend,
+    ) {
+        return match config_file_paths.not_found_error() {
+            Some(not_found) => Err(not_found.into()),
+            None => Err(e),
+        };
+    }
+
+    let exported = export_script(task, &backend.into_commands(), shell)?;
+    for warning in &exported.warnings {
+        eprintln!("{}", warning.yamis_warn());
+    }
+    fs::write(output, exported.contents)?;
+    #[cfg(unix)]
+    if shell == ExportShell::Sh {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(output, fs::Permissions::from_mode(0o755))?;
+    }
+    println!(
+        "{}",
+        format!("Exported task {} to {}", task, output.to_string_lossy()).yamis_info()
+    );
+    Ok(())
+}
+
+/// Prints the runs in progress
+fn print_runs() -> DynErrResult<()> {
+    let runs = runs::list_runs(&runs::default_runs_dir()?)?;
+    if runs.is_empty() {
+        println!("{}", "No tasks running".yamis_info());
+        return Ok(());
+    }
+    println!("{:<10} {:<8} {:<14} TASK", "ID", "PID", "STARTED");
+    for run in runs {
+        let pid = match run.pid {
+            Some(pid) => pid.to_string(),
+            None => String::from("-"),
+        };
+        let started = format!("{} ago", runs::format_elapsed(run.elapsed()));
+        println!(
+            "{:<10} {:<8} {:<14} {} ({})",
+            run.id,
+            pid,
+            started,
+            run.task.cyan(),
+            run.dir.to_string_lossy()
+        );
+    }
+    Ok(())
+}
+
+/// Returns the argument to set the path of the daemon socket
+#[cfg(feature = "daemon")]
+fn socket_arg() -> clap::Arg {
+    clap::Arg::new("socket")
+        .long("socket")
+        .action(ArgAction::Set)
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("Path of the daemon socket. Defaults to `daemon.sock` in the yamis runtime directory")
+        .value_name("PATH")
+}
+
+/// Returns the socket path given in the command line, or the default one
+#[cfg(all(feature = "daemon", unix))]
+fn get_socket_path(matches: &clap::ArgMatches) -> DynErrResult<PathBuf> {
+    match matches.get_one::<PathBuf>("socket") {
+        Some(path) => Ok(path.clone()),
+        None => daemon::default_socket_path(),
+    }
+}
+
+/// Starts the daemon and serves requests until it is killed
+#[cfg(all(feature = "daemon", unix))]
+fn run_daemon(matches: &clap::ArgMatches) -> DynErrResult<()> {
+    let daemon = daemon::Daemon::bind(&get_socket_path(matches)?)?;
+    // The socket is removed when the daemon is dropped, so ctrl-c only stops accepting requests
+    let socket_path = daemon.socket_path().to_path_buf();
+    ctrlc::set_handler(move || {
+        let _ = fs::remove_file(&socket_path);
+        std::process::exit(0);
+    })?;
+    println!(
+        "{}",
+        format!("Listening in {}", daemon.socket_path().display()).yamis_info()
+    );
+    daemon.serve()
+}
+
+/// Sends the request to the daemon, printing each response as a JSON line
+#[cfg(all(feature = "daemon", unix))]
+fn run_client(matches: &clap::ArgMatches) -> DynErrResult<()> {
+    let request: daemon::Request =
+        serde_json::from_str(matches.get_one::<String>("request").unwrap())?;
+    let mut failed = false;
+    daemon::send_request(&get_socket_path(matches)?, &request, |response| {
+        failed = matches!(
+            response,
+            daemon::Response::Error { .. } | daemon::Response::Exit { success: false, .. }
+        );
+        println!("{}", serde_json::to_string(response).unwrap());
+    })?;
+    if failed {
+        return Err("The request did not succeed".into());
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "daemon", not(unix)))]
+fn run_daemon(_matches: &clap::ArgMatches) -> DynErrResult<()> {
+    Err("The daemon is only supported in Unix systems".into())
+}
+
+#[cfg(all(feature = "daemon", not(unix)))]
+fn run_client(_matches: &clap::ArgMatches) -> DynErrResult<()> {
+    Err("The daemon is only supported in Unix systems".into())
 }
 
 #[cfg(test)]
@@ -525,7 +2573,7 @@ mod tests {
             .as_bytes(),
         )?;
         let expected = format!(
-            "{tmp_dir}/project.yamis.toml\n{global_config_dir}/user.yamis.toml\n",
+            "Discovered from {tmp_dir}:\n  {tmp_dir}/project.yamis.toml\nGlobal:\n  {global_config_dir}/user.yamis.toml\n",
             tmp_dir = tmp_dir.path().to_str().unwrap(),
             global_config_dir = global_config_dir.to_str().unwrap()
         );
@@ -537,4 +2585,15 @@ mod tests {
             .stdout(predicate::str::contains(expected));
         Ok(())
     }
+
+    #[cfg(feature = "updater")]
+    #[test]
+    fn test_is_env_flag_set() {
+        assert!(super::is_env_flag_set(Some("1")));
+        assert!(super::is_env_flag_set(Some("true")));
+        assert!(!super::is_env_flag_set(Some("")));
+        assert!(!super::is_env_flag_set(Some("0")));
+        assert!(!super::is_env_flag_set(Some("False")));
+        assert!(!super::is_env_flag_set(None));
+    }
 }
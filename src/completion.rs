@@ -0,0 +1,219 @@
+//! Dynamic shell completion, through the hidden `yamis __complete` subcommand. It receives the
+//! words given after `yamis`, the last one being the word to complete, and prints the candidates
+//! one per line: the names of the tasks, or the named arguments declared in the `params` of the
+//! task once it is given.
+use crate::config_files::{ConfigFile, ConfigFilePaths};
+use crate::params::{BasicParamType, ParamType};
+use crate::task_index::TaskIndex;
+use std::path::PathBuf;
+
+/// Options of yamis that take a value in the next word, which is not a task name
+const OPTIONS_WITH_VALUE: [&str; 10] = [
+    "-f",
+    "--file",
+    "--cwd",
+    "--env-file",
+    "--var",
+    "--execution-backend",
+    "--snapshot-env",
+    "--inline",
+    "-i",
+    "--task-info",
+];
+
+/// Options of yamis that take a task name in the next word
+const OPTIONS_WITH_TASK: [&str; 2] = ["-i", "--task-info"];
+
+/// Returns the name of the task given in the words before the one being completed, if any.
+/// Options of yamis and the `run` subcommand are skipped.
+///
+/// # Arguments
+///
+/// * `words`: Words given before the one being completed
+///
+/// returns: Option<&str>
+fn get_task_name(words: &[String]) -> Option<&str> {
+    let mut words = words.iter();
+    while let Some(word) = words.next() {
+        if OPTIONS_WITH_VALUE.contains(&word.as_str()) {
+            words.next();
+        } else if !word.starts_with('-') && word != "run" {
+            return Some(word);
+        }
+    }
+    None
+}
+
+/// Returns the completions of the named arguments declared in the params of a task. Arguments
+/// with choices or booleans are completed with each possible value, i.e. `--env=prod`.
+///
+/// # Arguments
+///
+/// * `name`: Name of the argument
+/// * `param_type`: Type of the argument
+///
+/// returns: Vec<String>
+fn get_param_candidates(name: &str, param_type: &ParamType) -> Vec<String> {
+    match param_type {
+        ParamType::Choices { choices } => choices
+            .iter()
+            .map(|choice| format!("--{}={}", name, choice))
+            .collect(),
+        ParamType::Basic(BasicParamType::Bool) => {
+            vec![format!("--{}=true", name), format!("--{}=false", name)]
+        }
+        ParamType::Basic(_) => vec![format!("--{}=", name)],
+    }
+}
+
+/// Returns the names of the public tasks of the given config files, without duplicates. The
+/// task index is used if possible, so that unchanged files are not parsed again.
+fn get_task_names(paths: &[PathBuf]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for path in paths {
+        let file_names: Vec<String> = match TaskIndex::load(path) {
+            Some(index) => index
+                .get_public_task_names()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            None => match ConfigFile::load(path.clone()) {
+                Ok(config_file) => config_file
+                    .get_public_task_names()
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                Err(_) => continue,
+            },
+        };
+        for name in file_names {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Returns the named arguments of the task, from the first config file that defines it
+fn get_task_candidates(paths: &[PathBuf], task_name: &str) -> Vec<String> {
+    for path in paths {
+        if TaskIndex::load(path).is_some_and(|index| !index.has_public_task(task_name)) {
+            continue;
+        }
+        let Ok(config_file) = ConfigFile::load(path.clone()) else {
+            continue;
+        };
+        if let Some(task) = config_file.get_public_task(task_name) {
+            return task
+                .get_params()
+                .map(|params| {
+                    params
+                        .iter()
+                        .flat_map(|(name, param_type)| get_param_candidates(name, param_type))
+                        .collect()
+                })
+                .unwrap_or_default();
+        }
+    }
+    Vec::new()
+}
+
+/// Returns the completions of the last of the given words, which are the ones given after
+/// `yamis`. Config files that cannot be loaded are skipped, as completion should never fail.
+///
+/// # Arguments
+///
+/// * `config_file_paths`: Paths of the config files to get the tasks from
+/// * `words`: Words given after `yamis`, the last one being the word to complete
+///
+/// returns: Vec<String>
+pub(crate) fn complete(config_file_paths: ConfigFilePaths, words: &[String]) -> Vec<String> {
+    let (current, previous) = match words.split_last() {
+        Some((current, previous)) => (current.as_str(), previous),
+        None => ("", words),
+    };
+    let option = previous.last().map(String::as_str).unwrap_or_default();
+    let paths: Vec<PathBuf> = config_file_paths.map_while(Result::ok).collect();
+    let candidates = if OPTIONS_WITH_TASK.contains(&option) {
+        get_task_names(&paths)
+    } else if OPTIONS_WITH_VALUE.contains(&option) {
+        Vec::new()
+    } else {
+        match get_task_name(previous) {
+            Some(task_name) => get_task_candidates(&paths, task_name),
+            None if current.starts_with('-') => Vec::new(),
+            None => get_task_names(&paths),
+        }
+    };
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(current))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use std::fs;
+
+    fn get_words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| String::from(*word)).collect()
+    }
+
+    #[test]
+    fn test_complete() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        fs::write(
+            &config_path,
+            r#"
+tasks:
+  deploy:
+    program: yamis::echo
+    params:
+      env:
+        choices: [staging, prod]
+      dry_run: bool
+      replicas: int
+  build:
+    program: yamis::echo
+  secret:
+    program: yamis::echo
+    private: true
+"#,
+        )
+        .unwrap();
+        let complete_words = |words: &[&str]| {
+            complete(
+                ConfigFilePaths::only(&config_path).unwrap(),
+                &get_words(words),
+            )
+        };
+
+        assert_eq!(complete_words(&[""]), vec!["deploy", "build"]);
+        assert_eq!(complete_words(&["b"]), vec!["build"]);
+        assert_eq!(complete_words(&["run", "de"]), vec!["deploy"]);
+        assert_eq!(complete_words(&["--var", "x=1", "b"]), vec!["build"]);
+        assert_eq!(
+            complete_words(&["deploy", ""]),
+            vec![
+                "--env=staging",
+                "--env=prod",
+                "--dry_run=true",
+                "--dry_run=false",
+                "--replicas="
+            ]
+        );
+        assert_eq!(
+            complete_words(&["deploy", "--env="]),
+            vec!["--env=staging", "--env=prod"]
+        );
+        assert_eq!(complete_words(&["-i", "d"]), vec!["deploy"]);
+        assert!(complete_words(&["-f", ""]).is_empty());
+        assert!(complete_words(&["build", ""]).is_empty());
+        assert!(complete_words(&["unknown", ""]).is_empty());
+        assert!(complete_words(&["--f"]).is_empty());
+    }
+}
@@ -1,22 +1,39 @@
 use crate::debug_config::ConfigFileDebugConfig;
 use crate::defaults::default_quote;
-use crate::parser::EscapeMode;
+use crate::escape::EscapeMode;
+use crate::parser::TaskVars;
+use crate::run_context::RunContext;
+use crate::settings::Settings;
+use crate::state_dirs::StateDirs;
+use crate::task_index::TaskIndex;
+use crate::task_templates::TaskTemplate;
+use crate::task_tests::TaskTest;
 use crate::tasks::Task;
 use crate::types::DynErrResult;
 use crate::utils::{
-    get_path_relative_to_base, get_task_dependency_graph, read_env_file, to_os_task_name,
+    add_unknown_field_suggestion, deserialize_optional_scalar_map, deserialize_scalar_map,
+    deserialize_unique_map, get_path_relative_to_base, get_task_dependency_graph, read_env_file,
+    to_os_task_name, TaskRelation, TMP_FOLDER_NAMESPACE,
 };
+use crate::versions::{ConfigFileKeys, ConfigVersion};
+use crate::wasm_plugins::WasmPlugins;
+use crate::workspace::Workspace;
 use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use md5::{Digest, Md5};
 use petgraph::algo::toposort;
+use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde_derive::Deserialize;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::OsStr;
 use std::fmt::{Display, Formatter};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::{env, error, fmt, fs};
 
-pub type ConfigFileSharedPtr = Arc<Mutex<ConfigFile>>;
+pub type ConfigFileSharedPtr = Arc<RwLock<ConfigFile>>;
 
 /// Config file names by order of priority. The first one refers to local config and
 /// should not be committed to the repository. The program should discover config files
@@ -24,8 +41,8 @@ pub type ConfigFileSharedPtr = Arc<Mutex<ConfigFile>>;
 /// or a the project config (last one on the list) is found.
 const CONFIG_FILES_PRIO: &[&str] = &["local.yamis", "yamis", "project.yamis"];
 
-const GLOBAL_CONFIG_FILE: &str = "user.yamis";
 /// Name the global config file, without extension.
+const GLOBAL_CONFIG_FILE: &str = "user.yamis";
 
 #[cfg(not(test))]
 const GLOBAL_CONFIG_FILE_PATH: &str = "~/.yamis";
@@ -38,8 +55,9 @@ const ALLOWED_EXTENSIONS: &[&str] = &["yml", "yaml", "toml"];
 pub(crate) enum ConfigError {
     // /// Raised when a config file is not found for a given path
     // FileNotFound(String), // Given config file not found
-    // /// Raised when no config file is found during auto-discovery
-    // NoConfigFile, // No config file was discovered
+    /// Raised when no config file is found during auto-discovery, with the directories
+    /// that were searched
+    NotFound(Vec<PathBuf>),
     /// Bad Config error
     BadConfigFile(PathBuf, String),
     /// Found a config file multiple times with different extensions
@@ -50,7 +68,13 @@ impl Display for ConfigError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match *self {
             // ConfigError::FileNotFound(ref s) => write!(f, "File {} not found.", s),
-            // ConfigError::NoConfigFile => write!(f, "No config file found."),
+            ConfigError::NotFound(ref dirs) => {
+                writeln!(f, "No config file found. Searched in:")?;
+                for dir in dirs {
+                    writeln!(f, "    {}", dir.to_string_lossy())?;
+                }
+                write!(f, "{}", supported_config_files())
+            }
             ConfigError::BadConfigFile(ref path, ref reason) => write!(f, "Bad config file `{}`:\n    {}", path.to_string_lossy(), reason),
             ConfigError::DuplicateConfigFile(ref s) => write!(f,
                                                               "Config file `{}` defined multiple times with different extensions in the same directory.", s),
@@ -60,14 +84,61 @@ impl Display for ConfigError {
 
 impl error::Error for ConfigError {}
 
+/// Returns a message explaining the names and extensions of config files
+fn supported_config_files() -> String {
+    // i.e. `a`, `b` or `c`
+    let join = |values: Vec<String>| match values.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new(),
+    };
+    let names = CONFIG_FILES_PRIO
+        .iter()
+        .map(|name| format!("`{}`", name))
+        .collect();
+    let extensions = ALLOWED_EXTENSIONS
+        .iter()
+        .map(|ext| format!("`.{}`", ext))
+        .collect();
+    format!(
+        "Config files are named {}, with the {} extension. Other names can be given with `-f`, \
+but the extension must be the same.",
+        join(names),
+        join(extensions)
+    )
+}
+
+/// Returns the environment variables that start with the given prefix, with the prefix
+/// stripped. Variables that are only the prefix are ignored.
+///
+/// # Arguments
+///
+/// * `prefix`: Prefix of the variables to import, i.e. `MYAPP_`
+/// * `vars`: Environment variables to import from
+///
+/// returns: IndexMap<String, String, RandomState>
+fn import_env_vars<I>(prefix: &str, vars: I) -> IndexMap<String, String>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut imported: IndexMap<String, String> = vars
+        .into_iter()
+        .filter_map(|(key, val)| match key.strip_prefix(prefix) {
+            Some(name) if !name.is_empty() => Some((String::from(name), val)),
+            _ => None,
+        })
+        .collect();
+    imported.sort_keys();
+    imported
+}
+
 /// Represents a config file.
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigFile {
     /// Version of the config file.
-    #[allow(dead_code)] // to avoid lint errors
-    #[serde(default, skip_serializing)]
-    version: serde::de::IgnoredAny,
+    #[serde(default)]
+    version: ConfigVersion,
     /// Path of the file.
     #[serde(skip)]
     pub(crate) filepath: PathBuf,
@@ -82,36 +153,110 @@ pub struct ConfigFile {
     /// Whether to automatically quote argument with spaces unless task specified
     #[serde(default = "default_quote")]
     pub(crate) quote: EscapeMode,
-    /// Tasks inside the config file.
-    #[serde(default)]
-    pub(crate) tasks: HashMap<String, Task>,
+    /// Tasks inside the config file, in the order they were declared.
+    #[serde(default, deserialize_with = "deserialize_unique_map")]
+    pub(crate) tasks: IndexMap<String, Task>,
+    /// Templates that generate a task for each of their instances
+    #[serde(default, deserialize_with = "deserialize_unique_map")]
+    task_templates: IndexMap<String, TaskTemplate>,
+    /// Tests of the tasks, run with `yamis test`
+    #[serde(default, deserialize_with = "deserialize_unique_map")]
+    pub(crate) tests: IndexMap<String, TaskTest>,
     /// Env variables for all the tasks.
+    #[serde(default, deserialize_with = "deserialize_optional_scalar_map")]
     pub(crate) env: Option<HashMap<String, String>>,
     /// Env file to read environment variables from
     pub(crate) env_file: Option<String>,
+    /// Members of the workspace, if this is the root of a monorepo
+    workspace: Option<Workspace>,
+    /// Directories prepended to the PATH of all the tasks, relative to the config file directory
+    #[serde(default)]
+    path: Vec<String>,
+    /// Constants available to all the tasks, which cannot be redefined by variables
+    #[serde(default, deserialize_with = "deserialize_unique_map")]
+    pub(crate) constants: IndexMap<String, String>,
+    /// Variables available to all the tasks
+    #[serde(default, deserialize_with = "deserialize_scalar_map")]
+    pub(crate) vars: IndexMap<String, String>,
+    /// Lists of arguments that program tasks can include with `{use: NAME}`
+    #[serde(default, deserialize_with = "deserialize_unique_map")]
+    pub(crate) snippets: IndexMap<String, Vec<String>>,
+    /// Settings of yamis, only allowed in the global config file
+    settings: Option<Settings>,
+    /// Prefix of the environment variables to import as `cfg` values
+    env_import_prefix: Option<String>,
+    /// Paths of the WASM plugins that provide functions to the tasks, relative to the config
+    /// file directory
+    #[serde(default)]
+    plugins: Vec<String>,
+    /// WASM plugins declared in `plugins`
+    #[serde(skip)]
+    pub(crate) wasm_plugins: WasmPlugins,
+    /// Names of the variables in `env` that were loaded from `env_file`
+    #[serde(skip)]
+    pub(crate) env_file_vars: HashSet<String>,
+    /// Environment variables imported with `env_import_prefix`, without the prefix
     #[serde(skip)]
-    pub(crate) loaded_tasks: HashMap<String, Arc<Task>>,
+    pub(crate) imported_env: IndexMap<String, String>,
+    /// State directories of the project, available to all the tasks
+    #[serde(skip)]
+    pub(crate) state_dirs: StateDirs,
+    /// Variables of the global config file, available to all the tasks unless overridden
+    #[serde(skip)]
+    pub(crate) global_vars: IndexMap<String, String>,
+    #[serde(skip)]
+    pub(crate) loaded_tasks: IndexMap<String, Arc<Task>>,
+}
+
+lazy_static! {
+    /// Config files written by [ConfigFilePaths::inline] in this process
+    static ref INLINE_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+}
+
+/// Removes the config files written by [ConfigFilePaths::inline], along with their task index,
+/// when dropped. Kept alive for the whole run, so that nested invocations can still load them.
+pub(crate) struct InlineFilesCleanup;
+
+impl Drop for InlineFilesCleanup {
+    fn drop(&mut self) {
+        let paths = match INLINE_FILES.lock() {
+            Ok(mut paths) => std::mem::take(&mut *paths),
+            Err(_) => return,
+        };
+        for path in paths {
+            let _ = fs::remove_file(&path);
+            TaskIndex::remove(&path);
+        }
+    }
 }
 
 /// Iterates over existing config file paths, in order of priority.
 pub struct ConfigFilePaths {
-    /// Index of value to use from `CONFIG_FILES_PRIO`
-    index: usize,
+    /// Config files found in the last scanned directory that are yet to be returned, in
+    /// order of priority. An error is always the last value.
+    pending: VecDeque<Result<PathBuf, ConfigError>>,
     /// Whether the iterator finished or not
     root_reached: bool,
     /// Whether the iterator finished or not
     ended: bool,
     /// Only loaded one file, which should already be in the cache
     single: bool,
+    /// Only searches the starting directory, without its parents or the global config file
+    single_dir: bool,
+    /// Does not search the global config file
+    skip_global: bool,
     /// Current directory
     current_dir: PathBuf,
     /// Cached config files
     cached: Vec<PathBuf>,
+    /// Directories searched so far
+    searched_dirs: Vec<PathBuf>,
 }
 
 pub struct ConfigFilesContainer {
-    /// Cached config files
-    cached: IndexMap<PathBuf, ConfigFileSharedPtr>,
+    /// Cached config files. Files are loaded without holding the lock, so that threads loading
+    /// different files do not wait for each other.
+    cached: RwLock<IndexMap<PathBuf, ConfigFileSharedPtr>>,
 }
 
 impl Iterator for ConfigFilePaths {
@@ -133,59 +278,29 @@ impl Iterator for ConfigFilePaths {
             };
         }
 
-        // Stores any error to return after breaking the loop
-        let mut err: Option<Box<dyn error::Error>> = None;
-
         // Loops until a project config file is found or the root path is reached
-        while !self.root_reached {
-            let config_file_name = CONFIG_FILES_PRIO[self.index];
-
-            // project file is the last one on the list
-            let checking_for_project_config = CONFIG_FILES_PRIO.len() - 1 == self.index;
-            self.index = (self.index + 1) % CONFIG_FILES_PRIO.len();
-
-            let found_file =
-                self.get_config_file_path(self.current_dir.as_path(), config_file_name);
-            let found_file = match found_file {
-                Ok(v) => v,
-                Err(e) => {
-                    err = Some(e.into());
-                    break;
+        loop {
+            match self.pending.pop_front() {
+                Some(Ok(found_file)) => {
+                    self.cached.push(found_file.clone());
+                    return Some(Ok(found_file));
                 }
-            };
-
-            if checking_for_project_config {
-                // When checking for project config, we need to update the next dir to check
-                let new_current = self.current_dir.parent();
-                match new_current {
-                    None => {
-                        self.root_reached = true;
-                    }
-                    Some(new_current) => {
-                        self.current_dir = new_current.to_path_buf();
-                    }
+                Some(Err(e)) => {
+                    self.ended = true;
+                    return Some(Err(e.into()));
                 }
-            }
-
-            if let Some(found_file) = found_file {
-                if checking_for_project_config {
-                    self.root_reached = true;
-                }
-                self.cached.push(found_file.clone());
-                return Some(Ok(found_file));
+                None if self.root_reached => break,
+                None => self.scan_current_dir(),
             }
         }
 
-        self.root_reached = true;
         self.ended = true;
-
-        if let Some(err) = err {
-            return Some(Err(err));
+        if self.single_dir || self.skip_global {
+            return None;
         }
+        self.searched_dirs.push(Self::get_global_config_file_dir());
 
-        let global_config_dir = Self::get_global_config_file_dir();
-        let found_file = self.get_config_file_path(&global_config_dir, GLOBAL_CONFIG_FILE);
-        let found_file = match found_file {
+        let found_file = match Self::get_global_config_file_path() {
             Ok(v) => v,
             Err(e) => {
                 return Some(Err(e.into()));
@@ -212,15 +327,89 @@ impl ConfigFilePaths {
     pub fn new<S: AsRef<OsStr> + ?Sized>(path: &S) -> ConfigFilePaths {
         let current = PathBuf::from(path);
         ConfigFilePaths {
-            index: 0,
+            pending: VecDeque::with_capacity(CONFIG_FILES_PRIO.len()),
             ended: false,
             root_reached: false,
             single: false,
+            single_dir: false,
+            skip_global: false,
             current_dir: current,
             cached: Vec::with_capacity(2),
+            searched_dirs: Vec::new(),
+        }
+    }
+
+    /// Initializes ConfigFilePaths such that it only returns the given config files, in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths`: Paths of the config files
+    ///
+    /// returns: ConfigFilePaths
+    pub fn from_paths(paths: Vec<PathBuf>) -> ConfigFilePaths {
+        ConfigFilePaths {
+            pending: paths.into_iter().map(Ok).collect(),
+            ended: false,
+            root_reached: true,
+            single: false,
+            single_dir: true,
+            skip_global: true,
+            current_dir: PathBuf::new(),
+            cached: Vec::new(),
+            searched_dirs: Vec::new(),
         }
     }
 
+    /// Initializes ConfigFilePaths to search the config files in the given directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir`: Directory to search the config files in
+    /// * `walk_up`: Whether to continue searching in the parent directories and the global
+    ///   config file, as when starting at the current directory
+    ///
+    /// returns: ConfigFilePaths
+    pub fn in_dir<S: AsRef<OsStr> + ?Sized>(dir: &S, walk_up: bool) -> ConfigFilePaths {
+        let mut config_files = ConfigFilePaths::new(dir);
+        config_files.single_dir = !walk_up;
+        config_files
+    }
+
+    /// Excludes the global config file from the search.
+    pub(crate) fn without_global(mut self) -> ConfigFilePaths {
+        self.skip_global = true;
+        self
+    }
+
+    /// Initializes ConfigFilePaths such that it only loads a config file with the given YAML
+    /// contents, i.e. read from stdin with `-f -`. The contents are written to a file in the
+    /// temporary directory, so that nested yamis invocations can load it from `YAMIS_FILE`, and
+    /// removed by [InlineFilesCleanup] once the run finishes.
+    ///
+    /// # Arguments
+    ///
+    /// * `contents`: YAML contents of the config file
+    ///
+    /// returns:  Result<ConfigFilePaths, Box<dyn error::Error>>
+    pub(crate) fn inline(contents: &str) -> DynErrResult<ConfigFilePaths> {
+        let mut path = env::temp_dir();
+        path.push(TMP_FOLDER_NAMESPACE);
+        path.push("inline");
+        fs::create_dir_all(&path)?;
+
+        let mut hasher = Md5::new();
+        hasher.update(contents.as_bytes());
+        // Named after the process too, so that removing it never breaks another yamis process
+        path.push(format!(
+            "{:X}-{}.yamis.yml",
+            hasher.finalize(),
+            std::process::id()
+        ));
+        fs::write(&path, contents)?;
+        INLINE_FILES.lock().unwrap().push(path.clone());
+        ConfigFilePaths::only(&path)
+    }
+
     /// Initializes ConfigFilePaths such that it only loads the config file for the given path.
     ///
     /// # Arguments
@@ -230,20 +419,47 @@ impl ConfigFilePaths {
     /// returns:  Result<ConfigFilePaths, Box<dyn error::Error>>
     pub fn only<S: AsRef<OsStr> + ?Sized>(path: &S) -> DynErrResult<ConfigFilePaths> {
         let path = PathBuf::from(path);
+        if path.is_dir() {
+            let reason = format!(
+                "Expected a file, but found a directory. {}",
+                supported_config_files()
+            );
+            return Err(ConfigError::BadConfigFile(path, reason).into());
+        }
         if !path.is_file() {
             return Err(format!("{} does not exist", path.display()).into());
         }
+        let has_allowed_extension = path
+            .extension()
+            .is_some_and(|ext| ALLOWED_EXTENSIONS.iter().any(|allowed| ext == *allowed));
+        if !has_allowed_extension {
+            let reason = format!("Unsupported extension. {}", supported_config_files());
+            return Err(ConfigError::BadConfigFile(path, reason).into());
+        }
         let config_files = ConfigFilePaths {
-            index: 0,
+            pending: VecDeque::new(),
             ended: false,
             root_reached: true,
             single: true,
+            single_dir: false,
+            skip_global: true,
             current_dir: path.clone(),
             cached: vec![path],
+            searched_dirs: Vec::new(),
         };
         Ok(config_files)
     }
 
+    /// Returns an error listing the searched directories if the search ended without finding
+    /// any config file. Nothing is returned if a single file was given.
+    pub(crate) fn not_found_error(&self) -> Option<ConfigError> {
+        if self.ended && !self.single && self.cached.is_empty() {
+            Some(ConfigError::NotFound(self.searched_dirs.clone()))
+        } else {
+            None
+        }
+    }
+
     /// Returns the path of the global config file directory.
     #[cfg(not(test))]
     pub(crate) fn get_global_config_file_dir() -> PathBuf {
@@ -264,38 +480,75 @@ impl ConfigFilePaths {
         TEST_GLOBAL_CONFIG_PATH.clone()
     }
 
+    /// Returns the path of the global config file, if it exists
+    pub(crate) fn get_global_config_file_path() -> Result<Option<PathBuf>, ConfigError> {
+        let global_config_dir = Self::get_global_config_file_dir();
+        Self::get_config_file_path(&global_config_dir, GLOBAL_CONFIG_FILE)
+    }
+
+    /// Searches for config files in the current directory, adding them to the pending files,
+    /// and moves to the parent directory. Marks the root as reached if a project config file
+    /// is found, there is no parent directory, or an error is found.
+    fn scan_current_dir(&mut self) {
+        self.searched_dirs.push(self.current_dir.clone());
+        for (i, config_file_name) in CONFIG_FILES_PRIO.iter().enumerate() {
+            match Self::get_config_file_path(&self.current_dir, config_file_name) {
+                Ok(Some(found_file)) => {
+                    self.pending.push_back(Ok(found_file));
+                    // project file is the last one on the list
+                    if i == CONFIG_FILES_PRIO.len() - 1 {
+                        self.root_reached = true;
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.pending.push_back(Err(e));
+                    self.root_reached = true;
+                    return;
+                }
+            }
+        }
+
+        if self.single_dir {
+            self.root_reached = true;
+            return;
+        }
+        match self.current_dir.parent() {
+            None => self.root_reached = true,
+            Some(parent) => self.current_dir = parent.to_path_buf(),
+        }
+    }
+
     /// Finds the appropriate filepath to load in the given dir.
     ///
     /// # Arguments
     ///
-    /// * `dir`:
-    /// * `config_file_name`:
+    /// * `dir`: Directory to search the config file in
+    /// * `config_file_name`: Name of the config file, without extension
     ///
     /// returns: Result<Option<PathBuf>, ConfigError>
     fn get_config_file_path(
-        &self,
         dir: &Path,
         config_file_name: &str,
     ) -> Result<Option<PathBuf>, ConfigError> {
-        let mut files_count: u8 = 0;
         let mut found_file: Option<PathBuf> = None;
+        // Reuses the same buffer for every candidate
+        let mut path = dir.join(config_file_name);
 
         for file_extension in ALLOWED_EXTENSIONS {
-            let file_name = format!("{}.{}", config_file_name, file_extension);
-            let path = dir.join(file_name);
+            path.set_file_name(format!("{}.{}", config_file_name, file_extension));
             if path.is_file() {
-                files_count += 1;
-                found_file = Some(path);
+                if found_file.is_some() {
+                    return Err(ConfigError::DuplicateConfigFile(String::from(
+                        config_file_name,
+                    )));
+                }
+                found_file = Some(path.clone());
             }
         }
 
-        if files_count > 1 {
-            Err(ConfigError::DuplicateConfigFile(String::from(
-                config_file_name,
-            )))
-        } else {
-            Ok(found_file)
-        }
+        Ok(found_file)
     }
 }
 
@@ -303,41 +556,45 @@ impl ConfigFilesContainer {
     /// Initializes ConfigFilesContainer.
     pub fn new() -> ConfigFilesContainer {
         ConfigFilesContainer {
-            cached: IndexMap::new(),
+            cached: RwLock::new(IndexMap::new()),
         }
     }
 
-    /// Reads the config file from the given path.
+    /// Reads the config file from the given path, or returns the cached one if it was already
+    /// read. Can be called from multiple threads.
     ///
     /// # Arguments
     ///
     /// * `path`: Path to read the config file from
     ///
-    /// returns: Result<Arc<Mutex<ConfigFile>>, Box<dyn Error, Global>>
-    pub fn read_config_file(&mut self, path: PathBuf) -> DynErrResult<ConfigFileSharedPtr> {
-        let config_file = ConfigFile::load(path.clone());
-        match config_file {
-            Ok(config_file) => {
-                let arc_config_file = Arc::new(Mutex::new(config_file));
-                let result = Ok(Arc::clone(&arc_config_file));
-                self.cached.insert(path, arc_config_file);
-                result
-            }
-            Err(e) => Err(e),
+    /// returns: Result<Arc<RwLock<ConfigFile>>, Box<dyn Error, Global>>
+    pub fn read_config_file(&self, path: PathBuf) -> DynErrResult<ConfigFileSharedPtr> {
+        if let Some(config_file) = self.cached.read().unwrap().get(&path) {
+            return Ok(Arc::clone(config_file));
         }
+        let config_file = ConfigFile::load(path.clone())?;
+        TaskIndex::store(&config_file);
+        // If another thread loaded it in the meantime, its copy is kept, as it might be in use
+        let mut cached = self.cached.write().unwrap();
+        let config_file = cached
+            .entry(path)
+            .or_insert_with(|| Arc::new(RwLock::new(config_file)));
+        Ok(Arc::clone(config_file))
+    }
+
+    /// Removes the config files read so far, so that they are read again, i.e. if they changed
+    pub fn clear(&self) {
+        self.cached.write().unwrap().clear();
     }
 
     #[cfg(test)] // Used in tests only for now, but still leaving it here just in case
     /// Returns whether the given task exists in the config files.
-    pub fn has_task<S: AsRef<str>>(&mut self, name: S) -> bool {
-        for config_file in self.cached.values() {
-            let config_file_ptr = config_file.as_ref();
-            let handle = config_file_ptr.lock().unwrap();
-            if handle.has_task(name.as_ref()) {
-                return true;
-            }
-        }
-        false
+    pub fn has_task<S: AsRef<str>>(&self, name: S) -> bool {
+        self.cached
+            .read()
+            .unwrap()
+            .values()
+            .any(|config_file| config_file.read().unwrap().has_task(name.as_ref()))
     }
 }
 
@@ -347,66 +604,602 @@ impl Default for ConfigFilesContainer {
     }
 }
 
+/// Lightweight representation of a config file that only holds the names of the tasks, so that
+/// it can be checked if a file defines a task without fully loading it.
+#[derive(Debug, Deserialize)]
+struct ConfigFileTaskNames {
+    /// Tasks inside the config file, with their values ignored.
+    #[serde(default)]
+    tasks: HashMap<String, serde::de::IgnoredAny>,
+    /// Templates of tasks, whose generated names are only known after loading the file
+    task_templates: Option<serde::de::IgnoredAny>,
+}
+
+/// Names of the tasks declared in a config file, found by scanning its lines instead of
+/// parsing it.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ScannedTaskNames {
+    /// Names of the tasks in the `tasks` section
+    tasks: Vec<String>,
+    /// Whether the file has `task_templates`, whose generated names are only known after
+    /// loading the file
+    has_templates: bool,
+}
+
+/// Returns whether the YAML contents have merge keys, i.e. `<<: *base`, either in a block or a
+/// flow mapping. Other uses of `<<`, like heredocs in scripts, are not merge keys.
+fn has_merge_keys(contents: &str) -> bool {
+    lazy_static! {
+        static ref MERGE_KEY_REGEX: Regex =
+            Regex::new(r"(?m)(^[ \t]*(- +)*|[{,] *)<< *:( |$)").unwrap();
+    }
+    MERGE_KEY_REGEX.is_match(contents)
+}
+
+/// Splits a YAML line into its key and the rest of the line after the colon. Returns None if
+/// the line is not a plain `key:` entry, i.e. a list item, a merge key or a key with escapes.
+fn split_yaml_key(line: &str) -> Option<(String, &str)> {
+    let (key, rest) = match line.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = line[1..].find(quote)? + 1;
+            let key = &line[1..end];
+            if key.contains('\\') || line[end + 1..].starts_with(quote) {
+                return None;
+            }
+            (key, line[end + 1..].trim_start_matches(' '))
+        }
+        '-' | '?' | ':' | ',' | '[' | ']' | '{' | '}' | '#' | '&' | '*' | '!' | '|' | '>' | '%'
+        | '@' | '`' | '<' => return None,
+        _ => {
+            let end = line
+                .match_indices(':')
+                .map(|(i, _)| i)
+                .find(|i| matches!(line[i + 1..].chars().next(), None | Some(' ')))?;
+            if line[..end].contains(" #") {
+                return None;
+            }
+            (line[..end].trim_end(), &line[end..])
+        }
+    };
+    let rest = rest.strip_prefix(':')?;
+    if !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    Some((key.to_string(), rest))
+}
+
+/// Returns the message of a duplicate key error with the location of both declarations of the
+/// key, or None if the error is not about a duplicate key or the declarations cannot be found.
+/// The parser only reports the location of the mapping that has the duplicate key, so the
+/// declarations are found by scanning the keys of the mapping.
+///
+/// # Arguments
+///
+/// * `contents`: YAML contents that failed to deserialize
+/// * `error`: Error returned when deserializing the contents
+///
+/// returns: Option<String>
+fn locate_duplicate_key(contents: &str, error: &serde_yaml::Error) -> Option<String> {
+    let message = error.to_string();
+    let key_start = message.find("duplicate key `")? + "duplicate key `".len();
+    let key = &message[key_start..key_start + message[key_start..].find('`')?];
+    let location = error.location()?;
+    let indent = location.column().checked_sub(1)?;
+    let mut declarations = Vec::new();
+    for (i, line) in contents
+        .lines()
+        .enumerate()
+        .skip(location.line().checked_sub(1)?)
+    {
+        let is_first_line = declarations.is_empty() && i + 1 == location.line();
+        if !is_first_line {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let line_indent = line.len() - line.trim_start_matches(' ').len();
+            if line_indent < indent {
+                break;
+            }
+            if line_indent > indent {
+                continue;
+            }
+        }
+        if split_yaml_key(line.get(indent..)?)?.0 == key {
+            declarations.push(i + 1);
+        }
+    }
+    match declarations[..] {
+        [first, second, ..] => Some(format!(
+            "{}, declared at line {} column {} and again at line {} column {}",
+            &message[..message.rfind(" at line ")?],
+            first,
+            location.column(),
+            second,
+            location.column()
+        )),
+        _ => None,
+    }
+}
+
+/// Returns the names of the tasks declared in the YAML contents by looking at the indentation
+/// of the lines, or None if the contents use syntax that needs to be parsed, i.e. flow mappings,
+/// merge keys or multiple documents.
+fn scan_yaml_task_names(contents: &str) -> Option<ScannedTaskNames> {
+    let mut scanned = ScannedTaskNames::default();
+    let mut root_indent = None;
+    let mut tasks_indent = None;
+    let mut in_tasks = false;
+    let mut has_tasks = false;
+    for line in contents.lines() {
+        let trimmed = line.trim_start_matches(' ');
+        if trimmed.starts_with('\t') {
+            return None;
+        }
+        if trimmed.trim_end().is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        let root_indent = *root_indent.get_or_insert(indent);
+        if indent < root_indent {
+            return None;
+        }
+        if indent == root_indent {
+            let (key, rest) = split_yaml_key(trimmed)?;
+            in_tasks = key == "tasks";
+            if in_tasks {
+                let rest = rest.trim();
+                if has_tasks || !(rest.is_empty() || rest.starts_with('#')) {
+                    return None;
+                }
+                has_tasks = true;
+            } else if key == "task_templates" {
+                scanned.has_templates = true;
+            }
+            continue;
+        }
+        if !in_tasks {
+            continue;
+        }
+        let tasks_indent = *tasks_indent.get_or_insert(indent);
+        if indent < tasks_indent {
+            return None;
+        }
+        if indent == tasks_indent {
+            scanned.tasks.push(split_yaml_key(trimmed)?.0);
+        }
+    }
+    Some(scanned)
+}
+
+/// Splits a TOML key into its first part and the rest of the line after it, i.e. `.script = 1`
+/// for `hello.script = 1`. Returns None if the key is not valid or has escapes.
+fn split_toml_key(line: &str) -> Option<(String, &str)> {
+    let line = line.trim_start();
+    let (key, rest) = match line.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = line[1..].find(quote)? + 1;
+            let key = &line[1..end];
+            if key.contains('\\') {
+                return None;
+            }
+            (key, &line[end + 1..])
+        }
+        _ => {
+            let end = line
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(line.len());
+            if end == 0 {
+                return None;
+            }
+            line.split_at(end)
+        }
+    };
+    Some((key.to_string(), rest.trim_start()))
+}
+
+/// Returns the first part of the key after a dot in the rest of a TOML key, i.e. `hello` for
+/// `.hello.script = 1`, or None if the key does not continue.
+fn next_toml_key(rest: &str) -> Option<String> {
+    split_toml_key(rest.strip_prefix('.')?).map(|(key, _)| key)
+}
+
+/// Returns the names of the tasks declared in the TOML contents by looking at the table headers
+/// and keys, or None if the contents use syntax that needs to be parsed, i.e. inline tables or
+/// values spanning multiple lines in the `tasks` table.
+fn scan_toml_task_names(contents: &str) -> Option<ScannedTaskNames> {
+    /// Table the lines belong to
+    #[derive(PartialEq)]
+    enum Table {
+        Root,
+        Tasks,
+        Other,
+    }
+
+    let mut scanned = ScannedTaskNames::default();
+    let mut table = Table::Root;
+    let mut multiline_string = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if let Some(delimiter) = multiline_string {
+            if line.matches(delimiter).count() % 2 == 1 {
+                multiline_string = None;
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let is_array = header.starts_with('[');
+            let (key, rest) = split_toml_key(header.trim_start_matches('['))?;
+            table = Table::Other;
+            if key == "task_templates" {
+                scanned.has_templates = true;
+            } else if key == "tasks" {
+                if is_array {
+                    return None;
+                }
+                match next_toml_key(rest) {
+                    Some(name) => scanned.tasks.push(name),
+                    None if rest.starts_with(']') => table = Table::Tasks,
+                    None => return None,
+                }
+            }
+        } else {
+            let (key, rest) = split_toml_key(line)?;
+            if table != Table::Other {
+                // Values spanning multiple lines could be confused with keys
+                let opened = rest.matches(['[', '{']).count();
+                if opened != rest.matches([']', '}']).count() {
+                    return None;
+                }
+            }
+            match table {
+                Table::Tasks => scanned.tasks.push(key),
+                Table::Root if key == "tasks" => scanned.tasks.push(next_toml_key(rest)?),
+                Table::Root if key == "task_templates" => scanned.has_templates = true,
+                _ => {}
+            }
+        }
+        for delimiter in ["\"\"\"", "'''"] {
+            if line.matches(delimiter).count() % 2 == 1 {
+                let (_, value) = line.split_once('=')?;
+                if !value.trim_start().starts_with(delimiter) {
+                    return None;
+                }
+                multiline_string = Some(delimiter);
+            }
+        }
+    }
+    Some(scanned)
+}
+
+/// Lightweight representation of a config file that holds the raw definitions of its tasks, so
+/// that tasks with the same name can be compared across config files.
+#[derive(Debug, Deserialize)]
+struct ConfigFileTaskDefinitions {
+    /// Raw definition of the tasks inside the config file
+    #[serde(default)]
+    tasks: HashMap<String, serde_json::Value>,
+}
+
+/// Lightweight representation of a task that only holds its env file, and the ones of its OS
+/// specific tasks
+#[derive(Debug, Deserialize)]
+struct TaskEnvFiles {
+    /// Env file to read environment variables from
+    env_file: Option<String>,
+    /// Task to run instead if the OS is linux
+    linux: Option<Box<TaskEnvFiles>>,
+    /// Task to run instead if the OS is windows
+    windows: Option<Box<TaskEnvFiles>>,
+    /// Task to run instead if the OS is macos
+    macos: Option<Box<TaskEnvFiles>>,
+}
+
+impl TaskEnvFiles {
+    /// Adds the env files of the task and its OS specific tasks to the given list
+    fn collect<'a>(&'a self, env_files: &mut Vec<&'a str>) {
+        if let Some(env_file) = &self.env_file {
+            env_files.push(env_file);
+        }
+        for os_task in [&self.linux, &self.windows, &self.macos]
+            .into_iter()
+            .flatten()
+        {
+            os_task.collect(env_files);
+        }
+    }
+}
+
+/// Lightweight representation of a config file that only holds its env files, and the ones of
+/// its tasks
+#[derive(Debug, Deserialize)]
+struct ConfigFileEnvFiles {
+    /// Env file to read environment variables from
+    env_file: Option<String>,
+    /// Tasks inside the config file
+    #[serde(default)]
+    tasks: IndexMap<String, TaskEnvFiles>,
+}
+
+/// Lightweight representation of a config file that only holds its workspace
+#[derive(Debug, Deserialize)]
+struct ConfigFileWorkspace {
+    /// Members of the workspace
+    workspace: Option<Workspace>,
+}
+
+/// Lightweight representation of a config file that only holds its settings, so that they
+/// can be read without fully loading the global config file.
+#[derive(Debug, Deserialize)]
+struct ConfigFileSettings {
+    /// Settings of yamis
+    #[serde(default)]
+    settings: Settings,
+}
+
+/// Lightweight representation of a config file that only holds its variables, so that the
+/// global variables can be read without fully loading the global config file.
+#[derive(Debug, Deserialize)]
+struct ConfigFileVars {
+    /// Variables available to all the tasks
+    #[serde(default, deserialize_with = "deserialize_scalar_map")]
+    vars: IndexMap<String, String>,
+}
+
 impl ConfigFile {
-    /// Reads the file from the path and constructs a config file
-    fn extract(path: &Path) -> DynErrResult<ConfigFile> {
+    /// Reads the file from the path and deserializes it as YAML or TOML, depending on the extension
+    fn deserialize_file<T: DeserializeOwned>(path: &Path) -> DynErrResult<T> {
+        let is_yaml = ConfigFile::is_yaml_file(path)?;
+        let contents = ConfigFile::read_contents(path)?;
+        ConfigFile::deserialize_contents(&contents, is_yaml)
+    }
+
+    /// Reads the contents of the config file in the given path
+    fn read_contents(path: &Path) -> DynErrResult<String> {
+        match fs::read_to_string(path) {
+            Ok(file_contents) => Ok(file_contents),
+            Err(e) => Err(format!("There was an error reading the file:\n{}", e).into()),
+        }
+    }
+
+    /// Deserializes the contents of a config file, checking first that the fields it uses
+    /// are available in its declared version
+    fn deserialize_config(contents: &str, is_yaml: bool) -> DynErrResult<ConfigFile> {
+        let keys: ConfigFileKeys = ConfigFile::deserialize_contents(contents, is_yaml)?;
+        keys.check_versions()?;
+        ConfigFile::deserialize_contents(contents, is_yaml)
+    }
+
+    /// Returns whether the config file in the given path is a YAML file, or an error if the
+    /// extension is not valid
+    fn is_yaml_file(path: &Path) -> DynErrResult<bool> {
         let extension = path
             .extension()
             .unwrap_or_else(|| OsStr::new(""))
             .to_string_lossy()
             .to_string();
 
-        let is_yaml = match extension.as_str() {
-            "yaml" => true,
-            "yml" => true,
-            "toml" => false,
-            _ => {
-                return Err(ConfigError::BadConfigFile(
-                    path.to_path_buf(),
-                    String::from("Extension must be either `.toml`, `.yaml` or `.yml`"),
-                )
-                .into());
-            }
+        match extension.as_str() {
+            "yaml" => Ok(true),
+            "yml" => Ok(true),
+            "toml" => Ok(false),
+            _ => Err(ConfigError::BadConfigFile(
+                path.to_path_buf(),
+                String::from("Extension must be either `.toml`, `.yaml` or `.yml`"),
+            )
+            .into()),
+        }
+    }
+
+    /// Deserializes the contents of a config file as YAML or TOML
+    fn deserialize_contents<T: DeserializeOwned>(contents: &str, is_yaml: bool) -> DynErrResult<T> {
+        let result = if is_yaml {
+            ConfigFile::deserialize_yaml(contents)
+                .map_err(|e| locate_duplicate_key(contents, &e).unwrap_or_else(|| e.to_string()))
+        } else {
+            toml::from_str(contents).map_err(|e| e.to_string())
         };
-        let contents = match fs::read_to_string(path) {
-            Ok(file_contents) => file_contents,
-            Err(e) => return Err(format!("There was an error reading the file:\n{}", e).into()),
+        result.map_err(|e| add_unknown_field_suggestion(&e).into())
+    }
+
+    /// Deserializes YAML content, merging the mappings referenced with merge keys, i.e.
+    /// `<<: *base`, into the surrounding mappings.
+    fn deserialize_yaml<T: DeserializeOwned>(contents: &str) -> Result<T, serde_yaml::Error> {
+        // Errors lose their location when deserializing from a Value, so only do it if needed
+        if !has_merge_keys(contents) {
+            return serde_yaml::from_str(contents);
+        }
+        let mut value: serde_yaml::Value = serde_yaml::from_str(contents)?;
+        value.apply_merge()?;
+        serde_yaml::from_value(value)
+    }
+
+    /// Reads the file from the path and constructs a config file
+    fn extract(path: &Path) -> DynErrResult<ConfigFile> {
+        let is_yaml = ConfigFile::is_yaml_file(path)?;
+        let contents = ConfigFile::read_contents(path)?;
+        ConfigFile::deserialize_config(&contents, is_yaml)
+    }
+
+    /// Returns whether the config file in the given path defines the task, by only reading the
+    /// name of the tasks. Useful to skip loading files that do not have the task. The names are
+    /// found by scanning the lines of the file, so that files that define the task are only
+    /// parsed once when loading them, and the file is only parsed if the scan is not conclusive.
+    /// If the file cannot be read, it returns true, so that the error is raised when fully
+    /// loading it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path of the config file
+    /// * `task_name`: Name of the task to look for
+    ///
+    /// returns: bool
+    pub fn defines_task(path: &Path, task_name: &str) -> bool {
+        let (is_yaml, contents) = match ConfigFile::is_yaml_file(path)
+            .and_then(|is_yaml| Ok((is_yaml, ConfigFile::read_contents(path)?)))
+        {
+            Ok(result) => result,
+            Err(_) => return true,
         };
-        if is_yaml {
-            Ok(serde_yaml::from_str(&contents)?)
+        let scanned = if is_yaml {
+            scan_yaml_task_names(&contents)
         } else {
-            Ok(toml::from_str(&contents)?)
+            scan_toml_task_names(&contents)
+        };
+        match scanned {
+            Some(scanned) => {
+                scanned.has_templates || scanned.tasks.iter().any(|name| name == task_name)
+            }
+            // Syntax the scan does not understand, i.e. flow mappings, is parsed instead
+            None => {
+                match ConfigFile::deserialize_contents::<ConfigFileTaskNames>(&contents, is_yaml) {
+                    Ok(config_file) => {
+                        config_file.task_templates.is_some()
+                            || config_file.tasks.contains_key(task_name)
+                    }
+                    Err(_) => true,
+                }
+            }
         }
     }
 
+    /// Returns the raw definition of the task in the config file in the given path, or None if
+    /// the file cannot be read or the task is not declared in its `tasks` section, i.e. if it is
+    /// generated by a template.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path of the config file
+    /// * `task_name`: Name of the task
+    ///
+    /// returns: Option<Value>
+    pub(crate) fn task_definition(path: &Path, task_name: &str) -> Option<serde_json::Value> {
+        let mut config_file =
+            ConfigFile::deserialize_file::<ConfigFileTaskDefinitions>(path).ok()?;
+        config_file.tasks.remove(task_name)
+    }
+
+    /// Returns the paths of the env files referenced by the config file in the given path and
+    /// its tasks, without duplicates and in the order they are declared.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path of the config file
+    ///
+    /// returns: Result<Vec<PathBuf, Global>, Box<dyn Error, Global>>
+    pub(crate) fn env_file_paths(path: &Path) -> DynErrResult<Vec<PathBuf>> {
+        let config_file: ConfigFileEnvFiles = ConfigFile::deserialize_file(path)?;
+        let mut env_files = Vec::new();
+        if let Some(env_file) = &config_file.env_file {
+            env_files.push(env_file.as_str());
+        }
+        for task in config_file.tasks.values() {
+            task.collect(&mut env_files);
+        }
+        let base_path = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut paths: Vec<PathBuf> = Vec::with_capacity(env_files.len());
+        for env_file in env_files {
+            let env_file = get_path_relative_to_base(base_path, env_file);
+            if !paths.contains(&env_file) {
+                paths.push(env_file);
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Returns the workspace declared in the config file in the given path, if any, without
+    /// fully loading it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path of the config file
+    ///
+    /// returns: Result<Option<Workspace>, Box<dyn Error, Global>>
+    pub(crate) fn workspace_of(path: &Path) -> DynErrResult<Option<Workspace>> {
+        let config_file: ConfigFileWorkspace = ConfigFile::deserialize_file(path)?;
+        Ok(config_file.workspace)
+    }
+
     /// Loads a config file
     ///
     /// # Arguments
     ///
     /// * path - path of the toml file to load
     pub fn load(path: PathBuf) -> DynErrResult<ConfigFile> {
-        let mut conf: ConfigFile = ConfigFile::extract(path.as_path())?;
+        let conf: ConfigFile = ConfigFile::extract(path.as_path())?;
+        ConfigFile::init(conf, path)
+    }
+
+    /// Loads a config file from the given contents instead of reading it. The path is only
+    /// used to know the format of the contents and to resolve relative paths.
+    ///
+    /// # Arguments
+    ///
+    /// * contents - contents of the config file
+    /// * path - path the config file would have
+    pub fn load_from_str(contents: &str, path: PathBuf) -> DynErrResult<ConfigFile> {
+        let is_yaml = ConfigFile::is_yaml_file(&path)?;
+        let conf = ConfigFile::deserialize_config(contents, is_yaml)?;
+        ConfigFile::init(conf, path)
+    }
+
+    /// Sets up a deserialized config file, loading the env file and the tasks
+    fn init(mut conf: ConfigFile, path: PathBuf) -> DynErrResult<ConfigFile> {
         conf.filepath = path;
+        if conf.settings.is_some() {
+            let is_global = matches!(
+                ConfigFilePaths::get_global_config_file_path(),
+                Ok(Some(global_path)) if global_path == conf.filepath
+            );
+            if !is_global {
+                return Err("`settings` can only be set in the global config file".into());
+            }
+        }
+        conf.global_vars = ConfigFile::load_global_vars(&conf.filepath);
+        conf.state_dirs = StateDirs::new(conf.directory());
+        if let Some(prefix) = &conf.env_import_prefix {
+            conf.imported_env = import_env_vars(prefix, std::env::vars());
+        }
+
+        conf.check_constants_not_redefined(&conf.vars, "vars.")?;
+        if let Some(workspace) = &conf.workspace {
+            workspace.validate()?;
+        }
+        if let Err(e) = env::join_paths(conf.get_path_dirs()) {
+            return Err(format!("Invalid `path`: {}", e).into());
+        }
+        let plugin_paths = conf
+            .plugins
+            .iter()
+            .map(|plugin| get_path_relative_to_base(conf.directory(), plugin))
+            .collect();
+        conf.wasm_plugins = WasmPlugins::new(plugin_paths)?;
 
         if let Some(env_file_path) = &conf.env_file {
             let env_file_path = get_path_relative_to_base(conf.directory(), &env_file_path);
             let env_from_file = read_env_file(&env_file_path)?;
-            match conf.env.as_mut() {
-                None => {
-                    conf.env = Some(HashMap::from_iter(env_from_file.into_iter()));
-                }
-                Some(env) => {
-                    for (key, val) in env_from_file.into_iter() {
-                        // manually set env takes precedence over env_file
-                        env.entry(key).or_insert(val);
-                    }
+            let env = conf.env.get_or_insert_with(HashMap::new);
+            for (key, val) in env_from_file.into_iter() {
+                // manually set env takes precedence over env_file
+                if let Entry::Vacant(entry) = env.entry(key) {
+                    conf.env_file_vars.insert(entry.key().clone());
+                    entry.insert(val);
                 }
             }
         }
 
+        conf.generate_template_tasks()?;
         let mut tasks = conf.get_flat_tasks()?;
+        // Tasks are loaded in order of dependency, so we keep the declaration order to restore it
+        let declaration_order: HashMap<String, usize> = tasks
+            .keys()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), i))
+            .collect();
 
-        let dep_graph = get_task_dependency_graph(&tasks)?;
+        let dep_graph = get_task_dependency_graph(&tasks, TaskRelation::Bases)?;
         let dependencies = toposort(&dep_graph, None);
         let dependencies = match dependencies {
             Ok(dependencies) => dependencies,
@@ -422,7 +1215,7 @@ impl ConfigFile {
 
         for dependency_name in dependencies {
             // temp remove because of rules of references
-            let mut task = tasks.remove(&dependency_name).unwrap();
+            let mut task = tasks.swap_remove(&dependency_name).unwrap();
             // task.bases should be empty for the first item in the iteration
             // we no longer need the bases
             let bases = std::mem::take(&mut task.bases);
@@ -444,9 +1237,126 @@ impl ConfigFile {
         for (task_name, task) in tasks {
             conf.loaded_tasks.insert(task_name, Arc::new(task));
         }
+        conf.loaded_tasks.sort_by(|name_a, _, name_b, _| {
+            declaration_order
+                .get(name_a)
+                .cmp(&declaration_order.get(name_b))
+        });
+
+        // `deps` can be inherited, so they are checked once the bases are merged
+        let deps_graph = get_task_dependency_graph(&conf.loaded_tasks, TaskRelation::Deps)?;
+        if let Err(e) = toposort(&deps_graph, None) {
+            return Err(format!(
+                "Found a cyclic dependency in the deps of Task:\n{}",
+                e.node_id()
+            )
+            .into());
+        }
         Ok(conf)
     }
 
+    /// Reads the settings of the global config file. Errors are ignored here, as they are raised
+    /// when loading the global config file.
+    pub(crate) fn load_global_settings() -> Settings {
+        match ConfigFilePaths::get_global_config_file_path() {
+            Ok(Some(global_path)) => {
+                match ConfigFile::deserialize_file::<ConfigFileSettings>(&global_path) {
+                    Ok(global_config_file) => global_config_file.settings,
+                    Err(_) => Settings::default(),
+                }
+            }
+            _ => Settings::default(),
+        }
+    }
+
+    /// Reads the variables of the global config file, unless the given path is the global config
+    /// file itself. Errors are ignored here, as they are raised when loading the global config file.
+    fn load_global_vars(path: &Path) -> IndexMap<String, String> {
+        match ConfigFilePaths::get_global_config_file_path() {
+            Ok(Some(global_path)) if global_path != path => {
+                match ConfigFile::deserialize_file::<ConfigFileVars>(&global_path) {
+                    Ok(global_config_file) => global_config_file.vars,
+                    Err(_) => IndexMap::new(),
+                }
+            }
+            _ => IndexMap::new(),
+        }
+    }
+
+    /// Returns the directories in `path`, resolved relative to the config file directory
+    fn get_path_dirs(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.path
+            .iter()
+            .map(|dir| get_path_relative_to_base(self.directory(), dir))
+    }
+
+    /// Returns the value of the PATH environment variable with the directories in `path`
+    /// prepended, or None if there are no directories to prepend.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_path`: Current value of the PATH environment variable, if any
+    ///
+    /// returns: Option<String>
+    pub(crate) fn get_path_env(&self, current_path: Option<&OsStr>) -> Option<String> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let current_dirs = current_path.map(env::split_paths).into_iter().flatten();
+        // Directories were already checked to be joinable when loading the config file
+        env::join_paths(self.get_path_dirs().chain(current_dirs))
+            .ok()
+            .map(|path| path.to_string_lossy().to_string())
+    }
+
+    /// Returns an error if any of the given variables has the name of a constant, as constants
+    /// are the single place where their values are defined
+    ///
+    /// # Arguments
+    ///
+    /// * `vars`: Variables to check
+    /// * `prefix`: How the variables are referred to in the error, i.e. `vars.`
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    pub(crate) fn check_constants_not_redefined(
+        &self,
+        vars: &IndexMap<String, String>,
+        prefix: &str,
+    ) -> DynErrResult<()> {
+        match vars.keys().find(|name| self.constants.contains_key(*name)) {
+            Some(name) => Err(format!(
+                "`{}{}` redefines the constant `{}`, constants cannot be redefined",
+                prefix, name, name
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the variables available to the tasks of this config file in the given run
+    ///
+    /// # Arguments
+    ///
+    /// * `context`: State of the run, with the variables given from the command line
+    ///
+    /// returns: TaskVars
+    pub(crate) fn get_vars<'a>(&'a self, context: &'a RunContext) -> TaskVars<'a> {
+        let vars = TaskVars::new(Some(&self.vars), Some(&self.global_vars))
+            .with_overrides(&context.var_overrides)
+            .with_constants(&self.constants)
+            .with_invocation(&context.invocation)
+            .with_imported_env(&self.imported_env)
+            .with_state_dirs(&self.state_dirs)
+            .with_plugins(&self.wasm_plugins)
+            .with_version(self.version)
+            .with_used_args(&context.used_args)
+            .with_trace(context.trace_templates);
+        match &self.workspace {
+            Some(workspace) => vars.with_workspace_members(&workspace.members),
+            None => vars,
+        }
+    }
+
     /// Returns the directory where the config file
     pub fn directory(&self) -> &Path {
         self.filepath.parent().unwrap()
@@ -461,46 +1371,62 @@ impl ConfigFile {
             .map(|wd| get_path_relative_to_base(self.directory(), wd))
     }
 
+    /// Adds the tasks generated by the task templates, after the declared tasks
+    fn generate_template_tasks(&mut self) -> DynErrResult<()> {
+        for (template_name, template) in std::mem::take(&mut self.task_templates) {
+            for (name, task) in template.generate(&template_name)? {
+                if self.tasks.contains_key(&name) {
+                    return Err(format!("Duplicate task `{}`", name).into());
+                }
+                self.tasks.insert(name, task);
+            }
+        }
+        Ok(())
+    }
+
     /// Returns plain and OS specific tasks with normalized names. This consumes `self.tasks`
-    fn get_flat_tasks(&mut self) -> DynErrResult<HashMap<String, Task>> {
-        let mut flat_tasks = HashMap::new();
+    fn get_flat_tasks(&mut self) -> DynErrResult<IndexMap<String, Task>> {
+        let mut flat_tasks = IndexMap::new();
         let tasks = std::mem::take(&mut self.tasks);
         for (name, mut task) in tasks {
+            // OS specific tasks are inserted after the plain one to keep the declaration order
+            let mut os_tasks = Vec::new();
             // TODO: Use a macro
             if task.linux.is_some() {
-                let os_task = std::mem::replace(&mut task.linux, None);
+                let os_task = task.linux.take();
                 let mut os_task = *os_task.unwrap();
                 let os_task_name = format!("{}.linux", name);
                 if flat_tasks.contains_key(&os_task_name) {
                     return Err(format!("Duplicate task `{}`", os_task_name).into());
                 }
                 os_task.setup(&os_task_name, self.directory())?;
-                flat_tasks.insert(os_task_name, os_task);
+                os_tasks.push((os_task_name, os_task));
             }
 
             if task.windows.is_some() {
-                let os_task = std::mem::replace(&mut task.windows, None);
+                let os_task = task.windows.take();
                 let mut os_task = *os_task.unwrap();
                 let os_task_name = format!("{}.windows", name);
                 if flat_tasks.contains_key(&os_task_name) {
                     return Err(format!("Duplicate task `{}`", os_task_name).into());
                 }
                 os_task.setup(&os_task_name, self.directory())?;
-                flat_tasks.insert(os_task_name, os_task);
+                os_tasks.push((os_task_name, os_task));
             }
 
             if task.macos.is_some() {
-                let os_task = std::mem::replace(&mut task.macos, None);
+                let os_task = task.macos.take();
                 let mut os_task = *os_task.unwrap();
                 let os_task_name = format!("{}.macos", name);
                 if flat_tasks.contains_key(&os_task_name) {
                     return Err(format!("Duplicate task `{}`", os_task_name).into());
                 }
                 os_task.setup(&os_task_name, self.directory())?;
-                flat_tasks.insert(os_task_name, os_task);
+                os_tasks.push((os_task_name, os_task));
             }
             task.setup(&name, self.directory())?;
             flat_tasks.insert(name, task);
+            flat_tasks.extend(os_tasks);
         }
         Ok(flat_tasks)
     }
@@ -513,14 +1439,21 @@ impl ConfigFile {
     ///
     /// * task_name - Name of the task to search for
     pub fn get_task(&self, task_name: &str) -> Option<Arc<Task>> {
+        self.get_task_ref(task_name).map(Arc::clone)
+    }
+
+    /// Same as [ConfigFile::get_task], but returns a reference to the task, so that it can be
+    /// used without touching the reference count.
+    ///
+    /// # Arguments
+    ///
+    /// * task_name - Name of the task to search for
+    pub(crate) fn get_task_ref(&self, task_name: &str) -> Option<&Arc<Task>> {
         let os_task_name = to_os_task_name(task_name);
 
-        if let Some(task) = self.loaded_tasks.get(&os_task_name) {
-            return Some(Arc::clone(task));
-        } else if let Some(task) = self.loaded_tasks.get(task_name) {
-            return Some(Arc::clone(task));
-        }
-        None
+        self.loaded_tasks
+            .get(&os_task_name)
+            .or_else(|| self.loaded_tasks.get(task_name))
     }
 
     /// Finds an public task by name on this config file and returns it if it exists.
@@ -531,20 +1464,10 @@ impl ConfigFile {
     ///
     /// * task_name - Name of the task to search for
     pub fn get_public_task(&self, task_name: &str) -> Option<Arc<Task>> {
-        let os_task_name = to_os_task_name(task_name);
-
-        if let Some(task) = self.loaded_tasks.get(&os_task_name) {
-            if task.is_private() {
-                return None;
-            }
-            return Some(Arc::clone(task));
-        } else if let Some(task) = self.loaded_tasks.get(task_name) {
-            if task.is_private() {
-                return None;
-            }
-            return Some(Arc::clone(task));
+        match self.get_task_ref(task_name) {
+            Some(task) if !task.is_private() => Some(Arc::clone(task)),
+            _ => None,
         }
-        None
     }
 
     /// Returns whether the config file has a task with the given name. This also
@@ -567,6 +1490,21 @@ impl ConfigFile {
         self.loaded_tasks.keys().collect()
     }
 
+    /// Returns the names of the tasks in this config file, excluding the OS specific ones
+    pub(crate) fn get_task_base_names(&self) -> Vec<&str> {
+        self.loaded_tasks
+            .keys()
+            .filter(|name| {
+                !["linux", "windows", "macos"].iter().any(|os| {
+                    name.strip_suffix(os)
+                        .and_then(|name| name.strip_suffix('.'))
+                        .is_some_and(|base| self.loaded_tasks.contains_key(base))
+                })
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
     /// Returns the list of names of tasks that are not private in this config file
     pub fn get_public_task_names(&self) -> Vec<&str> {
         self.loaded_tasks
@@ -575,14 +1513,351 @@ impl ConfigFile {
             .map(|t| t.get_name())
             .collect()
     }
+
+    /// Returns the names and summaries of the tasks that are not private
+    pub fn get_public_task_summaries(&self) -> Vec<(&str, &str)> {
+        self.loaded_tasks
+            .values()
+            .filter(|t| !t.is_private())
+            .map(|t| (t.get_name(), t.get_summary()))
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::ExecutionBackend;
+    use crate::tasks::RenderedCommand;
+    use crate::types::TaskArgs;
     use assert_fs::TempDir;
     use std::fs::File;
     use std::io::Write;
+    use std::sync::Condvar;
+    use std::time::Duration;
+
+    #[test]
+    fn test_tasks_declaration_order() {
+        let tmp_dir = TempDir::new().unwrap();
+        let yaml_path = tmp_dir.path().join("project.yamis.yml");
+        let mut yaml_file = File::create(yaml_path.as_path()).unwrap();
+        yaml_file
+            .write_all(
+                r#"
+    tasks:
+        zeta:
+            script: echo zeta
+            bases: [beta]
+        alpha:
+            script: echo alpha
+            linux:
+                script: echo alpha linux
+        beta:
+            script: echo beta
+    "#
+                .as_bytes(),
+            )
+            .unwrap();
+
+        let toml_path = tmp_dir.path().join("yamis.toml");
+        let mut toml_file = File::create(toml_path.as_path()).unwrap();
+        toml_file
+            .write_all(
+                r#"
+    [tasks.zeta]
+    script = "echo zeta"
+    bases = ["beta"]
+
+    [tasks.alpha]
+    script = "echo alpha"
+
+    [tasks.alpha.linux]
+    script = "echo alpha linux"
+
+    [tasks.beta]
+    script = "echo beta"
+    "#
+                .as_bytes(),
+            )
+            .unwrap();
+
+        for path in [yaml_path, toml_path] {
+            let config_file = ConfigFile::load(path).unwrap();
+            assert_eq!(
+                config_file.get_task_names(),
+                vec!["zeta", "alpha", "alpha.linux", "beta"]
+            );
+            assert_eq!(
+                config_file.get_task_base_names(),
+                vec!["zeta", "alpha", "beta"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_yaml_merge_keys() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        let mut config_file = File::create(config_path.as_path()).unwrap();
+        config_file
+            .write_all(
+                r#"
+    tasks:
+        base: &base
+            private: true
+            script: echo base
+            env: &base_env
+                A: base a
+                B: base b
+        hello:
+            <<: *base
+            private: false
+            env:
+                <<: *base_env
+                B: hello b
+    "#
+                .as_bytes(),
+            )
+            .unwrap();
+
+        let config_file = ConfigFile::load(config_path).unwrap();
+        let task = config_file.get_public_task("hello").unwrap();
+        assert_eq!(task.env.get("A").unwrap(), "base a");
+        assert_eq!(task.env.get("B").unwrap(), "hello b");
+        assert!(config_file.get_public_task("base").is_none());
+    }
+
+    #[test]
+    fn test_has_merge_keys() {
+        assert!(has_merge_keys("hello:\n  <<: *base\n"));
+        assert!(has_merge_keys("<<: *base"));
+        assert!(has_merge_keys("list:\n  - <<: *base\n"));
+        assert!(has_merge_keys("hello: {<<: *base, a: 1}"));
+        assert!(has_merge_keys("hello: {a: 1, << : *base}"));
+        assert!(!has_merge_keys("script: cat <<EOF > file"));
+        assert!(!has_merge_keys("script: |\n  cat << EOF\n  a: b\n  EOF\n"));
+        assert!(!has_merge_keys("script: echo \"<<: not a key\""));
+    }
+
+    #[test]
+    fn test_heredoc_errors_keep_location() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        fs::write(
+            &config_path,
+            "tasks:\n  hello:\n    script: |\n      cat <<EOF\n      hello\n      EOF\n    scrpt: x\n",
+        )
+        .unwrap();
+        let err = ConfigFile::load(config_path).unwrap_err();
+        assert!(err.to_string().contains("at line 7 column 5"), "{}", err);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        let mut config_file = File::create(config_path.as_path()).unwrap();
+        config_file
+            .write_all(
+                r#"
+    tasks:
+        build:
+            script: echo one
+        build:
+            script: echo two
+    "#
+                .as_bytes(),
+            )
+            .unwrap();
+        let err = ConfigFile::load(config_path).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "tasks: duplicate key `build`, declared at line 3 column 9 and again at line 5 column 9"
+        );
+
+        let config_path = tmp_dir.path().join("yamis.yml");
+        let mut config_file = File::create(config_path.as_path()).unwrap();
+        config_file
+            .write_all(
+                r#"
+    env:
+        A: one
+        B: two
+    tasks:
+        build:
+            script: echo one
+            env:
+                A: one
+                A: two
+    "#
+                .as_bytes(),
+            )
+            .unwrap();
+        let err = ConfigFile::load(config_path).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "tasks.build.env: duplicate key `A`, declared at line 9 column 17 and again at line 10 \
+            column 17"
+        );
+    }
+
+    #[test]
+    fn test_defines_task() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        let mut config_file = File::create(config_path.as_path()).unwrap();
+        config_file
+            .write_all(
+                r#"
+    tasks:
+        hello:
+            script: echo hello
+        bad:
+            unknown_field: true
+    "#
+                .as_bytes(),
+            )
+            .unwrap();
+
+        assert!(ConfigFile::defines_task(&config_path, "hello"));
+        assert!(ConfigFile::defines_task(&config_path, "bad"));
+        assert!(!ConfigFile::defines_task(&config_path, "bye"));
+
+        let config_path = tmp_dir.path().join("yamis.toml");
+        let mut config_file = File::create(config_path.as_path()).unwrap();
+        config_file
+            .write_all(b"[tasks.hello]\nscript = 1\n[")
+            .unwrap();
+        // Cannot be parsed, so the error should be raised when loading the file
+        assert!(ConfigFile::defines_task(&config_path, "bye"));
+    }
+
+    #[test]
+    fn test_scan_task_names() {
+        let scanned = scan_yaml_task_names(
+            r#"
+    version: 2
+    # tasks:
+    tests:
+      test_hello:
+        task: hello
+    tasks:
+      hello:
+        script: |
+          bye: not a task
+      "quoted name": # comment
+        script: echo quoted
+      build:prod:
+        script: echo prod
+
+      'single':
+        program: echo
+    vars:
+      other: 1
+    "#,
+        )
+        .unwrap();
+        assert_eq!(
+            scanned.tasks,
+            vec!["hello", "quoted name", "build:prod", "single"]
+        );
+        assert!(!scanned.has_templates);
+
+        let scanned = scan_yaml_task_names(
+            "tasks:
+  hello:
+    script: hi
+task_templates: []
+",
+        );
+        assert!(scanned.unwrap().has_templates);
+
+        // Syntax that needs to be parsed
+        assert_eq!(scan_yaml_task_names("tasks: {hello: {script: hi}}"), None);
+        assert_eq!(
+            scan_yaml_task_names(
+                "tasks:
+  <<: *base
+"
+            ),
+            None
+        );
+        assert_eq!(
+            scan_yaml_task_names(
+                "tasks:
+  - hello
+"
+            ),
+            None
+        );
+        assert_eq!(
+            scan_yaml_task_names(
+                "---
+tasks:
+  hello: {}
+"
+            ),
+            None
+        );
+
+        let scanned = scan_toml_task_names(
+            r#"
+    [settings]
+    tasks = 1
+
+    [tasks.hello]
+    script = """
+    [tasks.bye]
+    """
+
+    [tasks."quoted name"]
+    script = "echo quoted"
+
+    [tasks]
+    inline = { script = "echo inline" }
+    dotted.script = "echo dotted"
+    "#,
+        )
+        .unwrap();
+        assert_eq!(
+            scanned.tasks,
+            vec!["hello", "quoted name", "inline", "dotted"]
+        );
+        assert!(!scanned.has_templates);
+
+        let scanned = scan_toml_task_names(
+            "tasks.hello.script = 'hi'
+[[task_templates]]
+",
+        );
+        let scanned = scanned.unwrap();
+        assert_eq!(scanned.tasks, vec!["hello"]);
+        assert!(scanned.has_templates);
+
+        // Syntax that needs to be parsed
+        assert_eq!(
+            scan_toml_task_names("tasks = { hello = { script = 'hi' } }"),
+            None
+        );
+        assert_eq!(
+            scan_toml_task_names(
+                "[tasks]
+hello.args = [
+  'a = b',
+]
+"
+            ),
+            None
+        );
+        assert_eq!(
+            scan_toml_task_names(
+                "[tasks.hello]
+script = 1
+["
+            ),
+            None
+        );
+    }
 
     #[test]
     fn test_discovery() {
@@ -592,8 +1867,14 @@ mod tests {
         project_config_file
             .write_all(
                 r#"
+    [vars]
+    NAME = "project"
+
     [tasks.hello_project]
     script = "echo hello project"
+
+    [tasks.hello_vars]
+    script = "echo {vars.GREETING} {vars.NAME} {file.vars.GREETING?}"
     "#
                 .as_bytes(),
             )
@@ -631,6 +1912,13 @@ mod tests {
         global_config_file
             .write_all(
                 r#"
+                [settings]
+                update_check = "silent"
+
+                [vars]
+                NAME = "global"
+                GREETING = "hello"
+
                 [tasks.hello_global]
                 script = "echo hello project"
                 "#
@@ -638,13 +1926,28 @@ mod tests {
             )
             .unwrap();
 
-        let mut config_files = ConfigFilesContainer::new();
+        // Global variables are available, but the ones of the file take precedence
+        let project_config_file = ConfigFile::load(project_config_path).unwrap();
+        let commands = project_config_file
+            .get_task("hello_vars")
+            .unwrap()
+            .render(&TaskArgs::new(), &project_config_file)
+            .unwrap();
+        assert_eq!(commands[0].script(), Some("echo \"hello\" \"project\" "));
+
+        let config_files = ConfigFilesContainer::new();
         let mut paths = ConfigFilePaths::new(&tmp_dir.path());
         let local_path = paths.next().unwrap().unwrap();
         let regular_path = paths.next().unwrap().unwrap();
         let project_path = paths.next().unwrap().unwrap();
         let global_path = paths.next().unwrap().unwrap();
         assert!(paths.next().is_none());
+        assert_eq!(
+            ConfigFilePaths::new(&tmp_dir.path())
+                .without_global()
+                .count(),
+            3
+        );
         config_files.read_config_file(local_path).unwrap();
         config_files.read_config_file(regular_path).unwrap();
         config_files.read_config_file(project_path).unwrap();
@@ -672,7 +1975,7 @@ mod tests {
             )
             .unwrap();
 
-        let mut config_files = ConfigFilesContainer::new();
+        let config_files = ConfigFilesContainer::new();
         let mut paths = ConfigFilePaths::only(&sample_config_file_path).unwrap();
         let sample_path = paths.next().unwrap().unwrap();
         assert!(paths.next().is_none());
@@ -681,6 +1984,104 @@ mod tests {
         assert!(config_files.has_task("hello_project"));
     }
 
+    /// Records the commands it executes, waiting for the given number of runs to be executing
+    /// commands at the same time
+    struct OverlappingBackend {
+        runs: usize,
+        executing: Mutex<usize>,
+        all_executing: Condvar,
+        executed: Mutex<Vec<String>>,
+    }
+
+    impl ExecutionBackend for OverlappingBackend {
+        fn execute(&self, command: &RenderedCommand) -> DynErrResult<()> {
+            let mut executing = self.executing.lock().unwrap();
+            *executing += 1;
+            self.all_executing.notify_all();
+            let (executing, result) = self
+                .all_executing
+                .wait_timeout_while(executing, Duration::from_secs(10), |executing| {
+                    *executing < self.runs
+                })
+                .unwrap();
+            drop(executing);
+            if result.timed_out() {
+                return Err("The runs did not execute commands at the same time".into());
+            }
+            let arg = command.args()[0].to_string_lossy().to_string();
+            self.executed.lock().unwrap().push(arg);
+            Ok(())
+        }
+
+        fn is_dry_run(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_run_tasks_of_config_file_from_threads() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        fs::write(
+            &config_path,
+            r#"
+tasks:
+  build:
+    program: yamis::echo
+    args: ["build"]
+  deploy:
+    deps: [build]
+    program: yamis::echo
+    args: ["{$TARGET}"]
+"#,
+        )
+        .unwrap();
+
+        let config_files = ConfigFilesContainer::new();
+        let backend = OverlappingBackend {
+            runs: 2,
+            executing: Mutex::new(0),
+            all_executing: Condvar::new(),
+            executed: Mutex::new(Vec::new()),
+        };
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = ["staging", "prod"]
+                .into_iter()
+                .map(|target| {
+                    let config_files = &config_files;
+                    let config_path = config_path.clone();
+                    let backend = &backend;
+                    scope.spawn(move || {
+                        let config_file_ptr = config_files.read_config_file(config_path).unwrap();
+                        let config_file = config_file_ptr.read().unwrap();
+                        let context = RunContext::new().with_env_overrides(HashMap::from([(
+                            String::from("TARGET"),
+                            String::from(target),
+                        )]));
+                        config_file
+                            .get_task("deploy")
+                            .unwrap()
+                            .run_with_backend(&TaskArgs::new(), &config_file, &context, backend)
+                            .map_err(|e| e.to_string())
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap().unwrap();
+            }
+        });
+        // Each run has its own overrides and dependencies that already ran
+        let mut executed = backend.executed.into_inner().unwrap();
+        executed.sort();
+        assert_eq!(executed, vec!["build", "build", "prod", "staging"]);
+
+        // All the threads got the same config file
+        let first = config_files.read_config_file(config_path.clone()).unwrap();
+        let second = config_files.read_config_file(config_path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(config_files.cached.read().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_dup_config_error() {
         let tmp_dir = TempDir::new().unwrap();
@@ -704,27 +2105,53 @@ mod tests {
     fn test_config_file_only_iter() {
         let path = PathBuf::from("sample_path.yml");
         let mut config_files = ConfigFilePaths {
-            index: 0,
+            pending: VecDeque::new(),
             ended: false,
             root_reached: true,
             single: true,
+            single_dir: false,
+            skip_global: true,
             current_dir: path.clone(),
             cached: vec![],
+            searched_dirs: vec![],
         };
         // cache is empty, nothing to return
         assert!(config_files.next().is_none());
 
         let mut config_files = ConfigFilePaths {
-            index: 0,
+            pending: VecDeque::new(),
             ended: false,
             root_reached: true,
             single: true,
+            single_dir: false,
+            skip_global: true,
             current_dir: path.clone(),
             cached: vec![path.clone()],
+            searched_dirs: vec![],
         };
         assert_eq!(config_files.next().unwrap().unwrap(), path);
     }
 
+    #[test]
+    fn test_config_file_only_invalid() {
+        let tmp_dir = TempDir::new().unwrap();
+        let err = ConfigFilePaths::only(tmp_dir.path()).err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("Expected a file, but found a directory. Config files are named"));
+
+        let path = tmp_dir.path().join("tasks.json");
+        File::create(&path).unwrap();
+        let err = ConfigFilePaths::only(&path).err().unwrap();
+        assert!(err.to_string().contains(
+            "Unsupported extension. Config files are named `local.yamis`, `yamis` or `project.yamis`, with the `.yml`, `.yaml` or `.toml` extension."
+        ));
+
+        let path = tmp_dir.path().join("tasks.yml");
+        File::create(&path).unwrap();
+        assert!(ConfigFilePaths::only(&path).is_ok());
+    }
+
     #[test]
     fn test_config_file_invalid_path() {
         let cnfg = ConfigFile::extract(Path::new("non_existent"));
@@ -751,7 +2178,7 @@ mod tests {
             )
             .unwrap();
 
-        let mut config_files = ConfigFilesContainer::default();
+        let config_files = ConfigFilesContainer::default();
         let result = config_files.read_config_file(project_config_path);
 
         assert!(result.is_err());
@@ -917,4 +2344,181 @@ tasks:
             .to_string()
             .contains("Bad config file"));
     }
+
+    #[test]
+    fn test_import_env_vars() {
+        let vars = [
+            (String::from("MYAPP_NAME"), String::from("yamis")),
+            (String::from("MYAPP_"), String::from("ignored")),
+            (String::from("OTHER_NAME"), String::from("ignored")),
+            (String::from("MYAPP_A"), String::from("a")),
+        ];
+        let imported = import_env_vars("MYAPP_", vars);
+        assert_eq!(
+            imported,
+            IndexMap::from([
+                (String::from("A"), String::from("a")),
+                (String::from("NAME"), String::from("yamis")),
+            ])
+        );
+
+        std::env::set_var("YAMIS_TEST_IMPORT_NAME", "John");
+        let config_file = ConfigFile::load_from_str(
+            r#"
+env_import_prefix: YAMIS_TEST_IMPORT_
+tasks:
+  hello:
+    program: echo
+    args: ["{cfg.NAME}", "{cfg.MISSING?}"]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let commands = config_file
+            .get_task("hello")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap();
+        assert_eq!(commands[0].args(), &["John"]);
+    }
+
+    #[test]
+    fn test_constants() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+constants:
+  PORT: "8080"
+vars:
+  HOST: localhost
+tasks:
+  serve:
+    program: serve
+    args: ["{vars.HOST}", "{const.PORT}", "{const.MISSING?}"]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let commands = config_file
+            .get_task("serve")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap();
+        assert_eq!(commands[0].args(), &["localhost", "8080"]);
+
+        // Constants are not overridden by variables of the same name
+        let var_overrides = IndexMap::from([(String::from("PORT"), String::from("80"))]);
+        let context = RunContext::new().with_var_overrides(var_overrides.clone());
+        let commands = config_file
+            .get_task("serve")
+            .unwrap()
+            .render_in(&TaskArgs::new(), &config_file, &context)
+            .unwrap();
+        assert_eq!(commands[0].args(), &["localhost", "8080"]);
+        let err = config_file
+            .check_constants_not_redefined(&var_overrides, "--var ")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`--var PORT` redefines the constant `PORT`, constants cannot be redefined"
+        );
+
+        let err = ConfigFile::load_from_str(
+            "constants:\n  PORT: \"8080\"\nvars:\n  PORT: \"80\"\n",
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`vars.PORT` redefines the constant `PORT`, constants cannot be redefined"
+        );
+    }
+
+    #[test]
+    fn test_settings_only_in_global_config_file() {
+        let err = ConfigFile::load_from_str(
+            "settings:\n  update_check: off\n",
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`settings` can only be set in the global config file"
+        );
+    }
+
+    #[test]
+    fn test_task_templates() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  base:
+    private: true
+    program: deploy
+task_templates:
+  deploy:
+    instances:
+      - service: api
+      - service: web
+    bases: [base]
+    args: ["{{ param.service }}"]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        assert_eq!(
+            config_file.get_public_task_names(),
+            vec!["deploy-api", "deploy-web"]
+        );
+        let commands = config_file
+            .get_task("deploy-web")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap();
+        assert_eq!(commands[0].args(), &["web"]);
+        assert!(ConfigFile::defines_task(
+            Path::new("missing.yml"),
+            "deploy-web"
+        ));
+
+        let err = ConfigFile::load_from_str(
+            r#"
+tasks:
+  deploy-api:
+    script: echo api
+task_templates:
+  deploy:
+    instances: [{service: api}]
+    script: echo {{ param.service }}
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Duplicate task `deploy-api`");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_get_path_env() {
+        let config_file = ConfigFile::load_from_str(
+            "path: [node_modules/.bin, /opt/bin]\n",
+            PathBuf::from("/project/project.yamis.yml"),
+        )
+        .unwrap();
+        let current_path = env::join_paths([Path::new("/usr/bin"), Path::new("/bin")]).unwrap();
+        let path_env = config_file.get_path_env(Some(&current_path)).unwrap();
+        let dirs: Vec<PathBuf> = env::split_paths(&path_env).collect();
+        assert_eq!(
+            dirs,
+            vec![
+                PathBuf::from("/project").join("node_modules/.bin"),
+                PathBuf::from("/opt/bin"),
+                PathBuf::from("/usr/bin"),
+                PathBuf::from("/bin"),
+            ]
+        );
+
+        let config_file =
+            ConfigFile::load_from_str("{}", PathBuf::from("/project/project.yamis.yml")).unwrap();
+        assert_eq!(config_file.get_path_env(Some(&current_path)), None);
+    }
 }
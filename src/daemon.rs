@@ -0,0 +1,804 @@
+//! Daemon exposing a JSON API through a Unix socket, so that editors and other tools can
+//! list and run tasks without spawning a new yamis process for each action.
+//!
+//! The protocol is line based. Each line sent by the client is a JSON request, and each line
+//! sent back by the daemon is a JSON response. Requests are tagged by `command`:
+//!
+//! * `{"command": "list", "dir": "/path"}`: Lists the public tasks reachable from `dir`.
+//! * `{"command": "run", "dir": "/path", "task": "name", "args": ["arg"]}`: Runs the task,
+//!   streaming its output. The first response contains the id of the run.
+//! * `{"command": "cancel", "id": 1}`: Kills the process of the given run.
+//!
+//! Only the owner of the socket can connect to it, as it is created with `0600` permissions
+//! inside a directory with `0700` permissions.
+use crate::backend::{ExecutionBackend, OutputSink, ProcessBackend};
+use crate::cli::TaskSubcommand;
+use crate::config_files::{ConfigFile, ConfigFilePaths};
+use crate::run_context::RunContext;
+use crate::runs;
+use crate::runs::RunTracker;
+use crate::types::DynErrResult;
+use directories::ProjectDirs;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// Name of the socket file in the default directory
+const SOCKET_FILE_NAME: &str = "daemon.sock";
+
+/// Request sent by clients
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Request {
+    /// Lists the public tasks reachable from the given directory
+    List {
+        /// Directory to search for config files
+        dir: PathBuf,
+    },
+    /// Runs the task found from the given directory
+    Run {
+        /// Directory to search for config files
+        dir: PathBuf,
+        /// Name of the task
+        task: String,
+        /// Arguments given to the task, as they would be given in the command line
+        #[serde(default)]
+        args: Vec<String>,
+        /// Whether to run the task even if it is already running, like `--force`
+        #[serde(default)]
+        force: bool,
+    },
+    /// Cancels a running task
+    Cancel {
+        /// Id of the run, as returned when it started
+        id: u64,
+    },
+}
+
+/// Task listed by the daemon
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct TaskSummary {
+    /// Name of the task
+    pub name: String,
+    /// Help of the task
+    pub help: String,
+    /// Config file defining the task
+    pub file: PathBuf,
+}
+
+/// Response sent by the daemon
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// Tasks reachable from the requested directory
+    Tasks {
+        /// Tasks found, in the order of the config files
+        tasks: Vec<TaskSummary>,
+    },
+    /// The task started running
+    Started {
+        /// Id of the run, used to cancel it
+        id: u64,
+    },
+    /// Line written by the task to the standard output
+    Stdout {
+        /// Content of the line, without the line break
+        data: String,
+        /// Milliseconds since the run started when the line was read, so that it can be
+        /// ordered against the lines of the standard error
+        #[serde(default)]
+        elapsed_ms: u64,
+    },
+    /// Line written by the task to the standard error
+    Stderr {
+        /// Content of the line, without the line break
+        data: String,
+        /// Milliseconds since the run started when the line was read, so that it can be
+        /// ordered against the lines of the standard output
+        #[serde(default)]
+        elapsed_ms: u64,
+    },
+    /// The run was cancelled
+    Cancelled {
+        /// Id of the run
+        id: u64,
+    },
+    /// The task finished
+    Exit {
+        /// Exit code of the last process, None if it was terminated by a signal
+        code: Option<i32>,
+        /// Whether all the processes finished with an allowed exit code
+        success: bool,
+    },
+    /// The request failed
+    Error {
+        /// Error message
+        message: String,
+    },
+}
+
+impl Response {
+    /// Returns whether this is the last response for a request
+    pub fn is_final(&self) -> bool {
+        !matches!(
+            self,
+            Response::Started { .. } | Response::Stdout { .. } | Response::Stderr { .. }
+        )
+    }
+}
+
+/// Task being run by the daemon
+#[derive(Debug, Default)]
+struct RunningTask {
+    /// Pid of the process currently running, if any
+    pid: Option<u32>,
+    /// Whether a process was spawned
+    spawned: bool,
+    /// Whether the run was cancelled
+    cancelled: bool,
+}
+
+/// Runs currently in progress, by id
+type RunningTasks = Arc<Mutex<HashMap<u64, RunningTask>>>;
+
+/// Returns the default path of the socket
+pub fn default_socket_path() -> DynErrResult<PathBuf> {
+    let proj_dirs = match ProjectDirs::from("", "", "yamis") {
+        Some(proj_dirs) => proj_dirs,
+        None => return Err("Could not find the directory for the daemon socket".into()),
+    };
+    let dir = match proj_dirs.runtime_dir() {
+        Some(dir) => dir.to_path_buf(),
+        None => proj_dirs.cache_dir().to_path_buf(),
+    };
+    Ok(dir.join(SOCKET_FILE_NAME))
+}
+
+/// Daemon listening for requests in a Unix socket
+pub struct Daemon {
+    /// Listener of the socket
+    listener: UnixListener,
+    /// Path of the socket, removed when the daemon is dropped
+    socket_path: PathBuf,
+    /// Runs in progress, shared between connections
+    running: RunningTasks,
+    /// Id of the next run
+    next_id: Arc<AtomicU64>,
+}
+
+impl Daemon {
+    /// Binds the daemon to the given socket path. The parent directory is created if needed,
+    /// only accessible by the current user. Fails if another daemon is listening in the path.
+    ///
+    /// # Arguments
+    ///
+    /// * `socket_path`: Path of the socket
+    ///
+    /// returns: Result<Daemon, Box<dyn Error, Global>>
+    pub fn bind(socket_path: &Path) -> DynErrResult<Daemon> {
+        if let Some(parent) = socket_path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::DirBuilder::new()
+                    .recursive(true)
+                    .mode(0o700)
+                    .create(parent)?;
+            }
+        }
+        if socket_path.exists() {
+            if UnixStream::connect(socket_path).is_ok() {
+                return Err(
+                    format!("A daemon is already listening in {}", socket_path.display()).into(),
+                );
+            }
+            // Left by a daemon that did not exit cleanly
+            fs::remove_file(socket_path)?;
+        }
+        let listener = UnixListener::bind(socket_path)?;
+        fs::set_permissions(socket_path, fs::Permissions::from_mode(0o600))?;
+        Ok(Daemon {
+            listener,
+            socket_path: socket_path.to_path_buf(),
+            running: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU64::new(1)),
+        })
+    }
+
+    /// Returns the path of the socket
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Accepts connections until the listener fails, handling each one in its own thread
+    pub fn serve(&self) -> DynErrResult<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let running = Arc::clone(&self.running);
+            let next_id = Arc::clone(&self.next_id);
+            thread::spawn(move || {
+                // The client disconnected, nothing else to do
+                let _ = handle_connection(stream, &running, &next_id);
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
+
+/// Writes the response as a JSON line
+fn send(stream: &mut UnixStream, response: &Response) -> DynErrResult<()> {
+    let mut line = serde_json::to_string(response)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Handles the requests of a connection until it is closed
+fn handle_connection(
+    stream: UnixStream,
+    running: &RunningTasks,
+    next_id: &AtomicU64,
+) -> DynErrResult<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::List { dir }) => {
+                list_tasks(&dir).and_then(|tasks| send(&mut writer, &Response::Tasks { tasks }))
+            }
+            Ok(Request::Run {
+                dir,
+                task,
+                args,
+                force,
+            }) => {
+                let id = next_id.fetch_add(1, Ordering::SeqCst);
+                run_task(&dir, &task, args, force, id, running, &writer)
+            }
+            Ok(Request::Cancel { id }) => cancel_task(id, running)
+                .and_then(|_| send(&mut writer, &Response::Cancelled { id })),
+            Err(e) => Err(format!("Invalid request: {}", e).into()),
+        };
+        // If the error comes from a closed connection, sending it fails as well and the
+        // connection is dropped
+        if let Err(e) = result {
+            send(
+                &mut writer,
+                &Response::Error {
+                    message: e.to_string(),
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the public tasks reachable from the given directory
+fn list_tasks(dir: &Path) -> DynErrResult<Vec<TaskSummary>> {
+    let mut tasks = Vec::new();
+    for path in ConfigFilePaths::new(dir) {
+        let config_file = ConfigFile::load(path?)?;
+        for name in config_file.get_public_task_names() {
+            let task = config_file.get_public_task(name).unwrap();
+            tasks.push(TaskSummary {
+                name: String::from(name),
+                help: String::from(task.get_help()),
+                file: config_file.filepath.clone(),
+            });
+        }
+    }
+    Ok(tasks)
+}
+
+/// Loads the first config file reachable from the directory with a public task with the given
+/// name
+fn find_task_config_file(dir: &Path, task_name: &str) -> DynErrResult<ConfigFile> {
+    for path in ConfigFilePaths::new(dir) {
+        let config_file = ConfigFile::load(path?)?;
+        if config_file.get_public_task(task_name).is_some() {
+            return Ok(config_file);
+        }
+    }
+    Err(format!("Task {} not found", task_name).into())
+}
+
+/// Kills the process with the given pid
+fn kill_process(pid: u32) -> DynErrResult<()> {
+    let result = unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Cancels the given run, killing the process it is currently running, if any
+fn cancel_task(id: u64, running: &RunningTasks) -> DynErrResult<()> {
+    let mut running = running.lock().unwrap();
+    match running.get_mut(&id) {
+        Some(running_task) => {
+            running_task.cancelled = true;
+            match running_task.pid {
+                Some(pid) => kill_process(pid),
+                None => Ok(()),
+            }
+        }
+        None => Err(format!("No task running with id {}", id).into()),
+    }
+}
+
+/// Returns the milliseconds elapsed since the given instant
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+/// Streams the output of a run to the client, and keeps the pid of the process it is running
+/// up to date, so that it can be cancelled from other connections
+#[derive(Debug)]
+struct DaemonOutput {
+    /// Id of the run
+    id: u64,
+    /// Connection of the client that requested the run
+    writer: Mutex<UnixStream>,
+    /// Runs in progress, shared between connections
+    running: RunningTasks,
+    /// When the run started
+    started: Instant,
+}
+
+impl DaemonOutput {
+    /// Sends the response to the client
+    fn send(&self, response: &Response) -> DynErrResult<()> {
+        send(&mut self.writer.lock().unwrap(), response)
+    }
+}
+
+impl OutputSink for DaemonOutput {
+    fn write_line(&self, line: &str, is_stderr: bool) {
+        let data = String::from(line);
+        let elapsed_ms = elapsed_ms(self.started);
+        let response = if is_stderr {
+            Response::Stderr { data, elapsed_ms }
+        } else {
+            Response::Stdout { data, elapsed_ms }
+        };
+        // Nobody reads the output once the client disconnects
+        if self.send(&response).is_err() {
+            let _ = cancel_task(self.id, &self.running);
+        }
+    }
+
+    fn set_pid(&self, pid: Option<u32>) {
+        let mut running = self.running.lock().unwrap();
+        if let Some(running_task) = running.get_mut(&self.id) {
+            running_task.pid = pid;
+            if pid.is_some() {
+                running_task.spawned = true;
+            }
+            // Cancelled before the process was spawned, i.e. between two commands
+            if let (Some(pid), true) = (pid, running_task.cancelled) {
+                let _ = kill_process(pid);
+            }
+        }
+    }
+}
+
+/// Runs the task, streaming its output to the client. The task runs like it would from the
+/// command line, so its dependencies, lock and fingerprint are taken into account, and it fails
+/// if it is not reentrant and already running, unless forced.
+fn run_task(
+    dir: &Path,
+    task_name: &str,
+    args: Vec<String>,
+    force: bool,
+    id: u64,
+    running: &RunningTasks,
+    writer: &UnixStream,
+) -> DynErrResult<()> {
+    let args = args.into_iter().map(OsString::from).collect();
+    let args = TaskSubcommand::get_task_args(args, true);
+    let config_file = find_task_config_file(dir, task_name)?;
+    let task = config_file.get_public_task(task_name).unwrap();
+    let exclusive = !force && !task.is_reentrant();
+    let _run = runs::register_run(task_name, &config_file.filepath, exclusive)?;
+
+    // Registered before the run starts, so that it can be cancelled right away
+    running.lock().unwrap().insert(id, RunningTask::default());
+    let output = Arc::new(DaemonOutput {
+        id,
+        writer: Mutex::new(writer.try_clone()?),
+        running: Arc::clone(running),
+        started: Instant::now(),
+    });
+    // Tracking is not essential, so the task runs even if the state file cannot be written
+    let backend = match runs::default_runs_dir()
+        .and_then(|runs_dir| RunTracker::start_numbered(&runs_dir, task_name, dir, id))
+    {
+        Ok(run) => ProcessBackend::tracked(run),
+        Err(_) => ProcessBackend::new(),
+    };
+    let backend = backend.with_output(Arc::clone(&output) as Arc<dyn OutputSink>);
+    let result = output
+        .send(&Response::Started { id })
+        .and_then(|_| task.run_with_backend(&args, &config_file, &RunContext::new(), &backend));
+    let running_task = running.lock().unwrap().remove(&id).unwrap_or_default();
+
+    if running_task.cancelled {
+        return output.send(&Response::Cancelled { id });
+    }
+    let code = backend.last_exit_code();
+    let response = match result {
+        Ok(_) => Response::Exit {
+            code: code.or(Some(0)),
+            success: true,
+        },
+        // The task ran but failed, so the reason is part of its output
+        Err(e) if running_task.spawned || code.is_some() => {
+            for line in e.to_string().lines() {
+                output.write_line(line, true);
+            }
+            Response::Exit {
+                code,
+                success: false,
+            }
+        }
+        Err(e) => return Err(e),
+    };
+    output.send(&response)
+}
+
+/// Sends the request to the daemon listening in the given socket, calling `on_response` with
+/// each response until the final one.
+///
+/// # Arguments
+///
+/// * `socket_path`: Path of the socket
+/// * `request`: Request to send
+/// * `on_response`: Called with each response received
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+pub fn send_request<F: FnMut(&Response)>(
+    socket_path: &Path,
+    request: &Request,
+    mut on_response: F,
+) -> DynErrResult<()> {
+    let mut stream = match UnixStream::connect(socket_path) {
+        Ok(stream) => stream,
+        Err(e) => {
+            return Err(format!(
+                "Could not connect to the daemon in {}: {}",
+                socket_path.display(),
+                e
+            )
+            .into())
+        }
+    };
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    for line in BufReader::new(stream).lines() {
+        let response: Response = serde_json::from_str(&line?)?;
+        on_response(&response);
+        if response.is_final() {
+            return Ok(());
+        }
+    }
+    Err("The daemon closed the connection".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// Starts a daemon in a temporary directory, returning the socket path
+    fn start_daemon(tmp_dir: &TempDir) -> PathBuf {
+        let socket_path = tmp_dir.path().join("run").join(SOCKET_FILE_NAME);
+        let daemon = Daemon::bind(&socket_path).unwrap();
+        thread::spawn(move || {
+            let _ = daemon.serve();
+        });
+        socket_path
+    }
+
+    /// Sends the request, returning all the responses
+    fn request(socket_path: &Path, request: &Request) -> Vec<Response> {
+        let mut responses = Vec::new();
+        send_request(socket_path, request, |response| {
+            responses.push(response.clone())
+        })
+        .unwrap();
+        responses
+    }
+
+    #[test]
+    fn test_request_from_json() {
+        let request: Request =
+            serde_json::from_str(r#"{"command": "run", "dir": "/tmp", "task": "hello"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::Run {
+                dir: PathBuf::from("/tmp"),
+                task: String::from("hello"),
+                args: vec![],
+                force: false,
+            }
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"command": "other"}"#).is_err());
+    }
+
+    #[test]
+    fn test_daemon() {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::write(
+            tmp_dir.path().join("project.yamis.yml"),
+            r#"
+tasks:
+  hello:
+    help: Says hello
+    script: echo hello {$1} && echo error >&2
+  fail:
+    script: exit 3
+  sleep:
+    script: sleep 10
+  secret:
+    private: true
+    script: echo secret
+  deploy:
+    deps: [build]
+    program: echo
+    args: [deployed]
+  build:
+    private: true
+    script: echo built
+"#,
+        )
+        .unwrap();
+        let socket_path = start_daemon(&tmp_dir);
+
+        let permissions = fs::metadata(&socket_path).unwrap().permissions();
+        assert_eq!(permissions.mode() & 0o777, 0o600);
+        assert!(Daemon::bind(&socket_path).is_err());
+
+        let responses = request(
+            &socket_path,
+            &Request::List {
+                dir: tmp_dir.path().to_path_buf(),
+            },
+        );
+        let tasks = match &responses[..] {
+            [Response::Tasks { tasks }] => tasks,
+            _ => panic!("Unexpected responses {:?}", responses),
+        };
+        // Tasks from the global config file, written by other tests, are ignored
+        let project_file = tmp_dir.path().join("project.yamis.yml");
+        let names: Vec<&str> = tasks
+            .iter()
+            .filter(|task| task.file == project_file)
+            .map(|task| task.name.as_str())
+            .collect();
+        assert_eq!(names, ["hello", "fail", "sleep", "deploy"]);
+        assert_eq!(tasks[0].help, "Says hello");
+
+        let mut responses = request(
+            &socket_path,
+            &Request::Run {
+                dir: tmp_dir.path().to_path_buf(),
+                task: String::from("hello"),
+                args: vec![String::from("world")],
+                force: false,
+            },
+        );
+        assert!(matches!(responses.remove(0), Response::Started { .. }));
+        let exit = responses.pop().unwrap();
+        assert_eq!(
+            exit,
+            Response::Exit {
+                code: Some(0),
+                success: true
+            }
+        );
+        let stdout_ms = responses.iter().find_map(|response| match response {
+            Response::Stdout { data, elapsed_ms } if data == "hello world" => Some(*elapsed_ms),
+            _ => None,
+        });
+        let stderr_ms = responses.iter().find_map(|response| match response {
+            Response::Stderr { data, elapsed_ms } if data == "error" => Some(*elapsed_ms),
+            _ => None,
+        });
+        assert!(stdout_ms.is_some());
+        assert!(stderr_ms.is_some());
+
+        // Older clients and servers do not send the elapsed time
+        let response: Response =
+            serde_json::from_str(r#"{"type": "stderr", "data": "x"}"#).unwrap();
+        assert_eq!(
+            response,
+            Response::Stderr {
+                data: String::from("x"),
+                elapsed_ms: 0
+            }
+        );
+
+        let responses = request(
+            &socket_path,
+            &Request::Run {
+                dir: tmp_dir.path().to_path_buf(),
+                task: String::from("fail"),
+                args: vec![],
+                force: false,
+            },
+        );
+        assert_eq!(
+            responses.last().unwrap(),
+            &Response::Exit {
+                code: Some(3),
+                success: false
+            }
+        );
+        // Followed by the reason, a line per response
+        assert!(responses.iter().any(|response| matches!(
+            response,
+            Response::Stderr { data, .. } if data == "Process terminated with exit code 3"
+        )));
+
+        // Runs like in the command line, dependencies included
+        let responses = request(
+            &socket_path,
+            &Request::Run {
+                dir: tmp_dir.path().to_path_buf(),
+                task: String::from("deploy"),
+                args: vec![],
+                force: false,
+            },
+        );
+        let stdout: Vec<&str> = responses
+            .iter()
+            .filter_map(|response| match response {
+                Response::Stdout { data, .. } => Some(data.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(stdout, ["built", "deployed"]);
+
+        let responses = request(
+            &socket_path,
+            &Request::Run {
+                dir: tmp_dir.path().to_path_buf(),
+                task: String::from("secret"),
+                args: vec![],
+                force: false,
+            },
+        );
+        assert_eq!(
+            responses,
+            [Response::Error {
+                message: String::from("Task secret not found")
+            }]
+        );
+    }
+
+    #[test]
+    fn test_daemon_lock() {
+        let tmp_dir = TempDir::new().unwrap();
+        let lock_path = tmp_dir.path().join("deploy.lock");
+        fs::write(
+            tmp_dir.path().join("project.yamis.yml"),
+            format!(
+                "tasks:\n  deploy:\n    lock:\n      path: deploy.lock\n    program: cat\n    args: ['{}']\n",
+                lock_path.display()
+            ),
+        )
+        .unwrap();
+        let socket_path = start_daemon(&tmp_dir);
+
+        let responses = request(
+            &socket_path,
+            &Request::Run {
+                dir: tmp_dir.path().to_path_buf(),
+                task: String::from("deploy"),
+                args: vec![],
+                force: false,
+            },
+        );
+        // Held while the task runs, and released once it finishes
+        let holds_lock = responses.iter().any(|response| {
+            matches!(response, Response::Stdout { data, .. } if data.contains(r#""task":"deploy""#))
+        });
+        assert!(holds_lock, "Unexpected responses {:?}", responses);
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn test_daemon_cancel() {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::write(
+            tmp_dir.path().join("project.yamis.yml"),
+            "tasks:\n  sleep:\n    program: sleep\n    args: ['10']\n",
+        )
+        .unwrap();
+        let socket_path = start_daemon(&tmp_dir);
+
+        let (sender, receiver) = mpsc::channel();
+        let run_socket_path = socket_path.clone();
+        let dir = tmp_dir.path().to_path_buf();
+        let handle = thread::spawn(move || {
+            let mut responses = Vec::new();
+            send_request(
+                &run_socket_path,
+                &Request::Run {
+                    dir,
+                    task: String::from("sleep"),
+                    args: vec![],
+                    force: false,
+                },
+                |response| {
+                    if let Response::Started { id } = response {
+                        sender.send(*id).unwrap();
+                    }
+                    responses.push(response.clone());
+                },
+            )
+            .unwrap();
+            responses
+        });
+
+        let id = receiver.recv().unwrap();
+        // Not reentrant, so it cannot run twice at the same time unless forced
+        let responses = request(
+            &socket_path,
+            &Request::Run {
+                dir: tmp_dir.path().to_path_buf(),
+                task: String::from("sleep"),
+                args: vec![],
+                force: false,
+            },
+        );
+        match &responses[..] {
+            [Response::Error { message }] => {
+                assert!(message.starts_with("Task sleep is already running"))
+            }
+            _ => panic!("Unexpected responses {:?}", responses),
+        }
+
+        // The process is registered right after the run starts
+        let mut responses = Vec::new();
+        for _ in 0..50 {
+            responses = request(&socket_path, &Request::Cancel { id });
+            if responses == [Response::Cancelled { id }] {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(responses, [Response::Cancelled { id }]);
+
+        let run_responses = handle.join().unwrap();
+        assert_eq!(run_responses.last().unwrap(), &Response::Cancelled { id });
+
+        let responses = request(&socket_path, &Request::Cancel { id });
+        assert_eq!(
+            responses,
+            [Response::Error {
+                message: format!("No task running with id {}", id)
+            }]
+        );
+    }
+}
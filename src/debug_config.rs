@@ -11,6 +11,9 @@ pub(crate) struct ConfigFileDebugConfig {
     /// Print the config file path when it is initialized
     #[serde(default = "default_false")]
     pub(crate) print_file_path: bool,
+    /// Print the program that runs the task, useful when there are fallback programs
+    #[serde(default = "default_false")]
+    pub(crate) print_program: bool,
 }
 
 impl Default for ConfigFileDebugConfig {
@@ -18,6 +21,7 @@ impl Default for ConfigFileDebugConfig {
         Self {
             print_task_name: true,
             print_file_path: false,
+            print_program: false,
         }
     }
 }
@@ -28,12 +32,15 @@ impl Default for ConfigFileDebugConfig {
 pub(crate) struct TaskDebugConfig {
     /// Name of the task
     pub(crate) print_task_name: Option<bool>,
+    /// Program that runs the task
+    pub(crate) print_program: Option<bool>,
 }
 
 impl Clone for TaskDebugConfig {
     fn clone(&self) -> Self {
         Self {
             print_task_name: self.print_task_name,
+            print_program: self.print_program,
         }
     }
 }
@@ -46,6 +53,7 @@ impl Clone for TaskDebugConfig {
 /// from the config file and the task.
 pub(crate) struct ConcreteTaskDebugConfig {
     pub(crate) print_task_name: bool,
+    pub(crate) print_program: bool,
 }
 
 impl ConcreteTaskDebugConfig {
@@ -62,6 +70,9 @@ impl ConcreteTaskDebugConfig {
             print_task_name: task_debug_config
                 .print_task_name
                 .unwrap_or(config_file_debug_config.print_task_name),
+            print_program: task_debug_config
+                .print_program
+                .unwrap_or(config_file_debug_config.print_program),
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::parser::EscapeMode;
+use crate::escape::EscapeMode;
 
 /// Returns the default quote mode for config files during serde deserialization
 pub(crate) fn default_quote() -> EscapeMode {
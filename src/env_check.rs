@@ -0,0 +1,124 @@
+use crate::types::DynErrResult;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the example env file, looked up next to each env file
+pub(crate) const DEFAULT_EXAMPLE_FILE_NAME: &str = ".env.example";
+
+/// Result of comparing the keys of an env file against an example env file
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct EnvFileCheck {
+    /// Path of the env file
+    pub(crate) env_file: PathBuf,
+    /// Path of the example env file
+    pub(crate) example: PathBuf,
+    /// Keys declared in the example that are missing in the env file
+    pub(crate) missing: Vec<String>,
+    /// Keys declared in the env file that are not in the example
+    pub(crate) extra: Vec<String>,
+}
+
+impl EnvFileCheck {
+    /// Returns whether the env file declares the same keys as the example
+    pub(crate) fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Returns the example env file for the given env file, which is the given example, or
+/// `.env.example` in the same directory as the env file
+///
+/// # Arguments
+///
+/// * `env_file`: Path of the env file
+/// * `example`: Example env file given explicitly, if any
+///
+/// returns: PathBuf
+pub(crate) fn get_example_path(env_file: &Path, example: Option<&Path>) -> PathBuf {
+    match example {
+        Some(example) => example.to_path_buf(),
+        None => match env_file.parent() {
+            Some(parent) => parent.join(DEFAULT_EXAMPLE_FILE_NAME),
+            None => PathBuf::from(DEFAULT_EXAMPLE_FILE_NAME),
+        },
+    }
+}
+
+/// Returns the keys declared in the env file. Values are not parsed, as example files usually
+/// leave them empty, i.e. `KEY=`.
+///
+/// # Arguments
+///
+/// * `path`: Path of the env file
+///
+/// returns: Result<BTreeSet<String, Global>, Box<dyn Error, Global>>
+fn read_env_keys(path: &Path) -> DynErrResult<BTreeSet<String>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) => {
+            return Err(format!("Failed to read env file at {}: {}", path.display(), err).into())
+        }
+    };
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, _)| key.trim())
+        .map(|key| key.strip_prefix("export ").unwrap_or(key).trim())
+        .filter(|key| !key.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Compares the keys declared in the env file against the ones in the example env file.
+/// Values are ignored, as the example usually holds placeholders.
+///
+/// # Arguments
+///
+/// * `env_file`: Path of the env file
+/// * `example`: Path of the example env file
+///
+/// returns: Result<EnvFileCheck, Box<dyn Error, Global>>
+pub(crate) fn check_env_file(env_file: &Path, example: &Path) -> DynErrResult<EnvFileCheck> {
+    let env_keys = read_env_keys(env_file)?;
+    let example_keys = read_env_keys(example)?;
+    Ok(EnvFileCheck {
+        env_file: env_file.to_path_buf(),
+        example: example.to_path_buf(),
+        missing: example_keys.difference(&env_keys).cloned().collect(),
+        extra: env_keys.difference(&example_keys).cloned().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_check_env_file() {
+        let tmp_dir = TempDir::new().unwrap();
+        let env_file = tmp_dir.path().join(".env");
+        let example = get_example_path(&env_file, None);
+        assert_eq!(example, tmp_dir.path().join(".env.example"));
+
+        fs::write(&env_file, "A=1\nB=2\nD=4\n").unwrap();
+        fs::write(&example, "A=\nB=\nC=\n").unwrap();
+        let check = check_env_file(&env_file, &example).unwrap();
+        assert!(!check.is_ok());
+        assert_eq!(check.missing, vec![String::from("C")]);
+        assert_eq!(check.extra, vec![String::from("D")]);
+
+        fs::write(&example, "# Comment\nA=a\n\nexport B = b\nD=\"d\"\n").unwrap();
+        assert!(check_env_file(&env_file, &example).unwrap().is_ok());
+
+        let other_example = tmp_dir.path().join("other.example");
+        assert_eq!(
+            get_example_path(&env_file, Some(&other_example)),
+            other_example
+        );
+        assert!(check_env_file(&env_file, &other_example).is_err());
+    }
+}
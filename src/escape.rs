@@ -0,0 +1,413 @@
+use serde_derive::Deserialize;
+use std::path::Path;
+
+/// Modes to escape (add quotes) the arguments passed to the script
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EscapeMode {
+    /// Always quote the arguments
+    Always,
+    /// Only add quotes if the argument has spaces
+    Spaces,
+    /// Never quote the argument
+    Never,
+}
+
+/// Kind of shell that runs the script, as each of them needs different quoting rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    /// POSIX compatible shells, i.e. sh or bash
+    Posix,
+    /// Windows Command Prompt, cmd.exe
+    Cmd,
+    /// Windows PowerShell or PowerShell Core
+    PowerShell,
+    /// Any other script runner, i.e. python. Values are quoted but not escaped.
+    Other,
+}
+
+impl ShellKind {
+    /// Guesses the kind of shell from the script runner and the extension of the script.
+    ///
+    /// The script runner takes precedence, except for PowerShell, which runs `.cmd` and `.bat`
+    /// scripts in cmd.exe.
+    ///
+    /// # Arguments
+    ///
+    /// * `script_runner`: Program that runs the script, i.e. `bash` or `/bin/sh`
+    /// * `script_ext`: Extension of the script, with or without the leading dot
+    ///
+    /// returns: ShellKind
+    pub fn from_script_runner(script_runner: &str, script_ext: &str) -> Self {
+        let runner = Path::new(script_runner)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let ext = script_ext.trim_start_matches('.').to_lowercase();
+
+        match runner.as_str() {
+            "sh" | "bash" | "dash" | "zsh" | "ksh" | "ash" => ShellKind::Posix,
+            "cmd" => ShellKind::Cmd,
+            _ => match ext.as_str() {
+                "cmd" | "bat" => ShellKind::Cmd,
+                "ps1" => ShellKind::PowerShell,
+                "sh" | "bash" => ShellKind::Posix,
+                _ if runner == "powershell" || runner == "pwsh" => ShellKind::PowerShell,
+                _ => ShellKind::Other,
+            },
+        }
+    }
+}
+
+/// Returns whether the value needs to be quoted with the given escape mode
+fn should_quote(val: &str, mode: &EscapeMode) -> bool {
+    match mode {
+        EscapeMode::Always => true,
+        EscapeMode::Spaces => val.contains(' '),
+        EscapeMode::Never => false,
+    }
+}
+
+/// Escapes a POSIX double quoted string, where `\\`, `"`, `$` and `` ` `` need a backslash.
+fn escape_posix(val: &str) -> String {
+    let mut result = String::with_capacity(val.len() + 2);
+    result.push('"');
+    for c in val.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result.push('"');
+    result
+}
+
+/// Escapes a PowerShell double quoted string, where `` ` ``, `"` and `$` need a backtick.
+fn escape_powershell(val: &str) -> String {
+    let mut result = String::with_capacity(val.len() + 2);
+    result.push('"');
+    for c in val.chars() {
+        if matches!(c, '`' | '"' | '$') {
+            result.push('`');
+        }
+        result.push(c);
+    }
+    result.push('"');
+    result
+}
+
+/// Escapes a value for cmd.exe.
+///
+/// The value is quoted following the rules used by most windows programs to split the command
+/// line, where `"` is escaped with a backslash, and backslashes are only special if they precede
+/// a quote. Then every cmd.exe metacharacter, including the quotes, is escaped with a caret, so
+/// that cmd.exe passes it to the program as it is. `%` cannot be escaped with a caret in
+/// scripts, so it is doubled instead.
+fn escape_cmd(val: &str) -> String {
+    let mut quoted = String::with_capacity(val.len() + 2);
+    quoted.push('"');
+    let mut backslashes = 0;
+    for c in val.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+
+    let mut result = String::with_capacity(quoted.len() * 2);
+    for c in quoted.chars() {
+        match c {
+            '%' => result.push_str("%%"),
+            '^' | '&' | '|' | '<' | '>' | '(' | ')' | '!' | '"' => {
+                result.push('^');
+                result.push(c);
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Escapes a value so that it can be safely included in a script run by the given shell.
+///
+/// # Arguments
+///
+/// * `val`: Value to escape
+/// * `mode`: Whether to quote the value or not
+/// * `shell_kind`: Shell that will run the script
+///
+/// returns: String
+pub fn escape_value(val: &str, mode: &EscapeMode, shell_kind: &ShellKind) -> String {
+    if !should_quote(val, mode) {
+        return String::from(val);
+    }
+    match shell_kind {
+        ShellKind::Posix => escape_posix(val),
+        ShellKind::Cmd => escape_cmd(val),
+        ShellKind::PowerShell => escape_powershell(val),
+        ShellKind::Other => format!("\"{}\"", val),
+    }
+}
+
+/// Escapes each value with [escape_value] and joins them with a space.
+///
+/// # Arguments
+///
+/// * `values`: Values to escape
+/// * `mode`: Whether to quote the values or not
+/// * `shell_kind`: Shell that will run the script
+///
+/// returns: String
+pub fn escape_values(values: &[String], mode: &EscapeMode, shell_kind: &ShellKind) -> String {
+    values
+        .iter()
+        .map(|val| escape_value(val, mode, shell_kind))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Returns the length of the environment variable name at the start of the given bytes, or 0
+/// if there is none. Names start with a letter or `_`, followed by letters, digits or `_`.
+fn env_name_len(bytes: &[u8]) -> usize {
+    match bytes.first() {
+        Some(c) if c.is_ascii_alphabetic() || *c == b'_' => bytes
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_')
+            .count(),
+        _ => 0,
+    }
+}
+
+/// Parses the environment variable reference at the start of the given bytes, if any, returning
+/// the shell it is written for, the name and the length of the reference.
+///
+/// Recognizes `%NAME%` for cmd.exe, `$env:NAME` and `${env:NAME}` for PowerShell, and `$NAME`
+/// and `${NAME}` for POSIX shells.
+fn parse_env_ref(bytes: &[u8]) -> Option<(ShellKind, &[u8], usize)> {
+    let rest = &bytes[1..];
+    match bytes[0] {
+        b'%' => {
+            let len = env_name_len(rest);
+            (len > 0 && rest.get(len) == Some(&b'%'))
+                .then(|| (ShellKind::Cmd, &rest[..len], len + 2))
+        }
+        b'$' => {
+            if let Some(name) = rest.strip_prefix(b"env:") {
+                let len = env_name_len(name);
+                (len > 0).then(|| (ShellKind::PowerShell, &name[..len], len + 5))
+            } else if let Some(name) = rest.strip_prefix(b"{env:") {
+                let len = env_name_len(name);
+                (len > 0 && name.get(len) == Some(&b'}'))
+                    .then(|| (ShellKind::PowerShell, &name[..len], len + 7))
+            } else if let Some(name) = rest.strip_prefix(b"{") {
+                let len = env_name_len(name);
+                (len > 0 && name.get(len) == Some(&b'}'))
+                    .then(|| (ShellKind::Posix, &name[..len], len + 3))
+            } else {
+                let len = env_name_len(rest);
+                (len > 0).then(|| (ShellKind::Posix, &rest[..len], len + 1))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Translates the references to environment variables written for other shells to the syntax of
+/// the given one, i.e. `%HOME%` to `${HOME}` in POSIX shells, so that scripts inherited from a
+/// task for another OS can be reused. References are `%NAME%` for cmd.exe, `$env:NAME` for
+/// PowerShell and `$NAME` or `${NAME}` for POSIX shells. `%%` is kept as it is, and scripts run by
+/// other script runners are not translated.
+///
+/// # Arguments
+///
+/// * `script`: Literal content of the script
+/// * `shell_kind`: Shell that will run the script
+///
+/// returns: String
+pub fn translate_env_refs(script: &str, shell_kind: &ShellKind) -> String {
+    if *shell_kind == ShellKind::Other || !script.contains(['%', '$']) {
+        return String::from(script);
+    }
+    let bytes = script.as_bytes();
+    let mut result: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"%%") {
+            result.extend_from_slice(b"%%");
+            i += 2;
+            continue;
+        }
+        let (written_for, name, len) = match parse_env_ref(&bytes[i..]) {
+            Some(env_ref) => env_ref,
+            None => {
+                result.push(bytes[i]);
+                i += 1;
+                continue;
+            }
+        };
+        let next_is_name = bytes
+            .get(i + len)
+            .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_');
+        match shell_kind {
+            _ if written_for == *shell_kind => result.extend_from_slice(&bytes[i..i + len]),
+            ShellKind::Posix => result.extend([b"${", name, b"}"].concat()),
+            ShellKind::Cmd => result.extend([b"%", name, b"%"].concat()),
+            ShellKind::PowerShell if next_is_name => {
+                result.extend([b"${env:", name, b"}"].concat())
+            }
+            ShellKind::PowerShell => result.extend([b"$env:", name].concat()),
+            ShellKind::Other => unreachable!(),
+        }
+        i += len;
+    }
+    // Only ASCII bytes are replaced, so the result is still valid UTF-8
+    String::from_utf8(result).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_value() {
+        let shell = ShellKind::Posix;
+        assert_eq!(
+            escape_value("hello", &EscapeMode::Always, &shell),
+            "\"hello\""
+        );
+        assert_eq!(escape_value("hello", &EscapeMode::Spaces, &shell), "hello");
+        assert_eq!(
+            escape_value("hello world", &EscapeMode::Spaces, &shell),
+            "\"hello world\""
+        );
+        assert_eq!(
+            escape_value("hello world", &EscapeMode::Never, &shell),
+            "hello world"
+        );
+    }
+
+    #[test]
+    fn test_escape_posix() {
+        let shell = ShellKind::Posix;
+        assert_eq!(
+            escape_value(r#"say "hi" to $USER"#, &EscapeMode::Always, &shell),
+            r#""say \"hi\" to \$USER""#
+        );
+        assert_eq!(
+            escape_value(r"C:\dir `cmd`", &EscapeMode::Spaces, &shell),
+            r#""C:\\dir \`cmd\`""#
+        );
+    }
+
+    #[test]
+    fn test_escape_cmd() {
+        let shell = ShellKind::Cmd;
+        assert_eq!(
+            escape_value("hello world", &EscapeMode::Spaces, &shell),
+            r#"^"hello world^""#
+        );
+        assert_eq!(
+            escape_value("a & b | c > 100%", &EscapeMode::Always, &shell),
+            r#"^"a ^& b ^| c ^> 100%%^""#
+        );
+        assert_eq!(
+            escape_value(r#"say "hi""#, &EscapeMode::Always, &shell),
+            r#"^"say \^"hi\^"^""#
+        );
+        assert_eq!(
+            escape_value(r"C:\my dir\", &EscapeMode::Always, &shell),
+            r#"^"C:\my dir\\^""#
+        );
+    }
+
+    #[test]
+    fn test_escape_powershell() {
+        let shell = ShellKind::PowerShell;
+        assert_eq!(
+            escape_value(r#"say "hi" to $env:USER`"#, &EscapeMode::Always, &shell),
+            r#""say `"hi`" to `$env:USER``""#
+        );
+    }
+
+    #[test]
+    fn test_escape_other() {
+        assert_eq!(
+            escape_value("hello $world", &EscapeMode::Always, &ShellKind::Other),
+            "\"hello $world\""
+        );
+    }
+
+    #[test]
+    fn test_shell_kind_from_script_runner() {
+        assert_eq!(
+            ShellKind::from_script_runner("bash", "sh"),
+            ShellKind::Posix
+        );
+        assert_eq!(
+            ShellKind::from_script_runner("/usr/bin/zsh", ""),
+            ShellKind::Posix
+        );
+        assert_eq!(ShellKind::from_script_runner("cmd", "bat"), ShellKind::Cmd);
+        assert_eq!(
+            ShellKind::from_script_runner("powershell", "cmd"),
+            ShellKind::Cmd
+        );
+        assert_eq!(
+            ShellKind::from_script_runner("PowerShell.exe", ".ps1"),
+            ShellKind::PowerShell
+        );
+        assert_eq!(
+            ShellKind::from_script_runner("pwsh", ""),
+            ShellKind::PowerShell
+        );
+        assert_eq!(
+            ShellKind::from_script_runner("python", "py"),
+            ShellKind::Other
+        );
+        assert_eq!(ShellKind::from_script_runner("wsl", "sh"), ShellKind::Posix);
+    }
+
+    #[test]
+    fn test_escape_values() {
+        let values = vec![String::from("hello world"), String::from("bye")];
+        let result = escape_values(&values, &EscapeMode::Spaces, &ShellKind::Posix);
+        assert_eq!(result, "\"hello world\" bye");
+
+        let result = escape_values(&[], &EscapeMode::Always, &ShellKind::Posix);
+        assert_eq!(result, "");
+    }
+
+    #[test]
+    fn test_translate_env_refs() {
+        let script = "echo %HOME% $env:USER ${PWD} $SHELL ${env:TMP}dir 100%% ñ";
+        assert_eq!(
+            translate_env_refs(script, &ShellKind::Posix),
+            "echo ${HOME} ${USER} ${PWD} $SHELL ${TMP}dir 100%% ñ"
+        );
+        assert_eq!(
+            translate_env_refs(script, &ShellKind::Cmd),
+            "echo %HOME% %USER% %PWD% %SHELL% %TMP%dir 100%% ñ"
+        );
+        assert_eq!(
+            translate_env_refs(script, &ShellKind::PowerShell),
+            "echo $env:HOME $env:USER $env:PWD $env:SHELL ${env:TMP}dir 100%% ñ"
+        );
+        assert_eq!(translate_env_refs(script, &ShellKind::Other), script);
+        assert_eq!(
+            translate_env_refs("%HOME%USER echo $1 $? 50% %1 $", &ShellKind::PowerShell),
+            "${env:HOME}USER echo $1 $? 50% %1 $"
+        );
+    }
+}
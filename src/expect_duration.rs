@@ -0,0 +1,134 @@
+//! Bounds on how long a task is expected to take, set with `expect_duration` in tasks, i.e. so
+//! that a test runner that matched no tests and finished at once is flagged, as well as a task
+//! that overruns the time it is given.
+use crate::print_utils::YamisOutput;
+use crate::runs::format_elapsed;
+use crate::tasks::TaskError;
+use crate::types::DynErrResult;
+use crate::utils::deserialize_optional_duration;
+use serde_derive::Deserialize;
+use std::time::Duration;
+
+/// What happens when a task takes less or more time than expected
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DurationAction {
+    /// The task fails, even if its commands succeeded
+    #[default]
+    Fail,
+    /// A warning is printed, and the task succeeds
+    Warn,
+}
+
+/// Time a task is expected to take
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExpectDuration {
+    /// The task is flagged if it finishes faster than this
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    min: Option<Duration>,
+    /// The task is flagged if it takes longer than this
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    max: Option<Duration>,
+    /// What happens when the task is flagged
+    #[serde(default)]
+    action: DurationAction,
+}
+
+/// Formats the duration with tenths of a second if it is short, i.e. `0.3s`, or as `1m 5s`
+fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format_elapsed(duration.as_secs())
+    }
+}
+
+impl ExpectDuration {
+    /// Returns an error if the minimum is greater than the maximum
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) if min > max => Err(String::from(
+                "`expect_duration.min` cannot be greater than `expect_duration.max`.",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns why the task is flagged, if it took less or more time than expected
+    fn get_violation(&self, elapsed: Duration) -> Option<String> {
+        match (self.min, self.max) {
+            (Some(min), _) if elapsed < min => Some(format!(
+                "Finished in {}, expected to take at least {}",
+                format_duration(elapsed),
+                format_duration(min)
+            )),
+            (_, Some(max)) if elapsed > max => Some(format!(
+                "Took {}, expected to take at most {}",
+                format_duration(elapsed),
+                format_duration(max)
+            )),
+            _ => None,
+        }
+    }
+
+    /// Checks the time the task took. If it is out of the bounds, the task fails or a warning
+    /// is printed, depending on the action.
+    ///
+    /// # Arguments
+    ///
+    /// * `task_name`: Name of the task
+    /// * `elapsed`: Time the task took
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    pub(crate) fn check(&self, task_name: &str, elapsed: Duration) -> DynErrResult<()> {
+        let Some(violation) = self.get_violation(elapsed) else {
+            return Ok(());
+        };
+        match self.action {
+            DurationAction::Fail => {
+                Err(TaskError::RuntimeError(String::from(task_name), violation).into())
+            }
+            DurationAction::Warn => {
+                let msg = format!("tasks.{}: {}", task_name, violation);
+                eprintln!("{}", msg.yamis_warn());
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expect_duration() {
+        let expect: ExpectDuration = serde_yaml::from_str("min: 1s\nmax: 30m").unwrap();
+        assert_eq!(expect.action, DurationAction::Fail);
+        assert!(expect.validate().is_ok());
+        assert!(expect.check("test", Duration::from_secs(5)).is_ok());
+        assert_eq!(
+            expect
+                .check("test", Duration::from_millis(300))
+                .unwrap_err()
+                .to_string(),
+            TaskError::RuntimeError(
+                String::from("test"),
+                String::from("Finished in 0.3s, expected to take at least 1.0s")
+            )
+            .to_string()
+        );
+        assert_eq!(
+            expect.get_violation(Duration::from_secs(1805)),
+            Some(String::from("Took 30m 5s, expected to take at most 30m 0s"))
+        );
+
+        let expect: ExpectDuration = serde_yaml::from_str("min: 10\naction: warn").unwrap();
+        assert!(expect.check("test", Duration::from_secs(1)).is_ok());
+
+        let expect: ExpectDuration = serde_yaml::from_str("min: 1m\nmax: 10s").unwrap();
+        assert!(expect.validate().is_err());
+        assert!(serde_yaml::from_str::<ExpectDuration>("action: ignore").is_err());
+    }
+}
@@ -0,0 +1,290 @@
+//! Up-to-date checks of tasks that declare the files they read and write with `sources` and
+//! `outputs`, so that i.e. a build is skipped if nothing changed since it last ran.
+//!
+//! By default a task is up to date if all its outputs are newer than all its sources. With
+//! `fingerprint: checksum`, it is up to date if the contents of its sources did not change since
+//! it last succeeded, which is found by comparing with a checksum cached in the temporary folder.
+//! In both cases the checksum covers the rendered commands too, so that a task runs again if it
+//! is given other arguments or environment variables than the last time it succeeded.
+use crate::types::DynErrResult;
+use crate::utils::TMP_FOLDER_NAMESPACE;
+use md5::{Digest, Md5};
+use serde_derive::Deserialize;
+use std::env::temp_dir;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How a task decides whether it is up to date
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FingerprintMethod {
+    /// Up to date if all the outputs are newer than all the sources
+    #[default]
+    Timestamp,
+    /// Up to date if the contents of the sources did not change since the task last succeeded
+    Checksum,
+}
+
+/// Returns an error if any of the given glob patterns is invalid
+///
+/// # Arguments
+///
+/// * `key`: Name of the option the patterns were given in, i.e. `sources`
+/// * `patterns`: Glob patterns
+///
+/// returns: Result<(), String>
+pub(crate) fn validate_patterns(key: &str, patterns: &[String]) -> Result<(), String> {
+    for pattern in patterns {
+        if let Err(e) = glob::Pattern::new(pattern) {
+            return Err(format!("Invalid pattern `{}` in `{}`: {}", pattern, key, e));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the files matched by the patterns, sorted, or None if a pattern matches nothing
+///
+/// # Arguments
+///
+/// * `base_dir`: Directory relative patterns are resolved against
+/// * `patterns`: Glob patterns
+///
+/// returns: Result<Option<Vec<PathBuf, Global>>, Box<dyn Error, Global>>
+fn expand_patterns(base_dir: &Path, patterns: &[String]) -> DynErrResult<Option<Vec<PathBuf>>> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        // The base directory is escaped, as it might contain characters like `[`
+        let pattern = if Path::new(pattern).is_absolute() {
+            pattern.clone()
+        } else {
+            let base_dir = glob::Pattern::escape(&base_dir.to_string_lossy());
+            format!("{}/{}", base_dir, pattern)
+        };
+        let mut matched = false;
+        for path in glob::glob(&pattern)? {
+            let path = path?;
+            if path.is_file() {
+                matched = true;
+                files.push(path);
+            }
+        }
+        if !matched {
+            return Ok(None);
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(Some(files))
+}
+
+/// Returns the last modification time of each file
+fn modification_times(files: &[PathBuf]) -> DynErrResult<Vec<SystemTime>> {
+    let mut times = Vec::with_capacity(files.len());
+    for file in files {
+        times.push(fs::metadata(file)?.modified()?);
+    }
+    Ok(times)
+}
+
+/// Returns a checksum of the inputs of the task, and the paths and contents of the files, if any
+fn checksum(inputs: &str, base_dir: &Path, files: &[PathBuf]) -> DynErrResult<String> {
+    let mut hasher = Md5::new();
+    hasher.update(inputs.len().to_le_bytes());
+    hasher.update(inputs.as_bytes());
+    for file in files {
+        let path = file.strip_prefix(base_dir).unwrap_or(file);
+        let contents = fs::read(file)?;
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(contents.len().to_le_bytes());
+        hasher.update(contents);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Files a task reads and writes, used to skip it if it is up to date
+#[derive(Debug)]
+pub(crate) struct Fingerprint<'a> {
+    /// Directory the patterns are resolved against
+    base_dir: &'a Path,
+    /// Glob patterns of the files the task reads
+    sources: &'a [String],
+    /// Glob patterns of the files the task writes
+    outputs: &'a [String],
+    /// How the task decides whether it is up to date
+    method: FingerprintMethod,
+    /// File the checksum of the inputs and sources is cached in
+    cache_path: PathBuf,
+}
+
+impl<'a> Fingerprint<'a> {
+    /// Returns the fingerprint of a task
+    ///
+    /// # Arguments
+    ///
+    /// * `task_name`: Name of the task
+    /// * `config_file_path`: Path of the config file of the task, which together with the name
+    ///   of the task identifies its cached checksum
+    /// * `sources`: Glob patterns of the files the task reads, relative to the config file
+    /// * `outputs`: Glob patterns of the files the task writes, relative to the config file
+    /// * `method`: How the task decides whether it is up to date
+    ///
+    /// returns: Fingerprint
+    pub(crate) fn new(
+        task_name: &str,
+        config_file_path: &'a Path,
+        sources: &'a [String],
+        outputs: &'a [String],
+        method: FingerprintMethod,
+    ) -> Self {
+        let mut hasher = Md5::new();
+        hasher.update(config_file_path.to_string_lossy().as_bytes());
+        hasher.update(b"\0");
+        hasher.update(task_name.as_bytes());
+        let cache_path = temp_dir()
+            .join(TMP_FOLDER_NAMESPACE)
+            .join("fingerprints")
+            .join(format!("{:x}", hasher.finalize()));
+        Fingerprint {
+            base_dir: config_file_path.parent().unwrap_or(Path::new("")),
+            sources,
+            outputs,
+            method,
+            cache_path,
+        }
+    }
+
+    /// Returns whether the task is up to date. If it is not, the checksum to save once the
+    /// task succeeds is returned too, or None if the patterns match no files, in which case
+    /// the task always runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs`: Rendered commands of the task, or its arguments and environment variables
+    ///   if it does not run a command itself
+    ///
+    /// returns: Result<(bool, Option<String>), Box<dyn Error, Global>>
+    pub(crate) fn check(&self, inputs: &str) -> DynErrResult<(bool, Option<String>)> {
+        let Some(sources) = expand_patterns(self.base_dir, self.sources)? else {
+            return Ok((false, None));
+        };
+        let outputs = expand_patterns(self.base_dir, self.outputs)?;
+        let checksum = match self.method {
+            FingerprintMethod::Timestamp => {
+                let Some(outputs) = &outputs else {
+                    return Ok((false, None));
+                };
+                let newest_source = modification_times(&sources)?.into_iter().max();
+                let oldest_output = modification_times(outputs)?.into_iter().min();
+                let checksum = checksum(inputs, self.base_dir, &[])?;
+                if newest_source >= oldest_output {
+                    return Ok((false, Some(checksum)));
+                }
+                checksum
+            }
+            FingerprintMethod::Checksum => checksum(inputs, self.base_dir, &sources)?,
+        };
+        let cached = fs::read_to_string(&self.cache_path).ok();
+        if outputs.is_some() && cached.as_deref() == Some(checksum.as_str()) {
+            Ok((true, None))
+        } else {
+            Ok((false, Some(checksum)))
+        }
+    }
+
+    /// Caches the checksum of the inputs and sources, once the task succeeded
+    ///
+    /// # Arguments
+    ///
+    /// * `checksum`: Checksum returned by `check`
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    pub(crate) fn save(&self, checksum: &str) -> DynErrResult<()> {
+        fs::create_dir_all(self.cache_path.parent().unwrap())?;
+        // Replaced at once, so that other yamis processes never read it half written
+        let tmp_path = self
+            .cache_path
+            .with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp_path, checksum)?;
+        fs::rename(&tmp_path, &self.cache_path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use std::time::Duration;
+
+    /// Sets the modification time of the file to the given seconds since the Unix epoch
+    fn set_modified(path: &Path, secs: u64) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_fingerprint_timestamp() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        fs::create_dir(tmp_dir.path().join("src")).unwrap();
+        fs::write(tmp_dir.path().join("src").join("main.c"), "int main;").unwrap();
+        fs::write(tmp_dir.path().join("src").join("util.c"), "int util;").unwrap();
+        let sources = vec![String::from("src/*.c")];
+        let outputs = vec![String::from("main")];
+        let fingerprint = Fingerprint::new(
+            "build",
+            &config_path,
+            &sources,
+            &outputs,
+            FingerprintMethod::Timestamp,
+        );
+        // The output does not exist yet
+        assert_eq!(fingerprint.check("echo").unwrap(), (false, None));
+
+        fs::write(tmp_dir.path().join("main"), "binary").unwrap();
+        set_modified(&tmp_dir.path().join("src").join("main.c"), 100);
+        set_modified(&tmp_dir.path().join("src").join("util.c"), 200);
+        set_modified(&tmp_dir.path().join("main"), 300);
+        // The task did not succeed with these inputs yet
+        let (up_to_date, checksum) = fingerprint.check("echo").unwrap();
+        assert!(!up_to_date);
+        fingerprint.save(&checksum.unwrap()).unwrap();
+        assert_eq!(fingerprint.check("echo").unwrap(), (true, None));
+        assert!(!fingerprint.check("echo --release").unwrap().0);
+
+        set_modified(&tmp_dir.path().join("src").join("util.c"), 400);
+        assert!(!fingerprint.check("echo").unwrap().0);
+    }
+
+    #[test]
+    fn test_fingerprint_checksum() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        fs::write(tmp_dir.path().join("schema.sql"), "CREATE TABLE a;").unwrap();
+        let sources = vec![String::from("*.sql")];
+        let fingerprint = Fingerprint::new(
+            "migrate",
+            &config_path,
+            &sources,
+            &[],
+            FingerprintMethod::Checksum,
+        );
+        let (up_to_date, checksum) = fingerprint.check("migrate").unwrap();
+        assert!(!up_to_date);
+        fingerprint.save(&checksum.unwrap()).unwrap();
+        assert_eq!(fingerprint.check("migrate").unwrap(), (true, None));
+        // Other inputs, i.e. other arguments, run it again
+        assert!(!fingerprint.check("migrate --down").unwrap().0);
+
+        // Touching the file does not change the checksum, but changing its contents does
+        set_modified(&tmp_dir.path().join("schema.sql"), 100);
+        assert_eq!(fingerprint.check("migrate").unwrap(), (true, None));
+        fs::write(tmp_dir.path().join("schema.sql"), "CREATE TABLE b;").unwrap();
+        assert!(!fingerprint.check("migrate").unwrap().0);
+
+        assert!(validate_patterns("sources", &sources).is_ok());
+        assert!(validate_patterns("outputs", &[String::from("[a")]).is_err());
+    }
+}
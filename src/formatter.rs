@@ -0,0 +1,354 @@
+use crate::config_files::ConfigFile;
+use crate::types::DynErrResult;
+use std::fs;
+use std::path::Path;
+
+/// Order of the keys at the top level of config files. Unknown keys are left at the end.
+const CONFIG_FILE_KEYS: &[&str] = &[
+    "version",
+    "settings",
+    "workspace",
+    "debug_config",
+    "wd",
+    "path",
+    "plugins",
+    "quote",
+    "env_file",
+    "env",
+    "env_import_prefix",
+    "constants",
+    "vars",
+    "snippets",
+    "task_templates",
+    "tasks",
+    "tests",
+];
+
+/// Order of the keys in tasks. Unknown keys are left at the end.
+const TASK_KEYS: &[&str] = &[
+    "help",
+    "summary",
+    "private",
+    "bases",
+    "debug_config",
+    "wd",
+    "quote",
+    "env_file",
+    "env",
+    "env.linux",
+    "env.windows",
+    "env.macos",
+    "env_docs",
+    "params",
+    "script_runner",
+    "script_runner_args",
+    "script_ext",
+    "script",
+    "script_file",
+    "template",
+    "translate_env_refs",
+    "stable_script_path",
+    "program",
+    "args",
+    "args.linux",
+    "args.windows",
+    "args.macos",
+    "args_extend",
+    "serial",
+    "deps",
+    "run_always",
+    "reentrant",
+    "sandbox",
+    "lock",
+    "expect_duration",
+    "sources",
+    "outputs",
+    "fingerprint",
+    "watch",
+    "echo_command",
+    "export_args_as_env",
+    "strict_args",
+    "allowed_exit_codes",
+    "exit_code_map",
+    "linux",
+    "windows",
+    "macos",
+];
+
+/// Keys of the OS specific tasks
+const OS_TASK_KEYS: &[&str] = &["linux", "windows", "macos"];
+
+/// Aliases and the canonical key they are replaced with
+const KEY_ALIASES: &[(&str, &str)] =
+    &[("script_extension", "script_ext"), ("args+", "args_extend")];
+
+/// Returns the canonical name of the key
+fn canonical_key(key: &str) -> &str {
+    KEY_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == key)
+        .map(|(_, canonical)| *canonical)
+        .unwrap_or(key)
+}
+
+/// Sorts the entries of a map following the order of the given keys. The sort is stable,
+/// so entries with unknown keys are left at the end in the same order.
+fn sort_entries<K, V, F>(entries: &mut [(K, V)], keys_order: &[&str], get_key: F)
+where
+    F: Fn(&K) -> Option<&str>,
+{
+    entries.sort_by_key(|(key, _)| {
+        get_key(key)
+            .and_then(|key| keys_order.iter().position(|k| *k == key))
+            .unwrap_or(keys_order.len())
+    });
+}
+
+/// Returns whether the content has comments, which would be lost when formatting. It can
+/// give false positives, i.e. if a string contains ` #`.
+fn has_comments(contents: &str) -> bool {
+    contents
+        .lines()
+        .any(|line| line.trim_start().starts_with('#') || line.contains(" #"))
+}
+
+/// Normalizes a YAML mapping, renaming aliases and sorting the keys
+fn normalize_yaml_mapping(
+    mapping: serde_yaml::Mapping,
+    keys_order: &[&str],
+) -> serde_yaml::Mapping {
+    let mut entries: Vec<(serde_yaml::Value, serde_yaml::Value)> = mapping
+        .into_iter()
+        .map(|(key, value)| match key.as_str() {
+            Some(str_key) => (serde_yaml::Value::from(canonical_key(str_key)), value),
+            None => (key, value),
+        })
+        .collect();
+    sort_entries(&mut entries, keys_order, |key| key.as_str());
+    entries.into_iter().collect()
+}
+
+/// Normalizes a YAML task, including its OS specific tasks
+fn normalize_yaml_task(task: &mut serde_yaml::Value) {
+    if let serde_yaml::Value::Mapping(mapping) = task {
+        *mapping = normalize_yaml_mapping(std::mem::take(mapping), TASK_KEYS);
+        for os_task_key in OS_TASK_KEYS {
+            if let Some(os_task) = mapping.get_mut(*os_task_key) {
+                normalize_yaml_task(os_task);
+            }
+        }
+    }
+}
+
+/// Normalizes a YAML config file
+fn normalize_yaml_config(value: &mut serde_yaml::Value) {
+    if let serde_yaml::Value::Mapping(mapping) = value {
+        *mapping = normalize_yaml_mapping(std::mem::take(mapping), CONFIG_FILE_KEYS);
+        if let Some(serde_yaml::Value::Mapping(tasks)) = mapping.get_mut("tasks") {
+            for (_, task) in tasks.iter_mut() {
+                normalize_yaml_task(task);
+            }
+        }
+    }
+}
+
+/// Normalizes a TOML table, renaming aliases and sorting the keys
+fn normalize_toml_table(table: toml::value::Table, keys_order: &[&str]) -> toml::value::Table {
+    let mut entries: Vec<(String, toml::Value)> = table
+        .into_iter()
+        .map(|(key, value)| (String::from(canonical_key(&key)), value))
+        .collect();
+    sort_entries(&mut entries, keys_order, |key| Some(key.as_str()));
+    entries.into_iter().collect()
+}
+
+/// Normalizes a TOML task, including its OS specific tasks
+fn normalize_toml_task(task: &mut toml::Value) {
+    if let toml::Value::Table(table) = task {
+        *table = normalize_toml_table(std::mem::take(table), TASK_KEYS);
+        for os_task_key in OS_TASK_KEYS {
+            if let Some(os_task) = table.get_mut(*os_task_key) {
+                normalize_toml_task(os_task);
+            }
+        }
+    }
+}
+
+/// Normalizes a TOML config file
+fn normalize_toml_config(value: &mut toml::Value) {
+    if let toml::Value::Table(table) = value {
+        *table = normalize_toml_table(std::mem::take(table), CONFIG_FILE_KEYS);
+        if let Some(toml::Value::Table(tasks)) = table.get_mut("tasks") {
+            for (_, task) in tasks.iter_mut() {
+                normalize_toml_task(task);
+            }
+        }
+    }
+}
+
+/// Formats the contents of a YAML config file
+fn format_yaml(contents: &str) -> DynErrResult<String> {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(contents)?;
+    normalize_yaml_config(&mut value);
+    let formatted = serde_yaml::to_string(&value)?;
+    // Sanity check, the formatted content must have the same values
+    if serde_yaml::from_str::<serde_yaml::Value>(&formatted)? != value {
+        return Err("The formatted file does not match the original one".into());
+    }
+    Ok(formatted)
+}
+
+/// Formats the contents of a TOML config file
+fn format_toml(contents: &str) -> DynErrResult<String> {
+    let mut value: toml::Value = toml::from_str(contents)?;
+    normalize_toml_config(&mut value);
+    let formatted = toml::to_string_pretty(&value)?;
+    // Sanity check, the formatted content must have the same values
+    if toml::from_str::<toml::Value>(&formatted)? != value {
+        return Err("The formatted file does not match the original one".into());
+    }
+    Ok(formatted)
+}
+
+/// Result of formatting a config file
+pub(crate) struct FormattedConfigFile {
+    /// Formatted content
+    pub(crate) contents: String,
+    /// Whether the original content changed after formatting
+    pub(crate) changed: bool,
+    /// Whether the original content has comments, which are lost after formatting
+    pub(crate) has_comments: bool,
+}
+
+/// Formats the config file in the given path, with keys in a stable order, consistent
+/// indentation and aliases replaced by the canonical field names. The file must be a valid
+/// config file. Note that comments are not preserved, and YAML anchors are expanded.
+///
+/// # Arguments
+///
+/// * `path`: Path of the config file to format
+///
+/// returns: Result<FormattedConfigFile, Box<dyn Error, Global>>
+pub(crate) fn format_config_file(path: &Path) -> DynErrResult<FormattedConfigFile> {
+    // Only valid files are formatted
+    ConfigFile::load(path.to_path_buf())?;
+
+    let contents = fs::read_to_string(path)?;
+    let is_yaml = matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yml") | Some("yaml")
+    );
+    let formatted = if is_yaml {
+        format_yaml(&contents)?
+    } else {
+        format_toml(&contents)?
+    };
+    Ok(FormattedConfigFile {
+        changed: formatted != contents,
+        has_comments: has_comments(&contents),
+        contents: formatted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn test_format_yaml() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("project.yamis.yml");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(
+            br#"
+tasks:
+    zeta:
+        script: echo zeta
+        script_extension: sh
+        help: Zeta task
+    alpha:
+        program: echo
+        args+: [alpha]
+        linux:
+            args: [linux]
+            help: Linux
+env:
+    B: b
+    A: a
+"#,
+        )
+        .unwrap();
+
+        let formatted = format_config_file(&path).unwrap();
+        assert!(formatted.changed);
+        assert!(!formatted.has_comments);
+        assert_eq!(
+            formatted.contents,
+            r#"env:
+  B: b
+  A: a
+tasks:
+  zeta:
+    help: Zeta task
+    script_ext: sh
+    script: echo zeta
+  alpha:
+    program: echo
+    args_extend:
+    - alpha
+    linux:
+      help: Linux
+      args:
+      - linux
+"#
+        );
+
+        // Formatting is idempotent
+        fs::write(&path, &formatted.contents).unwrap();
+        let formatted_again = format_config_file(&path).unwrap();
+        assert!(!formatted_again.changed);
+    }
+
+    #[test]
+    fn test_format_toml() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("project.yamis.toml");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(
+            br#"
+# Comment
+[tasks.zeta]
+script = "echo zeta"
+help = "Zeta task"
+
+[env]
+A = "a"
+"#,
+        )
+        .unwrap();
+
+        let formatted = format_config_file(&path).unwrap();
+        assert!(formatted.changed);
+        assert!(formatted.has_comments);
+        assert_eq!(
+            formatted.contents,
+            r#"[env]
+A = 'a'
+[tasks.zeta]
+help = 'Zeta task'
+script = 'echo zeta'
+"#
+        );
+    }
+
+    #[test]
+    fn test_format_invalid_file() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("project.yamis.yml");
+        fs::write(&path, "tasks:\n  hello:\n    unknown: 1\n").unwrap();
+        assert!(format_config_file(&path).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Syntax highlighting of scripts printed to the terminal, based on their extension. Scripts are
+//! left as they are if the output is not colored, or if the `colors` feature is disabled.
+
+/// Returns the script with terminal escape codes that highlight its syntax, based on the
+/// extension of the script file, if the output is colored.
+///
+/// # Arguments
+///
+/// * `script`: Content of the script
+/// * `extension`: Extension of the script file, i.e. `py`
+///
+/// returns: String
+pub(crate) fn highlight_script(script: &str, extension: &str) -> String {
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        return String::from(script);
+    }
+    highlight_lines(script, extension)
+}
+
+/// Returns the script with terminal escape codes that highlight its syntax. Each line is reset
+/// to the default color at the end, so that lines can be printed separately. The script is
+/// returned as it is if there is no syntax for the extension.
+#[cfg(feature = "colors")]
+fn highlight_lines(script: &str, extension: &str) -> String {
+    use lazy_static::lazy_static;
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::{Theme, ThemeSet};
+    use syntect::parsing::SyntaxSet;
+    use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+
+    lazy_static! {
+        static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+        static ref THEME: Theme = ThemeSet::load_defaults()
+            .themes
+            .remove("base16-ocean.dark")
+            .unwrap();
+    }
+
+    // Batch files and PowerShell are not in the default syntaxes
+    let syntax = match SYNTAX_SET.find_syntax_by_extension(extension) {
+        Some(syntax) => syntax,
+        None => return String::from(script),
+    };
+    let mut highlighter = HighlightLines::new(syntax, &THEME);
+    let mut result = String::with_capacity(script.len() * 2);
+    for line in LinesWithEndings::from(script) {
+        let ranges = match highlighter.highlight_line(line, &SYNTAX_SET) {
+            Ok(ranges) => ranges,
+            Err(_) => return String::from(script),
+        };
+        let escaped = as_24_bit_terminal_escaped(&ranges, false);
+        match escaped.strip_suffix('\n') {
+            Some(escaped) => {
+                result.push_str(escaped);
+                result.push_str("\x1b[0m\n");
+            }
+            None => {
+                result.push_str(&escaped);
+                result.push_str("\x1b[0m");
+            }
+        }
+    }
+    result
+}
+
+/// Returns the script as it is, since the `colors` feature is disabled
+#[cfg(not(feature = "colors"))]
+fn highlight_lines(script: &str, _extension: &str) -> String {
+    String::from(script)
+}
+
+#[cfg(test)]
+#[cfg(feature = "colors")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_lines() {
+        let highlighted = highlight_lines("print('hello')\nx = 1", "py");
+        assert!(highlighted.contains("\x1b["));
+        assert!(highlighted.contains("hello"));
+        assert_eq!(highlighted.lines().count(), 2);
+        assert!(highlighted.ends_with("\x1b[0m"));
+
+        // Unknown extensions are not highlighted
+        assert_eq!(highlight_lines("echo hello", "unknown"), "echo hello");
+    }
+}
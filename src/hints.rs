@@ -0,0 +1,200 @@
+//! Hints printed below the error when a command fails, found by matching the last lines of its
+//! output against regular expressions, i.e. `Hint: The port is already in use ...` when the
+//! output contains `EADDRINUSE`.
+use crate::defaults::default_true;
+use crate::print_utils::YamisOutput;
+use regex::Regex;
+use serde_derive::Deserialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Number of lines of the output of a command that are matched against the hints
+const TAIL_LINES: usize = 50;
+
+/// Hints shipped with yamis, as pairs of regular expression and hint
+const DEFAULT_HINTS: [(&str, &str); 5] = [
+    (
+        r"command not found|is not recognized as an internal or external command",
+        "A program used by the task is not installed, or is not in the PATH",
+    ),
+    (
+        r"EADDRINUSE|[Aa]ddress already in use",
+        "The port is already in use, stop the process listening on it or use a different port",
+    ),
+    (
+        r"[Pp]ermission denied|EACCES",
+        "Check the permissions of the files used by the task, and whether scripts are executable",
+    ),
+    (
+        r"No space left on device|ENOSPC",
+        "The disk is full, free some space and try again",
+    ),
+    (
+        r"No such file or directory|ENOENT",
+        "A file or program used by the task does not exist, check the paths and the `wd` of the task",
+    ),
+];
+
+/// Hint given in the settings
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct HintRule {
+    /// Regular expression matched against the last lines of the output
+    pattern: String,
+    /// Hint printed if the pattern matches
+    hint: String,
+}
+
+/// Settings of the hints printed when a command fails
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct HintSettings {
+    /// Whether hints are printed. The output of the commands is piped to find them, so this
+    /// is disabled by default.
+    #[serde(default)]
+    pub(crate) enabled: bool,
+    /// Whether the hints shipped with yamis are used, after the ones given in `rules`
+    #[serde(default = "default_true")]
+    defaults: bool,
+    /// Hints given by the user, which take precedence over the default ones
+    #[serde(default)]
+    rules: Vec<HintRule>,
+}
+
+impl Default for HintSettings {
+    fn default() -> Self {
+        HintSettings {
+            enabled: false,
+            defaults: true,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Compiled hints, matched in order
+#[derive(Debug)]
+pub(crate) struct Hints {
+    /// Regular expressions and the hint printed if they match
+    rules: Vec<(Regex, String)>,
+}
+
+impl Hints {
+    /// Compiles the hints of the given settings. Rules with an invalid regular expression are
+    /// skipped after printing a warning, so that a typo in the settings does not break tasks.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings`: Settings of the hints
+    ///
+    /// returns: Hints
+    pub(crate) fn new(settings: &HintSettings) -> Self {
+        let mut rules = Vec::new();
+        for rule in &settings.rules {
+            match Regex::new(&rule.pattern) {
+                Ok(regex) => rules.push((regex, rule.hint.clone())),
+                Err(e) => {
+                    let msg = format!("Invalid hint pattern `{}`: {}", rule.pattern, e);
+                    eprintln!("{}", msg.yamis_warn());
+                }
+            }
+        }
+        if settings.defaults {
+            for (pattern, hint) in DEFAULT_HINTS {
+                rules.push((Regex::new(pattern).unwrap(), String::from(hint)));
+            }
+        }
+        Hints { rules }
+    }
+
+    /// Returns the hint of the first rule that matches the given output, if any
+    ///
+    /// # Arguments
+    ///
+    /// * `output`: Last lines of the output of the failed command, or the error it raised
+    ///
+    /// returns: Option<&str>
+    pub(crate) fn find(&self, output: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|(regex, _)| regex.is_match(output))
+            .map(|(_, hint)| hint.as_str())
+    }
+}
+
+/// Last lines of the output of a command, shared between the threads that forward it
+#[derive(Debug, Default)]
+pub(crate) struct OutputTail {
+    /// Lines kept, the oldest ones are dropped first
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl OutputTail {
+    /// Adds a line of the output, dropping the oldest one if there are too many
+    pub(crate) fn push(&self, line: &[u8]) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == TAIL_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(String::from_utf8_lossy(line).into_owned());
+    }
+
+    /// Returns the lines kept, joined
+    pub(crate) fn contents(&self) -> String {
+        self.lines
+            .lock()
+            .unwrap()
+            .iter()
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hints() {
+        let settings: HintSettings = serde_yaml::from_str(
+            r#"
+rules:
+  - pattern: "port 5432 .* refused"
+    hint: Start the database with `yamis db`
+  - pattern: "("
+    hint: Invalid
+"#,
+        )
+        .unwrap();
+        assert!(!settings.enabled);
+        let hints = Hints::new(&settings);
+        assert_eq!(
+            hints.find("connection to port 5432 failed: refused\n"),
+            Some("Start the database with `yamis db`")
+        );
+        assert_eq!(
+            hints.find("sh: 1: cargo-nextest: command not found\n"),
+            Some("A program used by the task is not installed, or is not in the PATH")
+        );
+        assert_eq!(
+            hints.find("Error: listen EADDRINUSE: address already in use :::3000"),
+            Some(DEFAULT_HINTS[1].1)
+        );
+        assert_eq!(hints.find("everything is fine"), None);
+
+        let settings: HintSettings = serde_yaml::from_str("defaults: false").unwrap();
+        assert_eq!(Hints::new(&settings).find("command not found"), None);
+        assert!(serde_yaml::from_str::<HintSettings>("enable: true").is_err());
+    }
+
+    #[test]
+    fn test_output_tail() {
+        let tail = OutputTail::default();
+        for i in 0..TAIL_LINES + 10 {
+            tail.push(format!("line {}\n", i).as_bytes());
+        }
+        let contents = tail.contents();
+        assert!(!contents.contains("line 9\n"));
+        assert!(contents.starts_with("line 10\n"));
+        assert!(contents.ends_with(&format!("line {}\n", TAIL_LINES + 9)));
+    }
+}
@@ -3,13 +3,53 @@ extern crate core;
 #[cfg(feature = "runtime")]
 pub mod cli;
 
+pub mod backend;
+mod builtins;
+mod completion;
 pub mod config_files;
+#[cfg(all(feature = "daemon", unix))]
+pub mod daemon;
 pub(crate) mod debug_config;
 mod defaults;
+mod env_check;
+mod escape;
+mod expect_duration;
+mod fingerprints;
 mod format_str;
+mod formatter;
+mod highlight;
+mod hints;
+mod lock_files;
+mod nesting;
+#[cfg(feature = "otel")]
+mod otel;
+pub mod panic_hook;
+mod params;
 mod parser;
+mod plugins;
 pub mod print_utils;
+mod reports;
+pub mod run_context;
+pub mod runs;
+mod sandbox;
+mod script_export;
+mod script_lint;
+mod settings;
+mod snapshots;
+mod state_dirs;
+mod status;
+mod task_index;
+mod task_templates;
+mod task_tests;
 pub mod tasks;
+#[cfg(feature = "runtime")]
+pub mod testing;
 pub(crate) mod types;
+#[cfg(feature = "updater")]
 pub(crate) mod updater;
 mod utils;
+mod versions;
+mod wasm_plugins;
+#[cfg(feature = "watch")]
+mod watcher;
+mod workspace;
@@ -0,0 +1,503 @@
+//! Advisory locks shared across machines, set with `lock` in tasks, i.e. so that deployments
+//! run from different machines against the same infrastructure do not overlap.
+//!
+//! A lock is a file in a path all the machines can reach, i.e. a network share, created
+//! exclusively by the process that holds it. The file records who holds the lock, and is
+//! refreshed periodically while the task runs. A lock is stale, and taken over, if it was not
+//! refreshed for a while, or if it is held by a process of the same machine that is no longer
+//! running. Stale locks are only removed while holding a second lock file, with the `.takeover`
+//! extension appended, so that two processes never take over the same stale lock.
+use crate::parser::get_system_field;
+use crate::print_utils::YamisOutput;
+use crate::runs::{format_elapsed, is_process_alive};
+use crate::types::DynErrResult;
+use crate::utils::{deserialize_optional_duration, get_path_relative_to_base, since_epoch};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// Time after which a lock that was not refreshed is stale, unless given in the task
+const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// Time between attempts to acquire a lock held by another process
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of locks acquired by this process so far, so that each holder has a unique id, even
+/// if several runs of the same process acquire locks at the same time
+static ACQUIRED_LOCKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Lock a task acquires before running
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TaskLock {
+    /// Path of the lock file, relative to the config file directory
+    path: String,
+    /// How long to wait for the lock if another process holds it. By default it fails at once
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    timeout: Option<Duration>,
+    /// Time after which a lock that was not refreshed is stale
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    stale_after: Option<Duration>,
+}
+
+/// Who holds a lock, as stored in the lock file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LockOwner {
+    /// Unique id of the holder, so that a process never removes a lock it does not hold
+    id: String,
+    /// Hostname of the machine
+    host: String,
+    /// Pid of the yamis process
+    pid: u32,
+    /// Name of the task
+    task: String,
+    /// Seconds since the Unix epoch when the lock was acquired
+    acquired_at: u64,
+    /// Seconds since the Unix epoch when the lock was last refreshed
+    refreshed_at: u64,
+}
+
+/// Returns the hostname of this machine, or an empty string if it is not known
+fn hostname() -> String {
+    get_system_field("hostname")
+        .and_then(|hostname| hostname.ok())
+        .unwrap_or_default()
+}
+
+/// Reads the owner of the lock file, or None if it does not exist, or is being written
+fn read_owner(path: &Path) -> Option<LockOwner> {
+    let contents = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Returns how long ago the file was modified, or None if it is not known
+fn modified_ago(path: &Path) -> Option<Duration> {
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+impl LockOwner {
+    /// Returns whether the lock is stale, as it was not refreshed in the given time, or its
+    /// process is no longer running. The process is only checked if the lock was acquired in
+    /// this machine, which is unknown if the hostname could not be found.
+    fn is_stale(&self, stale_after: Duration) -> bool {
+        since_epoch().as_secs().saturating_sub(self.refreshed_at) > stale_after.as_secs()
+            || (!self.host.is_empty() && self.host == hostname() && !is_process_alive(self.pid))
+    }
+}
+
+/// Lock held by a run of this process, released when dropped
+pub(crate) struct LockGuard<'a> {
+    /// Path of the lock file
+    path: PathBuf,
+    /// Lock files held by the run
+    held_locks: &'a Mutex<HashSet<PathBuf>>,
+    /// Id of the holder, to not remove the file if another process took over the lock
+    id: String,
+    /// Set to stop refreshing the lock
+    stop: Arc<AtomicBool>,
+    /// Thread that refreshes the lock file
+    refresher: Option<JoinHandle<()>>,
+}
+
+impl Drop for LockGuard<'_> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(refresher) = self.refresher.take() {
+            let _ = refresher.join();
+        }
+        if read_owner(&self.path).is_some_and(|owner| owner.id == self.id) {
+            let _ = fs::remove_file(&self.path);
+        }
+        self.held_locks.lock().unwrap().remove(&self.path);
+    }
+}
+
+/// Refreshes the lock file until stopped, as long as this process still holds the lock
+fn refresh_lock(path: PathBuf, mut owner: LockOwner, interval: Duration, stop: Arc<AtomicBool>) {
+    let step = Duration::from_millis(100).min(interval);
+    let mut waited = Duration::ZERO;
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(step);
+        waited += step;
+        if waited < interval {
+            continue;
+        }
+        waited = Duration::ZERO;
+        match read_owner(&path) {
+            Some(current) if current.id == owner.id => {
+                owner.refreshed_at = since_epoch().as_secs();
+                if let Ok(contents) = serde_json::to_string(&owner) {
+                    let _ = fs::write(&path, contents);
+                }
+            }
+            // Another process took over the lock, i.e. if this one could not refresh it in time
+            Some(_) => return,
+            None => {}
+        }
+    }
+}
+
+/// Returns the path of the file held while taking over the given lock file
+fn takeover_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".takeover");
+    path.with_file_name(file_name)
+}
+
+/// Removes the stale lock file if it still has the given owner, returning whether it was
+/// removed. Other processes might find the lock stale at the same time, so it is only removed
+/// while holding the takeover file, after checking again that the lock was not taken over already.
+///
+/// # Arguments
+///
+/// * `path`: Path of the lock file
+/// * `stale`: Owner of the lock when it was found stale, None if the file was empty
+/// * `stale_after`: Time after which a takeover file left by a process that stopped is removed
+///
+/// returns: bool
+fn remove_stale(path: &Path, stale: &Option<LockOwner>, stale_after: Duration) -> bool {
+    let takeover_path = takeover_path(path);
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&takeover_path)
+    {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            // Left by a process that stopped while taking over the lock
+            if modified_ago(&takeover_path).is_some_and(|ago| ago > stale_after) {
+                let _ = fs::remove_file(&takeover_path);
+            }
+            return false;
+        }
+        Err(_) => return false,
+    }
+    let removed = read_owner(path) == *stale && fs::remove_file(path).is_ok();
+    let _ = fs::remove_file(&takeover_path);
+    removed
+}
+
+impl TaskLock {
+    /// Returns the path of the lock file
+    ///
+    /// # Arguments
+    ///
+    /// * `base_path`: Directory of the config file of the task
+    ///
+    /// returns: PathBuf
+    fn get_path(&self, base_path: &Path) -> PathBuf {
+        get_path_relative_to_base(base_path, &self.path)
+    }
+
+    /// Tries to create the lock file. Returns None if another process holds the lock.
+    fn try_create(&self, path: &Path, owner: &LockOwner) -> DynErrResult<Option<()>> {
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => return Ok(None),
+            Err(e) => {
+                return Err(
+                    format!("Could not create the lock file {}: {}", path.display(), e).into(),
+                )
+            }
+        };
+        file.write_all(serde_json::to_string(owner)?.as_bytes())?;
+        Ok(Some(()))
+    }
+
+    /// Acquires the lock, waiting for it up to the timeout of the lock. Stale locks are taken
+    /// over. Returns None if the run already holds the lock, i.e. in a serial task. Other runs
+    /// of this process, i.e. in other threads, wait for the lock like other processes.
+    ///
+    /// # Arguments
+    ///
+    /// * `task`: Name of the task that acquires the lock
+    /// * `base_path`: Directory of the config file of the task
+    /// * `held_locks`: Lock files held by the run
+    ///
+    /// returns: Result<Option<LockGuard>, Box<dyn Error, Global>>
+    pub(crate) fn acquire<'a>(
+        &self,
+        task: &str,
+        base_path: &Path,
+        held_locks: &'a Mutex<HashSet<PathBuf>>,
+    ) -> DynErrResult<Option<LockGuard<'a>>> {
+        let path = self.get_path(base_path);
+        if !held_locks.lock().unwrap().insert(path.clone()) {
+            return Ok(None);
+        }
+        let stale_after = self.stale_after.unwrap_or(DEFAULT_STALE_AFTER);
+        let timeout = self.timeout.unwrap_or(Duration::ZERO);
+        let started = SystemTime::now();
+        let owner = LockOwner {
+            id: format!(
+                "{}-{}-{}-{}",
+                hostname(),
+                std::process::id(),
+                since_epoch().as_secs(),
+                ACQUIRED_LOCKS.fetch_add(1, Ordering::Relaxed)
+            ),
+            host: hostname(),
+            pid: std::process::id(),
+            task: String::from(task),
+            acquired_at: since_epoch().as_secs(),
+            refreshed_at: since_epoch().as_secs(),
+        };
+        let mut waiting = false;
+        loop {
+            match self.try_create(&path, &owner) {
+                Ok(Some(())) => break,
+                Ok(None) => {}
+                Err(e) => {
+                    held_locks.lock().unwrap().remove(&path);
+                    return Err(e);
+                }
+            }
+            let current = read_owner(&path);
+            let is_stale = match &current {
+                Some(current) => current.is_stale(stale_after),
+                // Being written, unless it was left empty a while ago
+                None => modified_ago(&path).is_some_and(|ago| ago > stale_after),
+            };
+            if is_stale && remove_stale(&path, &current, stale_after) {
+                let msg = match &current {
+                    Some(current) => format!(
+                        "Taking over the stale lock {}, held by task {} in {} (pid {})",
+                        path.display(),
+                        current.task,
+                        current.host,
+                        current.pid
+                    ),
+                    None => format!("Taking over the stale lock {}", path.display()),
+                };
+                eprintln!("{}", msg.yamis_warn());
+                continue;
+            }
+            let held_by = match &current {
+                Some(current) => format!(
+                    "held by task {} in {} (pid {}) since {} ago",
+                    current.task,
+                    current.host,
+                    current.pid,
+                    format_elapsed(since_epoch().as_secs().saturating_sub(current.acquired_at))
+                ),
+                None => String::from("held by another process"),
+            };
+            let waited = started.elapsed().unwrap_or_default();
+            if waited >= timeout {
+                held_locks.lock().unwrap().remove(&path);
+                return Err(
+                    format!("Could not acquire the lock {}, {}", path.display(), held_by).into(),
+                );
+            }
+            if !waiting {
+                let msg = format!("Waiting for the lock {}, {}", path.display(), held_by);
+                eprintln!("{}", msg.yamis_info());
+                waiting = true;
+            }
+            std::thread::sleep(RETRY_INTERVAL.min(timeout - waited));
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let refresher = {
+            let (path, owner, stop) = (path.clone(), owner.clone(), Arc::clone(&stop));
+            std::thread::spawn(move || refresh_lock(path, owner, stale_after / 4, stop))
+        };
+        Ok(Some(LockGuard {
+            path,
+            held_locks,
+            id: owner.id,
+            stop,
+            refresher: Some(refresher),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    fn get_lock(path: &str, timeout: Option<u64>) -> TaskLock {
+        TaskLock {
+            path: String::from(path),
+            timeout: timeout.map(Duration::from_millis),
+            stale_after: None,
+        }
+    }
+
+    #[test]
+    fn test_lock_durations() {
+        let lock: TaskLock =
+            serde_yaml::from_str("path: deploy.lock\ntimeout: 10m\nstale_after: 90\n").unwrap();
+        assert_eq!(lock.timeout, Some(Duration::from_secs(600)));
+        assert_eq!(lock.stale_after, Some(Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn test_acquire_lock() {
+        let tmp_dir = TempDir::new().unwrap();
+        let lock_path = tmp_dir.path().join("deploy.lock");
+        let lock = get_lock("deploy.lock", None);
+
+        let held_locks = Mutex::new(HashSet::new());
+        let guard = lock.acquire("deploy", tmp_dir.path(), &held_locks).unwrap();
+        assert!(guard.is_some());
+        let owner = read_owner(&lock_path).unwrap();
+        assert_eq!(owner.task, "deploy");
+        assert_eq!(owner.pid, std::process::id());
+        // Held by this run, i.e. by the task that runs a serial task with the same lock
+        assert!(lock
+            .acquire("deploy", tmp_dir.path(), &held_locks)
+            .unwrap()
+            .is_none());
+        drop(guard);
+        assert!(!lock_path.exists());
+
+        // Held by another running process
+        let other = LockOwner {
+            id: String::from("other"),
+            host: String::from("other-host"),
+            pid: 1,
+            task: String::from("deploy"),
+            acquired_at: since_epoch().as_secs(),
+            refreshed_at: since_epoch().as_secs(),
+        };
+        fs::write(&lock_path, serde_json::to_string(&other).unwrap()).unwrap();
+        let err = get_lock("deploy.lock", Some(200))
+            .acquire("deploy", tmp_dir.path(), &held_locks)
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("held by task deploy in other-host (pid 1)"));
+        assert_eq!(read_owner(&lock_path), Some(other.clone()));
+
+        // Stale, as it was not refreshed in time
+        let stale = LockOwner {
+            refreshed_at: since_epoch().as_secs() - DEFAULT_STALE_AFTER.as_secs() - 1,
+            ..other
+        };
+        fs::write(&lock_path, serde_json::to_string(&stale).unwrap()).unwrap();
+        let guard = lock.acquire("deploy", tmp_dir.path(), &held_locks).unwrap();
+        assert!(guard.is_some());
+        assert_eq!(read_owner(&lock_path).unwrap().pid, std::process::id());
+    }
+
+    #[test]
+    fn test_acquire_lock_from_threads() {
+        let tmp_dir = TempDir::new().unwrap();
+        let lock_path = tmp_dir.path().join("deploy.lock");
+        let held_locks = Mutex::new(HashSet::new());
+        let guard = get_lock("deploy.lock", None)
+            .acquire("deploy", tmp_dir.path(), &held_locks)
+            .unwrap();
+        assert!(guard.is_some());
+        let first_id = read_owner(&lock_path).unwrap().id;
+
+        std::thread::scope(|scope| {
+            // Another run of this process does not skip the lock
+            let other = scope.spawn(|| {
+                let held_locks = Mutex::new(HashSet::new());
+                get_lock("deploy.lock", None)
+                    .acquire("deploy", tmp_dir.path(), &held_locks)
+                    .map(|guard| guard.is_some())
+                    .map_err(|e| e.to_string())
+            });
+            let err = other.join().unwrap().unwrap_err();
+            assert!(err.contains("held by task deploy"));
+
+            // But waits for it
+            let other = scope.spawn(|| {
+                let held_locks = Mutex::new(HashSet::new());
+                let guard = get_lock("deploy.lock", Some(5000))
+                    .acquire("deploy", tmp_dir.path(), &held_locks)
+                    .map_err(|e| e.to_string())?;
+                Ok::<_, String>(guard.map(|guard| guard.id.clone()))
+            });
+            std::thread::sleep(Duration::from_millis(300));
+            assert_eq!(read_owner(&lock_path).unwrap().id, first_id);
+            drop(guard);
+            let second_id = other.join().unwrap().unwrap().unwrap();
+            assert_ne!(second_id, first_id);
+        });
+        // Released by the second run
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_unknown_host_is_not_local() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        let owner = LockOwner {
+            id: String::from("other"),
+            host: hostname(),
+            pid: child.id(),
+            task: String::from("deploy"),
+            acquired_at: since_epoch().as_secs(),
+            refreshed_at: since_epoch().as_secs(),
+        };
+        // Not running in this machine anymore
+        assert_eq!(owner.is_stale(DEFAULT_STALE_AFTER), !owner.host.is_empty());
+        // Might still be running in another machine
+        let owner = LockOwner {
+            host: String::new(),
+            ..owner
+        };
+        assert!(!owner.is_stale(DEFAULT_STALE_AFTER));
+    }
+
+    #[test]
+    fn test_takeover_is_exclusive() {
+        let tmp_dir = TempDir::new().unwrap();
+        let lock_path = tmp_dir.path().join("deploy.lock");
+        let stale = LockOwner {
+            id: String::from("other"),
+            host: String::from("other-host"),
+            pid: 1,
+            task: String::from("deploy"),
+            acquired_at: 0,
+            refreshed_at: 0,
+        };
+        fs::write(&lock_path, serde_json::to_string(&stale).unwrap()).unwrap();
+
+        // Another process is taking over the lock
+        let takeover_path = tmp_dir.path().join("deploy.lock.takeover");
+        fs::write(&takeover_path, "").unwrap();
+        assert!(!remove_stale(
+            &lock_path,
+            &Some(stale.clone()),
+            DEFAULT_STALE_AFTER
+        ));
+        assert!(lock_path.exists());
+        fs::remove_file(&takeover_path).unwrap();
+
+        // And already took it over
+        let new_owner = LockOwner {
+            id: String::from("new"),
+            refreshed_at: since_epoch().as_secs(),
+            ..stale.clone()
+        };
+        fs::write(&lock_path, serde_json::to_string(&new_owner).unwrap()).unwrap();
+        assert!(!remove_stale(
+            &lock_path,
+            &Some(stale.clone()),
+            DEFAULT_STALE_AFTER
+        ));
+        assert_eq!(read_owner(&lock_path), Some(new_owner));
+
+        fs::write(&lock_path, serde_json::to_string(&stale).unwrap()).unwrap();
+        assert!(remove_stale(&lock_path, &Some(stale), DEFAULT_STALE_AFTER));
+        assert!(!lock_path.exists());
+        assert!(!takeover_path.exists());
+    }
+}
@@ -4,8 +4,12 @@ use yamis::print_utils::YamisOutput;
 #[cfg(feature = "runtime")]
 use yamis::cli::exec;
 
+#[cfg(feature = "runtime")]
+use yamis::panic_hook;
+
 #[cfg(feature = "runtime")]
 fn main() {
+    panic_hook::install();
     match exec() {
         Ok(_) => {}
         Err(e) => {
@@ -0,0 +1,112 @@
+//! Detection of yamis invocations made by the commands of a task, i.e. a script that calls
+//! `yamis test`, through the environment variables yamis exports to the commands.
+//!
+//! Nested invocations use the config file of the task that runs them, see `YAMIS_FILE`, skip
+//! the dependencies their parents already ran in it, and indent their output one level more
+//! than their parent, so that it reads like a tree.
+use std::env;
+use std::path::Path;
+
+/// Environment variable with the number of yamis invocations the command runs in
+pub(crate) const DEPTH_ENV: &str = "YAMIS_DEPTH";
+
+/// Environment variable with the dependencies that already ran in the config file given in
+/// `YAMIS_FILE`, one per line, each identified by the name of the task and a checksum of its
+/// rendered command
+pub(crate) const EXECUTED_DEPS_ENV: &str = "YAMIS_EXECUTED_DEPS";
+
+/// Indentation of the output of each nesting level
+const INDENT: &str = "  ";
+
+/// Parses the nesting depth, which is 0 if not given or invalid
+fn parse_depth(value: Option<&str>) -> usize {
+    value
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Returns the number of yamis invocations this one is nested in, or 0 if it was not invoked by
+/// the command of a task
+pub(crate) fn get_depth() -> usize {
+    parse_depth(env::var(DEPTH_ENV).ok().as_deref())
+}
+
+/// Returns the indentation of the output of this invocation, empty if it is not nested
+pub(crate) fn get_indent() -> String {
+    INDENT.repeat(get_depth())
+}
+
+/// Parses the dependencies exported by the parent invocation, if they ran in the given config
+/// file
+///
+/// # Arguments
+///
+/// * `config_file_path`: Absolute path of the config file
+/// * `parent_file`: Config file of the parent invocation, given in `YAMIS_FILE`
+/// * `executed_deps`: Dependencies that ran in the parent invocation, one per line
+///
+/// returns: Vec<String, Global>
+fn parse_inherited_deps(
+    config_file_path: &Path,
+    parent_file: Option<&str>,
+    executed_deps: Option<&str>,
+) -> Vec<String> {
+    match (parent_file, executed_deps) {
+        (Some(parent_file), Some(executed_deps)) if Path::new(parent_file) == config_file_path => {
+            executed_deps
+                .lines()
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the dependencies that already ran in the given config file in the parent
+/// invocations, which do not run again in this one
+///
+/// # Arguments
+///
+/// * `config_file_path`: Path of the config file
+///
+/// returns: Vec<String, Global>
+pub(crate) fn get_inherited_deps(config_file_path: &Path) -> Vec<String> {
+    if get_depth() == 0 {
+        return Vec::new();
+    }
+    let Ok(config_file_path) = std::path::absolute(config_file_path) else {
+        return Vec::new();
+    };
+    parse_inherited_deps(
+        &config_file_path,
+        env::var("YAMIS_FILE").ok().as_deref(),
+        env::var(EXECUTED_DEPS_ENV).ok().as_deref(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nesting() {
+        assert_eq!(parse_depth(None), 0);
+        assert_eq!(parse_depth(Some("2")), 2);
+        assert_eq!(parse_depth(Some("deep")), 0);
+
+        let path = Path::new("/project/project.yamis.yml");
+        assert_eq!(
+            parse_inherited_deps(
+                path,
+                Some("/project/project.yamis.yml"),
+                Some("build\nlint\n")
+            ),
+            vec![String::from("build"), String::from("lint")]
+        );
+        assert!(
+            parse_inherited_deps(path, Some("/other/project.yamis.yml"), Some("build")).is_empty()
+        );
+        assert!(parse_inherited_deps(path, None, Some("build")).is_empty());
+    }
+}
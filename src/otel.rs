@@ -0,0 +1,635 @@
+//! Exports the runs as OpenTelemetry traces, so that the steps of a task show up in existing
+//! tracing, i.e. of CI pipelines. Enabled by the standard `OTEL_EXPORTER_OTLP_ENDPOINT` or
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` environment variables.
+//!
+//! Each run is a trace with a span for the task given in the command line, and a child span for
+//! each command it runs. If `TRACEPARENT` is set, i.e. by the CI, the run continues that trace.
+//! Spans are sent at once when the run ends, with the OTLP/HTTP protocol and JSON encoding.
+use crate::backend::ExecutionBackend;
+use crate::print_utils::YamisOutput;
+use crate::tasks::RenderedCommand;
+use crate::types::DynErrResult;
+use crate::utils::since_epoch;
+use md5::{Digest, Md5};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Protocol of the exporter, the only one supported
+const PROTOCOL: &str = "http/json";
+
+/// Path appended to `OTEL_EXPORTER_OTLP_ENDPOINT` to send the traces
+const TRACES_PATH: &str = "v1/traces";
+
+/// Default timeout of the exporter, in milliseconds
+const DEFAULT_TIMEOUT: u64 = 10000;
+
+/// Kind of the spans, `SPAN_KIND_INTERNAL`
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+/// Status code of spans that succeeded, `STATUS_CODE_OK`
+const STATUS_CODE_OK: u8 = 1;
+
+/// Status code of spans that failed, `STATUS_CODE_ERROR`
+const STATUS_CODE_ERROR: u8 = 2;
+
+/// Trace to continue, as given in the `TRACEPARENT` environment variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TraceParent {
+    /// Id of the trace, as 32 hex digits
+    trace_id: String,
+    /// Id of the parent span, as 16 hex digits
+    span_id: String,
+}
+
+impl TraceParent {
+    /// Parses a W3C trace context, i.e. `00-<trace id>-<span id>-01`
+    fn parse(value: &str) -> Option<TraceParent> {
+        let parts: Vec<&str> = value.trim().split('-').collect();
+        let [version, trace_id, span_id, _flags] = parts[..] else {
+            return None;
+        };
+        let is_id = |id: &str, len: usize| {
+            id.len() == len
+                && id.chars().all(|c| c.is_ascii_hexdigit())
+                && id.chars().any(|c| c != '0')
+        };
+        if version.len() != 2 || !is_id(trace_id, 32) || !is_id(span_id, 16) {
+            return None;
+        }
+        Some(TraceParent {
+            trace_id: trace_id.to_lowercase(),
+            span_id: span_id.to_lowercase(),
+        })
+    }
+}
+
+/// Where and how to export the traces, read from the standard `OTEL_*` environment variables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OtelConfig {
+    /// URL the traces are sent to
+    endpoint: String,
+    /// Headers of the requests, i.e. for authentication
+    headers: Vec<(String, String)>,
+    /// Timeout of the requests
+    timeout: Duration,
+    /// Attributes of the resource, including `service.name`
+    resource_attributes: Vec<(String, String)>,
+    /// Trace to continue, if any
+    parent: Option<TraceParent>,
+}
+
+/// Parses a list of `key=value` pairs separated by commas, as in `OTEL_EXPORTER_OTLP_HEADERS`,
+/// where the values can be percent encoded. Invalid pairs are ignored.
+fn parse_key_values(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, val)| (key.trim().to_string(), percent_decode(val.trim())))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+/// Decodes the percent encoded characters of the value, leaving invalid sequences as they are
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+impl OtelConfig {
+    /// Returns the configuration given by the environment variables, None if traces are not
+    /// enabled, or an error if they are enabled but cannot be exported by yamis
+    ///
+    /// # Arguments
+    ///
+    /// * `vars`: Environment variables to read the configuration from
+    ///
+    /// returns: Result<Option<OtelConfig>, String>
+    pub(crate) fn from_env<I>(vars: I) -> Result<Option<OtelConfig>, String>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let vars: HashMap<String, String> = vars
+            .into_iter()
+            .filter(|(key, _)| key.starts_with("OTEL_") || key == "TRACEPARENT")
+            .collect();
+        let get = |key: &str| {
+            vars.get(key)
+                .map(|val| val.trim())
+                .filter(|val| !val.is_empty())
+        };
+        // Signal specific variables take precedence over the general ones
+        let get_for_traces = |traces_key: &str, key: &str| get(traces_key).or_else(|| get(key));
+
+        let disabled = get("OTEL_SDK_DISABLED").is_some_and(|val| val.eq_ignore_ascii_case("true"));
+        let no_exporter = get("OTEL_TRACES_EXPORTER") == Some("none");
+        if disabled || no_exporter {
+            return Ok(None);
+        }
+        let endpoint = match (
+            get("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"),
+            get("OTEL_EXPORTER_OTLP_ENDPOINT"),
+        ) {
+            (Some(endpoint), _) => endpoint.to_string(),
+            (None, Some(endpoint)) => format!("{}/{}", endpoint.trim_end_matches('/'), TRACES_PATH),
+            (None, None) => return Ok(None),
+        };
+        if let Some(protocol) = get_for_traces(
+            "OTEL_EXPORTER_OTLP_TRACES_PROTOCOL",
+            "OTEL_EXPORTER_OTLP_PROTOCOL",
+        ) {
+            if protocol != PROTOCOL {
+                return Err(format!(
+                    "Traces are not exported, as the OTLP protocol `{}` is not supported. Set it to `{}` instead",
+                    protocol, PROTOCOL
+                ));
+            }
+        }
+        let timeout = match get_for_traces(
+            "OTEL_EXPORTER_OTLP_TRACES_TIMEOUT",
+            "OTEL_EXPORTER_OTLP_TIMEOUT",
+        ) {
+            Some(timeout) => match timeout.parse() {
+                Ok(timeout) => timeout,
+                Err(_) => return Err(format!("Invalid OTLP timeout `{}`", timeout)),
+            },
+            None => DEFAULT_TIMEOUT,
+        };
+        let headers = get_for_traces(
+            "OTEL_EXPORTER_OTLP_TRACES_HEADERS",
+            "OTEL_EXPORTER_OTLP_HEADERS",
+        )
+        .map(parse_key_values)
+        .unwrap_or_default();
+
+        let mut resource_attributes = get("OTEL_RESOURCE_ATTRIBUTES")
+            .map(parse_key_values)
+            .unwrap_or_default();
+        let service_name = get("OTEL_SERVICE_NAME").map(String::from);
+        let has_service_name = resource_attributes
+            .iter()
+            .any(|(key, _)| key == "service.name");
+        match service_name {
+            Some(service_name) => {
+                resource_attributes.retain(|(key, _)| key != "service.name");
+                resource_attributes.insert(0, (String::from("service.name"), service_name));
+            }
+            None if !has_service_name => {
+                resource_attributes
+                    .insert(0, (String::from("service.name"), String::from("yamis")));
+            }
+            None => {}
+        }
+
+        Ok(Some(OtelConfig {
+            endpoint,
+            headers,
+            timeout: Duration::from_millis(timeout),
+            resource_attributes,
+            parent: get("TRACEPARENT").and_then(TraceParent::parse),
+        }))
+    }
+}
+
+/// Value of an attribute of a span
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AttributeValue {
+    String(String),
+    Int(i64),
+}
+
+/// Span of the trace, recorded until it is exported
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Span {
+    /// Id of the span, as 16 hex digits
+    span_id: String,
+    /// Id of the parent span, if any
+    parent_span_id: Option<String>,
+    /// Name of the span
+    name: String,
+    /// Nanoseconds since the Unix epoch when the span started
+    start: u128,
+    /// Nanoseconds since the Unix epoch when the span ended
+    end: u128,
+    /// Attributes of the span
+    attributes: Vec<(String, AttributeValue)>,
+    /// Error message, if the span failed
+    error: Option<String>,
+}
+
+impl Span {
+    /// Returns the span as OTLP JSON
+    fn to_json(&self, trace_id: &str) -> Value {
+        let attributes: Vec<Value> = self
+            .attributes
+            .iter()
+            .map(|(key, val)| match val {
+                AttributeValue::String(val) => json!({"key": key, "value": {"stringValue": val}}),
+                // 64 bit integers are encoded as strings in OTLP JSON
+                AttributeValue::Int(val) => {
+                    json!({"key": key, "value": {"intValue": val.to_string()}})
+                }
+            })
+            .collect();
+        let status = match &self.error {
+            Some(message) => json!({"code": STATUS_CODE_ERROR, "message": message}),
+            None => json!({"code": STATUS_CODE_OK}),
+        };
+        let mut span = json!({
+            "traceId": trace_id,
+            "spanId": self.span_id,
+            "name": self.name,
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": self.start.to_string(),
+            "endTimeUnixNano": self.end.to_string(),
+            "attributes": attributes,
+            "status": status,
+        });
+        if let Some(parent_span_id) = &self.parent_span_id {
+            span["parentSpanId"] = json!(parent_span_id);
+        }
+        span
+    }
+}
+
+/// Returns a new id with the given number of bytes, as hex digits. Ids only need to be unique,
+/// so they are derived from the pid, the time and a counter.
+fn new_id(bytes: usize) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = Md5::new();
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.update(since_epoch().as_nanos().to_le_bytes());
+    hasher.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    hash[..bytes * 2].to_string()
+}
+
+/// Backend that records a span for each command run by another backend, and exports them as a
+/// trace when it is dropped. Export errors are only displayed as warnings, so that tracing
+/// never makes a task fail.
+pub(crate) struct OtelBackend {
+    /// Backend that runs the commands
+    inner: Box<dyn ExecutionBackend>,
+    /// Where to export the trace
+    config: OtelConfig,
+    /// Id of the trace, as 32 hex digits
+    trace_id: String,
+    /// Span of the whole run
+    root: RefCell<Span>,
+    /// Spans of the commands run so far
+    spans: RefCell<Vec<Span>>,
+}
+
+impl OtelBackend {
+    /// Returns a new backend that traces the run of the given task
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: Backend that runs the commands
+    /// * `config`: Where to export the trace
+    /// * `task`: Name of the task given in the command line
+    ///
+    /// returns: OtelBackend
+    pub(crate) fn new(inner: Box<dyn ExecutionBackend>, config: OtelConfig, task: &str) -> Self {
+        let (trace_id, parent_span_id) = match &config.parent {
+            Some(parent) => (parent.trace_id.clone(), Some(parent.span_id.clone())),
+            None => (new_id(16), None),
+        };
+        let root = Span {
+            span_id: new_id(8),
+            parent_span_id,
+            name: format!("yamis {}", task),
+            start: since_epoch().as_nanos(),
+            end: 0,
+            attributes: vec![(
+                String::from("yamis.task"),
+                AttributeValue::String(String::from(task)),
+            )],
+            error: None,
+        };
+        OtelBackend {
+            inner,
+            config,
+            trace_id,
+            root: RefCell::new(root),
+            spans: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the trace as an OTLP JSON request
+    fn to_json(&self) -> Value {
+        let resource_attributes: Vec<Value> = self
+            .config
+            .resource_attributes
+            .iter()
+            .map(|(key, val)| json!({"key": key, "value": {"stringValue": val}}))
+            .collect();
+        let spans: Vec<Value> = std::iter::once(&*self.root.borrow())
+            .chain(self.spans.borrow().iter())
+            .map(|span| span.to_json(&self.trace_id))
+            .collect();
+        json!({
+            "resourceSpans": [{
+                "resource": {"attributes": resource_attributes},
+                "scopeSpans": [{
+                    "scope": {"name": "yamis", "version": env!("CARGO_PKG_VERSION")},
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+
+    /// Sends the trace to the endpoint
+    fn export(&self) -> DynErrResult<()> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(self.config.timeout)
+            .build()?;
+        let mut request = client
+            .post(&self.config.endpoint)
+            .header("Content-Type", "application/json")
+            .body(self.to_json().to_string());
+        for (key, val) in &self.config.headers {
+            request = request.header(key, val);
+        }
+        request.send()?.error_for_status()?;
+        Ok(())
+    }
+}
+
+impl ExecutionBackend for OtelBackend {
+    fn execute(&self, command: &RenderedCommand) -> DynErrResult<()> {
+        let start = since_epoch().as_nanos();
+        let result = self.inner.execute(command);
+        let mut attributes = vec![
+            (
+                String::from("yamis.task"),
+                AttributeValue::String(String::from(command.task_name())),
+            ),
+            (
+                String::from("yamis.config_file"),
+                AttributeValue::String(command.config_file_path().to_string_lossy().to_string()),
+            ),
+        ];
+        // The program is hidden along with the rest of the command, as it might contain secrets
+        if command.echo() {
+            attributes.push((
+                String::from("process.executable.name"),
+                AttributeValue::String(String::from(command.program())),
+            ));
+        }
+        if let Some(code) = self.inner.last_exit_code() {
+            attributes.push((
+                String::from("process.exit_code"),
+                AttributeValue::Int(i64::from(code)),
+            ));
+        }
+        self.spans.borrow_mut().push(Span {
+            span_id: new_id(8),
+            parent_span_id: Some(self.root.borrow().span_id.clone()),
+            name: String::from(command.task_name()),
+            start,
+            end: since_epoch().as_nanos(),
+            attributes,
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        result
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.inner.is_dry_run()
+    }
+
+    fn last_exit_code(&self) -> Option<i32> {
+        self.inner.last_exit_code()
+    }
+}
+
+impl Drop for OtelBackend {
+    /// Ends the span of the run and exports the trace. The run failed if its last command did,
+    /// as tasks chained with `||` can recover from failures.
+    fn drop(&mut self) {
+        {
+            let mut root = self.root.borrow_mut();
+            root.end = since_epoch().as_nanos();
+            root.error = self
+                .spans
+                .borrow()
+                .last()
+                .and_then(|span| span.error.clone());
+        }
+        if let Err(e) = self.export() {
+            let msg = format!(
+                "Could not export the trace to {}: {}",
+                self.config.endpoint, e
+            );
+            eprintln!("{}", msg.yamis_warn());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ProcessBackend;
+    use crate::config_files::ConfigFile;
+    use crate::run_context::RunContext;
+    use crate::types::TaskArgs;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+
+    fn get_env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(key, val)| (key.to_string(), val.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_otel_config_from_env() {
+        assert_eq!(
+            OtelConfig::from_env(get_env(&[("HOME", "/home")])),
+            Ok(None)
+        );
+        assert_eq!(
+            OtelConfig::from_env(get_env(&[
+                ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4318"),
+                ("OTEL_SDK_DISABLED", "true"),
+            ])),
+            Ok(None)
+        );
+
+        let config = OtelConfig::from_env(get_env(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4318/"),
+            (
+                "OTEL_EXPORTER_OTLP_HEADERS",
+                "api-key=secret,x-team=build%20tools",
+            ),
+            (
+                "OTEL_RESOURCE_ATTRIBUTES",
+                "service.name=ci,deployment.environment=prod",
+            ),
+            ("OTEL_EXPORTER_OTLP_TIMEOUT", "500"),
+            (
+                "TRACEPARENT",
+                "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            ),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.endpoint, "http://localhost:4318/v1/traces");
+        assert_eq!(
+            config.headers,
+            get_env(&[("api-key", "secret"), ("x-team", "build tools")])
+        );
+        assert_eq!(config.timeout, Duration::from_millis(500));
+        assert_eq!(
+            config.resource_attributes,
+            get_env(&[("service.name", "ci"), ("deployment.environment", "prod")])
+        );
+        assert_eq!(
+            config.parent,
+            Some(TraceParent {
+                trace_id: String::from("4bf92f3577b34da6a3ce929d0e0e4736"),
+                span_id: String::from("00f067aa0ba902b7"),
+            })
+        );
+
+        // Signal specific variables take precedence
+        let config = OtelConfig::from_env(get_env(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4318"),
+            (
+                "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+                "http://traces:4318/traces",
+            ),
+            ("OTEL_SERVICE_NAME", "builds"),
+            ("TRACEPARENT", "invalid"),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.endpoint, "http://traces:4318/traces");
+        assert_eq!(
+            config.resource_attributes,
+            get_env(&[("service.name", "builds")])
+        );
+        assert_eq!(config.parent, None);
+
+        assert_eq!(
+            OtelConfig::from_env(get_env(&[
+                ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4317"),
+                ("OTEL_EXPORTER_OTLP_PROTOCOL", "grpc"),
+            ])),
+            Err(String::from(
+                "Traces are not exported, as the OTLP protocol `grpc` is not supported. Set it to `http/json` instead"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_otel_backend() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        // Receives a single request and returns its body
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((key, val)) = line.split_once(':') {
+                    if key.eq_ignore_ascii_case("content-length") {
+                        content_length = val.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  first:
+    program: yamis::echo
+  second:
+    program: yamis::fail
+    echo_command: false
+  all:
+    serial: [first, second]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let config = OtelConfig::from_env(get_env(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", &endpoint),
+            (
+                "TRACEPARENT",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+        ]))
+        .unwrap()
+        .unwrap();
+        let backend = OtelBackend::new(Box::new(ProcessBackend::new()), config, "all");
+        let task = config_file.get_task("all").unwrap();
+        assert!(task
+            .run_with_backend(&TaskArgs::new(), &config_file, &RunContext::new(), &backend)
+            .is_err());
+        drop(backend);
+
+        let request: Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+        let resource_spans = &request["resourceSpans"][0];
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0],
+            json!({"key": "service.name", "value": {"stringValue": "yamis"}})
+        );
+        let spans = resource_spans["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 3);
+        for span in spans {
+            assert_eq!(span["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        }
+        assert_eq!(spans[0]["name"], "yamis all");
+        assert_eq!(spans[0]["parentSpanId"], "00f067aa0ba902b7");
+        assert_eq!(spans[0]["status"]["code"], STATUS_CODE_ERROR);
+        assert_eq!(spans[1]["name"], "first");
+        assert_eq!(spans[1]["parentSpanId"], spans[0]["spanId"]);
+        assert_eq!(spans[1]["status"], json!({"code": STATUS_CODE_OK}));
+        assert!(spans[1]["attributes"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({"key": "process.exit_code", "value": {"intValue": "0"}})));
+        assert_eq!(spans[2]["name"], "second");
+        assert_eq!(spans[2]["status"]["code"], STATUS_CODE_ERROR);
+        // The program of hidden commands is not exported
+        assert!(!spans[2]["attributes"]
+            .to_string()
+            .contains("process.executable.name"));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::print_utils::YamisOutput;
+use std::backtrace::Backtrace;
+use std::panic::{self, PanicHookInfo};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Url where bugs should be reported
+const ISSUES_URL: &str = "https://github.com/adrianmrit/yamis/issues";
+
+/// Whether to print the backtrace when panicking, set with the `--backtrace` option
+static SHOW_BACKTRACE: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether the backtrace should be printed if the program panics.
+pub fn set_show_backtrace(show: bool) {
+    SHOW_BACKTRACE.store(show, Ordering::Relaxed);
+}
+
+/// Installs a panic hook that prints a friendly error message, inviting the user to report
+/// the bug, instead of the default panic output. The backtrace is only printed if enabled
+/// with [set_show_backtrace].
+pub fn install() {
+    panic::set_hook(Box::new(|info| {
+        let backtrace = if SHOW_BACKTRACE.load(Ordering::Relaxed) {
+            Some(Backtrace::force_capture().to_string())
+        } else {
+            None
+        };
+        let report = format_panic_report(
+            &get_panic_message(info),
+            info.location().map(|location| location.to_string()),
+            backtrace,
+        );
+        eprint!("{}", report.yamis_error());
+    }));
+}
+
+/// Returns the message the program panicked with.
+fn get_panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        String::from(*msg)
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        String::from("unknown error")
+    }
+}
+
+/// Formats the message displayed when the program panics.
+///
+/// # Arguments
+///
+/// * `message`: Message the program panicked with
+/// * `location`: Location in the source code where the program panicked
+/// * `backtrace`: Backtrace to include, if any
+///
+/// returns: String
+fn format_panic_report(
+    message: &str,
+    location: Option<String>,
+    backtrace: Option<String>,
+) -> String {
+    let mut report = format!(
+        "Yamis crashed unexpectedly. This is a bug, please report it at {}\n",
+        ISSUES_URL
+    );
+    report.push_str(&format!("Error: {}\n", message));
+    if let Some(location) = location {
+        report.push_str(&format!("Location: {}\n", location));
+    }
+    match backtrace {
+        Some(backtrace) => report.push_str(&format!("Backtrace:\n{}\n", backtrace.trim_end())),
+        None => report
+            .push_str("Run with the `--backtrace` option to include a backtrace in the report.\n"),
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_panic_report() {
+        let report = format_panic_report(
+            "Unexpected rule",
+            Some(String::from("src/parser/mod.rs:10:5")),
+            None,
+        );
+        assert_eq!(
+            report,
+            format!(
+                "Yamis crashed unexpectedly. This is a bug, please report it at {}\n\
+                Error: Unexpected rule\n\
+                Location: src/parser/mod.rs:10:5\n\
+                Run with the `--backtrace` option to include a backtrace in the report.\n",
+                ISSUES_URL
+            )
+        );
+
+        let report = format_panic_report("Unexpected rule", None, Some(String::from("0: main\n")));
+        assert!(report.ends_with("Error: Unexpected rule\nBacktrace:\n0: main\n"));
+    }
+}
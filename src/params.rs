@@ -0,0 +1,222 @@
+//! Types of the named arguments of a task, declared in `params`. Values given in the command line
+//! are validated and normalized before rendering the task.
+use crate::types::TaskArgs;
+use indexmap::IndexMap;
+use serde_derive::Deserialize;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::Path;
+
+/// Basic types of named arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BasicParamType {
+    /// Any value, which is left as it is
+    String,
+    /// Integer, normalized i.e. `+007` becomes `7`
+    Int,
+    /// Boolean, normalized to `true` or `false`. Accepts `true`, `yes`, `on` and `1`, and `false`,
+    /// `no`, `off` and `0`, in any case.
+    Bool,
+    /// Path to a file or directory that must exist
+    Path,
+}
+
+/// Type of a named argument
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ParamType {
+    /// Basic type, i.e. `int`
+    Basic(BasicParamType),
+    /// One of the given values
+    Choices {
+        /// Allowed values
+        choices: Vec<String>,
+    },
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamType::Basic(BasicParamType::String) => write!(f, "a string"),
+            ParamType::Basic(BasicParamType::Int) => write!(f, "an integer"),
+            ParamType::Basic(BasicParamType::Bool) => {
+                write!(f, "a boolean (true, false, yes, no, on, off, 1 or 0)")
+            }
+            ParamType::Basic(BasicParamType::Path) => write!(f, "an existing path"),
+            ParamType::Choices { choices } => write!(f, "one of: {}", choices.join(", ")),
+        }
+    }
+}
+
+impl ParamType {
+    /// Returns the normalized value, or None if the value does not have this type
+    ///
+    /// # Arguments
+    ///
+    /// * `value`: Value given in the command line
+    ///
+    /// returns: Option<OsString>
+    fn coerce(&self, value: &OsString) -> Option<OsString> {
+        // Only strings can have values that are not valid unicode
+        let str_value = value.to_str();
+        match self {
+            ParamType::Basic(BasicParamType::String) => Some(value.clone()),
+            ParamType::Basic(BasicParamType::Int) => str_value?
+                .parse::<i64>()
+                .ok()
+                .map(|value| OsString::from(value.to_string())),
+            ParamType::Basic(BasicParamType::Bool) => match str_value?.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Some(OsString::from("true")),
+                "false" | "no" | "off" | "0" => Some(OsString::from("false")),
+                _ => None,
+            },
+            ParamType::Basic(BasicParamType::Path) => {
+                Path::new(str_value?).exists().then(|| value.clone())
+            }
+            ParamType::Choices { choices } => {
+                let str_value = str_value?;
+                choices
+                    .iter()
+                    .any(|choice| choice == str_value)
+                    .then(|| value.clone())
+            }
+        }
+    }
+}
+
+/// Returns the given arguments with the values of the declared params normalized, or an error
+/// naming the argument, the value and the expected type if a value does not have its type.
+/// Arguments that are not declared are left as they are.
+///
+/// # Arguments
+///
+/// * `params`: Types of the named arguments
+/// * `args`: Arguments given in the command line
+///
+/// returns: Result<HashMap<String, Vec<OsString>, RandomState>, String>
+pub(crate) fn coerce_args(
+    params: &IndexMap<String, ParamType>,
+    args: &TaskArgs,
+) -> Result<TaskArgs, String> {
+    let mut coerced = args.clone();
+    for (name, param_type) in params {
+        let Some(values) = coerced.get_mut(name) else {
+            continue;
+        };
+        let count = values.len();
+        for (index, value) in values.iter_mut().enumerate() {
+            match param_type.coerce(value) {
+                Some(coerced_value) => *value = coerced_value,
+                None => {
+                    // The position only matters if the argument was given multiple times
+                    let position = if count > 1 {
+                        format!(" (value {} of {})", index + 1, count)
+                    } else {
+                        String::new()
+                    };
+                    return Err(format!(
+                        "Invalid value `{}` for `--{}`{}, expected {}",
+                        value.to_string_lossy(),
+                        name,
+                        position,
+                        param_type
+                    ));
+                }
+            }
+        }
+    }
+    Ok(coerced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    fn get_args(args: &[(&str, &[&str])]) -> TaskArgs {
+        args.iter()
+            .map(|(key, values)| {
+                (
+                    String::from(*key),
+                    values.iter().map(OsString::from).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_coerce_args() {
+        let params: IndexMap<String, ParamType> = serde_yaml::from_str(
+            r#"
+replicas: int
+dry_run: bool
+name: string
+env:
+  choices: [staging, prod]
+"#,
+        )
+        .unwrap();
+
+        let args = get_args(&[
+            ("replicas", &["+007"]),
+            ("dry_run", &["Yes"]),
+            ("name", &["007"]),
+            ("env", &["prod"]),
+            ("other", &["x"]),
+        ]);
+        let coerced = coerce_args(&params, &args).unwrap();
+        assert_eq!(
+            coerced,
+            get_args(&[
+                ("replicas", &["7"]),
+                ("dry_run", &["true"]),
+                ("name", &["007"]),
+                ("env", &["prod"]),
+                ("other", &["x"]),
+            ])
+        );
+
+        // Params that are not given are ignored
+        assert_eq!(coerce_args(&params, &TaskArgs::new()), Ok(TaskArgs::new()));
+
+        let args = get_args(&[("replicas", &["two"])]);
+        assert_eq!(
+            coerce_args(&params, &args).unwrap_err(),
+            "Invalid value `two` for `--replicas`, expected an integer"
+        );
+
+        let args = get_args(&[("env", &["staging", "dev"])]);
+        assert_eq!(
+            coerce_args(&params, &args).unwrap_err(),
+            "Invalid value `dev` for `--env` (value 2 of 2), expected one of: staging, prod"
+        );
+
+        let args = get_args(&[("dry_run", &["maybe"])]);
+        assert_eq!(
+            coerce_args(&params, &args).unwrap_err(),
+            "Invalid value `maybe` for `--dry_run`, expected a boolean (true, false, yes, no, on, off, 1 or 0)"
+        );
+    }
+
+    #[test]
+    fn test_coerce_path() {
+        let tmp_dir = TempDir::new().unwrap();
+        let params: IndexMap<String, ParamType> = serde_yaml::from_str("config: path").unwrap();
+        let existing = tmp_dir.path().to_string_lossy().to_string();
+        let args = get_args(&[("config", &[&existing])]);
+        assert_eq!(coerce_args(&params, &args), Ok(args));
+
+        let missing = tmp_dir.path().join("missing").to_string_lossy().to_string();
+        let args = get_args(&[("config", &[&missing])]);
+        assert_eq!(
+            coerce_args(&params, &args).unwrap_err(),
+            format!(
+                "Invalid value `{}` for `--config`, expected an existing path",
+                missing
+            )
+        );
+
+        assert!(serde_yaml::from_str::<ParamType>("float").is_err());
+    }
+}
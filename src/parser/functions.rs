@@ -4,6 +4,7 @@ use std::ops::Index;
 use lazy_static::lazy_static;
 
 use crate::format_str::format_string;
+use crate::plugins::{call_plugin_function, PluginValue};
 use crate::types::DynErrResult;
 
 /// Wraps a value passed to a function, which can be either a str pointer or pointer to a
@@ -22,7 +23,7 @@ pub enum FunResult {
 
 impl FunResult {
     /// Converts the result to a value
-    pub(crate) fn as_val(&self) -> FunVal {
+    pub(crate) fn as_val(&self) -> FunVal<'_> {
         match self {
             FunResult::String(val) => FunVal::String(val),
             FunResult::Vec(val) => FunVal::Vec(val),
@@ -37,6 +38,24 @@ impl FunResult {
     }
 }
 
+impl From<FunResult> for PluginValue {
+    fn from(value: FunResult) -> Self {
+        match value {
+            FunResult::String(s) => PluginValue::String(s),
+            FunResult::Vec(values) => PluginValue::List(values),
+        }
+    }
+}
+
+impl From<PluginValue> for FunResult {
+    fn from(value: PluginValue) -> Self {
+        match value {
+            PluginValue::String(s) => FunResult::String(s),
+            PluginValue::List(values) => FunResult::Vec(values),
+        }
+    }
+}
+
 /// Validates the number of arguments for a function, but not their type
 ///
 /// # Arguments
@@ -49,7 +68,7 @@ impl FunResult {
 /// returns: Result<(), Box<dyn Error, Global>>
 fn validate_arguments_length(
     fn_name: &str,
-    args: &Vec<FunVal>,
+    args: &[FunVal],
     min: usize,
     max: usize,
 ) -> DynErrResult<()> {
@@ -180,7 +199,7 @@ fn map(args: &Vec<FunVal>) -> DynErrResult<FunResult> {
     validate_arguments_length(fn_name, args, 2, 2)?;
     let fmt_string = validate_string(fn_name, args, 0)?;
 
-    return match args.index(1) {
+    match args.index(1) {
         FunVal::String(s) => {
             let result = map_format_string(fmt_string, s)?;
             Ok(FunResult::String(result))
@@ -192,7 +211,7 @@ fn map(args: &Vec<FunVal>) -> DynErrResult<FunResult> {
             }
             Ok(FunResult::Vec(result))
         }
-    };
+    }
 }
 
 /// Like calling map and then joining the values with the empty string
@@ -207,7 +226,7 @@ fn jmap(args: &Vec<FunVal>) -> DynErrResult<FunResult> {
     validate_arguments_length(fn_name, args, 2, 2)?;
     let fmt_string = validate_string(fn_name, args, 0)?;
 
-    return match args.index(1) {
+    match args.index(1) {
         FunVal::String(s) => {
             let result = map_format_string(fmt_string, s)?;
             Ok(FunResult::String(result))
@@ -219,7 +238,7 @@ fn jmap(args: &Vec<FunVal>) -> DynErrResult<FunResult> {
             }
             Ok(FunResult::String(result))
         }
-    };
+    }
 }
 
 /// Joins multiple values.
@@ -349,6 +368,85 @@ fn trim(args: &Vec<FunVal>) -> DynErrResult<FunResult> {
     }
 }
 
+/// Returns the number of values, where a string counts as a single value
+///
+/// # Arguments
+///
+/// * `args`: Function values
+///
+/// returns: Result<FunResult, Box<dyn Error, Global>>
+fn count(args: &Vec<FunVal>) -> DynErrResult<FunResult> {
+    let fn_name = "count";
+    validate_arguments_length(fn_name, args, 1, 1)?;
+    let count = match args.index(0) {
+        FunVal::String(_) => 1,
+        FunVal::Vec(values) => values.len(),
+    };
+    Ok(FunResult::String(count.to_string()))
+}
+
+/// Returns the first value, or the string itself. Returns an empty string if there are no
+/// values.
+///
+/// # Arguments
+///
+/// * `args`: Function values
+///
+/// returns: Result<FunResult, Box<dyn Error, Global>>
+fn first(args: &Vec<FunVal>) -> DynErrResult<FunResult> {
+    let fn_name = "first";
+    validate_arguments_length(fn_name, args, 1, 1)?;
+    match args.index(0) {
+        FunVal::String(s) => Ok(FunResult::String(s.to_string())),
+        FunVal::Vec(values) => Ok(FunResult::String(
+            values.first().cloned().unwrap_or_default(),
+        )),
+    }
+}
+
+/// Returns the last value, or the string itself. Returns an empty string if there are no
+/// values.
+///
+/// # Arguments
+///
+/// * `args`: Function values
+///
+/// returns: Result<FunResult, Box<dyn Error, Global>>
+fn last(args: &Vec<FunVal>) -> DynErrResult<FunResult> {
+    let fn_name = "last";
+    validate_arguments_length(fn_name, args, 1, 1)?;
+    match args.index(0) {
+        FunVal::String(s) => Ok(FunResult::String(s.to_string())),
+        FunVal::Vec(values) => Ok(FunResult::String(
+            values.last().cloned().unwrap_or_default(),
+        )),
+    }
+}
+
+/// Calls a function of a plugin, an executable named `yamis-<name>` in the PATH. The first argument
+/// is the name of the plugin, the second one is the name of the function, and the rest are passed
+/// to the function.
+///
+/// # Arguments
+///
+/// * `args`: Function values
+///
+/// returns: Result<FunResult, Box<dyn Error, Global>>
+fn plugin(args: &Vec<FunVal>) -> DynErrResult<FunResult> {
+    let fn_name = "plugin";
+    validate_arguments_length(fn_name, args, 2, usize::MAX)?;
+    let plugin_name = validate_string(fn_name, args, 0)?;
+    let function = validate_string(fn_name, args, 1)?;
+    let plugin_args: Vec<PluginValue> = args[2..]
+        .iter()
+        .map(|arg| match arg {
+            FunVal::String(s) => PluginValue::String(s.to_string()),
+            FunVal::Vec(values) => PluginValue::List(values.to_vec()),
+        })
+        .collect();
+    Ok(call_plugin_function(plugin_name, function, &plugin_args)?.into())
+}
+
 /// Returns a FunctionRegistry with the default functions
 fn load_default_functions() -> FunctionRegistry {
     let mut functions: HashMap<String, Function> = HashMap::new();
@@ -358,6 +456,10 @@ fn load_default_functions() -> FunctionRegistry {
     functions.insert(String::from("fmt"), fmt);
     functions.insert(String::from("split"), split);
     functions.insert(String::from("trim"), trim);
+    functions.insert(String::from("count"), count);
+    functions.insert(String::from("first"), first);
+    functions.insert(String::from("last"), last);
+    functions.insert(String::from("plugin"), plugin);
     FunctionRegistry { functions }
 }
 
@@ -529,4 +631,26 @@ Invalid format string:
         let expected = FunResult::Vec(vec!["world".to_string(), "people".to_string()]);
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_count_first_last() {
+        let values = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let vars = vec![FunVal::Vec(&values)];
+        assert_eq!(count(&vars).unwrap(), FunResult::String(String::from("3")));
+        assert_eq!(first(&vars).unwrap(), FunResult::String(String::from("a")));
+        assert_eq!(last(&vars).unwrap(), FunResult::String(String::from("c")));
+
+        let vars = vec![FunVal::String("a")];
+        assert_eq!(count(&vars).unwrap(), FunResult::String(String::from("1")));
+        assert_eq!(first(&vars).unwrap(), FunResult::String(String::from("a")));
+        assert_eq!(last(&vars).unwrap(), FunResult::String(String::from("a")));
+
+        let values: Vec<String> = vec![];
+        let vars = vec![FunVal::Vec(&values)];
+        assert_eq!(count(&vars).unwrap(), FunResult::String(String::from("0")));
+        assert!(first(&vars).unwrap().is_empty());
+        assert!(last(&vars).unwrap().is_empty());
+
+        assert!(count(&vec![]).is_err());
+    }
 }
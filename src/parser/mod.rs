@@ -1,29 +1,31 @@
+use crate::escape::{escape_value, escape_values, translate_env_refs, EscapeMode, ShellKind};
 use crate::parser::functions::{FunResult, DEFAULT_FUNCTIONS};
+pub(crate) use crate::parser::system::get_system_field;
+use crate::plugins::PluginValue;
+use crate::print_utils::YamisOutput;
+use crate::state_dirs::StateDirs;
 use crate::types::{DynErrResult, TaskArgs};
+use crate::versions::{check_context_key, ConfigVersion};
+use crate::wasm_plugins::WasmPlugins;
+use indexmap::IndexMap;
 use pest::error::{Error as PestError, ErrorVariant};
-use pest::iterators::Pair;
+use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use pest_derive::Parser;
-use serde_derive::Deserialize;
 use std::cmp::{max, min};
 use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Mutex;
 use std::{error, fmt};
 
 mod functions;
+mod system;
+mod used_args;
 
-/// Modes to escape (add quotes) the arguments passed to the script
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
-pub enum EscapeMode {
-    /// Always quote the arguments
-    Always,
-    /// Only add quotes if the argument has spaces
-    Spaces,
-    /// Never quote the argument
-    Never,
-}
+pub(crate) use used_args::UsedArgs;
 
 /// Represents the slice from the user, either by index or range
 enum Slice {
@@ -58,6 +60,292 @@ fn custom_span_error(span: pest::Span, msg: String) -> PestError<Rule> {
 //     PestError::new_from_pos(ErrorVariant::CustomError { message: msg }, pos)
 // }
 
+/// Returns an error for a pair with a rule that was not expected. Should not happen unless the
+/// grammar and the parser get out of sync, but it is better to error than to panic.
+fn unexpected_rule_error(pair: &Pair<Rule>) -> Box<dyn error::Error> {
+    custom_span_error(
+        pair.as_span(),
+        format!("Unexpected {}", rename_rules(&pair.as_rule())),
+    )
+    .into()
+}
+
+/// Returns the next pair, or an error at the given span if there are no more pairs.
+fn next_pair<'a>(pairs: &mut Pairs<'a, Rule>, span: pest::Span) -> DynErrResult<Pair<'a, Rule>> {
+    match pairs.next() {
+        Some(pair) => Ok(pair),
+        None => Err(custom_span_error(span, String::from("Expected a value")).into()),
+    }
+}
+
+/// Metadata about how yamis was invoked, available to all the tasks
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    /// Directory yamis was called from
+    pub cwd: String,
+    /// Arguments yamis was called with, including the binary
+    pub argv: Vec<String>,
+    /// Whether the commands are printed instead of run
+    pub dry_run: bool,
+}
+
+impl Invocation {
+    /// Returns the invocation of the current process
+    pub fn from_env() -> Self {
+        Invocation {
+            cwd: std::env::current_dir()
+                .map(|cwd| cwd.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            argv: std::env::args_os()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect(),
+            dry_run: false,
+        }
+    }
+}
+
+/// Variables declared in config files, available to all the tasks
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TaskVars<'a> {
+    /// Variables of the config file of the task
+    file: Option<&'a IndexMap<String, String>>,
+    /// Variables of the global config file, which are overridden by the ones of the file
+    global: Option<&'a IndexMap<String, String>>,
+    /// Variables given from the command line, which override the ones of the config files
+    overrides: Option<&'a IndexMap<String, String>>,
+    /// Constants of the config file of the task, which cannot be overridden
+    constants: Option<&'a IndexMap<String, String>>,
+    /// Metadata about how yamis was invoked
+    invocation: Option<&'a Invocation>,
+    /// Environment variables imported with `env_import_prefix`, without the prefix
+    imported_env: Option<&'a IndexMap<String, String>>,
+    /// State directories of the project
+    state_dirs: Option<&'a StateDirs>,
+    /// Members of the workspace declared in the config file of the task
+    workspace_members: Option<&'a [String]>,
+    /// WASM plugins declared in the config file of the task, which provide extra functions
+    plugins: Option<&'a WasmPlugins>,
+    /// Declared version of the config file of the task, which decides the keys available
+    version: ConfigVersion,
+    /// Where the arguments used by the rendered templates are recorded
+    used_args: Option<&'a Mutex<UsedArgs>>,
+    /// Whether to print each tag and the value it rendered to, set with `--trace-templates`
+    trace: bool,
+    /// Whether to mask all the values printed with `--trace-templates`, i.e. for tasks that
+    /// do not echo their commands
+    mask_trace: bool,
+}
+
+impl<'a> TaskVars<'a> {
+    /// Returns a new TaskVars
+    ///
+    /// # Arguments
+    ///
+    /// * `file`: Variables of the config file of the task
+    /// * `global`: Variables of the global config file
+    ///
+    /// returns: TaskVars
+    pub fn new(
+        file: Option<&'a IndexMap<String, String>>,
+        global: Option<&'a IndexMap<String, String>>,
+    ) -> Self {
+        TaskVars {
+            file,
+            global,
+            overrides: None,
+            constants: None,
+            invocation: None,
+            imported_env: None,
+            state_dirs: None,
+            workspace_members: None,
+            plugins: None,
+            version: ConfigVersion::default(),
+            used_args: None,
+            trace: false,
+            mask_trace: false,
+        }
+    }
+
+    /// Returns the same variables, overridden by the given ones
+    pub fn with_overrides(self, overrides: &'a IndexMap<String, String>) -> Self {
+        TaskVars {
+            overrides: Some(overrides),
+            ..self
+        }
+    }
+
+    /// Returns the same variables, along with the given constants
+    pub fn with_constants(self, constants: &'a IndexMap<String, String>) -> Self {
+        TaskVars {
+            constants: Some(constants),
+            ..self
+        }
+    }
+
+    /// Returns the same variables, along with the given invocation metadata
+    pub fn with_invocation(self, invocation: &'a Invocation) -> Self {
+        TaskVars {
+            invocation: Some(invocation),
+            ..self
+        }
+    }
+
+    /// Returns the same variables, along with the given imported environment variables
+    pub fn with_imported_env(self, imported_env: &'a IndexMap<String, String>) -> Self {
+        TaskVars {
+            imported_env: Some(imported_env),
+            ..self
+        }
+    }
+
+    /// Returns the same variables, along with the given state directories
+    pub fn with_state_dirs(self, state_dirs: &'a StateDirs) -> Self {
+        TaskVars {
+            state_dirs: Some(state_dirs),
+            ..self
+        }
+    }
+
+    /// Returns the same variables, along with the given workspace members
+    pub fn with_workspace_members(self, workspace_members: &'a [String]) -> Self {
+        TaskVars {
+            workspace_members: Some(workspace_members),
+            ..self
+        }
+    }
+
+    /// Returns the same variables, along with the given WASM plugins
+    pub fn with_plugins(self, plugins: &'a WasmPlugins) -> Self {
+        TaskVars {
+            plugins: Some(plugins),
+            ..self
+        }
+    }
+
+    /// Returns the same variables, for a config file that declares the given version
+    pub fn with_version(self, version: ConfigVersion) -> Self {
+        TaskVars { version, ..self }
+    }
+
+    /// Returns the same variables, recording the arguments used by the templates in the given
+    /// value
+    pub(crate) fn with_used_args(self, used_args: &'a Mutex<UsedArgs>) -> Self {
+        TaskVars {
+            used_args: Some(used_args),
+            ..self
+        }
+    }
+
+    /// Returns the same variables, printing each tag and the value it rendered to if `trace`
+    /// is true
+    pub(crate) fn with_trace(self, trace: bool) -> Self {
+        TaskVars { trace, ..self }
+    }
+
+    /// Returns the same variables, masking all the values printed with `--trace-templates` if
+    /// `mask_trace` is true
+    pub(crate) fn with_masked_trace(self, mask_trace: bool) -> Self {
+        TaskVars { mask_trace, ..self }
+    }
+
+    /// Prints the given tag and the value it rendered to, if tracing is enabled. The value is
+    /// only formatted if needed.
+    fn trace_tag<F: FnOnce() -> String>(&self, tag: &str, value: F) {
+        if self.trace {
+            let masked = self.mask_trace || looks_secret(tag);
+            let value = if masked {
+                String::from(MASKED_VALUE)
+            } else {
+                value()
+            };
+            eprintln!("{}", format!("{} = {}", tag, value).yamis_info());
+        }
+    }
+
+    /// Records that the argument referenced by the given pair is used, if it references one
+    fn record_used_arg(&self, param: &Pair<Rule>) {
+        let Some(used_args) = self.used_args else {
+            return;
+        };
+        match param.as_rule() {
+            Rule::arg => {
+                let position = param
+                    .clone()
+                    .into_inner()
+                    .next()
+                    .and_then(|index| usize::from_str(index.as_str()).ok());
+                if let Some(position) = position {
+                    used_args.lock().unwrap().use_positional(position);
+                }
+            }
+            Rule::kwarg => {
+                if let Some(name) = param.clone().into_inner().next() {
+                    used_args.lock().unwrap().use_named(name.as_str());
+                }
+            }
+            // The arguments yamis was called with include the ones of the task
+            Rule::all_args => used_args.lock().unwrap().use_all(),
+            Rule::invocation if param.as_str().starts_with("invocation.argv") => {
+                used_args.lock().unwrap().use_all()
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the directory yamis was called from, or the current directory if unknown
+    fn get_invocation_dir(&self) -> PathBuf {
+        match self.invocation {
+            Some(invocation) if !invocation.cwd.is_empty() => PathBuf::from(&invocation.cwd),
+            _ => std::env::current_dir().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the imported environment variable with the given name, without the prefix
+    fn get_imported_env(&self, name: &str) -> Option<&'a String> {
+        self.imported_env
+            .and_then(|imported_env| imported_env.get(name))
+    }
+
+    /// Returns the constant with the given name
+    fn get_constant(&self, name: &str) -> Option<&'a String> {
+        self.constants.and_then(|constants| constants.get(name))
+    }
+
+    /// Returns the variable with the given name, looking at the file variables first
+    fn get(&self, name: &str) -> Option<&'a String> {
+        self.get_file_var(name)
+            .or_else(|| self.global.and_then(|global| global.get(name)))
+    }
+
+    /// Returns the variable with the given name, only looking at the file variables, unless
+    /// it is overridden
+    fn get_file_var(&self, name: &str) -> Option<&'a String> {
+        self.overrides
+            .and_then(|overrides| overrides.get(name))
+            .or_else(|| self.file.and_then(|file| file.get(name)))
+    }
+}
+
+/// Words that hint a tag references a secret, in which case its value is masked when traced
+const SECRET_HINTS: [&str; 7] = [
+    "SECRET",
+    "TOKEN",
+    "PASSWORD",
+    "PASSWD",
+    "CREDENTIAL",
+    "KEY",
+    "AUTH",
+];
+
+/// Printed instead of the values of the tags that might contain secrets
+const MASKED_VALUE: &str = "******";
+
+/// Returns whether the tag seems to reference a secret, i.e. `{$API_TOKEN}`
+fn looks_secret(tag: &str) -> bool {
+    let tag = tag.to_uppercase();
+    SECRET_HINTS.iter().any(|hint| tag.contains(hint))
+}
+
 /// Pest parser for script
 #[derive(Parser)]
 #[grammar = "parser/grammar.pest"]
@@ -81,11 +369,28 @@ fn rename_rules(rule: &Rule) -> String {
         Rule::kwarg => "keyword argument".to_string(),
         Rule::env_var_name => "environment variable name".to_string(),
         Rule::env_var => "environment variable".to_string(),
+        Rule::var_name => "variable name".to_string(),
+        Rule::var => "variable".to_string(),
+        Rule::file_var => "file variable".to_string(),
+        Rule::constant => "constant".to_string(),
+        Rule::imported_env => "imported environment variable".to_string(),
+        Rule::dirs_name => "directory name".to_string(),
+        Rule::dirs => "state directory".to_string(),
+        Rule::invocation_field => "invocation field".to_string(),
+        Rule::invocation => "invocation metadata".to_string(),
+        Rule::system_field => "system field".to_string(),
+        Rule::system => "system information".to_string(),
+        Rule::workspace_field => "workspace field".to_string(),
+        Rule::workspace => "workspace field".to_string(),
         Rule::fun_name => "function identifier".to_string(),
         Rule::expression_inner => "expression".to_string(),
         Rule::expression => "expression".to_string(),
         Rule::fun_params => "function parameters".to_string(),
         Rule::fun => "function".to_string(),
+        Rule::quote_filter => "q".to_string(),
+        Rule::no_quote_filter => "nq".to_string(),
+        Rule::project_path_filter => "project_path".to_string(),
+        Rule::filter => "filter".to_string(),
         Rule::tag => "tag".to_string(),
         Rule::special_val => "valid escaped character".to_string(),
         Rule::escape => "valid escaped value".to_string(),
@@ -112,8 +417,9 @@ fn parse_int(s: &str) -> Result<isize, IntParsingError> {
 
 /// Returns a `Slice` enum from the pair
 fn get_slice_repr(slice: Pair<Rule>) -> DynErrResult<Slice> {
+    let span = slice.as_span();
     let mut slice_inner = slice.into_inner();
-    let val = slice_inner.next().unwrap();
+    let val = next_pair(&mut slice_inner, span)?;
     match val.as_rule() {
         Rule::index => Ok(Slice::Index(parse_int(val.as_str())?)),
         Rule::range => {
@@ -124,29 +430,27 @@ fn get_slice_repr(slice: Pair<Rule>) -> DynErrResult<Slice> {
                 match val.as_rule() {
                     Rule::range_from => from = Some(parse_int(val.as_str())?),
                     Rule::range_to => to = Some(parse_int(val.as_str())?),
-                    v => unreachable!("Unexpected rule {:?}", v),
+                    _ => return Err(unexpected_rule_error(&val)),
                 }
             }
             Ok(Slice::Range(from, to))
         }
-        v => unreachable!("Unexpected rule {:?}", v),
+        _ => Err(unexpected_rule_error(&val)),
     }
 }
 
 /// Slices a string
 fn slice_string(val: String, slice: RealSlice) -> FunResult {
     match slice {
-        RealSlice::Index(i) => {
-            if i >= val.len() {
-                return FunResult::String("".to_string());
-            }
-            FunResult::String(val.chars().nth(i).unwrap().to_string())
-        }
+        RealSlice::Index(i) => match val.chars().nth(i) {
+            Some(c) => FunResult::String(c.to_string()),
+            None => FunResult::String("".to_string()),
+        },
         RealSlice::Range(from, to) => {
-            if from >= val.len() || from >= to {
+            if from >= to {
                 return FunResult::String("".to_string());
             }
-            FunResult::String(String::from(val.get(from..to).unwrap_or("")))
+            FunResult::String(val.chars().skip(from).take(to - from).collect())
         }
     }
 }
@@ -154,7 +458,10 @@ fn slice_string(val: String, slice: RealSlice) -> FunResult {
 /// Slices a vector
 fn slice_vec(mut val: Vec<String>, slice: RealSlice) -> FunResult {
     match slice {
-        RealSlice::Index(i) => FunResult::String(String::from(&val[i])),
+        RealSlice::Index(i) => match val.get(i) {
+            Some(v) => FunResult::String(v.clone()),
+            None => FunResult::String("".to_string()),
+        },
         RealSlice::Range(from, to) => {
             if from >= val.len() || from >= to {
                 FunResult::Vec(vec![])
@@ -174,28 +481,63 @@ fn slice_val(val: FunResult, slice: RealSlice) -> FunResult {
     }
 }
 
+/// Returns the key of the template context the pair reads, as declared in
+/// [CONTEXT_KEYS](crate::versions::CONTEXT_KEYS), or None if it does not read the context
+fn get_context_key(pair: &Pair<Rule>) -> Option<String> {
+    match pair.as_rule() {
+        Rule::arg => Some(String::from("$<n>")),
+        Rule::all_args => Some(String::from("$@")),
+        Rule::kwarg => Some(String::from("<name>")),
+        Rule::env_var => Some(String::from("$<name>")),
+        Rule::var => Some(String::from("vars.<name>")),
+        Rule::file_var => Some(String::from("file.vars.<name>")),
+        Rule::constant => Some(String::from("const.<name>")),
+        Rule::imported_env => Some(String::from("cfg.<name>")),
+        Rule::dirs | Rule::invocation | Rule::system | Rule::workspace => {
+            Some(String::from(pair.as_str()))
+        }
+        _ => None,
+    }
+}
+
 /// Parses the inner value of a expression, excluding immediate following slices and modifiers
 fn parse_expression_inner(
     expression_inner: Pair<Rule>,
     cli_args: &TaskArgs,
     env: &HashMap<String, String>,
+    vars: &TaskVars,
 ) -> DynErrResult<FunResult> {
+    let span = expression_inner.as_span();
     let mut expression_inner = expression_inner.into_inner();
-    let param = expression_inner.next().unwrap();
+    let param = next_pair(&mut expression_inner, span)?;
+    if let Some(key) = get_context_key(&param) {
+        if let Err(e) = check_context_key(&key, vars.version) {
+            return Err(custom_span_error(param.as_span(), e).into());
+        }
+    }
+    vars.record_used_arg(&param);
     match param.as_rule() {
-        Rule::fun => parse_fun(param, cli_args, env),
+        Rule::fun => parse_fun(param, cli_args, env, vars),
         Rule::arg => parse_arg(param, cli_args),
         Rule::kwarg => parse_kwargs(param, cli_args),
         Rule::all_args => parse_all(cli_args),
         Rule::env_var => parse_env_var(param, env),
+        Rule::var => parse_var(param, |name| vars.get(name)),
+        Rule::file_var => parse_var(param, |name| vars.get_file_var(name)),
+        Rule::constant => parse_var(param, |name| vars.get_constant(name)),
+        Rule::imported_env => parse_var(param, |name| vars.get_imported_env(name)),
+        Rule::dirs => parse_state_dir(param, vars.state_dirs),
+        Rule::invocation => parse_invocation(param, vars.invocation),
+        Rule::system => parse_system(param),
+        Rule::workspace => parse_workspace(param, vars.workspace_members),
         Rule::string => parse_string(param),
-        v => unreachable!("Unexpected rule {:?}", v),
+        _ => Err(unexpected_rule_error(&param)),
     }
 }
 
 fn parse_slice(expression: Pair<Rule>, val: FunResult, optional: bool) -> DynErrResult<FunResult> {
     let val_len = match val {
-        FunResult::String(ref v) => v.len(),
+        FunResult::String(ref v) => v.chars().count(),
         FunResult::Vec(ref v) => v.len(),
     } as isize;
     // usize is 2^32 in 32 bit systems, or 2^64 in 64 bit systems
@@ -251,17 +593,19 @@ fn parse_expression(
     expression: Pair<Rule>,
     cli_args: &TaskArgs,
     env: &HashMap<String, String>,
+    vars: &TaskVars,
 ) -> DynErrResult<FunResult> {
     // We need to get the string representation even if there is no error because into_inner
     // consumes the pair, making it impossible (at least that I know of) to get the
     // representation later.
     let expression_copy = expression.clone();
+    let expression_span = expression.as_span();
     let mut expression_inner_values = expression.into_inner();
-    let expression_inner = expression_inner_values.next().unwrap();
+    let expression_inner = next_pair(&mut expression_inner_values, expression_span)?;
     let span = expression_inner.as_span();
     let mut val = match expression_inner.as_rule() {
-        Rule::expression_inner => parse_expression_inner(expression_inner, cli_args, env)?,
-        v => unreachable!("Unexpected rule {:?}", v),
+        Rule::expression_inner => parse_expression_inner(expression_inner, cli_args, env, vars)?,
+        _ => return Err(unexpected_rule_error(&expression_inner)),
     };
     // We check if it is optional first so that we can return the appropriate error message
     let optional = match expression_copy.into_inner().last() {
@@ -274,7 +618,7 @@ fn parse_expression(
                 val = parse_slice(slice_or_modifier, val, optional)?;
             }
             Rule::optional => (), // we already checked if it is optional
-            v => unreachable!("Unexpected rule {:?}", v),
+            _ => return Err(unexpected_rule_error(&slice_or_modifier)),
         }
     }
     if !optional && val.is_empty() {
@@ -293,22 +637,26 @@ fn parse_fun(
     function_pair: Pair<Rule>,
     cli_args: &TaskArgs,
     env: &HashMap<String, String>,
+    vars: &TaskVars,
 ) -> DynErrResult<FunResult> {
     let function_span = function_pair.as_span();
     let mut function_inner = function_pair.into_inner();
-    let fun_name_pair = function_inner.next().unwrap();
+    let fun_name_pair = next_pair(&mut function_inner, function_span)?;
     let fun_name = fun_name_pair.as_str();
     let arguments = function_inner.next();
-    let fun = match DEFAULT_FUNCTIONS.functions.get(fun_name) {
-        None => {
-            return Err(custom_span_error(
-                fun_name_pair.as_span(),
-                format!("Undefined function `{}`", fun_name_pair.as_str()),
-            )
-            .into())
-        }
-        Some(fun) => fun,
+    let fun = DEFAULT_FUNCTIONS.functions.get(fun_name);
+    // Functions of the plugins cannot override the default ones
+    let plugins = match (fun, vars.plugins) {
+        (None, Some(plugins)) if plugins.has_function(fun_name)? => Some(plugins),
+        _ => None,
     };
+    if fun.is_none() && plugins.is_none() {
+        return Err(custom_span_error(
+            fun_name_pair.as_span(),
+            format!("Undefined function `{}`", fun_name_pair.as_str()),
+        )
+        .into());
+    }
 
     let arguments: Vec<FunResult> = match arguments {
         None => {
@@ -317,13 +665,22 @@ fn parse_fun(
         Some(arguments) => {
             let mut arguments_list: Vec<FunResult> = vec![];
             for param in arguments.into_inner() {
-                let param = parse_expression(param, cli_args, env)?;
+                let param = parse_expression(param, cli_args, env, vars)?;
                 arguments_list.push(param);
             }
             arguments_list
         }
     };
-    match fun(&arguments.iter().map(|v| v.as_val()).collect()) {
+    let result = match (fun, plugins) {
+        (Some(fun), _) => fun(&arguments.iter().map(|v| v.as_val()).collect()),
+        (None, Some(plugins)) => {
+            let plugin_args: Vec<PluginValue> =
+                arguments.into_iter().map(PluginValue::from).collect();
+            plugins.call(fun_name, &plugin_args).map(FunResult::from)
+        }
+        (None, None) => unreachable!("Undefined functions are checked above"),
+    };
+    match result {
         Ok(v) => Ok(v),
         Err(e) => Err(custom_span_error(
             function_span,
@@ -341,8 +698,9 @@ fn parse_string(tag: Pair<Rule>) -> DynErrResult<FunResult> {
         match pair.as_rule() {
             Rule::string_content => result.push_str(pair.as_str()),
             Rule::escape => {
+                let span = pair.as_span();
                 let mut inner = pair.into_inner();
-                let val = inner.next().unwrap();
+                let val = next_pair(&mut inner, span)?;
                 match val.as_str() {
                     "n" => result.push('\n'),
                     "r" => result.push('\r'),
@@ -352,46 +710,71 @@ fn parse_string(tag: Pair<Rule>) -> DynErrResult<FunResult> {
                     "\"" => result.push('"'),
                     "'" => result.push('\''),
                     v => {
-                        unreachable!("Unexpected escaped value {}", v)
+                        return Err(custom_span_error(
+                            val.as_span(),
+                            format!("Unexpected escaped value `{}`", v),
+                        )
+                        .into())
                     }
                 }
                 if let Some(other) = inner.next() {
-                    unreachable!("Unexpected pair {:?}", other)
+                    return Err(unexpected_rule_error(&other));
                 }
             }
-            v => unreachable!("Unexpected rule {:?}", v),
+            _ => return Err(unexpected_rule_error(&pair)),
         }
     }
     Ok(FunResult::String(result))
 }
 
+/// Converts the given OS strings into strings, replacing invalid unicode sequences
+fn to_string_vec(values: &[OsString]) -> Vec<String> {
+    values
+        .iter()
+        .map(|val| val.to_string_lossy().to_string())
+        .collect()
+}
+
 /// Parses an argument
 fn parse_arg(tag: Pair<Rule>, cli_args: &TaskArgs) -> DynErrResult<FunResult> {
+    let span = tag.as_span();
     let mut tag_inner = tag.into_inner();
-    let arg_index = tag_inner.next().unwrap().as_str();
-    let real_index: usize = usize::from_str(arg_index).unwrap() - 1;
-    let val: Option<&String> = cli_args.get("*").unwrap().get(real_index);
+    let arg_index = next_pair(&mut tag_inner, span)?.as_str();
+    let real_index = match usize::from_str(arg_index)
+        .ok()
+        .and_then(|i| i.checked_sub(1))
+    {
+        Some(i) => i,
+        None => {
+            return Err(
+                custom_span_error(span, String::from("Positional arguments start at $1")).into(),
+            )
+        }
+    };
+    let val: Option<&OsString> = cli_args.get("*").and_then(|args| args.get(real_index));
     match val {
         None => Ok(FunResult::String(String::from(""))),
-        Some(val) => Ok(FunResult::String(String::from(val))),
+        Some(val) => Ok(FunResult::String(val.to_string_lossy().to_string())),
     }
 }
 
 /// Parses named arguments
 fn parse_kwargs(tag: Pair<Rule>, cli_args: &TaskArgs) -> DynErrResult<FunResult> {
+    let span = tag.as_span();
     let mut tag_inner = tag.into_inner();
-    let arg_name = tag_inner.next().unwrap().as_str();
+    let arg_name = next_pair(&mut tag_inner, span)?.as_str();
     let values = cli_args.get(arg_name);
     match values {
         None => Ok(FunResult::Vec(vec![])),
-        Some(values) => Ok(FunResult::Vec(values.clone())),
+        Some(values) => Ok(FunResult::Vec(to_string_vec(values))),
     }
 }
 
 /// Parses environment variables
 fn parse_env_var(tag: Pair<Rule>, env: &HashMap<String, String>) -> DynErrResult<FunResult> {
+    let span = tag.as_span();
     let mut tag_inner = tag.into_inner();
-    let env_var_name = tag_inner.next().unwrap();
+    let env_var_name = next_pair(&mut tag_inner, span)?;
     let env_var = env.get(env_var_name.as_str());
     match env_var {
         None => Ok(FunResult::String(String::from(""))),
@@ -399,12 +782,126 @@ fn parse_env_var(tag: Pair<Rule>, env: &HashMap<String, String>) -> DynErrResult
     }
 }
 
+/// Parses variables declared in config files
+///
+/// # Arguments
+///
+/// * `tag`: Pair of the variable
+/// * `get_var`: Returns the value of the variable with the given name, if any
+///
+/// returns: Result<FunResult, Box<dyn Error, Global>>
+fn parse_var<'a, F>(tag: Pair<Rule>, get_var: F) -> DynErrResult<FunResult>
+where
+    F: Fn(&str) -> Option<&'a String>,
+{
+    let span = tag.as_span();
+    let mut tag_inner = tag.into_inner();
+    let var_name = next_pair(&mut tag_inner, span)?;
+    match get_var(var_name.as_str()) {
+        None => Ok(FunResult::String(String::from(""))),
+        Some(val) => Ok(FunResult::String(val.clone())),
+    }
+}
+
+/// Parses a state directory, creating it if it does not exist
+///
+/// # Arguments
+///
+/// * `tag`: Pair of the state directory
+/// * `state_dirs`: State directories of the project, if known
+///
+/// returns: Result<FunResult, Box<dyn Error, Global>>
+fn parse_state_dir(tag: Pair<Rule>, state_dirs: Option<&StateDirs>) -> DynErrResult<FunResult> {
+    let span = tag.as_span();
+    let mut tag_inner = tag.into_inner();
+    let name = next_pair(&mut tag_inner, span)?;
+    let state_dirs = match state_dirs {
+        Some(state_dirs) => state_dirs,
+        None => {
+            return Err(custom_span_error(
+                name.as_span(),
+                String::from("State directories are not available"),
+            )
+            .into())
+        }
+    };
+    match state_dirs.get(name.as_str()) {
+        Ok(dir) => Ok(FunResult::String(dir.to_string_lossy().to_string())),
+        Err(e) => Err(custom_span_error(name.as_span(), e.to_string()).into()),
+    }
+}
+
+/// Parses a field of the invocation metadata
+///
+/// # Arguments
+///
+/// * `tag`: Pair of the invocation field
+/// * `invocation`: Metadata about how yamis was invoked, if known
+///
+/// returns: Result<FunResult, Box<dyn Error, Global>>
+fn parse_invocation(tag: Pair<Rule>, invocation: Option<&Invocation>) -> DynErrResult<FunResult> {
+    let span = tag.as_span();
+    let mut tag_inner = tag.into_inner();
+    let field = next_pair(&mut tag_inner, span)?;
+    let default_invocation = Invocation::default();
+    let invocation = invocation.unwrap_or(&default_invocation);
+    match field.as_str() {
+        "cwd" => Ok(FunResult::String(invocation.cwd.clone())),
+        "argv" => Ok(FunResult::Vec(invocation.argv.clone())),
+        "dry_run" => Ok(FunResult::String(invocation.dry_run.to_string())),
+        _ => Err(unexpected_rule_error(&field)),
+    }
+}
+
+/// Parses a field of the system information
+///
+/// # Arguments
+///
+/// * `tag`: Pair of the system field
+///
+/// returns: Result<FunResult, Box<dyn Error, Global>>
+fn parse_system(tag: Pair<Rule>) -> DynErrResult<FunResult> {
+    let span = tag.as_span();
+    let mut tag_inner = tag.into_inner();
+    let field = next_pair(&mut tag_inner, span)?;
+    match get_system_field(field.as_str()) {
+        Some(Ok(value)) => Ok(FunResult::String(value)),
+        Some(Err(e)) => Err(custom_span_error(field.as_span(), e).into()),
+        None => Err(unexpected_rule_error(&field)),
+    }
+}
+
+/// Parses a field of the workspace
+///
+/// # Arguments
+///
+/// * `tag`: Pair of the workspace field
+/// * `workspace_members`: Members of the workspace, if the config file declares one
+///
+/// returns: Result<FunResult, Box<dyn Error, Global>>
+fn parse_workspace(
+    tag: Pair<Rule>,
+    workspace_members: Option<&[String]>,
+) -> DynErrResult<FunResult> {
+    let span = tag.as_span();
+    let mut tag_inner = tag.into_inner();
+    let field = next_pair(&mut tag_inner, span)?;
+    match field.as_str() {
+        "members" => Ok(FunResult::Vec(
+            workspace_members
+                .map(<[String]>::to_vec)
+                .unwrap_or_default(),
+        )),
+        _ => Err(unexpected_rule_error(&field)),
+    }
+}
+
 /// Parses the star variable
 fn parse_all(cli_args: &TaskArgs) -> DynErrResult<FunResult> {
     // * is assumed to exist
     match cli_args.get("*") {
         None => Ok(FunResult::Vec(vec![])),
-        Some(v) => Ok(FunResult::Vec(v.clone())),
+        Some(v) => Ok(FunResult::Vec(to_string_vec(v))),
     }
 }
 
@@ -413,11 +910,92 @@ fn parse_tag(
     tag: Pair<Rule>,
     cli_args: &TaskArgs,
     env: &HashMap<String, String>,
+    vars: &TaskVars,
 ) -> DynErrResult<FunResult> {
-    if let Some(tag) = tag.into_inner().next() {
-        return parse_expression(tag, cli_args, env);
+    let span = tag.as_span();
+    let project_path = has_project_path_filter(&tag);
+    let expression = next_pair(&mut tag.into_inner(), span)?;
+    let mut value = parse_expression(expression, cli_args, env, vars)?;
+    if project_path {
+        let invocation_dir = vars.get_invocation_dir();
+        let resolve = |value: String| {
+            resolve_project_path(OsStr::new(&value), &invocation_dir)
+                .to_string_lossy()
+                .into_owned()
+        };
+        value = match value {
+            FunResult::String(value) => FunResult::String(resolve(value)),
+            FunResult::Vec(values) => FunResult::Vec(values.into_iter().map(resolve).collect()),
+        };
+    }
+    vars.trace_tag(span.as_str(), || match &value {
+        FunResult::String(value) => format!("{:?}", value),
+        FunResult::Vec(values) => format!("{:?}", values),
+    });
+    Ok(value)
+}
+
+/// Returns the filters of the tag, in order, i.e. `q` in `{$@|q}`
+fn get_tag_filters<'a>(tag: &Pair<'a, Rule>) -> DynErrResult<Vec<Pair<'a, Rule>>> {
+    let mut filters = Vec::new();
+    for filter in tag
+        .clone()
+        .into_inner()
+        .filter(|pair| pair.as_rule() == Rule::filter)
+    {
+        let span = filter.as_span();
+        filters.push(next_pair(&mut filter.into_inner(), span)?);
+    }
+    Ok(filters)
+}
+
+/// Returns the escape mode set by the filters of the tag, if any, i.e. `{$@|q}`. The last
+/// one wins if several are given.
+fn get_tag_escape_mode(tag: &Pair<Rule>) -> DynErrResult<Option<EscapeMode>> {
+    let mut escape_mode = None;
+    for filter_value in get_tag_filters(tag)? {
+        match filter_value.as_rule() {
+            Rule::quote_filter => escape_mode = Some(EscapeMode::Always),
+            Rule::no_quote_filter => escape_mode = Some(EscapeMode::Never),
+            Rule::project_path_filter => {}
+            _ => return Err(unexpected_rule_error(&filter_value)),
+        }
+    }
+    Ok(escape_mode)
+}
+
+/// Returns whether the tag has the `project_path` filter, i.e. `{$1|project_path}`
+fn has_project_path_filter(tag: &Pair<Rule>) -> bool {
+    tag.clone()
+        .into_inner()
+        .filter(|pair| pair.as_rule() == Rule::filter)
+        .flat_map(|filter| filter.into_inner())
+        .any(|filter_value| filter_value.as_rule() == Rule::project_path_filter)
+}
+
+/// Resolves a relative path against the directory yamis was called from, so that paths given
+/// by the user still point to the same file if the task runs in another directory. Absolute
+/// paths and empty values are returned as they are.
+///
+/// # Arguments
+///
+/// * `value`: Path to resolve
+/// * `invocation_dir`: Directory yamis was called from
+///
+/// returns: OsString
+fn resolve_project_path(value: &OsStr, invocation_dir: &Path) -> OsString {
+    let path = Path::new(value);
+    if value.is_empty() || path.is_absolute() {
+        value.to_os_string()
+    } else {
+        invocation_dir.join(path).into_os_string()
     }
-    unreachable!("tag should have inner values");
+}
+
+/// Returns whether the value has no tags, comments or escaped brackets, in which case it
+/// renders to itself and does not need to be parsed
+fn is_plain_text(value: &str) -> bool {
+    !value.contains(['{', '}'])
 }
 
 /// Parses the script, returning a String
@@ -427,6 +1005,11 @@ fn parse_tag(
 /// * `script`: Script to parse
 /// * `args`: cli arguments
 /// * `env`: env variables
+/// * `vars`: variables declared in config files
+/// * `escape_mode`: whether to quote the values of the tags
+/// * `shell_kind`: shell that will run the script, used to escape the values of the tags
+/// * `translate_env`: whether to translate the references to environment variables written
+///   for other shells in the literal parts of the script, see [translate_env_refs]
 ///
 /// returns: Result<String, Box<dyn Error, Global>>
 ///
@@ -434,120 +1017,190 @@ pub fn parse_script<S: AsRef<str>>(
     script: S,
     args: &TaskArgs,
     env: &HashMap<String, String>,
+    vars: &TaskVars,
     escape_mode: &EscapeMode,
+    shell_kind: &ShellKind,
+    translate_env: bool,
 ) -> DynErrResult<String> {
+    // Literals are translated at once, as escaped brackets split them, i.e. in `${{HOME}}`
+    let flush_literal = |result: &mut String, literal: &mut String| {
+        if translate_env {
+            result.push_str(&translate_env_refs(literal, shell_kind));
+        } else {
+            result.push_str(literal);
+        }
+        literal.clear();
+    };
+    if is_plain_text(script.as_ref()) {
+        let mut result = String::new();
+        flush_literal(&mut result, &mut String::from(script.as_ref()));
+        return Ok(result);
+    }
     let tokens = ScriptParser::parse(Rule::all, script.as_ref());
 
     let mut result = String::new();
 
     let tokens = match tokens {
-        Ok(mut tokens) => tokens.next().unwrap().into_inner(),
+        Ok(mut tokens) => match tokens.next() {
+            Some(token) => token.into_inner(),
+            None => return Ok(result),
+        },
         Err(e) => return Err(e.renamed_rules(rename_rules).to_string().into()),
     };
 
+    let mut literal_buf = String::new();
     for token in tokens {
         match token.as_rule() {
             Rule::comment => {} // just ignore
             Rule::literal => {
                 for literal in token.into_inner() {
                     match literal.as_rule() {
-                        Rule::esc_ob => result.push('{'),
-                        Rule::esc_cb => result.push('}'),
-                        Rule::literal_content => result.push_str(literal.as_str()),
-                        v => {
-                            unreachable!("Unexpected rule {:?}", v);
-                        }
+                        Rule::esc_ob => literal_buf.push('{'),
+                        Rule::esc_cb => literal_buf.push('}'),
+                        Rule::literal_content => literal_buf.push_str(literal.as_str()),
+                        _ => return Err(unexpected_rule_error(&literal)),
                     }
                 }
             }
             Rule::tag => {
-                let tag_val = parse_tag(token, args, env)?;
+                flush_literal(&mut result, &mut literal_buf);
+                let tag_escape_mode = get_tag_escape_mode(&token)?;
+                let escape_mode = tag_escape_mode.as_ref().unwrap_or(escape_mode);
+                let tag_val = parse_tag(token, args, env, vars)?;
                 match tag_val {
                     FunResult::String(val) => {
                         if !val.is_empty() {
-                            let escape = match escape_mode {
-                                EscapeMode::Always => true,
-                                EscapeMode::Spaces => val.contains(' '),
-                                EscapeMode::Never => false,
-                            };
-                            if escape {
-                                result.push('"');
-                            }
-                            result.push_str(&val);
-                            if escape {
-                                result.push('"');
-                            }
+                            result.push_str(&escape_value(&val, escape_mode, shell_kind));
                         }
                     }
                     FunResult::Vec(values) => {
-                        if !values.is_empty() {
-                            let last_val_index = values.len() - 1;
-                            for (i, val) in values.iter().enumerate() {
-                                let escape = match escape_mode {
-                                    EscapeMode::Always => true,
-                                    EscapeMode::Spaces => val.contains(' '),
-                                    EscapeMode::Never => false,
-                                };
-
-                                if escape {
-                                    result.push('"');
-                                }
-                                result.push_str(val);
-                                if escape {
-                                    result.push('"');
-                                }
-                                if i != last_val_index {
-                                    result.push(' ');
-                                }
-                            }
-                        }
+                        result.push_str(&escape_values(&values, escape_mode, shell_kind));
                     }
                 }
             }
             Rule::EOI => {
+                flush_literal(&mut result, &mut literal_buf);
                 break;
             }
-            v => {
-                unreachable!("Unexpected rule {:?}", v);
-            }
+            _ => return Err(unexpected_rule_error(&token)),
         }
     }
     Ok(result)
 }
 
-/// Parses the param, returning either a string or list of strings
+/// Returns the raw values referenced by the tag, if it only consists of a positional argument,
+/// named argument or `$@`, without slices or functions. This way values that are not valid
+/// unicode, i.e. some file names, can be passed to programs untouched.
+///
+/// Returns None if the tag does not qualify, or the value is empty, in which case the tag
+/// should be parsed as usual.
+///
+/// # Arguments
+///
+/// * `tag`: Tag to get the raw value for
+/// * `cli_args`: cli arguments
+/// * `vars`: variables declared in config files, where the used arguments are recorded
+///
+/// returns: Option<Vec<OsString>>
+fn get_raw_tag_value(
+    tag: &Pair<Rule>,
+    cli_args: &TaskArgs,
+    vars: &TaskVars,
+) -> Option<Vec<OsString>> {
+    let expression = tag.clone().into_inner().next()?;
+    let mut expression_values = expression.into_inner();
+    let expression_inner = expression_values.next()?;
+    if expression_values.any(|modifier| modifier.as_rule() != Rule::optional) {
+        return None;
+    }
+    let value = expression_inner.into_inner().next()?;
+    vars.record_used_arg(&value);
+    let values = match value.as_rule() {
+        Rule::arg => {
+            let arg_index = value.into_inner().next()?.as_str();
+            let real_index = usize::from_str(arg_index).ok()?.checked_sub(1)?;
+            let val = cli_args.get("*")?.get(real_index)?;
+            if val.is_empty() {
+                return None;
+            }
+            vec![val.clone()]
+        }
+        Rule::kwarg => {
+            let arg_name = value.into_inner().next()?.as_str();
+            cli_args.get(arg_name)?.clone()
+        }
+        Rule::all_args => cli_args.get("*")?.clone(),
+        _ => return None,
+    };
+    if values.is_empty() {
+        None
+    } else if has_project_path_filter(tag) {
+        let invocation_dir = vars.get_invocation_dir();
+        Some(
+            values
+                .iter()
+                .map(|value| resolve_project_path(value, &invocation_dir))
+                .collect(),
+        )
+    } else {
+        Some(values)
+    }
+}
+
+/// Parses the param, returning the list of values it expands to
 ///
 /// # Arguments
 ///
 /// * `script`: Script to parse
 /// * `args`: cli arguments
 /// * `env`: env variables
+/// * `vars`: variables declared in config files
 ///
-/// returns: Result<String, Box<dyn Error, Global>>
+/// returns: Result<Vec<OsString>, Box<dyn Error, Global>>
 ///
 fn parse_param(
     param: &str,
     args: &TaskArgs,
     env: &HashMap<String, String>,
-) -> DynErrResult<FunResult> {
+    vars: &TaskVars,
+) -> DynErrResult<Vec<OsString>> {
+    if is_plain_text(param) {
+        return match param.is_empty() {
+            true => Ok(vec![]),
+            false => Ok(vec![OsString::from(param)]),
+        };
+    }
     let pairs = ScriptParser::parse(Rule::task_arg, param);
 
     let mut pairs = match pairs {
-        Ok(mut tokens) => tokens.next().unwrap().into_inner(),
+        Ok(mut tokens) => match tokens.next() {
+            Some(token) => token.into_inner(),
+            None => return Ok(vec![]),
+        },
         Err(e) => return Err(e.renamed_rules(rename_rules).to_string().into()),
     };
 
-    match pairs.peek().unwrap().as_rule() {
+    let first = match pairs.peek() {
+        Some(pair) => pair,
+        None => return Ok(vec![]),
+    };
+
+    let result = match first.as_rule() {
         Rule::tag => {
-            let tag = pairs.next().unwrap();
-            let next = pairs.next().unwrap();
-            match next.as_rule() {
-                Rule::EOI => (), // expected
-                v => {
-                    unreachable!("Unexpected rule {:?}", v);
+            let tag = next_pair(&mut pairs, first.as_span())?;
+            if let Some(next) = pairs.next() {
+                if next.as_rule() != Rule::EOI {
+                    return Err(unexpected_rule_error(&next));
                 }
             }
-            parse_tag(tag, args, env)
+            if let Some(values) = get_raw_tag_value(&tag, args, vars) {
+                vars.trace_tag(tag.as_str(), || {
+                    let values: Vec<_> = values.iter().map(|val| val.to_string_lossy()).collect();
+                    format!("{:?}", values)
+                });
+                return Ok(values);
+            }
+            parse_tag(tag, args, env, vars)?
         }
         Rule::literal => {
             let mut buffer = String::new();
@@ -560,17 +1213,28 @@ fn parse_param(
                                 Rule::esc_ob => buffer.push('{'),
                                 Rule::esc_cb => buffer.push('}'),
                                 Rule::literal_content => buffer.push_str(pair.as_str()),
-                                v => unreachable!("Unexpected rule {:?}", v),
+                                _ => return Err(unexpected_rule_error(&pair)),
                             }
                         }
                     }
-                    v => unreachable!("Unexpected rule {:?}", v),
+                    _ => return Err(unexpected_rule_error(&pair)),
                 }
             }
-            Ok(FunResult::String(buffer))
+            FunResult::String(buffer)
+        }
+        Rule::EOI => FunResult::String(String::new()),
+        _ => return Err(unexpected_rule_error(&first)),
+    };
+
+    match result {
+        FunResult::String(val) => {
+            if val.is_empty() {
+                Ok(vec![])
+            } else {
+                Ok(vec![OsString::from(val)])
+            }
         }
-        Rule::EOI => Ok(FunResult::String(String::new())),
-        v => unreachable!("Unexpected rule {:?}", v),
+        FunResult::Vec(values) => Ok(values.into_iter().map(OsString::from).collect()),
     }
 }
 
@@ -581,24 +1245,19 @@ fn parse_param(
 /// * `script`: Script to parse
 /// * `args`: cli arguments
 /// * `env`: env variables
+/// * `vars`: variables declared in config files
 ///
-/// returns: Result<String, Box<dyn Error, Global>>
+/// returns: Result<Vec<OsString>, Box<dyn Error, Global>>
 ///
 pub fn parse_params(
     params: &Vec<String>,
     args: &TaskArgs,
     env: &HashMap<String, String>,
-) -> DynErrResult<Vec<String>> {
+    vars: &TaskVars,
+) -> DynErrResult<Vec<OsString>> {
     let mut result = Vec::with_capacity(params.capacity());
     for param in params {
-        match parse_param(param, args, env)? {
-            FunResult::String(val) => {
-                if !val.is_empty() {
-                    result.push(val)
-                }
-            }
-            FunResult::Vec(values) => result.extend(values),
-        }
+        result.extend(parse_param(param, args, env, vars)?);
     }
     Ok(result)
 }
@@ -606,15 +1265,26 @@ pub fn parse_params(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use std::path::Path;
 
     #[test]
     fn test_parse_script() {
         // TODO: Separate into individual tests
-        let mut vars = HashMap::<String, Vec<String>>::new();
+        let mut vars = TaskArgs::new();
         let mut env = HashMap::new();
 
         let script = "hello {$@?}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(result, "hello ");
 
         env.insert(
@@ -625,28 +1295,46 @@ mod tests {
         vars.insert(
             String::from("*"),
             vec![
-                String::from("positional"),
-                String::from("--key=val1"),
-                String::from("--key=val2"),
-                String::from("spaced value"),
+                OsString::from("positional"),
+                OsString::from("--key=val1"),
+                OsString::from("--key=val2"),
+                OsString::from("spaced value"),
             ],
         );
 
         vars.insert(
             String::from("key"),
-            vec![String::from("val1"), String::from("val2")],
+            vec![OsString::from("val1"), OsString::from("val2")],
         );
 
         let script =
             "Echo {{Hello}} {$@}{hello?} {key} {$1} {$2} {$5?} {$TEST_ENV_VARIABLE} {$TEST_ENV_VARIABLE2?}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Always).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Always,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(
             result,
             "Echo {Hello} \"positional\" \"--key=val1\" \"--key=val2\" \"spaced value\" \"val1\" \"val2\" \"positional\" \"--key=val1\"  \"sample_val\" "
         );
 
         let script = "Echo {{Hello}} {$@}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Spaces).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Spaces,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(
             result,
             "Echo {Hello} positional --key=val1 --key=val2 \"spaced value\""
@@ -654,7 +1342,16 @@ mod tests {
 
         let script = r#"Echo {{map(Hello)}} {map("--f=\"%s.txt\"",key)}"#;
 
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(
             result,
             "Echo {map(Hello)} --f=\"val1.txt\" --f=\"val2.txt\""
@@ -675,11 +1372,29 @@ a = [
 ]
 print("values are:", a)"#;
 
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(result, expected);
 
         let script = "echo {$@[0]} {$@[-2]} {$@[-4:]} {key[:5]}{key[5]?}{key[5:]?}{key[5]?}{$1[15]?}{$1[10:]?}{key[2:0]?}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(
             result,
             "echo positional --key=val2 positional --key=val1 --key=val2 spaced value val1 val2"
@@ -687,47 +1402,142 @@ print("values are:", a)"#;
 
         let script =
             "echo {key[0][0]} {key[:5][0][1]} {key[0][2:3]} {key[0][3:]} {key[0][4]?} {key[:5][10:][1]?} {key[5:0]?} end";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(result, "echo v a l 1    end");
 
         let script = "echo {key[3][0]}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert!(result
             .to_string()
             .ends_with("Index out of bounds for mandatory expression"));
 
         let script = "echo {key[0][10]}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert!(result
             .to_string()
             .ends_with("Index out of bounds for mandatory expression"));
 
         let script = "echo {key[0][-5]}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert!(result
             .to_string()
             .ends_with("Index out of bounds for mandatory expression"));
 
         let script = "echo {key[5:0]}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert!(result
             .to_string()
             .ends_with("Range out of bounds for mandatory expression"));
 
         let script = "echo {key[-10:5]}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert!(result
             .to_string()
             .ends_with("Range out of bounds for mandatory expression"));
     }
 
+    #[test]
+    fn test_parse_script_translate_env() {
+        let vars = TaskArgs::new();
+        let env = HashMap::from([(String::from("NAME"), String::from("$HOME %HOME%"))]);
+
+        // Tags and their values are not translated
+        let script = "cd ${{HOME}} && echo %USER% {$NAME} $env:TMP";
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Cmd,
+            true,
+        )
+        .unwrap();
+        assert_eq!(result, "cd %HOME% && echo %USER% $HOME %HOME% %TMP%");
+
+        let result = parse_script(
+            "echo %USER%",
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            true,
+        )
+        .unwrap();
+        assert_eq!(result, "echo ${USER}");
+    }
+
     #[test]
     fn test_parse_script_errors() {
-        let vars = HashMap::<String, Vec<String>>::new();
+        let vars = TaskArgs::new();
         let env = HashMap::new();
 
         let script = "hello {$";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert_eq!(result.to_string(), " --> 1:9\n  |\n1 | hello {$\n  |         ^---\n  |\n  = expected integer or environment variable name");
 
         // TODO: Test more parsing errors
@@ -736,54 +1546,200 @@ print("values are:", a)"#;
     #[test]
     fn test_parse_escape_spaces() {
         // TODO: Separate into individual tests
-        let mut vars = HashMap::<String, Vec<String>>::new();
+        let mut vars = TaskArgs::new();
         let env = HashMap::new();
 
         vars.insert(
             String::from("*"),
-            vec![String::from("with spaces"), String::from("nospaces")],
+            vec![OsString::from("with spaces"), OsString::from("nospaces")],
         );
 
         let script = "{$@} {key?}end";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Spaces).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Spaces,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(result, "\"with spaces\" nospaces end");
     }
 
     #[test]
     fn test_parse_escape_always() {
         // TODO: Separate into individual tests
-        let mut vars = HashMap::<String, Vec<String>>::new();
+        let mut vars = TaskArgs::new();
         let env = HashMap::new();
 
         vars.insert(
             String::from("*"),
-            vec![String::from("with spaces"), String::from("nospaces")],
+            vec![OsString::from("with spaces"), OsString::from("nospaces")],
         );
 
         let script = "{$@} {key?}end";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Always).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Always,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(result, "\"with spaces\" \"nospaces\" end");
     }
 
     #[test]
     fn test_parse_escape_never() {
         // TODO: Separate into individual tests
-        let mut vars = HashMap::<String, Vec<String>>::new();
+        let mut vars = TaskArgs::new();
         let env = HashMap::new();
 
         vars.insert(
             String::from("*"),
-            vec![String::from("with spaces"), String::from("nospaces")],
+            vec![OsString::from("with spaces"), OsString::from("nospaces")],
         );
 
         let script = "{$@} {key?}end";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
         assert_eq!(result, "with spaces nospaces end");
     }
 
+    #[test]
+    fn test_parse_escape_filters() {
+        let mut vars = TaskArgs::new();
+        let env = HashMap::new();
+
+        vars.insert(
+            String::from("*"),
+            vec![OsString::from("with spaces"), OsString::from("nospaces")],
+        );
+
+        let script = "{$@|q} {$@ | nq} {$1} {$3?|q}end";
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Spaces,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\"with spaces\" \"nospaces\" with spaces nospaces \"with spaces\" end"
+        );
+
+        let script = "{$2|q} {$2}";
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, "\"nospaces\" nospaces");
+
+        let script = "{$2|x}";
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
+        assert!(result
+            .to_string()
+            .ends_with("expected q, nq, or project_path"));
+    }
+
+    #[test]
+    fn test_parse_project_path_filter() {
+        let invocation = Invocation {
+            cwd: String::from("/home/user/project"),
+            ..Invocation::default()
+        };
+        let vars = TaskVars::default().with_invocation(&invocation);
+        let absolute_path = std::env::temp_dir().join("out");
+        let mut args = TaskArgs::new();
+        args.insert(
+            String::from("*"),
+            vec![
+                OsString::from("src/main.rs"),
+                absolute_path.clone().into_os_string(),
+            ],
+        );
+        let env = HashMap::new();
+        let project_dir = Path::new("/home/user/project");
+
+        let result = parse_script(
+            "cat {$1|project_path} {$2 | project_path} {$3?|project_path}end",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            format!(
+                "cat {} {} end",
+                project_dir.join("src/main.rs").display(),
+                absolute_path.display()
+            )
+        );
+
+        let result =
+            parse_params(&vec![String::from("{$@|project_path}")], &args, &env, &vars).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                project_dir.join("src/main.rs").into_os_string(),
+                absolute_path.into_os_string()
+            ]
+        );
+
+        let result = parse_script(
+            "cat {$1|project_path|q}",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            format!("cat \"{}\"", project_dir.join("src/main.rs").display())
+        );
+    }
+
     #[test]
     fn test_parse_params() {
-        let mut vars = HashMap::<String, Vec<String>>::new();
+        let mut vars = TaskArgs::new();
         let mut env = HashMap::new();
 
         env.insert(
@@ -794,15 +1750,15 @@ print("values are:", a)"#;
         vars.insert(
             String::from("*"),
             vec![
-                String::from("positional"),
-                String::from("--key=val1"),
-                String::from("--key=val2"),
+                OsString::from("positional"),
+                OsString::from("--key=val1"),
+                OsString::from("--key=val2"),
             ],
         );
 
         vars.insert(
             String::from("key"),
-            vec![String::from("val1"), String::from("val2")],
+            vec![OsString::from("val1"), OsString::from("val2")],
         );
 
         let params = vec![
@@ -817,8 +1773,13 @@ print("values are:", a)"#;
             "{$TEST_ENV_VARIABLE2?}",
         ];
 
-        let result =
-            parse_params(&params.iter().map(|v| v.to_string()).collect(), &vars, &env).unwrap();
+        let result = parse_params(
+            &params.iter().map(|v| v.to_string()).collect(),
+            &vars,
+            &env,
+            &TaskVars::default(),
+        )
+        .unwrap();
         assert_eq!(
             result,
             vec![
@@ -835,14 +1796,19 @@ print("values are:", a)"#;
             ]
         );
 
-        let params = vec![
+        let params = [
             "Echo",
             "{{map(Hello)}}",
             r#"{ map("--f=\"%s.txt\"", key) }"#,
         ];
 
-        let result =
-            parse_params(&params.iter().map(|v| v.to_string()).collect(), &vars, &env).unwrap();
+        let result = parse_params(
+            &params.iter().map(|v| v.to_string()).collect(),
+            &vars,
+            &env,
+            &TaskVars::default(),
+        )
+        .unwrap();
         assert_eq!(
             result,
             vec![
@@ -853,14 +1819,19 @@ print("values are:", a)"#;
             ]
         );
 
-        let params = vec![
+        let params = [
             "Echo",
             "{{jmap(Hello)}}",
             r#"{ jmap("--f=\"%s.txt\" ", key) }"#,
         ];
 
-        let result =
-            parse_params(&params.iter().map(|v| v.to_string()).collect(), &vars, &env).unwrap();
+        let result = parse_params(
+            &params.iter().map(|v| v.to_string()).collect(),
+            &vars,
+            &env,
+            &TaskVars::default(),
+        )
+        .unwrap();
         assert_eq!(
             result,
             vec![
@@ -873,10 +1844,19 @@ print("values are:", a)"#;
 
     #[test]
     fn test_parse_undef_function() {
-        let vars = HashMap::<String, Vec<String>>::new();
+        let vars = TaskArgs::new();
         let env = HashMap::new();
         let script = "echo {undef_function('hello')}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert!(result
             .to_string()
             .ends_with("Undefined function `undef_function`"));
@@ -884,10 +1864,19 @@ print("values are:", a)"#;
 
     #[test]
     fn test_parse_function_error() {
-        let vars = HashMap::<String, Vec<String>>::new();
+        let vars = TaskArgs::new();
         let env = HashMap::new();
         let script = "echo {fmt('hello %', 'world')}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert!(result
             .to_string()
             .contains("Error running function `fmt`: Invalid format string:"));
@@ -895,11 +1884,20 @@ print("values are:", a)"#;
 
     #[test]
     fn test_parse_function_out_required() {
-        let mut vars = HashMap::<String, Vec<String>>::new();
+        let mut vars = TaskArgs::new();
         vars.insert(String::from("*"), vec![]);
         let env = HashMap::new();
         let script = "echo {fmt('%s', $1?)}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert!(result
             .to_string()
             .contains("Mandatory expression did not return a value"));
@@ -907,14 +1905,442 @@ print("values are:", a)"#;
 
     #[test]
     fn test_parse_int_error() {
-        let mut vars = HashMap::<String, Vec<String>>::new();
+        let mut vars = TaskArgs::new();
         vars.insert(String::from("*"), vec![]);
         let env = HashMap::new();
         // int too big
         let script = "echo {hello[999999999999999999999]}";
-        let result = parse_script(script, &vars, &env, &EscapeMode::Never).unwrap_err();
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
         assert!(result
             .to_string()
             .contains("Error parsing `999999999999999999999` as an integer"));
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parse_params_non_unicode() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let non_unicode = OsString::from_vec(vec![b'f', 0xff, b'o']);
+        let mut vars = TaskArgs::new();
+        let env = HashMap::new();
+        vars.insert(String::from("*"), vec![non_unicode.clone()]);
+        vars.insert(String::from("key"), vec![non_unicode.clone()]);
+
+        let params = ["{$1}", "{key}", "{$@}", "{ $1? }", "{$2?}"];
+        let result = parse_params(
+            &params.iter().map(|v| v.to_string()).collect(),
+            &vars,
+            &env,
+            &TaskVars::default(),
+        )
+        .unwrap();
+        assert_eq!(result, vec![non_unicode.clone(); 4]);
+
+        // Values are no longer raw if they are transformed
+        let params = ["{trim($1)}", "{key[0]}"];
+        let result = parse_params(
+            &params.iter().map(|v| v.to_string()).collect(),
+            &vars,
+            &env,
+            &TaskVars::default(),
+        )
+        .unwrap();
+        assert_eq!(result, vec!["f\u{FFFD}o", "f\u{FFFD}o"]);
+    }
+
+    #[test]
+    fn test_parse_state_dirs() {
+        let state_dirs = StateDirs::new(Path::new("/home/user/project"));
+        let vars = TaskVars::default().with_state_dirs(&state_dirs);
+        let args = TaskArgs::new();
+        let env = HashMap::new();
+
+        let result = parse_script(
+            "{dirs.tmp}",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, state_dirs.get("tmp").unwrap().to_string_lossy());
+        assert!(Path::new(&result).is_dir());
+
+        let err = parse_script(
+            "{dirs.tmp}",
+            &args,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("State directories are not available"));
+    }
+
+    #[test]
+    fn test_parse_workspace() {
+        let members = vec![String::from("app"), String::from("lib")];
+        let vars = TaskVars::default().with_workspace_members(&members);
+        let args = TaskArgs::new();
+        let env = HashMap::new();
+
+        let result = parse_script(
+            "echo {workspace.members} {workspace.members[0]}",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, "echo app lib app");
+
+        // Mandatory unless the config file declares a workspace
+        let result = parse_script(
+            "echo {workspace.members?}",
+            &args,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, "echo ");
+        assert!(parse_script(
+            "echo {workspace.members}",
+            &args,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_invocation() {
+        let invocation = Invocation {
+            cwd: String::from("/home/user"),
+            argv: vec![
+                String::from("yamis"),
+                String::from("hello"),
+                String::from("a b"),
+            ],
+            dry_run: true,
+        };
+        let vars = TaskVars::default().with_invocation(&invocation);
+        let args = TaskArgs::new();
+        let env = HashMap::new();
+
+        let result = parse_script(
+            "echo {invocation.cwd} {invocation.argv[1:]} {invocation.dry_run}",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Always,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, "echo \"/home/user\" \"hello\" \"a b\" \"true\"");
+
+        // Not set when rendering outside of a run
+        let result = parse_script(
+            "echo {invocation.dry_run}",
+            &args,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, "echo false");
+
+        assert!(parse_script(
+            "echo {invocation.other}",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_system() {
+        let args = TaskArgs::new();
+        let env = HashMap::new();
+        let vars = TaskVars::default();
+
+        let result = parse_script(
+            "make -j{system.cpus}",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        let cpus = result.strip_prefix("make -j").unwrap();
+        assert!(cpus.parse::<usize>().unwrap() > 0);
+
+        let result = parse_script(
+            "{system.hostname}",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert!(!result.is_empty());
+
+        #[cfg(unix)]
+        {
+            let result = parse_script(
+                "{system.memory_mb}",
+                &args,
+                &env,
+                &vars,
+                &EscapeMode::Never,
+                &ShellKind::Posix,
+                false,
+            )
+            .unwrap();
+            assert!(result.parse::<u64>().unwrap() > 0);
+        }
+
+        assert!(parse_script(
+            "echo {system.other}",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_vars() {
+        let file_vars = IndexMap::from([
+            (String::from("NAME"), String::from("file")),
+            (String::from("FILE_ONLY"), String::from("file only")),
+        ]);
+        let global_vars = IndexMap::from([
+            (String::from("NAME"), String::from("global")),
+            (String::from("GLOBAL_ONLY"), String::from("global only")),
+        ]);
+        let vars = TaskVars::new(Some(&file_vars), Some(&global_vars));
+        let args = TaskArgs::new();
+        let env = HashMap::new();
+
+        let script = "echo {vars.NAME} {vars.FILE_ONLY} {vars.GLOBAL_ONLY} {file.vars.NAME} {file.vars.GLOBAL_ONLY?}";
+        let result = parse_script(
+            script,
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, "echo file file only global only file ");
+
+        let result = parse_script(
+            "echo {file.vars.GLOBAL_ONLY}",
+            &args,
+            &env,
+            &vars,
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap_err();
+        assert!(result
+            .to_string()
+            .ends_with("Mandatory expression did not return a value"));
+
+        // A named argument called `vars` can still be used
+        let args = TaskArgs::from([(String::from("vars"), vec![OsString::from("arg")])]);
+        let result = parse_params(
+            &vec![String::from("{vars}"), String::from("{vars.NAME}")],
+            &args,
+            &env,
+            &vars,
+        )
+        .unwrap();
+        assert_eq!(result, vec![OsString::from("arg"), OsString::from("file")]);
+
+        // Variables given from the command line take precedence
+        let overrides = IndexMap::from([
+            (String::from("NAME"), String::from("cli")),
+            (String::from("CLI_ONLY"), String::from("cli only")),
+        ]);
+        let vars = vars.with_overrides(&overrides);
+        let result = parse_params(
+            &vec![
+                String::from("{vars.NAME}"),
+                String::from("{file.vars.NAME}"),
+                String::from("{vars.CLI_ONLY}"),
+                String::from("{vars.GLOBAL_ONLY}"),
+            ],
+            &TaskArgs::new(),
+            &env,
+            &vars,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            vec![
+                OsString::from("cli"),
+                OsString::from("cli"),
+                OsString::from("cli only"),
+                OsString::from("global only")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_positional_args() {
+        let mut vars = TaskArgs::new();
+        vars.insert(String::from("*"), vec![OsString::from("hello")]);
+        let env = HashMap::new();
+
+        let result = parse_script(
+            "{$0}",
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Positional arguments start at $1"));
+
+        let result = parse_params(
+            &vec![String::from("{$99999999999999999999999}")],
+            &vars,
+            &env,
+            &TaskVars::default(),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Positional arguments start at $1"));
+
+        let result = parse_script(
+            "{$1?}",
+            &TaskArgs::new(),
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        );
+        assert_eq!(result.unwrap(), "");
+    }
+
+    #[test]
+    fn test_slice_non_ascii() {
+        let vars = TaskArgs::new();
+        let env = HashMap::new();
+        let script = "{'añb'[1]} {'añb'[1:]} {'ñ'[1]?} {'ññ'[-1]}";
+        let result = parse_script(
+            script,
+            &vars,
+            &env,
+            &TaskVars::default(),
+            &EscapeMode::Never,
+            &ShellKind::Posix,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, "ñ ñb  ñ");
+    }
+
+    /// Arguments used to fuzz the parser
+    fn get_fuzz_args() -> TaskArgs {
+        let mut vars = TaskArgs::new();
+        vars.insert(
+            String::from("*"),
+            vec![
+                OsString::from("hello"),
+                OsString::from("ñandú"),
+                OsString::from(""),
+            ],
+        );
+        vars.insert(String::from("a"), vec![OsString::from("a value")]);
+        vars
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_script_does_not_panic(
+            script in r#"[{}$@?:\[\]0-9a-z"'\\|(), /ñ+-]{0,40}"#
+        ) {
+            let vars = get_fuzz_args();
+            let env = HashMap::from([(String::from("HOME"), String::from("/home/ñ"))]);
+            let _ = parse_script(&script, &vars, &env, &TaskVars::default(), &EscapeMode::Spaces, &ShellKind::Cmd, false);
+        }
+
+        #[test]
+        fn test_parse_param_does_not_panic(
+            param in r#"[{}$@?:\[\]0-9a-z"'\\|(), /ñ+-]{0,40}"#
+        ) {
+            let vars = get_fuzz_args();
+            let env = HashMap::new();
+            let _ = parse_param(&param, &vars, &env, &TaskVars::default());
+        }
+
+        #[test]
+        fn test_parse_any_script_does_not_panic(script in r"\PC*") {
+            let vars = get_fuzz_args();
+            let env = HashMap::new();
+            let _ = parse_script(&script, &vars, &env, &TaskVars::default(), &EscapeMode::Always, &ShellKind::Posix, false);
+            let _ = parse_param(&script, &vars, &env, &TaskVars::default());
+        }
+
+        #[test]
+        fn test_plain_text_is_parsed_as_literal(text in r"[^{}]*") {
+            // Plain text skips the parser, so it must be what the parser would return
+            prop_assert!(is_plain_text(&text));
+            let parsed: String = ScriptParser::parse(Rule::all, &text)
+                .unwrap()
+                .flatten()
+                .filter(|pair| pair.as_rule() == Rule::literal_content)
+                .map(|pair| pair.as_str())
+                .collect();
+            prop_assert_eq!(parsed, text);
+        }
+    }
 }
@@ -0,0 +1,79 @@
+//! Information about the machine yamis runs in, available to tasks as `{system.FIELD}`. Each value
+//! is only computed the first time it is used.
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref CPUS: Result<String, String> = get_cpus();
+    static ref MEMORY_MB: Result<String, String> = get_memory_mb();
+    static ref HOSTNAME: Result<String, String> = get_hostname();
+}
+
+/// Returns the value of the given field, or an error if it cannot be read in this machine
+///
+/// # Arguments
+///
+/// * `field`: Name of the field, i.e. `cpus`
+///
+/// returns: Option<Result<String, String>>
+pub(crate) fn get_system_field(field: &str) -> Option<Result<String, String>> {
+    match field {
+        "cpus" => Some(CPUS.clone()),
+        "memory_mb" => Some(MEMORY_MB.clone()),
+        "hostname" => Some(HOSTNAME.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the number of CPUs available to yamis
+fn get_cpus() -> Result<String, String> {
+    match std::thread::available_parallelism() {
+        Ok(cpus) => Ok(cpus.to_string()),
+        Err(e) => Err(format!("Could not get the number of CPUs: {}", e)),
+    }
+}
+
+/// Returns the total physical memory, in megabytes
+#[cfg(unix)]
+fn get_memory_mb() -> Result<String, String> {
+    // sysconf returns -1 for names that are not supported
+    let (pages, page_size) = unsafe {
+        (
+            libc::sysconf(libc::_SC_PHYS_PAGES),
+            libc::sysconf(libc::_SC_PAGESIZE),
+        )
+    };
+    if pages <= 0 || page_size <= 0 {
+        return Err(String::from("Could not get the total memory"));
+    }
+    let bytes = pages as u64 * page_size as u64;
+    Ok((bytes / (1024 * 1024)).to_string())
+}
+
+/// Returns an error, as the total memory is only known in Unix systems
+#[cfg(not(unix))]
+fn get_memory_mb() -> Result<String, String> {
+    Err(String::from("The total memory is not available in this OS"))
+}
+
+/// Returns the hostname of the machine
+#[cfg(unix)]
+fn get_hostname() -> Result<String, String> {
+    let mut buffer = [0u8; 256];
+    // The length given is the length of the buffer, so it does not write past it
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return Err(format!(
+            "Could not get the hostname: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    // The name might not be null terminated if it was truncated
+    let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    Ok(String::from_utf8_lossy(&buffer[..len]).to_string())
+}
+
+/// Returns the hostname of the machine
+#[cfg(not(unix))]
+fn get_hostname() -> Result<String, String> {
+    std::env::var("COMPUTERNAME").map_err(|_| String::from("Could not get the hostname"))
+}
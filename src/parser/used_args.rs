@@ -0,0 +1,150 @@
+//! Arguments used by the templates rendered so far, to find the ones given in the command line
+//! that are not used by a task, i.e. typos like `--relase`.
+use crate::types::TaskArgs;
+use std::collections::HashSet;
+
+/// Arguments referenced by the rendered templates, or declared in `params`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct UsedArgs {
+    /// Whether all the arguments are used, i.e. by `$@`
+    all: bool,
+    /// Positions of the positional arguments used, starting at 1
+    positional: HashSet<usize>,
+    /// Names of the named arguments used or declared
+    named: HashSet<String>,
+}
+
+impl UsedArgs {
+    /// Marks all the arguments as used
+    pub(crate) fn use_all(&mut self) {
+        self.all = true;
+    }
+
+    /// Marks the positional argument at the given position, starting at 1, as used
+    pub(crate) fn use_positional(&mut self, position: usize) {
+        self.positional.insert(position);
+    }
+
+    /// Marks the named argument as used
+    pub(crate) fn use_named(&mut self, name: &str) {
+        if !self.named.contains(name) {
+            self.named.insert(String::from(name));
+        }
+    }
+
+    /// Returns the arguments that were given but not used, as given in the command line. Named
+    /// arguments are used if their name is used, or if their position is used, as `$<n>` refers
+    /// to every argument.
+    ///
+    /// # Arguments
+    ///
+    /// * `args`: Arguments given to the task
+    ///
+    /// returns: Vec<String, Global>
+    pub(crate) fn get_unused_args(&self, args: &TaskArgs) -> Vec<String> {
+        let Some(all_args) = args.get("*") else {
+            return vec![];
+        };
+        if self.all {
+            return vec![];
+        }
+        let mut unused = Vec::new();
+        let mut index = 0;
+        while index < all_args.len() {
+            let arg = all_args[index].to_string_lossy();
+            let position = index + 1;
+            // Named arguments given as `--key value` take the next argument as their value
+            let (name, has_value) = match get_named_arg(&arg, args) {
+                Some((name, has_value)) => (Some(name), has_value),
+                None => (None, true),
+            };
+            let value = (!has_value && index + 1 < all_args.len())
+                .then(|| all_args[index + 1].to_string_lossy());
+            let used = name.is_some_and(|name| self.named.contains(name))
+                || self.positional.contains(&position)
+                || (value.is_some() && self.positional.contains(&(position + 1)));
+            if !used {
+                match &value {
+                    Some(value) => unused.push(format!("{} {}", arg, value)),
+                    None => unused.push(arg.to_string()),
+                }
+            }
+            index += if value.is_some() { 2 } else { 1 };
+        }
+        unused
+    }
+}
+
+/// Returns the name of the named argument the given command line argument sets, if any, and
+/// whether it also has the value, i.e. `--key=value`
+///
+/// # Arguments
+///
+/// * `arg`: Argument given in the command line
+/// * `args`: Arguments given to the task, to check that the name was parsed as a named argument
+///
+/// returns: Option<(&str, bool)>
+fn get_named_arg<'a>(arg: &'a str, args: &TaskArgs) -> Option<(&'a str, bool)> {
+    let key_value = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-'))?;
+    let (name, has_value) = match key_value.split_once('=') {
+        Some((name, _)) => (name, true),
+        None => (key_value, false),
+    };
+    if name == "*" || !args.contains_key(name) {
+        return None;
+    }
+    Some((name, has_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn get_args(all_args: &[&str], named: &[(&str, &[&str])]) -> TaskArgs {
+        let mut args: TaskArgs = named
+            .iter()
+            .map(|(name, values)| {
+                (
+                    String::from(*name),
+                    values.iter().map(OsString::from).collect(),
+                )
+            })
+            .collect();
+        args.insert(
+            String::from("*"),
+            all_args.iter().map(OsString::from).collect(),
+        );
+        args
+    }
+
+    #[test]
+    fn test_get_unused_args() {
+        let args = get_args(
+            &["build", "--relase", "--target", "x86", "--jobs=4", "extra"],
+            &[("relase", &["--target"]), ("jobs", &["4"])],
+        );
+        // `--relase --target` is taken as `relase` with the value `--target`
+        let mut used_args = UsedArgs::default();
+        used_args.use_positional(1);
+        used_args.use_named("jobs");
+        assert_eq!(
+            used_args.get_unused_args(&args),
+            vec!["--relase --target", "x86", "extra"]
+        );
+
+        used_args.use_named("relase");
+        used_args.use_positional(4);
+        assert_eq!(used_args.get_unused_args(&args), vec!["extra"]);
+
+        used_args.use_all();
+        assert!(used_args.get_unused_args(&args).is_empty());
+
+        // Named arguments can also be used by their position
+        let args = get_args(&["--name", "John"], &[("name", &["John"])]);
+        let mut used_args = UsedArgs::default();
+        assert_eq!(used_args.get_unused_args(&args), vec!["--name John"]);
+        used_args.use_positional(2);
+        assert!(used_args.get_unused_args(&args).is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+//! Plugins are executables named `yamis-<name>` found in the PATH. They extend yamis in two ways:
+//!
+//! * As subcommands: `yamis <name> [ARGS]...` runs `yamis-<name> [ARGS]...` if no task is named
+//!   `<name>`, like git and cargo do.
+//! * As functions: `{ plugin("<name>", "<function>", args...) }` runs `yamis-<name> --yamis-function`,
+//!   writes a JSON request with the function and its arguments to its standard input, i.e.
+//!   `{"function": "get", "args": ["token", ["a", "b"]]}`, and reads a JSON response from its
+//!   standard output, either `{"result": "value"}`, `{"result": ["a", "b"]}` or
+//!   `{"error": "message"}`.
+use crate::types::DynErrResult;
+use crate::utils::find_in_path;
+use serde_derive::{Deserialize, Serialize};
+use std::env;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Prefix of the name of the plugin executables
+pub(crate) const PLUGIN_PREFIX: &str = "yamis-";
+
+/// Argument given to plugins to call one of their functions
+const FUNCTION_ARG: &str = "--yamis-function";
+
+/// Value passed to or returned by a plugin function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum PluginValue {
+    /// Single string
+    String(String),
+    /// List of strings
+    List(Vec<String>),
+}
+
+/// Request written to the standard input of a plugin to call one of its functions
+#[derive(Debug, Serialize)]
+struct FunctionRequest<'a> {
+    /// Name of the function
+    function: &'a str,
+    /// Arguments of the function
+    args: &'a [PluginValue],
+}
+
+/// Response of a plugin after calling one of its functions
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum FunctionResponse {
+    /// The function returned a value
+    Result {
+        /// Returned value
+        result: PluginValue,
+    },
+    /// The function failed
+    Error {
+        /// Reason why it failed
+        error: String,
+    },
+}
+
+impl FunctionResponse {
+    /// Returns the value returned by the function, or its error
+    pub(crate) fn into_result(self) -> DynErrResult<PluginValue> {
+        match self {
+            FunctionResponse::Result { result } => Ok(result),
+            FunctionResponse::Error { error } => Err(error.into()),
+        }
+    }
+}
+
+/// Returns the path of the plugin with the given name, if it is in the PATH
+///
+/// # Arguments
+///
+/// * `name`: Name of the plugin, without the `yamis-` prefix
+///
+/// returns: Option<PathBuf>
+pub(crate) fn find_plugin(name: &str) -> Option<PathBuf> {
+    // Names with separators would point outside the PATH
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    let path_var = env::var_os("PATH")?;
+    find_in_path(&format!("{}{}", PLUGIN_PREFIX, name), &path_var)
+}
+
+/// Runs the plugin as a subcommand, with the given arguments and the standard streams of yamis
+///
+/// # Arguments
+///
+/// * `path`: Path of the plugin
+/// * `args`: Arguments given after the name of the subcommand
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+pub(crate) fn run_plugin(path: &Path, args: &[OsString]) -> DynErrResult<()> {
+    let status = match Command::new(path).args(args).status() {
+        Ok(status) => status,
+        Err(e) => return Err(format!("Could not run the plugin {}: {}", path.display(), e).into()),
+    };
+    match status.code() {
+        Some(0) => Ok(()),
+        Some(code) => Err(format!(
+            "Plugin {} terminated with exit code {}",
+            path.display(),
+            code
+        )
+        .into()),
+        None => Err(format!("Plugin {} terminated by a signal", path.display()).into()),
+    }
+}
+
+/// Calls a function of the plugin with the given name, returning its result
+///
+/// # Arguments
+///
+/// * `name`: Name of the plugin, without the `yamis-` prefix
+/// * `function`: Name of the function
+/// * `args`: Arguments of the function
+///
+/// returns: Result<PluginValue, Box<dyn Error, Global>>
+pub(crate) fn call_plugin_function(
+    name: &str,
+    function: &str,
+    args: &[PluginValue],
+) -> DynErrResult<PluginValue> {
+    let Some(path) = find_plugin(name) else {
+        return Err(format!("Plugin {}{} not found in the PATH", PLUGIN_PREFIX, name).into());
+    };
+    let mut child = Command::new(&path)
+        .arg(FUNCTION_ARG)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    let request = serde_json::to_string(&FunctionRequest { function, args })?;
+    {
+        let mut stdin = child.stdin.take().unwrap();
+        // The plugin might exit without reading the request, which is reported below
+        let _ = writeln!(stdin, "{}", request);
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "Plugin {} failed when calling `{}`",
+            path.display(),
+            function
+        )
+        .into());
+    }
+    match serde_json::from_slice::<FunctionResponse>(&output.stdout) {
+        Ok(response) => response.into_result(),
+        Err(e) => Err(format!(
+            "Invalid response from plugin {} when calling `{}`: {}",
+            path.display(),
+            function,
+            e
+        )
+        .into()),
+    }
+}
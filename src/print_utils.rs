@@ -1,10 +1,118 @@
 use colored::{Color, ColoredString, Colorize};
+use serde::Deserializer;
+use serde_derive::Deserialize;
+use std::sync::OnceLock;
 
 const PREFIX: &str = "[YAMIS]";
 const INFO_COLOR: Color = Color::BrightBlue;
 const WARN_COLOR: Color = Color::BrightYellow;
 const ERROR_COLOR: Color = Color::BrightRed;
 
+/// Theme used for the output of yamis, set once at startup
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// User-facing messages, which can be replaced, i.e. to localize them
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Messages {
+    /// Notice displayed when the commands are printed instead of being run
+    pub dry_run: String,
+    /// Header of the errors raised while running a task
+    pub error_running: String,
+    /// Header of the errors raised because a task is not properly configured
+    pub improperly_configured: String,
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Messages {
+            dry_run: String::from("Dry run, the commands are printed instead of being run"),
+            error_running: String::from("Error running"),
+            improperly_configured: String::from("Improperly configured"),
+        }
+    }
+}
+
+/// Prefix, colors and messages used for the output of yamis
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Prefix added to each line of the output of yamis
+    pub prefix: String,
+    /// Color of informative messages
+    #[serde(deserialize_with = "deserialize_color")]
+    pub info_color: Color,
+    /// Color of warnings
+    #[serde(deserialize_with = "deserialize_color")]
+    pub warn_color: Color,
+    /// Color of errors
+    #[serde(deserialize_with = "deserialize_color")]
+    pub error_color: Color,
+    /// User-facing messages
+    pub messages: Messages,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            prefix: String::from(PREFIX),
+            info_color: INFO_COLOR,
+            warn_color: WARN_COLOR,
+            error_color: ERROR_COLOR,
+            messages: Messages::default(),
+        }
+    }
+}
+
+/// Deserializes a color by its name, i.e. `red` or `bright blue`
+fn deserialize_color<'de, D>(deserializer: D) -> Result<Color, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name: String = serde::Deserialize::deserialize(deserializer)?;
+    name.parse()
+        .map_err(|_| serde::de::Error::custom(format!("unknown color `{}`", name)))
+}
+
+impl Theme {
+    /// Returns the given string with the prefix in each line. The prefix will also take the
+    /// given color.
+    fn prefix_lines<S: Into<Color> + Clone>(&self, text: &str, color: S) -> String {
+        let prefix = self.prefix.color(color).to_string();
+
+        let mut result = String::new();
+        for line in text.split_inclusive('\n') {
+            result.push_str(&prefix);
+            result.push(' ');
+            result.push_str(line);
+        }
+        result
+    }
+
+    /// Returns the given string with the prefix in each line. The whole string will have the
+    /// given color.
+    fn colorize_lines<S: Into<Color> + Clone>(&self, text: &str, color: S) -> String {
+        let mut result = String::new();
+        for line in text.split_inclusive('\n') {
+            result.push_str(&self.prefix);
+            result.push(' ');
+            result.push_str(line);
+        }
+        result.color(color).to_string()
+    }
+}
+
+/// Sets the theme used for the output of yamis. It can only be set once, before any output is
+/// printed, so later calls are ignored.
+pub fn set_theme(theme: Theme) {
+    THEME.set(theme).unwrap_or(());
+}
+
+/// Returns the theme used for the output of yamis
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
 pub trait YamisOutput {
     /// Returns the given string with the `[YAMIS]` prefix in each line. The prefix will also take the given color.
     fn yamis_prefix<S: Into<Color> + Clone>(&self, color: S) -> String;
@@ -26,52 +134,35 @@ pub trait YamisOutput {
 
 impl YamisOutput for str {
     fn yamis_prefix<S: Into<Color> + Clone>(&self, color: S) -> String {
-        let lines = self.split_inclusive('\n');
-        let prefix = PREFIX.color(color).to_string();
-
-        let mut result = String::new();
-        for line in lines {
-            result.push_str(&prefix);
-            result.push(' ');
-            result.push_str(line);
-        }
-        result
+        theme().prefix_lines(self, color)
     }
 
     fn yamis_colorize<S: Into<Color> + Clone>(&self, color: S) -> String {
-        let lines = self.split_inclusive('\n');
-
-        let mut result = String::new();
-        for line in lines {
-            result.push_str(PREFIX);
-            result.push(' ');
-            result.push_str(line);
-        }
-        result.color(color).to_string()
+        theme().colorize_lines(self, color)
     }
 
     fn yamis_info(&self) -> String {
-        self.yamis_colorize(INFO_COLOR)
+        self.yamis_colorize(theme().info_color)
     }
 
     fn yamis_prefix_info(&self) -> String {
-        self.yamis_prefix(INFO_COLOR)
+        self.yamis_prefix(theme().info_color)
     }
 
     fn yamis_warn(&self) -> String {
-        self.yamis_colorize(WARN_COLOR)
+        self.yamis_colorize(theme().warn_color)
     }
 
     fn yamis_prefix_warn(&self) -> String {
-        self.yamis_prefix(WARN_COLOR)
+        self.yamis_prefix(theme().warn_color)
     }
 
     fn yamis_error(&self) -> String {
-        self.yamis_colorize(ERROR_COLOR)
+        self.yamis_colorize(theme().error_color)
     }
 
     fn yamis_prefix_error(&self) -> String {
-        self.yamis_prefix(ERROR_COLOR)
+        self.yamis_prefix(theme().error_color)
     }
 }
 
@@ -111,6 +202,77 @@ impl YamisOutput for ColoredString {
     }
 }
 
+/// Returns the given Markdown-like text colored for the terminal. Lines starting with `- ` or
+/// `* ` are displayed as bullets, and code spans, i.e. `` `make` ``, are highlighted. Other
+/// formatting is left as it is.
+///
+/// # Arguments
+///
+/// * `text`: Text to format, i.e. the help of a task
+///
+/// returns: String
+pub(crate) fn format_markdown(text: &str) -> String {
+    text.lines()
+        .map(format_markdown_line)
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Returns a single line of Markdown-like text colored for the terminal
+fn format_markdown_line(line: &str) -> String {
+    let content = line.trim_start();
+    let mut result = String::from(&line[..line.len() - content.len()]);
+    let content = match content
+        .strip_prefix("- ")
+        .or_else(|| content.strip_prefix("* "))
+    {
+        Some(content) => {
+            result.push_str(&format!("{} ", "•".cyan()));
+            content
+        }
+        None => content,
+    };
+    let parts: Vec<&str> = content.split('`').collect();
+    // Code spans are between two backticks, so an unclosed one is left as it is
+    let last_closed = if parts.len() % 2 == 1 {
+        parts.len()
+    } else {
+        parts.len() - 1
+    };
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        if i >= last_closed {
+            result.push_str(&format!("`{}", part).green().to_string());
+        } else if i % 2 == 1 {
+            result.push_str(&part.yellow().to_string());
+        } else {
+            result.push_str(&part.green().to_string());
+        }
+    }
+    result
+}
+
+#[test]
+fn test_format_markdown() {
+    let text = "Builds the `release` binary\n\n- Runs `cargo`\n  * Nested\nUnclosed `code";
+    let expected = [
+        format!(
+            "{}{}{}",
+            "Builds the ".green(),
+            "release".yellow(),
+            " binary".green()
+        ),
+        String::new(),
+        format!("{} {}{}", "•".cyan(), "Runs ".green(), "cargo".yellow()),
+        format!("  {} {}", "•".cyan(), "Nested".green()),
+        format!("{}{}", "Unclosed ".green(), "`code".green()),
+    ]
+    .join("\n");
+    assert_eq!(format_markdown(text), expected);
+}
+
 #[test]
 fn test_yamis_prefix() {
     let info_prefix = PREFIX.color(INFO_COLOR);
@@ -166,3 +328,37 @@ fn test_yamis_prefix() {
     let expected_output = "";
     assert_eq!(colored_output, expected_output);
 }
+
+#[test]
+fn test_theme() {
+    let theme: Theme = serde_yaml::from_str(
+        r#"
+prefix: "[tasks]"
+error_color: bright magenta
+messages:
+  error_running: Fehler beim Ausführen von
+"#,
+    )
+    .unwrap();
+    assert_eq!(theme.info_color, INFO_COLOR);
+    assert_eq!(theme.error_color, Color::BrightMagenta);
+    assert_eq!(theme.messages.error_running, "Fehler beim Ausführen von");
+    assert_eq!(
+        theme.messages.improperly_configured,
+        Messages::default().improperly_configured
+    );
+
+    let prefix = "[tasks]".color(Color::Red);
+    assert_eq!(
+        theme.prefix_lines("first\nsecond", Color::Red),
+        format!("{prefix} first\n{prefix} second")
+    );
+    assert_eq!(
+        theme.colorize_lines("first\n", Color::Red),
+        "[tasks] first\n".color(Color::Red).to_string()
+    );
+
+    let err = serde_yaml::from_str::<Theme>("info_color: light blue").unwrap_err();
+    assert!(err.to_string().contains("unknown color `light blue`"));
+    assert!(serde_yaml::from_str::<Theme>("colour: red").is_err());
+}
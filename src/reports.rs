@@ -0,0 +1,205 @@
+//! Reports of the runs, sent once they finish to the endpoint or written to the directory set in
+//! the `report` section of the settings, so that organizations can collect metrics of the tasks
+//! run by their developers.
+use crate::parser::get_system_field;
+use crate::status::{RunStatus, StepStatus};
+use crate::types::DynErrResult;
+use crate::utils::deserialize_optional_duration;
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Time to wait for the endpoint to respond, unless given in the settings
+#[cfg_attr(not(feature = "reports"), allow(dead_code))]
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the reports are sent, set in the `report` section of the settings
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ReportSettings {
+    /// Endpoint the reports are POSTed to as JSON, if any
+    url: Option<String>,
+    /// Directory the reports are written to, one JSON file per run, if any
+    dir: Option<String>,
+    /// Time to wait for the endpoint to respond
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    timeout: Option<Duration>,
+}
+
+/// Command of a run, as reported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct StepReport {
+    /// Name of the task the command was rendered from
+    task: String,
+    /// Seconds since the Unix epoch when the command started
+    started_at: u64,
+    /// Seconds the command took
+    duration_secs: u64,
+    /// Exit code of the command, if it finished with one
+    exit_code: Option<i32>,
+    /// Whether the command succeeded
+    success: bool,
+}
+
+/// Summary of a finished run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RunReport {
+    /// Name of the task given in the command line
+    task: String,
+    /// Hostname of the machine the task ran in
+    host: String,
+    /// Version of yamis
+    yamis_version: String,
+    /// Seconds since the Unix epoch when the run started
+    started_at: u64,
+    /// Seconds the run took
+    duration_secs: u64,
+    /// Whether the run succeeded
+    success: bool,
+    /// Commands of the run, in order
+    steps: Vec<StepReport>,
+}
+
+impl StepReport {
+    /// Returns the report of a finished command
+    fn new(step: &StepStatus) -> Self {
+        StepReport {
+            task: step.task.clone(),
+            started_at: step.started_at,
+            duration_secs: step
+                .finished_at
+                .unwrap_or(step.started_at)
+                .saturating_sub(step.started_at),
+            exit_code: step.exit_code,
+            success: step.success.unwrap_or(false),
+        }
+    }
+}
+
+impl RunReport {
+    /// Returns the report of a finished run
+    pub(crate) fn new(status: &RunStatus) -> Self {
+        RunReport {
+            task: status.task.clone(),
+            host: get_system_field("hostname")
+                .and_then(Result::ok)
+                .unwrap_or_default(),
+            yamis_version: String::from(env!("CARGO_PKG_VERSION")),
+            started_at: status.started_at,
+            duration_secs: status
+                .finished_at
+                .unwrap_or(status.started_at)
+                .saturating_sub(status.started_at),
+            success: status.success.unwrap_or(false),
+            steps: status.finished_steps.iter().map(StepReport::new).collect(),
+        }
+    }
+
+    /// Writes the report to the directory, in a file named after the start time of the run and
+    /// the pid of yamis, so that runs from different processes do not overwrite each other
+    fn write_to_dir(&self, dir: &str, pid: u32) -> DynErrResult<()> {
+        let dir = PathBuf::from(shellexpand::tilde(dir).as_ref());
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}-{}.json", self.started_at, pid));
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// POSTs the report to the endpoint as JSON
+    #[cfg(feature = "reports")]
+    fn send(&self, url: &str, timeout: Duration) -> DynErrResult<()> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .build()?;
+        client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(self)?)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Reports can only be sent with the `reports` feature
+    #[cfg(not(feature = "reports"))]
+    fn send(&self, _url: &str, _timeout: Duration) -> DynErrResult<()> {
+        Err("yamis was built without the `reports` feature, so reports cannot be sent".into())
+    }
+}
+
+impl ReportSettings {
+    /// Writes and sends the report of the finished run, as set in the settings
+    ///
+    /// # Arguments
+    ///
+    /// * `status`: Status of the finished run
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    pub(crate) fn report(&self, status: &RunStatus) -> DynErrResult<()> {
+        let report = RunReport::new(status);
+        if let Some(dir) = &self.dir {
+            report
+                .write_to_dir(dir, status.pid)
+                .map_err(|e| format!("Could not write the report to {}: {}", dir, e))?;
+        }
+        if let Some(url) = &self.url {
+            report
+                .send(url, self.timeout.unwrap_or(DEFAULT_TIMEOUT))
+                .map_err(|e| format!("Could not send the report to {}: {}", url, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_report_to_dir() {
+        let tmp_dir = TempDir::new().unwrap();
+        let settings: ReportSettings = serde_yaml::from_str(&format!(
+            "dir: {}",
+            tmp_dir.path().join("reports").display()
+        ))
+        .unwrap();
+        let status = RunStatus {
+            task: String::from("deploy"),
+            pid: 42,
+            started_at: 100,
+            finished_at: Some(130),
+            success: Some(true),
+            current_step: None,
+            finished_steps: vec![StepStatus {
+                task: String::from("build"),
+                started_at: 100,
+                finished_at: Some(120),
+                exit_code: Some(0),
+                success: Some(true),
+            }],
+        };
+        settings.report(&status).unwrap();
+
+        let path = tmp_dir.path().join("reports").join("100-42.json");
+        let report: RunReport = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(report.task, "deploy");
+        assert_eq!(report.duration_secs, 30);
+        assert!(report.success);
+        assert_eq!(
+            report.steps,
+            vec![StepReport {
+                task: String::from("build"),
+                started_at: 100,
+                duration_secs: 20,
+                exit_code: Some(0),
+                success: true,
+            }]
+        );
+
+        assert!(serde_yaml::from_str::<ReportSettings>("path: reports").is_err());
+    }
+}
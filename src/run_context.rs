@@ -0,0 +1,141 @@
+//! State of a single run of a task, i.e. the overrides given from the command line and the
+//! tasks that already ran in it.
+//!
+//! Config files are cached and shared between threads, so this state is kept apart from them.
+//! That way several runs of the tasks of the same config file, i.e. one per environment when
+//! fanning out, can happen at the same time without seeing the state of each other.
+use crate::parser::{Invocation, UsedArgs};
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// State of a single run of a task
+#[derive(Debug)]
+pub struct RunContext {
+    /// Metadata about how yamis was invoked, available to the tasks
+    pub(crate) invocation: Invocation,
+    /// Environment variables given from the command line, which take precedence over the
+    /// ones of the config file and tasks
+    pub(crate) env_overrides: HashMap<String, String>,
+    /// Variables given from the command line, which take precedence over the ones of the
+    /// config files
+    pub(crate) var_overrides: IndexMap<String, String>,
+    /// Whether to print each tag of the templates and the value it rendered to, set with
+    /// `--trace-templates`
+    pub(crate) trace_templates: bool,
+    /// Tasks that ran a command so far, identified by their name and inputs, so that tasks
+    /// referenced multiple times only run once with the same inputs
+    executed_commands: Mutex<HashSet<String>>,
+    /// Tasks that ran as dependencies so far, identified by their name and inputs, so that
+    /// they run only once with the same inputs
+    executed_deps: Mutex<HashSet<String>>,
+    /// Arguments used by the templates rendered so far, to find the ones that are not used
+    pub(crate) used_args: Mutex<UsedArgs>,
+    /// Lock files held by the run, so that serial tasks can use the same lock as the task that
+    /// runs them
+    pub(crate) held_locks: Mutex<HashSet<PathBuf>>,
+}
+
+impl Default for RunContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RunContext {
+    /// Returns the context of a new run, without overrides
+    pub fn new() -> Self {
+        RunContext {
+            invocation: Invocation::from_env(),
+            env_overrides: HashMap::new(),
+            var_overrides: IndexMap::new(),
+            trace_templates: false,
+            executed_commands: Mutex::new(HashSet::new()),
+            executed_deps: Mutex::new(HashSet::new()),
+            used_args: Mutex::new(UsedArgs::default()),
+            held_locks: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Returns the same context, but for a run where the commands are printed instead of run
+    pub(crate) fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.invocation.dry_run = dry_run;
+        self
+    }
+
+    /// Returns the same context, but with the given environment variables taking precedence
+    /// over the ones of the config file and tasks
+    pub(crate) fn with_env_overrides(self, env_overrides: HashMap<String, String>) -> Self {
+        RunContext {
+            env_overrides,
+            ..self
+        }
+    }
+
+    /// Returns the same context, but with the given variables taking precedence over the ones
+    /// of the config files
+    pub(crate) fn with_var_overrides(self, var_overrides: IndexMap<String, String>) -> Self {
+        RunContext {
+            var_overrides,
+            ..self
+        }
+    }
+
+    /// Returns the same context, but printing each tag of the templates and the value it
+    /// rendered to if `trace_templates` is true
+    pub(crate) fn with_trace_templates(self, trace_templates: bool) -> Self {
+        RunContext {
+            trace_templates,
+            ..self
+        }
+    }
+
+    /// Returns the same context, but with the given dependencies already executed, i.e. by the
+    /// parent yamis invocation
+    pub(crate) fn with_executed_deps(self, executed_deps: Vec<String>) -> Self {
+        RunContext {
+            executed_deps: Mutex::new(executed_deps.into_iter().collect()),
+            ..self
+        }
+    }
+
+    /// Records that the given task ran its command. Distinct tasks with the same command run
+    /// each, as they are identified by their name.
+    ///
+    /// # Arguments
+    ///
+    /// * `identity`: Name of the task that is about to run, followed by a checksum of its
+    ///   rendered command, which includes its program, arguments, script, environment
+    ///   variables and working directory
+    ///
+    /// returns: bool, whether the task had not run with those inputs before
+    pub(crate) fn mark_executed(&self, identity: &str) -> bool {
+        self.executed_commands
+            .lock()
+            .unwrap()
+            .insert(String::from(identity))
+    }
+
+    /// Records that the given task ran as a dependency of another task.
+    ///
+    /// # Arguments
+    ///
+    /// * `identity`: Name of the task that is about to run, followed by a checksum of its
+    ///   inputs, so that it runs again if it is given other inputs
+    ///
+    /// returns: bool, whether the task had not run as a dependency with those inputs before
+    pub(crate) fn mark_dep_executed(&self, identity: &str) -> bool {
+        self.executed_deps
+            .lock()
+            .unwrap()
+            .insert(String::from(identity))
+    }
+
+    /// Returns the identities of the tasks that ran as dependencies so far, sorted
+    pub(crate) fn get_executed_deps(&self) -> Vec<String> {
+        let mut deps: Vec<String> = self.executed_deps.lock().unwrap().iter().cloned().collect();
+        deps.sort();
+        deps
+    }
+}
@@ -0,0 +1,569 @@
+//! Tracks the tasks being run, so that they can be listed with `yamis ps` and stopped with
+//! `yamis kill` from another terminal.
+//!
+//! Each run writes a state file, named after the id of the run, in the runs directory. The file
+//! is removed when the run finishes. Files left by runs that did not finish cleanly are removed
+//! when listing the runs.
+use crate::types::DynErrResult;
+use crate::utils::since_epoch;
+use directories::ProjectDirs;
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Extension of the state files
+const STATE_FILE_EXTENSION: &str = "json";
+
+/// State of a run, as stored in its state file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RunState {
+    /// Id of the run, which is the pid of the yamis process, followed by the number of the run
+    /// for processes that run several tasks at the same time, i.e. `1234-2`
+    pub id: String,
+    /// Pid of the yamis process
+    pub process: u32,
+    /// Pid of the process currently run by the task, if any
+    pub pid: Option<u32>,
+    /// Name of the task
+    pub task: String,
+    /// Directory yamis was called from
+    pub dir: PathBuf,
+    /// Seconds since the Unix epoch when the run started
+    pub started: u64,
+    /// Config file the task was declared in, known once the task runs its first command
+    #[serde(default)]
+    pub config_file: Option<PathBuf>,
+}
+
+impl RunState {
+    /// Returns the seconds elapsed since the run started
+    pub fn elapsed(&self) -> u64 {
+        since_epoch().as_secs().saturating_sub(self.started)
+    }
+}
+
+/// Returns the default directory of the state files
+pub fn default_runs_dir() -> DynErrResult<PathBuf> {
+    match ProjectDirs::from("", "", "yamis") {
+        Some(proj_dirs) => Ok(proj_dirs.cache_dir().join("runs")),
+        None => Err("Could not find the directory to track runs".into()),
+    }
+}
+
+/// Returns the path of the state file of the given run
+fn state_file_path(runs_dir: &Path, id: &str) -> PathBuf {
+    runs_dir.join(format!("{}.{}", id, STATE_FILE_EXTENSION))
+}
+
+lazy_static! {
+    /// Number of runs of each task, by name and config file, in progress in this process
+    static ref PROCESS_RUNS: Mutex<HashMap<(String, PathBuf), usize>> = Mutex::new(HashMap::new());
+}
+
+/// Keeps the state file of a run up to date, removing it when dropped
+#[derive(Debug)]
+pub struct RunTracker {
+    /// Path of the state file
+    path: PathBuf,
+    /// Current state of the run
+    state: RunState,
+}
+
+impl RunTracker {
+    /// Starts tracking a run of the current process, writing its state file.
+    ///
+    /// # Arguments
+    ///
+    /// * `runs_dir`: Directory of the state files, created if it does not exist
+    /// * `task`: Name of the task being run
+    /// * `dir`: Directory yamis was called from
+    ///
+    /// returns: Result<RunTracker, Box<dyn Error, Global>>
+    pub fn start(runs_dir: &Path, task: &str, dir: &Path) -> DynErrResult<RunTracker> {
+        let id = std::process::id().to_string();
+        RunTracker::start_with_id(runs_dir, id, task, dir)
+    }
+
+    /// Same as [RunTracker::start], but for processes that run several tasks at the same time,
+    /// i.e. the daemon, which tell their runs apart by number. The number is part of the id of
+    /// the run.
+    ///
+    /// # Arguments
+    ///
+    /// * `runs_dir`: Directory of the state files, created if it does not exist
+    /// * `task`: Name of the task being run
+    /// * `dir`: Directory the task runs from
+    /// * `number`: Number of the run within the process
+    ///
+    /// returns: Result<RunTracker, Box<dyn Error, Global>>
+    #[cfg(all(feature = "daemon", unix))]
+    pub(crate) fn start_numbered(
+        runs_dir: &Path,
+        task: &str,
+        dir: &Path,
+        number: u64,
+    ) -> DynErrResult<RunTracker> {
+        let id = format!("{}-{}", std::process::id(), number);
+        RunTracker::start_with_id(runs_dir, id, task, dir)
+    }
+
+    /// Starts tracking a run of the current process with the given id
+    fn start_with_id(
+        runs_dir: &Path,
+        id: String,
+        task: &str,
+        dir: &Path,
+    ) -> DynErrResult<RunTracker> {
+        fs::create_dir_all(runs_dir)?;
+        let tracker = RunTracker {
+            path: state_file_path(runs_dir, &id),
+            state: RunState {
+                id,
+                process: std::process::id(),
+                pid: None,
+                task: String::from(task),
+                dir: dir.to_path_buf(),
+                started: since_epoch().as_secs(),
+                config_file: None,
+            },
+        };
+        tracker.write()?;
+        Ok(tracker)
+    }
+
+    /// Returns the current state of the run
+    pub fn state(&self) -> &RunState {
+        &self.state
+    }
+
+    /// Sets the pid of the process currently run by the task
+    pub fn set_pid(&mut self, pid: Option<u32>) -> DynErrResult<()> {
+        self.state.pid = pid;
+        self.write()
+    }
+
+    /// Sets the config file the task was declared in. The state file is only written if it
+    /// changed.
+    pub fn set_config_file(&mut self, config_file: &Path) -> DynErrResult<()> {
+        if self.state.config_file.as_deref() == Some(config_file) {
+            return Ok(());
+        }
+        self.state.config_file = Some(config_file.to_path_buf());
+        self.write()
+    }
+
+    /// Writes the state file
+    fn write(&self) -> DynErrResult<()> {
+        fs::write(&self.path, serde_json::to_string(&self.state)?)?;
+        Ok(())
+    }
+}
+
+impl Drop for RunTracker {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Returns the runs in progress, sorted by the time they started. State files of runs whose
+/// process is no longer alive are removed.
+///
+/// # Arguments
+///
+/// * `runs_dir`: Directory of the state files
+///
+/// returns: Result<Vec<RunState, Global>, Box<dyn Error, Global>>
+pub fn list_runs(runs_dir: &Path) -> DynErrResult<Vec<RunState>> {
+    let mut runs = Vec::new();
+    let entries = match fs::read_dir(runs_dir) {
+        Ok(entries) => entries,
+        // Nothing has run yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(runs),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(STATE_FILE_EXTENSION) {
+            continue;
+        }
+        // The file might be removed or being written at the same time
+        let state = match fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<RunState>(&contents).ok())
+        {
+            Some(state) => state,
+            None => continue,
+        };
+        if is_process_alive(state.process) {
+            runs.push(state);
+        } else {
+            let _ = fs::remove_file(&path);
+        }
+    }
+    runs.sort_by(|a, b| (a.started, &a.id).cmp(&(b.started, &b.id)));
+    Ok(runs)
+}
+
+/// Returns a run of the given task, declared in the given config file, by another yamis process
+///
+/// # Arguments
+///
+/// * `runs_dir`: Directory of the state files
+/// * `task`: Name of the task
+/// * `config_file`: Config file the task was declared in
+///
+/// returns: Result<Option<RunState>, Box<dyn Error, Global>>
+pub fn find_other_run(
+    runs_dir: &Path,
+    task: &str,
+    config_file: &Path,
+) -> DynErrResult<Option<RunState>> {
+    let process = std::process::id();
+    Ok(list_runs(runs_dir)?.into_iter().find(|run| {
+        run.process != process
+            && run.task == task
+            && run.config_file.as_deref() == Some(config_file)
+    }))
+}
+
+/// Run of a task registered with [register_run], unregistered when dropped
+#[derive(Debug)]
+pub(crate) struct RegisteredRun {
+    /// Name of the task and config file it was declared in
+    key: (String, PathBuf),
+}
+
+impl Drop for RegisteredRun {
+    fn drop(&mut self) {
+        let mut runs = PROCESS_RUNS.lock().unwrap();
+        if let Some(count) = runs.get_mut(&self.key) {
+            *count -= 1;
+            if *count == 0 {
+                runs.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Registers a run of the given task in this process until the returned guard is dropped. If
+/// the run is exclusive, returns an error instead if the task is already being run by this
+/// process, i.e. by another request to the daemon, or by another yamis process.
+///
+/// # Arguments
+///
+/// * `task`: Name of the task
+/// * `config_file`: Path of the config file the task is declared in
+/// * `exclusive`: Whether the task must not be running already
+///
+/// returns: Result<RegisteredRun, Box<dyn Error, Global>>
+pub(crate) fn register_run(
+    task: &str,
+    config_file: &Path,
+    exclusive: bool,
+) -> DynErrResult<RegisteredRun> {
+    let key = (String::from(task), config_file.to_path_buf());
+    let mut runs = PROCESS_RUNS.lock().unwrap();
+    if exclusive {
+        if runs.contains_key(&key) {
+            return Err(format!(
+                "Task {} is already running in this yamis process. Use `--force` to run it anyway, or set `reentrant: true` in the task",
+                task
+            )
+            .into());
+        }
+        check_not_running(task, config_file)?;
+    }
+    *runs.entry(key.clone()).or_insert(0) += 1;
+    Ok(RegisteredRun { key })
+}
+
+/// Returns an error if another yamis process is running the given task of the given config file
+///
+/// # Arguments
+///
+/// * `task`: Name of the task
+/// * `config_file`: Path of the config file the task is declared in
+///
+/// returns: Result<(), Box<dyn Error, Global>>
+fn check_not_running(task: &str, config_file: &Path) -> DynErrResult<()> {
+    // Runs are not tracked if there is no directory for them
+    let Ok(runs_dir) = default_runs_dir() else {
+        return Ok(());
+    };
+    match find_other_run(&runs_dir, task, config_file)? {
+        Some(run) => Err(format!(
+            "Task {} is already running with id {}, started {} ago from {}. Use `--force` to run it anyway, or set `reentrant: true` in the task",
+            task,
+            run.id,
+            format_elapsed(run.elapsed()),
+            run.dir.to_string_lossy()
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Gracefully stops the process currently run by the given run. Yamis then stops the run as
+/// the process did not finish successfully.
+///
+/// # Arguments
+///
+/// * `runs_dir`: Directory of the state files
+/// * `id`: Id of the run, as listed by [list_runs]
+///
+/// returns: Result<RunState, Box<dyn Error, Global>>
+pub fn stop_run(runs_dir: &Path, id: &str) -> DynErrResult<RunState> {
+    let state = match list_runs(runs_dir)?.into_iter().find(|run| run.id == id) {
+        Some(state) => state,
+        None => return Err(format!("No task running with id {}", id).into()),
+    };
+    match state.pid {
+        Some(pid) => terminate_process(pid)?,
+        None => {
+            return Err(format!(
+                "Task {} with id {} is not running a process at the moment",
+                state.task, id
+            )
+            .into())
+        }
+    }
+    Ok(state)
+}
+
+/// Returns whether a process with the given pid is alive
+#[cfg(unix)]
+pub(crate) fn is_process_alive(pid: u32) -> bool {
+    // Signal 0 only checks if the signal can be sent
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Returns whether a process with the given pid is alive
+#[cfg(windows)]
+pub(crate) fn is_process_alive(pid: u32) -> bool {
+    match std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+        .output()
+    {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid)),
+        // Keep the run listed if it cannot be checked
+        Err(_) => true,
+    }
+}
+
+/// Asks the process with the given pid to terminate
+#[cfg(unix)]
+fn terminate_process(pid: u32) -> DynErrResult<()> {
+    let result = unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Asks the process with the given pid, and its children, to terminate
+#[cfg(windows)]
+fn terminate_process(pid: u32) -> DynErrResult<()> {
+    let output = std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T"])
+        .output()?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+    }
+    Ok(())
+}
+
+/// Formats the seconds as hours, minutes and seconds, i.e. `1h 2m 3s`
+pub fn format_elapsed(secs: u64) -> String {
+    let (hours, minutes, secs) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, secs)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, secs)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_run_tracker() {
+        let tmp_dir = TempDir::new().unwrap();
+        let runs_dir = tmp_dir.path().join("runs");
+        assert_eq!(list_runs(&runs_dir).unwrap(), vec![]);
+
+        let mut tracker = RunTracker::start(&runs_dir, "hello", tmp_dir.path()).unwrap();
+        tracker.set_pid(Some(1234)).unwrap();
+        let runs = list_runs(&runs_dir).unwrap();
+        assert_eq!(runs, vec![tracker.state().clone()]);
+        assert_eq!(runs[0].id, std::process::id().to_string());
+        assert_eq!(runs[0].process, std::process::id());
+        assert_eq!(runs[0].pid, Some(1234));
+        assert_eq!(runs[0].task, "hello");
+
+        drop(tracker);
+        assert_eq!(list_runs(&runs_dir).unwrap(), vec![]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_find_other_run() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_file = tmp_dir.path().join("project.yamis.yml");
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let state = RunState {
+            id: child.id().to_string(),
+            process: child.id(),
+            pid: None,
+            task: String::from("deploy"),
+            dir: tmp_dir.path().to_path_buf(),
+            started: since_epoch().as_secs(),
+            config_file: Some(config_file.clone()),
+        };
+        let path = state_file_path(tmp_dir.path(), &state.id);
+        fs::write(&path, serde_json::to_string(&state).unwrap()).unwrap();
+
+        // Runs of the current process are ignored
+        let mut tracker = RunTracker::start(tmp_dir.path(), "deploy", tmp_dir.path()).unwrap();
+        tracker.set_config_file(&config_file).unwrap();
+
+        let found = find_other_run(tmp_dir.path(), "deploy", &config_file).unwrap();
+        assert_eq!(found, Some(state));
+        let other_config_file = tmp_dir.path().join("other.yamis.yml");
+        assert_eq!(
+            find_other_run(tmp_dir.path(), "deploy", &other_config_file).unwrap(),
+            None
+        );
+        assert_eq!(
+            find_other_run(tmp_dir.path(), "build", &config_file).unwrap(),
+            None
+        );
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(
+            find_other_run(tmp_dir.path(), "deploy", &config_file).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_stale_runs_are_removed() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        let state = RunState {
+            id: child.id().to_string(),
+            process: child.id(),
+            pid: None,
+            task: String::from("hello"),
+            dir: tmp_dir.path().to_path_buf(),
+            started: since_epoch().as_secs(),
+            config_file: None,
+        };
+        let path = state_file_path(tmp_dir.path(), &state.id);
+        fs::write(&path, serde_json::to_string(&state).unwrap()).unwrap();
+
+        assert_eq!(list_runs(tmp_dir.path()).unwrap(), vec![]);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_stop_run() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let mut tracker = RunTracker::start(tmp_dir.path(), "sleep", tmp_dir.path()).unwrap();
+        let id = tracker.state().id.clone();
+
+        let err = stop_run(tmp_dir.path(), &id).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Task sleep with id {} is not running a process at the moment",
+                id
+            )
+        );
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        tracker.set_pid(Some(child.id())).unwrap();
+        stop_run(tmp_dir.path(), &id).unwrap();
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGTERM));
+
+        let err = stop_run(tmp_dir.path(), "0").unwrap_err();
+        assert_eq!(err.to_string(), "No task running with id 0");
+    }
+
+    #[test]
+    #[cfg(all(feature = "daemon", unix))]
+    fn test_numbered_runs() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let mut first =
+            RunTracker::start_numbered(tmp_dir.path(), "sleep", tmp_dir.path(), 1).unwrap();
+        let mut second =
+            RunTracker::start_numbered(tmp_dir.path(), "sleep", tmp_dir.path(), 2).unwrap();
+        let pid = std::process::id();
+        assert_eq!(first.state().id, format!("{}-1", pid));
+        assert_eq!(second.state().id, format!("{}-2", pid));
+        assert_eq!(list_runs(tmp_dir.path()).unwrap().len(), 2);
+
+        let mut first_child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let mut second_child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        first.set_pid(Some(first_child.id())).unwrap();
+        second.set_pid(Some(second_child.id())).unwrap();
+        let stopped = stop_run(tmp_dir.path(), &format!("{}-2", pid)).unwrap();
+        assert_eq!(stopped.pid, Some(second_child.id()));
+        assert_eq!(second_child.wait().unwrap().signal(), Some(libc::SIGTERM));
+        assert!(first_child.try_wait().unwrap().is_none());
+        first_child.kill().unwrap();
+        first_child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_register_run() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_file = tmp_dir.path().join("project.yamis.yml");
+        let run = register_run("deploy", &config_file, true).unwrap();
+        let err = register_run("deploy", &config_file, true).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Task deploy is already running in this yamis process"));
+        let forced_run = register_run("deploy", &config_file, false).unwrap();
+        assert!(register_run("build", &config_file, true).is_ok());
+
+        drop(run);
+        assert!(register_run("deploy", &config_file, true).is_err());
+        drop(forced_run);
+        assert!(register_run("deploy", &config_file, true).is_ok());
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(5), "5s");
+        assert_eq!(format_elapsed(65), "1m 5s");
+        assert_eq!(format_elapsed(3725), "1h 2m 5s");
+    }
+}
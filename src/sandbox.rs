@@ -0,0 +1,131 @@
+//! Sandboxes that restrict what the commands of a task can do, set with `sandbox` in the task.
+//!
+//! With `sandbox: read_only`, the directory of the config file of the task is mounted read-only
+//! for its commands, so that i.e. lint or check tasks are guaranteed not to modify the project.
+//! This uses [bubblewrap](https://github.com/containers/bubblewrap) on Linux. If it is not
+//! available, or in other OSs, the commands run without the sandbox after a warning.
+use serde_derive::{Deserialize, Serialize};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::Path;
+
+/// Name of the bubblewrap executable
+#[cfg(target_os = "linux")]
+const BWRAP: &str = "bwrap";
+
+/// Restrictions applied to the commands of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxMode {
+    /// The directory of the config file cannot be modified
+    ReadOnly,
+}
+
+impl fmt::Display for SandboxMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxMode::ReadOnly => write!(f, "read_only"),
+        }
+    }
+}
+
+/// Returns the program and arguments that run a command inside the sandbox, which are followed
+/// by the program and arguments of the command, or the reason why the sandbox is not available
+///
+/// # Arguments
+///
+/// * `mode`: Restrictions to apply
+/// * `project_dir`: Directory of the config file of the task
+/// * `path_var`: Value of the PATH environment variable, where the sandbox is searched
+///
+/// returns: Result<Vec<OsString, Global>, String>
+#[cfg(target_os = "linux")]
+pub(crate) fn get_sandbox_prefix(
+    mode: SandboxMode,
+    project_dir: &Path,
+    path_var: &OsStr,
+) -> Result<Vec<OsString>, String> {
+    let Some(bwrap) = crate::utils::find_in_path(BWRAP, path_var) else {
+        return Err(format!("bubblewrap ({}) was not found in the PATH", BWRAP));
+    };
+    match mode {
+        SandboxMode::ReadOnly => Ok(vec![
+            bwrap.into_os_string(),
+            // Everything else is left as it is, including devices
+            OsString::from("--dev-bind"),
+            OsString::from("/"),
+            OsString::from("/"),
+            OsString::from("--ro-bind"),
+            project_dir.as_os_str().to_os_string(),
+            project_dir.as_os_str().to_os_string(),
+            OsString::from("--die-with-parent"),
+            OsString::from("--"),
+        ]),
+    }
+}
+
+/// Returns the reason why the sandbox is not available, as it is only supported in Linux
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn get_sandbox_prefix(
+    _mode: SandboxMode,
+    _project_dir: &Path,
+    _path_var: &OsStr,
+) -> Result<Vec<OsString>, String> {
+    Err(String::from("sandboxes are only supported in Linux"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_get_sandbox_prefix() {
+        use assert_fs::TempDir;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let project_dir = Path::new("/home/user/project");
+        assert_eq!(
+            get_sandbox_prefix(
+                SandboxMode::ReadOnly,
+                project_dir,
+                tmp_dir.path().as_os_str()
+            ),
+            Err(String::from("bubblewrap (bwrap) was not found in the PATH"))
+        );
+
+        let bwrap = tmp_dir.path().join("bwrap");
+        std::fs::write(&bwrap, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&bwrap, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let prefix = get_sandbox_prefix(
+            SandboxMode::ReadOnly,
+            project_dir,
+            tmp_dir.path().as_os_str(),
+        )
+        .unwrap();
+        assert_eq!(prefix[0], bwrap.into_os_string());
+        assert_eq!(
+            prefix[1..],
+            [
+                "--dev-bind",
+                "/",
+                "/",
+                "--ro-bind",
+                "/home/user/project",
+                "/home/user/project",
+                "--die-with-parent",
+                "--"
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn test_get_sandbox_prefix() {
+        assert_eq!(
+            get_sandbox_prefix(SandboxMode::ReadOnly, Path::new("project"), OsStr::new("")),
+            Err(String::from("sandboxes are only supported in Linux"))
+        );
+    }
+}
@@ -0,0 +1,543 @@
+//! Export of tasks as standalone shell scripts, for environments where yamis cannot be
+//! installed. The commands are rendered when exporting, so the script runs the same commands,
+//! with the same arguments, environment variables and working directories, as yamis would at
+//! that point, but it does not change if the config files do.
+use crate::builtins::{sleep_seconds, Builtin};
+use crate::escape::{escape_value, EscapeMode, ShellKind};
+use crate::tasks::RenderedCommand;
+use crate::types::DynErrResult;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Shell the tasks are exported for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportShell {
+    /// POSIX shell script, run with `/bin/sh`
+    Sh,
+    /// PowerShell script
+    PowerShell,
+}
+
+impl ExportShell {
+    /// Names of the shells, as given in the command line
+    pub(crate) const NAMES: [&'static str; 2] = ["sh", "powershell"];
+
+    /// Returns the name of the shell the tasks are exported for by default in this OS
+    pub(crate) fn default_name() -> &'static str {
+        if cfg!(windows) {
+            "powershell"
+        } else {
+            "sh"
+        }
+    }
+
+    /// Returns the kind of shell, to escape the values with
+    fn shell_kind(&self) -> ShellKind {
+        match self {
+            ExportShell::Sh => ShellKind::Posix,
+            ExportShell::PowerShell => ShellKind::PowerShell,
+        }
+    }
+}
+
+impl FromStr for ExportShell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sh" => Ok(ExportShell::Sh),
+            "powershell" => Ok(ExportShell::PowerShell),
+            _ => Err(format!(
+                "Unknown shell `{}`, expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Script exported from a task
+#[derive(Debug)]
+pub(crate) struct ExportedScript {
+    /// Content of the script
+    pub(crate) contents: String,
+    /// Things that the script does differently than yamis, or that the user should know about
+    pub(crate) warnings: Vec<String>,
+}
+
+/// Builds the script of a single shell
+struct ScriptWriter {
+    /// Shell the script is written for
+    shell: ExportShell,
+    /// Content of the script so far
+    contents: String,
+    /// Warnings found so far
+    warnings: Vec<String>,
+}
+
+impl ScriptWriter {
+    /// Returns the value quoted and escaped for the shell
+    fn quote(&self, val: &str) -> String {
+        escape_value(val, &EscapeMode::Always, &self.shell.shell_kind())
+    }
+
+    /// Returns the arguments of the command as strings. Arguments that are not valid unicode
+    /// are converted lossily, with a warning.
+    fn get_args(&mut self, command: &RenderedCommand) -> Vec<String> {
+        let mut args = Vec::with_capacity(command.args().len());
+        for arg in command.args() {
+            if arg.to_str().is_none() {
+                self.warnings.push(format!(
+                    "Task {} has arguments that are not valid unicode, invalid characters were replaced",
+                    command.task_name()
+                ));
+            }
+            args.push(arg.to_string_lossy().to_string());
+        }
+        args
+    }
+
+    /// Returns the environment variables of the command, sorted by name so that the script is
+    /// the same each time it is exported
+    fn get_env(command: &RenderedCommand) -> Vec<(&String, &String)> {
+        let mut env: Vec<_> = command.env().iter().collect();
+        env.sort();
+        env
+    }
+
+    /// Writes the header of the script
+    fn write_header(&mut self, task_name: &str) {
+        match self.shell {
+            ExportShell::Sh => {
+                self.contents.push_str("#!/bin/sh\n");
+            }
+            ExportShell::PowerShell => {}
+        }
+        let _ = writeln!(
+            self.contents,
+            "# Task `{}`, exported by yamis {}",
+            task_name,
+            clap::crate_version!()
+        );
+        if self.shell == ExportShell::PowerShell {
+            self.contents.push_str("$ErrorActionPreference = 'Stop'\n");
+        }
+    }
+
+    /// Writes the command, followed by the check of its exit code
+    fn write_command(&mut self, command: &RenderedCommand) -> DynErrResult<()> {
+        if !command.echo() {
+            self.warnings.push(format!(
+                "Task {} hides its command, but the exported script contains it in plain text",
+                command.task_name()
+            ));
+        }
+        if let Some(sandbox) = command.sandbox() {
+            self.warnings.push(format!(
+                "Task {} runs in a {} sandbox, but the exported script runs it without one",
+                command.task_name(),
+                sandbox
+            ));
+        }
+        let _ = writeln!(self.contents, "\n# {}", command.task_name());
+        match self.shell {
+            ExportShell::Sh => self.write_sh_command(command)?,
+            ExportShell::PowerShell => self.write_powershell_command(command)?,
+        }
+        self.write_exit_code_check(command);
+        Ok(())
+    }
+
+    /// Writes the command for a POSIX shell. It runs in a subshell, so that the working
+    /// directory and environment variables do not leak to the next commands.
+    fn write_sh_command(&mut self, command: &RenderedCommand) -> DynErrResult<()> {
+        self.contents.push_str("(\n");
+        if let Some(wd) = command.wd() {
+            let wd = self.quote(&wd.to_string_lossy());
+            let _ = writeln!(self.contents, "  cd {} || exit 1", wd);
+        }
+        for (key, value) in Self::get_env(command) {
+            if !is_sh_variable_name(key) {
+                self.warnings.push(format!(
+                    "Environment variable {} of task {} cannot be exported by sh, it was skipped",
+                    key,
+                    command.task_name()
+                ));
+                continue;
+            }
+            let _ = writeln!(self.contents, "  export {}={}", key, self.quote(value));
+        }
+        let args = self.get_args(command);
+        match (command.script(), command.builtin()) {
+            (Some(script), _) => {
+                let extension = command.script_extension().unwrap_or_default();
+                let script_name = match extension.trim_start_matches('.') {
+                    "" => String::from("script"),
+                    extension => format!("script.{}", extension),
+                };
+                self.contents
+                    .push_str("  yamis_script_dir=$(mktemp -d) || exit 1\n");
+                self.contents
+                    .push_str("  trap 'rm -rf \"$yamis_script_dir\"' EXIT\n");
+                let _ = writeln!(
+                    self.contents,
+                    "  printf '%s' {} > \"$yamis_script_dir/{}\"",
+                    self.quote(script),
+                    script_name
+                );
+                let mut line = vec![self.quote(command.program())];
+                line.extend(args.iter().map(|arg| self.quote(arg)));
+                line.push(format!("\"$yamis_script_dir/{}\"", script_name));
+                let _ = writeln!(self.contents, "  {}", line.join(" "));
+            }
+            (None, Some(Builtin::Echo)) => {
+                let _ = writeln!(
+                    self.contents,
+                    "  printf '%s\\n' {}",
+                    self.quote(&args.join(" "))
+                );
+            }
+            (None, Some(Builtin::Sleep)) => {
+                let _ = writeln!(self.contents, "  sleep {}", sleep_seconds(&args)?);
+            }
+            (None, Some(Builtin::Fail)) => {
+                let _ = writeln!(
+                    self.contents,
+                    "  printf '%s\\n' {} >&2",
+                    self.quote(&fail_reason(&args))
+                );
+                self.contents.push_str("  exit 1\n");
+            }
+            (None, None) => {
+                let mut line = vec![self.quote(command.program())];
+                line.extend(args.iter().map(|arg| self.quote(arg)));
+                let _ = writeln!(self.contents, "  {}", line.join(" "));
+            }
+        }
+        self.contents.push_str(")\nyamis_code=$?\n");
+        Ok(())
+    }
+
+    /// Writes the command for PowerShell. The working directory and environment variables are
+    /// restored afterwards, so that they do not leak to the next commands.
+    fn write_powershell_command(&mut self, command: &RenderedCommand) -> DynErrResult<()> {
+        self.contents.push_str("$yamisSaved = @{}\n");
+        for (key, value) in Self::get_env(command) {
+            let key = self.quote(key);
+            let _ = writeln!(
+                self.contents,
+                "$yamisSaved[{}] = [Environment]::GetEnvironmentVariable({})",
+                key, key
+            );
+            let _ = writeln!(
+                self.contents,
+                "[Environment]::SetEnvironmentVariable({}, {})",
+                key,
+                self.quote(value)
+            );
+        }
+        if let Some(wd) = command.wd() {
+            let wd = self.quote(&wd.to_string_lossy());
+            let _ = writeln!(self.contents, "Push-Location -LiteralPath {}", wd);
+        }
+        self.contents.push_str("try {\n");
+        let args = self.get_args(command);
+        match (command.script(), command.builtin()) {
+            (Some(script), _) => {
+                let extension = match command.script_extension().unwrap_or_default() {
+                    "" => String::new(),
+                    extension => format!(".{}", extension.trim_start_matches('.')),
+                };
+                let _ = writeln!(
+                    self.contents,
+                    "    $yamisScript = Join-Path ([IO.Path]::GetTempPath()) (\"yamis-\" + [guid]::NewGuid() + {})",
+                    self.quote(&extension)
+                );
+                let _ = writeln!(
+                    self.contents,
+                    "    [IO.File]::WriteAllText($yamisScript, {})",
+                    self.quote(script)
+                );
+                let mut line = vec![String::from("&"), self.quote(command.program())];
+                line.extend(args.iter().map(|arg| self.quote(arg)));
+                line.push(String::from("$yamisScript"));
+                let _ = writeln!(self.contents, "    {}", line.join(" "));
+                self.contents.push_str("    $yamisCode = $LASTEXITCODE\n");
+                self.contents
+                    .push_str("    Remove-Item -LiteralPath $yamisScript -ErrorAction Ignore\n");
+            }
+            (None, Some(Builtin::Echo)) => {
+                let _ = writeln!(
+                    self.contents,
+                    "    Write-Output {}",
+                    self.quote(&args.join(" "))
+                );
+                self.contents.push_str("    $yamisCode = 0\n");
+            }
+            (None, Some(Builtin::Sleep)) => {
+                let milliseconds = (sleep_seconds(&args)? * 1000.0).round();
+                let _ = writeln!(
+                    self.contents,
+                    "    Start-Sleep -Milliseconds {}",
+                    milliseconds
+                );
+                self.contents.push_str("    $yamisCode = 0\n");
+            }
+            (None, Some(Builtin::Fail)) => {
+                let _ = writeln!(
+                    self.contents,
+                    "    [Console]::Error.WriteLine({})",
+                    self.quote(&fail_reason(&args))
+                );
+                self.contents.push_str("    $yamisCode = 1\n");
+            }
+            (None, None) => {
+                let mut line = vec![String::from("&"), self.quote(command.program())];
+                line.extend(args.iter().map(|arg| self.quote(arg)));
+                let _ = writeln!(self.contents, "    {}", line.join(" "));
+                self.contents.push_str("    $yamisCode = $LASTEXITCODE\n");
+            }
+        }
+        self.contents.push_str("} finally {\n");
+        if command.wd().is_some() {
+            self.contents.push_str("    Pop-Location\n");
+        }
+        self.contents.push_str(
+            "    foreach ($yamisName in $yamisSaved.Keys) { [Environment]::SetEnvironmentVariable($yamisName, $yamisSaved[$yamisName]) }\n",
+        );
+        self.contents.push_str("}\n");
+        Ok(())
+    }
+
+    /// Writes the check of the exit code of the last command, which prints the messages mapped
+    /// to it and exits if it is not allowed, like yamis does
+    fn write_exit_code_check(&mut self, command: &RenderedCommand) {
+        let mut codes: Vec<i32> = command.allowed_exit_codes().to_vec();
+        codes.extend(command.exit_code_map().keys());
+        codes.sort();
+        codes.dedup();
+
+        let failed_message = self.quote(&format!(
+            "Task {} failed with exit code ",
+            command.task_name()
+        ));
+        let failed_message = match self.shell {
+            ExportShell::Sh => format!("{}\"$yamis_code\"", failed_message),
+            ExportShell::PowerShell => format!("({} + $yamisCode)", failed_message),
+        };
+
+        match self.shell {
+            ExportShell::Sh => self.contents.push_str("case $yamis_code in\n"),
+            ExportShell::PowerShell => self.contents.push_str("switch ($yamisCode) {\n"),
+        }
+        for code in codes {
+            let allowed = command.allowed_exit_codes().contains(&code);
+            let message = command.exit_code_map().get(&code).map(|message| {
+                if allowed {
+                    self.quote(message)
+                } else {
+                    self.quote(&format!("{} (exit code {})", message, code))
+                }
+            });
+            let mut actions = Vec::new();
+            match (self.shell, message) {
+                (ExportShell::Sh, Some(message)) if allowed => {
+                    actions.push(format!("printf '%s\\n' {}", message))
+                }
+                (ExportShell::Sh, Some(message)) => {
+                    actions.push(format!("printf '%s\\n' {} >&2", message))
+                }
+                (ExportShell::PowerShell, Some(message)) if allowed => {
+                    actions.push(format!("Write-Output {}", message))
+                }
+                (ExportShell::PowerShell, Some(message)) => {
+                    actions.push(format!("[Console]::Error.WriteLine({})", message))
+                }
+                (_, None) => {}
+            }
+            if !allowed {
+                actions.push(format!("exit {}", code));
+            }
+            match self.shell {
+                ExportShell::Sh if actions.is_empty() => {
+                    let _ = writeln!(self.contents, "  {}) ;;", code);
+                }
+                ExportShell::Sh => {
+                    let _ = writeln!(self.contents, "  {}) {} ;;", code, actions.join("; "));
+                }
+                ExportShell::PowerShell => {
+                    let _ = writeln!(self.contents, "    {} {{ {} }}", code, actions.join("; "));
+                }
+            }
+        }
+        match self.shell {
+            ExportShell::Sh => {
+                let _ = writeln!(
+                    self.contents,
+                    "  *) printf '%s\\n' {} >&2; exit $yamis_code ;;\nesac",
+                    failed_message
+                );
+            }
+            ExportShell::PowerShell => {
+                let _ = writeln!(
+                    self.contents,
+                    "    default {{ [Console]::Error.WriteLine({}); exit $yamisCode }}\n}}",
+                    failed_message
+                );
+            }
+        }
+    }
+}
+
+/// Returns the reason `yamis::fail` fails with
+fn fail_reason(args: &[String]) -> String {
+    if args.is_empty() {
+        String::from("Failed on purpose")
+    } else {
+        args.join(" ")
+    }
+}
+
+/// Returns whether the name can be used as a variable by POSIX shells
+fn is_sh_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+            chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        }
+        _ => false,
+    }
+}
+
+/// Returns a standalone script that runs the given commands in order, as rendered from a task,
+/// stopping at the first one that fails. The script does not need yamis to run.
+///
+/// # Arguments
+///
+/// * `task_name`: Name of the exported task
+/// * `commands`: Commands rendered from the task
+/// * `shell`: Shell the script is written for
+///
+/// returns: Result<ExportedScript, Box<dyn Error, Global>>
+pub(crate) fn export_script(
+    task_name: &str,
+    commands: &[RenderedCommand],
+    shell: ExportShell,
+) -> DynErrResult<ExportedScript> {
+    let mut writer = ScriptWriter {
+        shell,
+        contents: String::new(),
+        warnings: Vec::new(),
+    };
+    writer.write_header(task_name);
+    for command in commands {
+        writer.write_command(command)?;
+    }
+    // The same warning is given once per task
+    let mut warnings = Vec::with_capacity(writer.warnings.len());
+    for warning in writer.warnings {
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+    Ok(ExportedScript {
+        contents: writer.contents,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::RecordBackend;
+    use crate::config_files::ConfigFile;
+    use crate::run_context::RunContext;
+    use crate::types::TaskArgs;
+    use std::path::PathBuf;
+
+    /// Returns the commands of the given task of the config file
+    fn record_commands(config_file: &str, task: &str) -> Vec<RenderedCommand> {
+        let config_file =
+            ConfigFile::load_from_str(config_file, PathBuf::from("project.yamis.yml")).unwrap();
+        let backend = RecordBackend::new();
+        let task = config_file.get_task(task).unwrap();
+        task.run_with_backend(&TaskArgs::new(), &config_file, &RunContext::new(), &backend)
+            .unwrap();
+        backend.into_commands()
+    }
+
+    #[test]
+    fn test_export_script() {
+        let commands = record_commands(
+            r#"
+tasks:
+  greet:
+    program: echo
+    args: ["hello $USER"]
+    env:
+      B: b
+      A: a
+  code:
+    program: yamis::sleep
+    args: ["0.5"]
+    allowed_exit_codes: [0, 3]
+    exit_code_map:
+      3: Three is fine
+      4: Four is not
+  hidden:
+    program: yamis::fail
+    echo_command: false
+  all:
+    serial: [greet, code, hidden]
+"#,
+            "all",
+        );
+
+        let exported = export_script("all", &commands, ExportShell::Sh).unwrap();
+        assert!(exported.contents.starts_with("#!/bin/sh\n# Task `all`"));
+        assert!(exported.contents.contains(
+            "# greet\n(\n  export A=\"a\"\n  export B=\"b\"\n  \"echo\" \"hello \\$USER\"\n)\nyamis_code=$?\n"
+        ));
+        assert!(exported.contents.contains(
+            "  sleep 0.5\n)\nyamis_code=$?\ncase $yamis_code in\n  0) ;;\n  3) printf '%s\\n' \"Three is fine\" ;;\n  4) printf '%s\\n' \"Four is not (exit code 4)\" >&2; exit 4 ;;\n"
+        ));
+        assert!(exported
+            .contents
+            .contains("  printf '%s\\n' \"Failed on purpose\" >&2\n  exit 1\n"));
+        assert_eq!(
+            exported.warnings,
+            vec![
+                "Task hidden hides its command, but the exported script contains it in plain text"
+            ]
+        );
+
+        let exported = export_script("all", &commands, ExportShell::PowerShell).unwrap();
+        assert!(exported.contents.starts_with("# Task `all`"));
+        assert!(exported
+            .contents
+            .contains("[Environment]::SetEnvironmentVariable(\"A\", \"a\")\n"));
+        assert!(exported
+            .contents
+            .contains("    & \"echo\" \"hello `$USER\"\n    $yamisCode = $LASTEXITCODE\n"));
+        assert!(exported
+            .contents
+            .contains("    Start-Sleep -Milliseconds 500\n"));
+        assert!(exported
+            .contents
+            .contains("    3 { Write-Output \"Three is fine\" }\n"));
+    }
+
+    #[test]
+    fn test_export_invalid_sleep() {
+        let commands = record_commands(
+            "tasks:\n  wait:\n    program: yamis::sleep\n    args: [soon]\n",
+            "wait",
+        );
+        assert_eq!(
+            export_script("wait", &commands, ExportShell::Sh)
+                .unwrap_err()
+                .to_string(),
+            "`yamis::sleep` expects a number of seconds, got `soon`"
+        );
+    }
+}
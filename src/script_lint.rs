@@ -0,0 +1,333 @@
+//! Lints the scripts of tasks with external linters, i.e. shellcheck, with `yamis lint-scripts`.
+//!
+//! Scripts are rendered as they would run, written to a temporal file and passed to the linter
+//! of their shell. Linters must print their findings one per line, in the format used by gcc,
+//! i.e. `<file>:<line>:<column>: <message>`, which is then mapped back to the line of the task
+//! script it was rendered from.
+use crate::escape::ShellKind;
+use crate::types::DynErrResult;
+use crate::utils::TMP_FOLDER_NAMESPACE;
+use serde_derive::Deserialize;
+use std::env::temp_dir;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Placeholder replaced by the path of the script in the arguments of the linters
+const FILE_PLACEHOLDER: &str = "{file}";
+
+/// Placeholder replaced by the name of the shell that runs the script, i.e. `bash`
+const SHELL_PLACEHOLDER: &str = "{shell}";
+
+/// Command that lints a script
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LinterCommand {
+    /// Program to run
+    program: String,
+    /// Arguments of the program. `{file}` is replaced by the path of the script, which is
+    /// passed as the last argument if not given, and `{shell}` by the name of the shell
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// Linters of the scripts of each shell, set in the `linters` section of the settings. A linter
+/// set to null disables the linting of the scripts of that shell.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LinterSettings {
+    /// Linter of POSIX shell scripts, shellcheck by default
+    #[serde(default = "default_sh_linter")]
+    sh: Option<LinterCommand>,
+    /// Linter of PowerShell scripts, PSScriptAnalyzer by default
+    #[serde(default = "default_powershell_linter")]
+    powershell: Option<LinterCommand>,
+}
+
+/// Returns the default linter of POSIX shell scripts
+fn default_sh_linter() -> Option<LinterCommand> {
+    Some(LinterCommand {
+        program: String::from("shellcheck"),
+        args: vec![
+            String::from("--format=gcc"),
+            format!("--shell={}", SHELL_PLACEHOLDER),
+            String::from(FILE_PLACEHOLDER),
+        ],
+    })
+}
+
+/// Returns the default linter of PowerShell scripts
+fn default_powershell_linter() -> Option<LinterCommand> {
+    Some(LinterCommand {
+        program: String::from("pwsh"),
+        args: vec![
+            String::from("-NoProfile"),
+            String::from("-NonInteractive"),
+            String::from("-Command"),
+            format!(
+                "Invoke-ScriptAnalyzer -Path '{}' | ForEach-Object {{ \"$($_.Line):$($_.Column): $($_.Severity): $($_.Message) [$($_.RuleName)]\" }}",
+                FILE_PLACEHOLDER
+            ),
+        ],
+    })
+}
+
+impl Default for LinterSettings {
+    fn default() -> Self {
+        LinterSettings {
+            sh: default_sh_linter(),
+            powershell: default_powershell_linter(),
+        }
+    }
+}
+
+impl LinterSettings {
+    /// Returns the linter of the scripts run by the given shell, if any
+    pub(crate) fn get(&self, shell_kind: ShellKind) -> Option<&LinterCommand> {
+        match shell_kind {
+            ShellKind::Posix => self.sh.as_ref(),
+            ShellKind::PowerShell => self.powershell.as_ref(),
+            ShellKind::Cmd | ShellKind::Other => None,
+        }
+    }
+}
+
+/// Problem found by a linter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Finding {
+    /// Line of the rendered script, starting at 1
+    pub(crate) line: usize,
+    /// Column of the rendered script, starting at 1
+    pub(crate) column: usize,
+    /// Message of the linter, usually starting with the severity
+    pub(crate) message: String,
+}
+
+/// Returns the name of the POSIX shell that runs the script, as understood by shellcheck
+///
+/// # Arguments
+///
+/// * `script_runner`: Program that runs the script, i.e. `/bin/bash`
+/// * `script_ext`: Extension of the script
+///
+/// returns: &str
+fn get_shell_name<'a>(script_runner: &'a str, script_ext: &'a str) -> &'a str {
+    let runner = Path::new(script_runner)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    match runner {
+        "sh" | "bash" | "dash" | "ksh" => runner,
+        _ if script_ext.trim_start_matches('.') == "bash" => "bash",
+        _ => "sh",
+    }
+}
+
+/// Parses the findings printed by a linter, ignoring lines in other formats
+///
+/// # Arguments
+///
+/// * `output`: Output of the linter
+/// * `file`: Path of the script, which prefixes each finding if given
+///
+/// returns: Vec<Finding, Global>
+fn parse_findings(output: &str, file: &Path) -> Vec<Finding> {
+    let file_prefix = format!("{}:", file.to_string_lossy());
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line.strip_prefix(&file_prefix).unwrap_or(line);
+            let mut parts = line.splitn(3, ':');
+            let line_number = parts.next()?.trim().parse().ok()?;
+            let column = parts.next()?.trim().parse().ok()?;
+            let message = parts.next()?.trim();
+            Some(Finding {
+                line: line_number,
+                column,
+                message: String::from(message),
+            })
+        })
+        .collect()
+}
+
+/// Returns the line of the template a line of the rendered script comes from, starting at 1.
+/// Lines can only be mapped if rendering did not add or remove lines, i.e. if no value with
+/// line breaks was rendered in it.
+///
+/// # Arguments
+///
+/// * `template`: Script of the task, before rendering
+/// * `rendered`: Rendered script
+/// * `line`: Line of the rendered script, starting at 1
+///
+/// returns: Option<usize>
+pub(crate) fn get_template_line(template: &str, rendered: &str, line: usize) -> Option<usize> {
+    let template_lines = template.trim_end().lines().count();
+    if line == 0 || line > template_lines || template_lines != rendered.trim_end().lines().count() {
+        return None;
+    }
+    Some(line)
+}
+
+impl LinterCommand {
+    /// Returns the name of the program of the linter
+    pub(crate) fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Lints the rendered script
+    ///
+    /// # Arguments
+    ///
+    /// * `script`: Content of the script
+    /// * `script_runner`: Program that runs the script, used to guess its shell
+    /// * `script_ext`: Extension of the script
+    ///
+    /// returns: Result<Vec<Finding, Global>, Box<dyn Error, Global>>. See [is_not_found] to
+    /// tell whether it failed because the linter is not installed.
+    pub(crate) fn lint(
+        &self,
+        script: &str,
+        script_runner: &str,
+        script_ext: &str,
+    ) -> DynErrResult<Vec<Finding>> {
+        let dir = temp_dir().join(TMP_FOLDER_NAMESPACE).join("lint");
+        fs::create_dir_all(&dir)?;
+        let file = dir.join(format!(
+            "{}.{}",
+            std::process::id(),
+            script_ext.trim_start_matches('.')
+        ));
+        fs::write(&file, script)?;
+        let result = self.run(&file, get_shell_name(script_runner, script_ext));
+        let _ = fs::remove_file(&file);
+        result
+    }
+
+    /// Runs the linter on the given script file
+    fn run(&self, file: &Path, shell: &str) -> DynErrResult<Vec<Finding>> {
+        let file_str = file.to_string_lossy();
+        let mut args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| {
+                arg.replace(FILE_PLACEHOLDER, &file_str)
+                    .replace(SHELL_PLACEHOLDER, shell)
+            })
+            .collect();
+        if !self.args.iter().any(|arg| arg.contains(FILE_PLACEHOLDER)) {
+            args.push(file_str.to_string());
+        }
+        let output = Command::new(&self.program)
+            .args(&args)
+            .stdin(Stdio::null())
+            .output()?;
+        let findings = parse_findings(&String::from_utf8_lossy(&output.stdout), file);
+        // Linters usually fail when they find problems, so that is only an error without findings
+        if !output.status.success() && findings.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "Linter `{}` failed with {}:\n{}",
+                self.program,
+                output.status,
+                stderr.trim_end()
+            )
+            .into());
+        }
+        Ok(findings)
+    }
+}
+
+/// Returns whether the error means that the linter is not installed
+pub(crate) fn is_not_found(error: &(dyn std::error::Error + 'static)) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_findings() {
+        let file = Path::new("/tmp/yamis/lint/1.sh");
+        let output =
+            "/tmp/yamis/lint/1.sh:3:6: warning: Double quote to prevent globbing. [SC2086]\n\
+                      In /tmp/yamis/lint/1.sh line 3:\n\
+                      2:1: note: Not following. [SC1091]\n";
+        assert_eq!(
+            parse_findings(output, file),
+            vec![
+                Finding {
+                    line: 3,
+                    column: 6,
+                    message: String::from("warning: Double quote to prevent globbing. [SC2086]"),
+                },
+                Finding {
+                    line: 2,
+                    column: 1,
+                    message: String::from("note: Not following. [SC1091]"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_template_line() {
+        let template = "cd {$dir}\nrm -rf {$@}\n";
+        assert_eq!(
+            get_template_line(template, "cd \"a\"\nrm -rf \"b\"", 2),
+            Some(2)
+        );
+        assert_eq!(
+            get_template_line(template, "cd \"a\"\nrm -rf \"b\"", 3),
+            None
+        );
+        assert_eq!(get_template_line(template, "cd \"a\nb\"\nrm -rf", 2), None);
+        assert_eq!(get_shell_name("/bin/dash", "sh"), "dash");
+        assert_eq!(get_shell_name("env", "bash"), "bash");
+        assert_eq!(get_shell_name("zsh", "sh"), "sh");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lint() {
+        let linter = LinterCommand {
+            program: String::from("sh"),
+            args: vec![
+                String::from("-c"),
+                String::from(
+                    r#"grep -n "rm" "$1" | cut -d: -f1 | sed "s/$/:1: warning: rm in $0/""#,
+                ),
+                String::from(SHELL_PLACEHOLDER),
+            ],
+        };
+        let findings = linter
+            .lint("cd /tmp\nrm -rf build\n", "/bin/bash", "sh")
+            .unwrap();
+        assert_eq!(
+            findings,
+            vec![Finding {
+                line: 2,
+                column: 1,
+                message: String::from("warning: rm in bash"),
+            }]
+        );
+
+        let missing = LinterCommand {
+            program: String::from("yamis-missing-linter"),
+            args: vec![],
+        };
+        let e = missing.lint("echo hi", "bash", "sh").unwrap_err();
+        assert!(is_not_found(e.as_ref()));
+
+        let settings = LinterSettings::default();
+        assert_eq!(
+            settings.get(ShellKind::Posix).unwrap().program(),
+            "shellcheck"
+        );
+        assert!(settings.get(ShellKind::Cmd).is_none());
+    }
+}
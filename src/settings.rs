@@ -0,0 +1,140 @@
+//! Settings of yamis, which can only be set in the `settings` section of the global config file.
+use crate::defaults::default_true;
+use crate::hints::HintSettings;
+use crate::print_utils::Theme;
+use crate::reports::ReportSettings;
+use crate::script_lint::LinterSettings;
+use serde_derive::Deserialize;
+
+/// Default number of seconds between update checks
+#[cfg_attr(not(feature = "updater"), allow(dead_code))]
+const DEFAULT_UPDATE_CHECK_INTERVAL: u64 = 60 * 60 * 24; // 1 day
+
+/// How to check for new releases of yamis
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum UpdateCheck {
+    /// Prints a message when a new release is available, or if the check fails
+    #[default]
+    Notify,
+    /// Prints a message when a new release is available, ignoring errors, i.e. when offline
+    Silent,
+    /// Never checks for new releases
+    Off,
+}
+
+/// Whether to run tasks from the global config file when they are not found in the project
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum GlobalTasks {
+    /// Runs them, printing a message that the global config file is used
+    #[default]
+    Notify,
+    /// Runs them without printing any message
+    Silent,
+    /// Never searches tasks in the global config file
+    Off,
+}
+
+/// Settings of yamis
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Settings {
+    /// How to check for new releases
+    #[serde(default)]
+    pub(crate) update_check: UpdateCheck,
+    /// Seconds between update checks
+    update_check_interval: Option<u64>,
+    /// Whether to run tasks from the global config file when not found in the project
+    #[serde(default)]
+    pub(crate) global_tasks: GlobalTasks,
+    /// Whether to print a message when a task shadows a different task with the same name in a
+    /// parent directory or in the global config file
+    #[serde(default = "default_true")]
+    pub(crate) warn_shadowed_tasks: bool,
+    /// Prefix, colors and messages used for the output of yamis
+    #[serde(default)]
+    pub(crate) output: Theme,
+    /// Hints printed below the error when a command fails
+    #[serde(default)]
+    pub(crate) hints: HintSettings,
+    /// Where the reports of the runs are sent, if anywhere
+    pub(crate) report: Option<ReportSettings>,
+    /// Linters used by `yamis lint-scripts`
+    #[serde(default)]
+    pub(crate) linters: LinterSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            update_check: UpdateCheck::default(),
+            update_check_interval: None,
+            global_tasks: GlobalTasks::default(),
+            warn_shadowed_tasks: true,
+            output: Theme::default(),
+            hints: HintSettings::default(),
+            report: None,
+            linters: LinterSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Returns the seconds between update checks
+    #[cfg_attr(not(feature = "updater"), allow(dead_code))]
+    pub(crate) fn update_check_interval(&self) -> u64 {
+        self.update_check_interval
+            .unwrap_or(DEFAULT_UPDATE_CHECK_INTERVAL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escape::ShellKind;
+
+    #[test]
+    fn test_settings() {
+        let settings: Settings = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(settings.update_check, UpdateCheck::Notify);
+        assert_eq!(settings.global_tasks, GlobalTasks::Notify);
+        assert!(settings.warn_shadowed_tasks);
+        assert_eq!(settings, Settings::default());
+        assert_eq!(
+            settings.update_check_interval(),
+            DEFAULT_UPDATE_CHECK_INTERVAL
+        );
+
+        let settings: Settings =
+            serde_yaml::from_str("update_check: silent\nupdate_check_interval: 3600").unwrap();
+        assert_eq!(settings.update_check, UpdateCheck::Silent);
+        assert_eq!(settings.update_check_interval(), 3600);
+
+        let settings: Settings =
+            toml::from_str("update_check = 'off'\nglobal_tasks = 'off'").unwrap();
+        assert_eq!(settings.update_check, UpdateCheck::Off);
+        assert_eq!(settings.global_tasks, GlobalTasks::Off);
+
+        let settings: Settings = serde_yaml::from_str("warn_shadowed_tasks: false").unwrap();
+        assert!(!settings.warn_shadowed_tasks);
+        assert_eq!(settings.output, Theme::default());
+
+        let settings: Settings = toml::from_str(
+            "[output]\nprefix = '[tasks]'\n[output.messages]\ndry_run = 'Simulation'",
+        )
+        .unwrap();
+        assert_eq!(settings.output.prefix, "[tasks]");
+        assert_eq!(settings.output.messages.dry_run, "Simulation");
+
+        let settings: Settings = serde_yaml::from_str("hints:\n  enabled: true").unwrap();
+        assert!(settings.hints.enabled);
+
+        let settings: Settings = serde_yaml::from_str("linters:\n  sh: null").unwrap();
+        assert!(settings.linters.get(ShellKind::Posix).is_none());
+        assert!(settings.linters.get(ShellKind::PowerShell).is_some());
+
+        assert!(serde_yaml::from_str::<Settings>("update_check: never").is_err());
+        assert!(serde_yaml::from_str::<Settings>("update_interval: 1").is_err());
+    }
+}
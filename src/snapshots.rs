@@ -0,0 +1,200 @@
+//! Snapshots of runs, with the environment, rendered commands and hashes of the config files, so
+//! that a run can be replayed later with exactly the same inputs.
+use crate::backend::{ExecutionBackend, ProcessBackend};
+use crate::tasks::RenderedCommand;
+use crate::types::DynErrResult;
+use md5::{Digest, Md5};
+use serde_derive::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Environment, commands and config files of a run
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    /// Environment variables of yamis when the run started. Variables that are not valid
+    /// unicode are skipped.
+    env: BTreeMap<String, String>,
+    /// MD5 hashes of the config files the commands were declared in
+    config_files: BTreeMap<PathBuf, String>,
+    /// Commands run, in order
+    commands: Vec<RenderedCommand>,
+}
+
+/// Returns the MD5 hash of the file in the given path, or None if it cannot be read
+fn hash_file(path: &Path) -> Option<String> {
+    let content = fs::read(path).ok()?;
+    let mut hasher = Md5::new();
+    hasher.update(content);
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+impl Snapshot {
+    /// Returns a new snapshot with the current environment and no commands
+    pub(crate) fn new() -> Self {
+        let env = env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        Snapshot {
+            env,
+            ..Snapshot::default()
+        }
+    }
+
+    /// Adds a command to the snapshot, along with the hash of its config file
+    fn add(&mut self, command: &RenderedCommand) {
+        let config_file_path = command.config_file_path();
+        if !self.config_files.contains_key(config_file_path) {
+            if let Some(hash) = hash_file(config_file_path) {
+                self.config_files
+                    .insert(config_file_path.to_path_buf(), hash);
+            }
+        }
+        self.commands.push(command.clone());
+    }
+
+    /// Writes the snapshot as JSON to the given path
+    pub(crate) fn save(&self, path: &Path) -> DynErrResult<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        if let Err(e) = fs::write(path, content) {
+            return Err(
+                format!("Could not write the snapshot to {}: {}", path.display(), e).into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Reads a snapshot from the given path
+    pub(crate) fn load(path: &Path) -> DynErrResult<Snapshot> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                return Err(
+                    format!("Could not read the snapshot at {}: {}", path.display(), e).into(),
+                )
+            }
+        };
+        match serde_json::from_str(&content) {
+            Ok(snapshot) => Ok(snapshot),
+            Err(e) => Err(format!("Invalid snapshot at {}: {}", path.display(), e).into()),
+        }
+    }
+
+    /// Returns the config files that changed, or no longer exist, since the snapshot was taken
+    pub(crate) fn changed_config_files(&self) -> Vec<&Path> {
+        self.config_files
+            .iter()
+            .filter(|(path, hash)| hash_file(path).as_ref() != Some(*hash))
+            .map(|(path, _)| path.as_path())
+            .collect()
+    }
+
+    /// Runs the commands of the snapshot again, in order, with the environment of the snapshot
+    /// instead of the current one. Stops at the first command that fails.
+    pub(crate) fn replay(&self) -> DynErrResult<()> {
+        let backend = ProcessBackend::with_env(self.env.clone());
+        for command in &self.commands {
+            backend.execute(command)?;
+        }
+        Ok(())
+    }
+}
+
+/// Backend that adds the commands to a snapshot before running them with another backend.
+/// The snapshot is written after each command, so that it is available even if the run fails.
+pub(crate) struct SnapshotBackend {
+    /// Backend that runs the commands
+    inner: Box<dyn ExecutionBackend>,
+    /// Path the snapshot is written to
+    path: PathBuf,
+    /// Snapshot of the run so far
+    snapshot: RefCell<Snapshot>,
+}
+
+impl SnapshotBackend {
+    /// Returns a new backend that writes the snapshot to the given path
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: Backend that runs the commands
+    /// * `path`: Path the snapshot is written to
+    ///
+    /// returns: SnapshotBackend
+    pub(crate) fn new(inner: Box<dyn ExecutionBackend>, path: PathBuf) -> Self {
+        SnapshotBackend {
+            inner,
+            path,
+            snapshot: RefCell::new(Snapshot::new()),
+        }
+    }
+}
+
+impl ExecutionBackend for SnapshotBackend {
+    fn execute(&self, command: &RenderedCommand) -> DynErrResult<()> {
+        {
+            let mut snapshot = self.snapshot.borrow_mut();
+            snapshot.add(command);
+            snapshot.save(&self.path)?;
+        }
+        self.inner.execute(command)
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.inner.is_dry_run()
+    }
+
+    fn last_exit_code(&self) -> Option<i32> {
+        self.inner.last_exit_code()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::RecordBackend;
+    use crate::config_files::ConfigFile;
+    use crate::run_context::RunContext;
+    use crate::types::TaskArgs;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_snapshot() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        fs::write(
+            &config_path,
+            "tasks:\n  hello:\n    program: echo\n    args: [hello, '{$1}']\n    env: {A: a}\n",
+        )
+        .unwrap();
+        let snapshot_path = tmp_dir.path().join("snapshot.json");
+
+        let config_file = ConfigFile::load(config_path.clone()).unwrap();
+        let backend = SnapshotBackend::new(Box::new(RecordBackend::new()), snapshot_path.clone());
+        assert!(backend.is_dry_run());
+        let args = TaskArgs::from([(String::from("*"), vec!["world".into()])]);
+        config_file
+            .get_task("hello")
+            .unwrap()
+            .run_with_backend(&args, &config_file, &RunContext::new(), &backend)
+            .unwrap();
+
+        let snapshot = Snapshot::load(&snapshot_path).unwrap();
+        assert_eq!(snapshot, backend.snapshot.into_inner());
+        assert_eq!(snapshot.commands.len(), 1);
+        assert_eq!(snapshot.commands[0].args(), &["hello", "world"]);
+        assert_eq!(snapshot.commands[0].env().get("A").unwrap(), "a");
+        assert!(snapshot.config_files.contains_key(&config_path));
+        assert!(snapshot.changed_config_files().is_empty());
+
+        fs::write(&config_path, "tasks: {}\n").unwrap();
+        assert_eq!(snapshot.changed_config_files(), vec![config_path.as_path()]);
+
+        fs::write(&snapshot_path, "{}").unwrap();
+        assert!(Snapshot::load(&snapshot_path)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid snapshot at"));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::types::DynErrResult;
+use crate::utils::TMP_FOLDER_NAMESPACE;
+use directories::ProjectDirs;
+use md5::{Digest, Md5};
+use std::env::temp_dir;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories where the tasks of a project can store state, caches or temporal files. These
+/// are stable across runs, and only created when requested.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StateDirs {
+    /// Directory for persistent state
+    state: PathBuf,
+    /// Directory for caches, which can be deleted at any time
+    cache: PathBuf,
+    /// Directory for temporal files, which might be deleted by the OS
+    tmp: PathBuf,
+}
+
+/// Returns a directory name that identifies the project, made of the name of the project
+/// directory and a hash of its full path
+fn project_key(project_dir: &Path) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(project_dir.to_string_lossy().as_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    let name = project_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("root"));
+    format!("{}-{}", name, &hash[..16])
+}
+
+impl StateDirs {
+    /// Returns the state directories of the project in the given directory
+    ///
+    /// # Arguments
+    ///
+    /// * `project_dir`: Directory of the config file of the project
+    ///
+    /// returns: StateDirs
+    pub fn new(project_dir: &Path) -> Self {
+        let key = project_key(project_dir);
+        let tmp_root = temp_dir().join(TMP_FOLDER_NAMESPACE).join("projects");
+        let (state_root, cache_root) = match ProjectDirs::from("", "", "yamis") {
+            Some(proj_dirs) => (
+                proj_dirs.data_local_dir().join("state"),
+                proj_dirs.cache_dir().join("projects"),
+            ),
+            // Better than failing, although these might be deleted by the OS
+            None => (tmp_root.join("state"), tmp_root.join("cache")),
+        };
+        StateDirs {
+            state: state_root.join(&key),
+            cache: cache_root.join(&key),
+            tmp: tmp_root.join(&key),
+        }
+    }
+
+    /// Returns the directory with the given name, creating it if it does not exist
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: Name of the directory, either `state`, `cache` or `tmp`
+    ///
+    /// returns: Result<&Path, Box<dyn Error, Global>>
+    pub(crate) fn get(&self, name: &str) -> DynErrResult<&Path> {
+        let dir = match name {
+            "state" => &self.state,
+            "cache" => &self.cache,
+            "tmp" => &self.tmp,
+            _ => return Err(format!("Unknown directory `{}`", name).into()),
+        };
+        if let Err(e) = fs::create_dir_all(dir) {
+            return Err(format!("Could not create directory {}: {}", dir.display(), e).into());
+        }
+        Ok(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_state_dirs() {
+        let dirs = StateDirs::new(Path::new("/home/user/project"));
+        assert_eq!(dirs, StateDirs::new(Path::new("/home/user/project")));
+        assert_ne!(dirs, StateDirs::new(Path::new("/home/other/project")));
+        assert!(dirs
+            .tmp
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("project-"));
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dirs = StateDirs {
+            state: tmp_dir.path().join("state").join("project"),
+            cache: tmp_dir.path().join("cache").join("project"),
+            tmp: tmp_dir.path().join("tmp").join("project"),
+        };
+        for name in ["state", "cache", "tmp"] {
+            let dir = dirs.get(name).unwrap();
+            assert!(dir.is_dir());
+            assert!(dir.ends_with(Path::new(name).join("project")));
+        }
+        assert!(dirs.get("other").is_err());
+    }
+}
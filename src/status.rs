@@ -0,0 +1,254 @@
+//! Status of the current run, kept in a JSON file in the state directory of the project, i.e.
+//! `{dirs.state}/status.json`, so that external dashboards or shell prompts can poll it. The file
+//! is updated before and after each command, and finalized when the run ends.
+use crate::backend::ExecutionBackend;
+use crate::print_utils::YamisOutput;
+use crate::reports::ReportSettings;
+use crate::state_dirs::StateDirs;
+use crate::tasks::RenderedCommand;
+use crate::types::DynErrResult;
+use crate::utils::since_epoch;
+use serde_derive::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the status file, in the state directory of the project
+pub(crate) const STATUS_FILE_NAME: &str = "status.json";
+
+/// Status of a command of the run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct StepStatus {
+    /// Name of the task the command was rendered from
+    pub(crate) task: String,
+    /// Seconds since the Unix epoch when the command started
+    pub(crate) started_at: u64,
+    /// Seconds since the Unix epoch when the command finished, if it did
+    pub(crate) finished_at: Option<u64>,
+    /// Exit code of the command, if it finished with one
+    pub(crate) exit_code: Option<i32>,
+    /// Whether the command succeeded, if it finished
+    pub(crate) success: Option<bool>,
+}
+
+/// Status of a run, as stored in the status file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RunStatus {
+    /// Name of the task given in the command line
+    pub(crate) task: String,
+    /// Pid of the yamis process
+    pub(crate) pid: u32,
+    /// Seconds since the Unix epoch when the run started
+    pub(crate) started_at: u64,
+    /// Seconds since the Unix epoch when the run finished, if it did
+    pub(crate) finished_at: Option<u64>,
+    /// Whether the run succeeded, if it finished
+    pub(crate) success: Option<bool>,
+    /// Command running now, if any
+    pub(crate) current_step: Option<StepStatus>,
+    /// Commands that finished, in order
+    pub(crate) finished_steps: Vec<StepStatus>,
+}
+
+/// Returns the path of the status file of the project in the given directory, creating the
+/// state directory if it does not exist
+///
+/// # Arguments
+///
+/// * `project_dir`: Directory of the config file of the project
+///
+/// returns: Result<PathBuf, Box<dyn Error, Global>>
+pub(crate) fn status_file_path(project_dir: &Path) -> DynErrResult<PathBuf> {
+    Ok(StateDirs::new(project_dir)
+        .get("state")?
+        .join(STATUS_FILE_NAME))
+}
+
+/// Backend that keeps the status file of the project up to date while another backend runs the
+/// commands. The project is the one of the first command. Errors writing the status file are
+/// ignored, as it is not essential to run the task.
+pub(crate) struct StatusBackend {
+    /// Backend that runs the commands
+    inner: Box<dyn ExecutionBackend>,
+    /// Path of the status file, known once the first command runs
+    path: RefCell<Option<PathBuf>>,
+    /// Status of the run so far
+    status: RefCell<RunStatus>,
+    /// Where the report of the run is sent once it finishes, if anywhere
+    report: Option<ReportSettings>,
+}
+
+impl StatusBackend {
+    /// Returns a new backend that writes the status of the run of the given task
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: Backend that runs the commands
+    /// * `task`: Name of the task given in the command line
+    ///
+    /// returns: StatusBackend
+    pub(crate) fn new(inner: Box<dyn ExecutionBackend>, task: &str) -> Self {
+        StatusBackend {
+            inner,
+            path: RefCell::new(None),
+            status: RefCell::new(RunStatus {
+                task: String::from(task),
+                pid: std::process::id(),
+                started_at: since_epoch().as_secs(),
+                finished_at: None,
+                success: None,
+                current_step: None,
+                finished_steps: Vec::new(),
+            }),
+            report: None,
+        }
+    }
+
+    /// Returns the same backend, but sending the report of the run once it finishes as set in
+    /// the given settings
+    pub(crate) fn with_report(mut self, report: Option<ReportSettings>) -> Self {
+        self.report = report;
+        self
+    }
+
+    /// Writes the status file, if its path is known. The file is replaced at once, so that
+    /// readers never see it half written.
+    fn write(&self) {
+        let path = self.path.borrow();
+        let Some(path) = path.as_ref() else {
+            return;
+        };
+        let Ok(content) = serde_json::to_string_pretty(&*self.status.borrow()) else {
+            return;
+        };
+        // Named after the process, so that runs of other processes do not write to it
+        let tmp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        if fs::write(&tmp_path, content).is_ok() {
+            let _ = fs::rename(&tmp_path, path);
+        }
+    }
+}
+
+impl ExecutionBackend for StatusBackend {
+    fn execute(&self, command: &RenderedCommand) -> DynErrResult<()> {
+        if self.path.borrow().is_none() {
+            let project_dir = command
+                .config_file_path()
+                .parent()
+                .unwrap_or(Path::new("."));
+            *self.path.borrow_mut() = status_file_path(project_dir).ok();
+        }
+        self.status.borrow_mut().current_step = Some(StepStatus {
+            task: String::from(command.task_name()),
+            started_at: since_epoch().as_secs(),
+            finished_at: None,
+            exit_code: None,
+            success: None,
+        });
+        self.write();
+
+        let result = self.inner.execute(command);
+        {
+            let mut status = self.status.borrow_mut();
+            if let Some(mut step) = status.current_step.take() {
+                step.finished_at = Some(since_epoch().as_secs());
+                step.exit_code = self.inner.last_exit_code();
+                step.success = Some(result.is_ok());
+                status.finished_steps.push(step);
+            }
+        }
+        self.write();
+        result
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.inner.is_dry_run()
+    }
+
+    fn last_exit_code(&self) -> Option<i32> {
+        self.inner.last_exit_code()
+    }
+}
+
+impl Drop for StatusBackend {
+    /// Finalizes the status file, and sends the report of the run if set. The run succeeded if
+    /// its last command did, as tasks chained with `||` can recover from failures.
+    fn drop(&mut self) {
+        {
+            let mut status = self.status.borrow_mut();
+            status.finished_at = Some(since_epoch().as_secs());
+            status.success = match status.finished_steps.last() {
+                Some(step) => step.success,
+                None => Some(true),
+            };
+            // Set if the run ended while a command was running
+            if let Some(mut step) = status.current_step.take() {
+                step.finished_at = Some(since_epoch().as_secs());
+                step.success = Some(false);
+                status.finished_steps.push(step);
+                status.success = Some(false);
+            }
+        }
+        self.write();
+        if let Some(report) = &self.report {
+            if let Err(e) = report.report(&self.status.borrow()) {
+                eprintln!("{}", e.to_string().yamis_warn());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ProcessBackend;
+    use crate::config_files::ConfigFile;
+    use crate::run_context::RunContext;
+    use crate::types::TaskArgs;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_status_backend() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        fs::write(
+            &config_path,
+            "tasks:\n  first:\n    program: yamis::echo\n  second:\n    program: yamis::fail\n  all:\n    serial: [first, second]\n",
+        )
+        .unwrap();
+        let config_file = ConfigFile::load(config_path).unwrap();
+        let status_path = tmp_dir.path().join(STATUS_FILE_NAME);
+
+        let backend = StatusBackend::new(Box::new(ProcessBackend::new()), "all");
+        *backend.path.borrow_mut() = Some(status_path.clone());
+        let task = config_file.get_task("all").unwrap();
+        assert!(task
+            .run_with_backend(&TaskArgs::new(), &config_file, &RunContext::new(), &backend)
+            .is_err());
+
+        let status: RunStatus =
+            serde_json::from_str(&fs::read_to_string(&status_path).unwrap()).unwrap();
+        assert_eq!(status.task, "all");
+        assert_eq!(status.pid, std::process::id());
+        assert_eq!(status.finished_at, None);
+        assert_eq!(status.current_step, None);
+        let steps: Vec<_> = status
+            .finished_steps
+            .iter()
+            .map(|step| (step.task.as_str(), step.exit_code, step.success))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("first", Some(0), Some(true)),
+                ("second", Some(1), Some(false))
+            ]
+        );
+
+        drop(backend);
+        let status: RunStatus =
+            serde_json::from_str(&fs::read_to_string(&status_path).unwrap()).unwrap();
+        assert!(status.finished_at.is_some());
+        assert_eq!(status.success, Some(false));
+    }
+}
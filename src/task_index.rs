@@ -0,0 +1,366 @@
+use crate::config_files::ConfigFile;
+use crate::utils::to_os_task_name;
+use md5::{Digest, Md5};
+use std::fs;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[cfg(test)]
+use assert_fs::TempDir;
+#[cfg(not(test))]
+use directories::ProjectDirs;
+
+/// Name of the folder, inside the cache directory, where the indexes are stored
+const INDEX_FOLDER_NAME: &str = "task_index";
+
+/// Summary of a task stored in the index.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct IndexedTask {
+    /// Name of the task, including the OS suffix for OS specific tasks
+    pub(crate) name: String,
+    /// Whether the task is private
+    pub(crate) private: bool,
+    /// One line summary of the task
+    pub(crate) summary: String,
+}
+
+/// Cached list of tasks defined in a config file, so that unchanged files do not need to be
+/// parsed again to know which tasks they define. An index is only valid while the modification
+/// time and size of the config file stay the same, and its contents still have the same hash, as
+/// the modification time might not change if the file is edited quickly enough.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct TaskIndex {
+    /// Path of the config file
+    path: PathBuf,
+    /// Modification time of the config file, in nanoseconds since the unix epoch
+    modified: u128,
+    /// Size of the config file in bytes
+    len: u64,
+    /// MD5 hash of the contents of the config file
+    hash: String,
+    /// Tasks defined in the config file
+    tasks: Vec<IndexedTask>,
+}
+
+impl TaskIndex {
+    /// Loads the index for the config file in the given path. Returns None if there is no index
+    /// or if it is outdated.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_path`: Path of the config file
+    ///
+    /// returns: Option<TaskIndex>
+    pub(crate) fn load(config_path: &Path) -> Option<TaskIndex> {
+        let (modified, len) = Self::get_file_stamp(config_path)?;
+        let content = fs::read_to_string(Self::get_index_path(config_path)?).ok()?;
+        let index = Self::parse(&content)?;
+        if index.path == config_path
+            && index.modified == modified
+            && index.len == len
+            && index.hash == Self::get_file_hash(config_path)?
+        {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Stores the index for the given config file. Errors are ignored, as the index is only an
+    /// optimization.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file`: Loaded config file to store the index for
+    ///
+    /// returns: ()
+    pub(crate) fn store(config_file: &ConfigFile) {
+        let tasks = config_file
+            .loaded_tasks
+            .values()
+            .map(|task| IndexedTask {
+                name: String::from(task.get_name()),
+                private: task.is_private(),
+                summary: String::from(task.get_summary()),
+            })
+            .collect();
+        let _ = Self::write(&config_file.filepath, tasks);
+    }
+
+    /// Removes the index of the given config file, if any
+    ///
+    /// # Arguments
+    ///
+    /// * `config_path`: Path of the config file
+    pub(crate) fn remove(config_path: &Path) {
+        if let Some(index_path) = Self::get_index_path(config_path) {
+            let _ = fs::remove_file(index_path);
+        }
+    }
+
+    /// Writes the index with the given tasks for the config file in the given path
+    fn write(config_path: &Path, tasks: Vec<IndexedTask>) -> Option<()> {
+        let (modified, len) = Self::get_file_stamp(config_path)?;
+        let index = TaskIndex {
+            path: config_path.to_path_buf(),
+            modified,
+            len,
+            hash: Self::get_file_hash(config_path)?,
+            tasks,
+        };
+        let content = index.serialize()?;
+        let index_path = Self::get_index_path(config_path)?;
+        create_dir_all(index_path.parent()?).ok()?;
+        fs::write(index_path, content).ok()
+    }
+
+    /// Returns whether the config file defines a public task with the given name. Follows the
+    /// same rules as [ConfigFile::get_public_task].
+    ///
+    /// # Arguments
+    ///
+    /// * `task_name`: Name of the task
+    ///
+    /// returns: bool
+    pub(crate) fn has_public_task(&self, task_name: &str) -> bool {
+        let os_task_name = to_os_task_name(task_name);
+        let task = match self.get_task(&os_task_name) {
+            Some(task) => Some(task),
+            None => self.get_task(task_name),
+        };
+        matches!(task, Some(task) if !task.private)
+    }
+
+    /// Returns the list of names of tasks that are not private
+    pub(crate) fn get_public_task_names(&self) -> Vec<&str> {
+        self.tasks
+            .iter()
+            .filter(|task| !task.private)
+            .map(|task| task.name.as_str())
+            .collect()
+    }
+
+    /// Returns the names and summaries of the tasks that are not private
+    pub(crate) fn get_public_task_summaries(&self) -> Vec<(&str, &str)> {
+        self.tasks
+            .iter()
+            .filter(|task| !task.private)
+            .map(|task| (task.name.as_str(), task.summary.as_str()))
+            .collect()
+    }
+
+    /// Returns the indexed task with the given name
+    fn get_task(&self, task_name: &str) -> Option<&IndexedTask> {
+        self.tasks.iter().find(|task| task.name == task_name)
+    }
+
+    /// Serializes the index. The first four lines contain the path of the config file, its
+    /// modification time, its size and the hash of its contents, followed by a line for each task, containing `1` if it
+    /// is private or `0` otherwise, the name of the task and its summary, separated by tabs.
+    ///
+    /// Returns None if the index cannot be serialized, i.e. a name contains a new line.
+    fn serialize(&self) -> Option<String> {
+        let path = self.path.to_str()?;
+        let mut content = format!("{}\n{}\n{}\n{}\n", path, self.modified, self.len, self.hash);
+        for task in &self.tasks {
+            if task.name.contains(['\n', '\t'])
+                || task.summary.contains('\n')
+                || path.contains('\n')
+            {
+                return None;
+            }
+            let private = if task.private { "1" } else { "0" };
+            content.push_str(&format!("{}\t{}\t{}\n", private, task.name, task.summary));
+        }
+        Some(content)
+    }
+
+    /// Parses the index, returning None if it is invalid
+    fn parse(content: &str) -> Option<TaskIndex> {
+        let mut lines = content.lines();
+        let path = PathBuf::from(lines.next()?);
+        let modified = lines.next()?.parse().ok()?;
+        let len = lines.next()?.parse().ok()?;
+        let hash = String::from(lines.next()?);
+        if hash.len() != 32 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut tasks = Vec::new();
+        for line in lines {
+            let (private, rest) = line.split_once('\t')?;
+            let (name, summary) = rest.split_once('\t')?;
+            let private = match private {
+                "1" => true,
+                "0" => false,
+                _ => return None,
+            };
+            tasks.push(IndexedTask {
+                name: String::from(name),
+                private,
+                summary: String::from(summary),
+            });
+        }
+        Some(TaskIndex {
+            path,
+            modified,
+            len,
+            hash,
+            tasks,
+        })
+    }
+
+    /// Returns the modification time, in nanoseconds since the unix epoch, and size of the file
+    fn get_file_stamp(path: &Path) -> Option<(u128, u64)> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        Some((modified, metadata.len()))
+    }
+
+    /// Returns the MD5 hash of the contents of the file
+    fn get_file_hash(path: &Path) -> Option<String> {
+        let mut hasher = Md5::new();
+        hasher.update(fs::read(path).ok()?);
+        Some(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Returns the path of the index for the given config file, named after the hash of its path
+    fn get_index_path(config_path: &Path) -> Option<PathBuf> {
+        let mut hasher = Md5::new();
+        hasher.update(config_path.to_str()?.as_bytes());
+        let file_name = format!("{:x}", hasher.finalize());
+        Some(Self::get_index_dir()?.join(file_name))
+    }
+
+    /// Returns the directory where the indexes are stored
+    #[cfg(not(test))]
+    fn get_index_dir() -> Option<PathBuf> {
+        let proj_dir = ProjectDirs::from("", "", "yamis")?;
+        Some(proj_dir.cache_dir().join(INDEX_FOLDER_NAME))
+    }
+
+    /// Returns the directory where the indexes are stored
+    #[cfg(test)]
+    fn get_index_dir() -> Option<PathBuf> {
+        use lazy_static::lazy_static;
+        lazy_static! {
+            static ref INDEX_DIR: TempDir = TempDir::new().unwrap();
+        }
+        Some(INDEX_DIR.path().join(INDEX_FOLDER_NAME))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn test_store_and_load_index() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.toml");
+        let mut file = File::create(&config_path).unwrap();
+        file.write_all(
+            format!(
+                r#"
+    [tasks.hello]
+    script = "echo hello"
+    summary = "Says hello"
+
+    [tasks.hello.{os}]
+    script = "echo hello {os}"
+
+    [tasks.bye]
+    script = "echo bye"
+    private = true
+    "#,
+                os = std::env::consts::OS
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        drop(file);
+
+        assert!(TaskIndex::load(&config_path).is_none());
+
+        let config_file = ConfigFile::load(config_path.clone()).unwrap();
+        TaskIndex::store(&config_file);
+
+        let index = TaskIndex::load(&config_path).unwrap();
+        assert!(index.has_public_task("hello"));
+        assert!(!index.has_public_task("bye"));
+        assert!(!index.has_public_task("unknown"));
+        let mut names = index.get_public_task_names();
+        names.sort();
+        assert_eq!(names, vec!["hello".to_string(), to_os_task_name("hello")]);
+        assert!(index
+            .get_public_task_summaries()
+            .contains(&("hello", "Says hello")));
+
+        // The index is invalidated when the file changes
+        let mut file = File::options().append(true).open(&config_path).unwrap();
+        file.write_all(b"\n[tasks.other]\nscript = \"echo other\"\n")
+            .unwrap();
+        drop(file);
+        assert!(TaskIndex::load(&config_path).is_none());
+
+        // Or when its contents change without changing its size and modification time
+        let config_file = ConfigFile::load(config_path.clone()).unwrap();
+        TaskIndex::store(&config_file);
+        assert!(TaskIndex::load(&config_path).is_some());
+        let modified = fs::metadata(&config_path).unwrap().modified().unwrap();
+        let contents = fs::read_to_string(&config_path).unwrap();
+        fs::write(&config_path, contents.replace("other", "again")).unwrap();
+        File::options()
+            .write(true)
+            .open(&config_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert!(TaskIndex::load(&config_path).is_none());
+    }
+
+    #[test]
+    fn test_parse_invalid_index() {
+        let header = "path\n10\n20\n0123456789abcdef0123456789abcdef\n";
+        assert!(TaskIndex::parse("").is_none());
+        assert!(TaskIndex::parse(&header.replace("10", "not a number")).is_none());
+        assert!(TaskIndex::parse(&format!("{}2\thello\n", header)).is_none());
+        assert!(TaskIndex::parse(&format!("{}hello\n", header)).is_none());
+        // Indexes without summaries are outdated
+        assert!(TaskIndex::parse(&format!("{}0\thello\n", header)).is_none());
+        // Indexes without hashes are outdated
+        assert!(TaskIndex::parse("path\n10\n20\n0\thello\tSays hello\n").is_none());
+
+        let index =
+            TaskIndex::parse(&format!("{}0\thello\tSays hello\n1\tbye\t\n", header)).unwrap();
+        assert_eq!(
+            index,
+            TaskIndex {
+                path: PathBuf::from("path"),
+                modified: 10,
+                len: 20,
+                hash: String::from("0123456789abcdef0123456789abcdef"),
+                tasks: vec![
+                    IndexedTask {
+                        name: String::from("hello"),
+                        private: false,
+                        summary: String::from("Says hello"),
+                    },
+                    IndexedTask {
+                        name: String::from("bye"),
+                        private: true,
+                        summary: String::new(),
+                    },
+                ]
+            }
+        );
+        assert_eq!(TaskIndex::parse(&index.serialize().unwrap()), Some(index));
+    }
+}
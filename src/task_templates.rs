@@ -0,0 +1,193 @@
+//! Task templates generate a family of nearly identical tasks, one for each of their instances.
+//! Placeholders like `{{ param.service }}` are replaced by the values of the instance when the
+//! config file is loaded.
+use crate::tasks::Task;
+use crate::types::DynErrResult;
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+lazy_static! {
+    /// Matches placeholders, i.e. `{{ param.service }}`
+    static ref PLACEHOLDER_RE: Regex =
+        Regex::new(r"\{\{\s*param\.([A-Za-z0-9_-]+)\s*\}\}").unwrap();
+}
+
+/// Template of tasks, declared in the `task_templates` section of a config file
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct TaskTemplate {
+    /// Name of the generated tasks, which can contain placeholders. Defaults to the name of the
+    /// template followed by the values of the instance, separated by `-`.
+    #[serde(default)]
+    name: Option<String>,
+    /// Parameters of each generated task
+    instances: Vec<IndexMap<String, String>>,
+    /// Definition of the generated tasks, which can contain placeholders
+    #[serde(flatten)]
+    task: serde_json::Map<String, Value>,
+}
+
+/// Replaces the placeholders in the given string with the parameters of an instance
+///
+/// # Arguments
+///
+/// * `value`: String with placeholders
+/// * `params`: Parameters of the instance
+///
+/// returns: Result<String, Box<dyn Error, Global>>
+fn replace_placeholders(value: &str, params: &IndexMap<String, String>) -> DynErrResult<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut last_end = 0;
+    for captures in PLACEHOLDER_RE.captures_iter(value) {
+        let placeholder = captures.get(0).unwrap();
+        let param = &captures[1];
+        let param_value = match params.get(param) {
+            Some(param_value) => param_value,
+            None => return Err(format!("Unknown parameter `{}`", param).into()),
+        };
+        result.push_str(&value[last_end..placeholder.start()]);
+        result.push_str(param_value);
+        last_end = placeholder.end();
+    }
+    result.push_str(&value[last_end..]);
+    Ok(result)
+}
+
+/// Replaces the placeholders in all the strings and keys of the given value
+fn replace_value_placeholders(
+    value: &Value,
+    params: &IndexMap<String, String>,
+) -> DynErrResult<Value> {
+    Ok(match value {
+        Value::String(string) => Value::String(replace_placeholders(string, params)?),
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| replace_value_placeholders(value, params))
+                .collect::<DynErrResult<Vec<Value>>>()?,
+        ),
+        Value::Object(map) => {
+            let mut new_map = serde_json::Map::with_capacity(map.len());
+            for (key, value) in map {
+                new_map.insert(
+                    replace_placeholders(key, params)?,
+                    replace_value_placeholders(value, params)?,
+                );
+            }
+            Value::Object(new_map)
+        }
+        other => other.clone(),
+    })
+}
+
+impl TaskTemplate {
+    /// Returns the tasks generated by the template, in the order of the instances
+    ///
+    /// # Arguments
+    ///
+    /// * `template_name`: Name of the template
+    ///
+    /// returns: Result<Vec<(String, Task), Global>, Box<dyn Error, Global>>
+    pub(crate) fn generate(&self, template_name: &str) -> DynErrResult<Vec<(String, Task)>> {
+        let task_value = Value::Object(self.task.clone());
+        let mut tasks = Vec::with_capacity(self.instances.len());
+        for params in &self.instances {
+            let result = self.generate_one(template_name, &task_value, params);
+            match result {
+                Ok(task) => tasks.push(task),
+                Err(e) => {
+                    return Err(format!("task_templates.{}: {}", template_name, e).into());
+                }
+            }
+        }
+        Ok(tasks)
+    }
+
+    /// Returns the task generated for a single instance
+    fn generate_one(
+        &self,
+        template_name: &str,
+        task_value: &Value,
+        params: &IndexMap<String, String>,
+    ) -> DynErrResult<(String, Task)> {
+        let name = match &self.name {
+            Some(name) => replace_placeholders(name, params)?,
+            None => std::iter::once(template_name)
+                .chain(params.values().map(String::as_str))
+                .collect::<Vec<&str>>()
+                .join("-"),
+        };
+        let task: Task = serde_json::from_value(replace_value_placeholders(task_value, params)?)?;
+        Ok((name, task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_placeholders() {
+        let params = IndexMap::from([
+            (String::from("service"), String::from("api")),
+            (String::from("port"), String::from("8080")),
+        ]);
+        assert_eq!(
+            replace_placeholders(
+                "deploy {{ param.service }}:{{param.port}} {{hello}}",
+                &params
+            )
+            .unwrap(),
+            "deploy api:8080 {{hello}}"
+        );
+        assert_eq!(
+            replace_placeholders("{{ param.other }}", &params)
+                .unwrap_err()
+                .to_string(),
+            "Unknown parameter `other`"
+        );
+    }
+
+    #[test]
+    fn test_generate() {
+        let template: TaskTemplate = serde_yaml::from_str(
+            r#"
+instances:
+  - service: api
+  - service: web
+help: Deploys {{ param.service }}
+script: ./deploy.sh {{ param.service }}
+env:
+  "{{ param.service }}_DEPLOY": "1"
+"#,
+        )
+        .unwrap();
+        let tasks = template.generate("deploy").unwrap();
+        let names: Vec<&str> = tasks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["deploy-api", "deploy-web"]);
+        assert_eq!(tasks[1].1.get_help(), "Deploys web");
+
+        let template: TaskTemplate = toml::from_str(
+            r#"
+name = "{{ param.service }}-up"
+instances = [{service = "db"}]
+script = "echo {{ param.port }}"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            template.generate("up").unwrap_err().to_string(),
+            "task_templates.up: Unknown parameter `port`"
+        );
+
+        let template: TaskTemplate =
+            serde_yaml::from_str("instances: [{a: b}]\nunknown: 1").unwrap();
+        assert!(template
+            .generate("tpl")
+            .unwrap_err()
+            .to_string()
+            .starts_with("task_templates.tpl: unknown field `unknown`"));
+    }
+}
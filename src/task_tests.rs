@@ -0,0 +1,241 @@
+//! Tests declared in the `tests` section of config files and run with `yamis test`. Each test
+//! runs a task with sample arguments and environment variables against the record backend, and
+//! compares the commands it would run, or the error it fails with, against the expected ones, so
+//! that changes to complex templates do not break tasks unnoticed.
+use crate::backend::RecordBackend;
+use crate::config_files::ConfigFile;
+use crate::run_context::RunContext;
+use crate::tasks::RenderedCommand;
+use crate::types::TaskArgs;
+use crate::utils::{deserialize_optional_scalar_map, deserialize_scalar_map};
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+
+/// Command a task is expected to render. Only the given fields are compared.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExpectedCommand {
+    /// Program that runs the command, i.e. the script runner for scripts
+    program: Option<String>,
+    /// Arguments of the program
+    args: Option<Vec<String>>,
+    /// Content of the script, ignoring trailing whitespace
+    script: Option<String>,
+    /// Environment variables the command must have, among others
+    #[serde(default, deserialize_with = "deserialize_optional_scalar_map")]
+    env: Option<HashMap<String, String>>,
+}
+
+/// Test of a task, declared in the `tests` section of a config file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TaskTest {
+    /// Name of the task, which must not be private
+    task: String,
+    /// Arguments given to the task, as they would be given in the command line
+    #[serde(default)]
+    args: Vec<String>,
+    /// Environment variables given to the task, as if they were given with `--env-file`
+    #[serde(default, deserialize_with = "deserialize_scalar_map")]
+    env: HashMap<String, String>,
+    /// Commands the task is expected to render, in order
+    commands: Option<Vec<ExpectedCommand>>,
+    /// Text the error of the task is expected to contain, if it is expected to fail
+    error: Option<String>,
+}
+
+impl ExpectedCommand {
+    /// Returns why the command does not match the expected one, if it does not
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: Index of the command, used in the messages
+    /// * `command`: Command rendered by the task
+    ///
+    /// returns: Result<(), String>
+    fn compare(&self, index: usize, command: &RenderedCommand) -> Result<(), String> {
+        if let Some(program) = &self.program {
+            if program != command.program() {
+                return Err(format!(
+                    "commands[{}].program: expected `{}`, got `{}`",
+                    index,
+                    program,
+                    command.program()
+                ));
+            }
+        }
+        if let Some(args) = &self.args {
+            let actual: Vec<String> = command
+                .args()
+                .iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect();
+            if args != &actual {
+                return Err(format!(
+                    "commands[{}].args: expected {:?}, got {:?}",
+                    index, args, actual
+                ));
+            }
+        }
+        if let Some(script) = &self.script {
+            let actual = command.script().unwrap_or_default().trim_end();
+            if script.trim_end() != actual {
+                return Err(format!(
+                    "commands[{}].script: expected\n{}\ngot\n{}",
+                    index,
+                    script.trim_end(),
+                    actual
+                ));
+            }
+        }
+        for (key, value) in self.env.iter().flatten() {
+            match command.env().get(key) {
+                Some(actual) if actual == value => {}
+                Some(actual) => {
+                    return Err(format!(
+                        "commands[{}].env.{}: expected `{}`, got `{}`",
+                        index, key, value, actual
+                    ))
+                }
+                None => {
+                    return Err(format!(
+                        "commands[{}].env.{}: expected `{}`, but it is not set",
+                        index, key, value
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TaskTest {
+    /// Returns the arguments given to the task, to be parsed as if they were given in the
+    /// command line
+    pub(crate) fn get_args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Runs the task against the record backend and returns why the test failed, if it did
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file`: Config file the test was declared in
+    /// * `args`: Arguments given to the task, parsed
+    ///
+    /// returns: Result<(), String>
+    pub(crate) fn run(&self, config_file: &ConfigFile, args: &TaskArgs) -> Result<(), String> {
+        if self.commands.is_some() && self.error.is_some() {
+            return Err(String::from(
+                "`commands` and `error` cannot be given at the same time",
+            ));
+        }
+        let task = config_file
+            .get_public_task(&self.task)
+            .ok_or_else(|| format!("Task `{}` not found", self.task))?;
+
+        // Each test starts from scratch, as if yamis was called once per test
+        let context = RunContext::new()
+            .with_dry_run(true)
+            .with_env_overrides(self.env.clone());
+
+        let backend = RecordBackend::new();
+        let result = task
+            .check_strict_args(args, config_file, &context)
+            .and_then(|_| task.run_with_backend(args, config_file, &context, &backend));
+        match (result, &self.error) {
+            (Ok(_), Some(expected)) => Err(format!(
+                "expected the task to fail with `{}`, but it succeeded",
+                expected
+            )),
+            (Err(e), Some(expected)) if !e.to_string().contains(expected.as_str()) => Err(format!(
+                "expected the task to fail with `{}`, got:\n{}",
+                expected, e
+            )),
+            (Err(_), Some(_)) => Ok(()),
+            (Err(e), None) => Err(format!("the task failed:\n{}", e)),
+            (Ok(_), None) => {
+                let Some(expected) = &self.commands else {
+                    return Ok(());
+                };
+                let commands = backend.into_commands();
+                if commands.len() != expected.len() {
+                    return Err(format!(
+                        "expected {} commands, got {}",
+                        expected.len(),
+                        commands.len()
+                    ));
+                }
+                for (index, (expected, command)) in expected.iter().zip(&commands).enumerate() {
+                    expected.compare(index, command)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_task_tests() {
+        let mut config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  deploy:
+    program: kubectl
+    args: [apply, -f, "{$1}"]
+    env:
+      REGION: "{$REGION?}"
+    params:
+      replicas: int
+tests:
+  deploy_prod:
+    task: deploy
+    args: [prod]
+    env:
+      REGION: eu
+    commands:
+      - program: kubectl
+        args: [apply, -f, prod]
+        env:
+          REGION: eu
+  deploy_bad_replicas:
+    task: deploy
+    args: [prod, --replicas, many]
+    error: "--replicas"
+  deploy_wrong:
+    task: deploy
+    args: [dev]
+    commands:
+      - args: [apply, -f, prod]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let tests = std::mem::take(&mut config_file.tests);
+        let args = TaskArgs::from([(String::from("*"), vec![OsString::from("prod")])]);
+        assert_eq!(tests["deploy_prod"].run(&config_file, &args), Ok(()));
+
+        let args = TaskArgs::from([
+            (String::from("*"), vec![OsString::from("prod")]),
+            (String::from("replicas"), vec![OsString::from("many")]),
+        ]);
+        assert_eq!(
+            tests["deploy_bad_replicas"].run(&config_file, &args),
+            Ok(())
+        );
+
+        let args = TaskArgs::from([(String::from("*"), vec![OsString::from("dev")])]);
+        assert_eq!(
+            tests["deploy_wrong"].run(&config_file, &args),
+            Err(String::from(
+                r#"commands[0].args: expected ["apply", "-f", "prod"], got ["apply", "-f", "dev"]"#
+            ))
+        );
+    }
+}
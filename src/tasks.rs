@@ -1,21 +1,40 @@
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::env;
 use std::env::temp_dir;
+use std::ffi::OsString;
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::sync::Arc;
-use std::{error, fmt, fs, mem};
+use std::process::Command;
+use std::time::Instant;
+use std::{error, fmt, fs};
 
+use crate::backend::{ExecutionBackend, ProcessBackend};
+use crate::builtins::Builtin;
 use crate::config_files::ConfigFile;
 use crate::debug_config::{ConcreteTaskDebugConfig, TaskDebugConfig};
-use crate::defaults::default_false;
-use crate::parser::{parse_params, parse_script, EscapeMode};
-use crate::print_utils::YamisOutput;
-use serde_derive::Deserialize;
+use crate::defaults::{default_false, default_true};
+use crate::escape::{EscapeMode, ShellKind};
+use crate::expect_duration::ExpectDuration;
+use crate::fingerprints::{validate_patterns, Fingerprint, FingerprintMethod};
+use crate::lock_files::TaskLock;
+use crate::nesting;
+use crate::params::{coerce_args, ParamType};
+use crate::parser::{parse_params, parse_script, TaskVars};
+use crate::print_utils::{theme, YamisOutput};
+use crate::run_context::RunContext;
+use crate::sandbox::{get_sandbox_prefix, SandboxMode};
+use serde_derive::{Deserialize, Serialize};
 
 use crate::types::{DynErrResult, TaskArgs};
-use crate::utils::{get_path_relative_to_base, read_env_file, TMP_FOLDER_NAMESPACE};
+use crate::utils::{
+    deserialize_exit_code_map, deserialize_optional_unique_map, deserialize_os_strings,
+    deserialize_scalar_map, deserialize_unique_map, get_path_relative_to_base, is_in_path,
+    read_env_file, serialize_os_strings_lossy, TMP_FOLDER_NAMESPACE,
+};
+use indexmap::IndexMap;
 use md5::{Digest, Md5};
 
 cfg_if::cfg_if! {
@@ -35,28 +54,184 @@ cfg_if::cfg_if! {
 }
 
 /// Task errors
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug)]
 pub enum TaskError {
     /// Raised when there is an error running a task
     RuntimeError(String, String),
     /// Raised when the task is improperly configured
     ImproperlyConfigured(String, String),
+    /// Raised when a task of a serial task fails, with the name of the serial task, the index
+    /// of the failed task and the error it raised
+    SerialError(String, usize, Box<dyn error::Error>),
 }
 
 impl fmt::Display for TaskError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             TaskError::RuntimeError(ref name, ref reason) => {
-                write!(f, "Error running tasks.{}:\n{}", name, reason)
+                let header = &theme().messages.error_running;
+                write!(f, "{} tasks.{}:\n{}", header, name, reason)
             }
             TaskError::ImproperlyConfigured(ref name, ref reason) => {
-                write!(f, "Improperly configured tasks.{}:\n{}", name, reason)
+                let header = &theme().messages.improperly_configured;
+                write!(f, "{} tasks.{}:\n{}", header, name, reason)
             }
+            TaskError::SerialError(ref name, index, ref source) => {
+                // Nested serial tasks are displayed as a single chain that ends in the task
+                // that failed, i.e. tasks.deploy.serial[2] → tasks.migrate
+                let messages = &theme().messages;
+                let mut chain = vec![format!("tasks.{}.serial[{}]", name, index)];
+                let mut source: &(dyn error::Error + 'static) = source.as_ref();
+                loop {
+                    match source.downcast_ref::<TaskError>() {
+                        Some(TaskError::SerialError(name, index, inner)) => {
+                            chain.push(format!("tasks.{}.serial[{}]", name, index));
+                            source = inner.as_ref();
+                        }
+                        Some(TaskError::RuntimeError(name, reason)) => {
+                            let chain = chain.join(" → ");
+                            return write!(
+                                f,
+                                "{} {} → tasks.{}:\n{}",
+                                messages.error_running, chain, name, reason
+                            );
+                        }
+                        Some(TaskError::ImproperlyConfigured(name, reason)) => {
+                            let chain = chain.join(" → ");
+                            return write!(
+                                f,
+                                "{} {} → tasks.{}:\n{}",
+                                messages.improperly_configured, chain, name, reason
+                            );
+                        }
+                        None => {
+                            let chain = chain.join(" → ");
+                            return write!(f, "{} {}:\n{}", messages.error_running, chain, source);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl error::Error for TaskError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TaskError::SerialError(_, _, source) => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Program of a task
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+enum Program {
+    /// Single program
+    Single(String),
+    /// List of programs, from which the first one found is used
+    Fallbacks(Vec<String>),
+}
+
+/// Operating system a step of a serial task can be restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Os {
+    Linux,
+    Macos,
+    Windows,
+}
+
+impl Os {
+    /// Returns whether this is the current operating system
+    fn is_current(&self) -> bool {
+        match self {
+            Os::Linux => std::env::consts::OS == "linux",
+            Os::Macos => std::env::consts::OS == "macos",
+            Os::Windows => std::env::consts::OS == "windows",
         }
     }
 }
 
-impl error::Error for TaskError {}
+/// Step of a serial task that only runs in some operating systems
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OsSerialStep {
+    /// Name of the task to run
+    task: String,
+    /// Operating systems the task runs in, it is skipped in the others
+    os: Vec<Os>,
+}
+
+/// Step of a serial task
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum SerialStep {
+    /// Name of the task to run. The variant of the current OS is used if there is one, unless
+    /// a variant is given explicitly, i.e. `build.windows`
+    Task(String),
+    /// Task that only runs in some operating systems, i.e. `{task: notarize, os: [macos]}`
+    Os(OsSerialStep),
+}
+
+impl SerialStep {
+    /// Returns the name of the task to run, or None if the step is skipped in the current OS
+    fn get_task_name(&self) -> Option<&str> {
+        match self {
+            SerialStep::Task(task_name) => Some(task_name),
+            SerialStep::Os(step) if step.os.iter().any(Os::is_current) => Some(&step.task),
+            SerialStep::Os(_) => None,
+        }
+    }
+}
+
+/// Argument of a program task
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum TaskArg {
+    /// Single argument, which can contain tags
+    Value(String),
+    /// Arguments of the snippet with the given name, given as `{use: NAME}`
+    Snippet {
+        /// Name of the snippet
+        #[serde(rename = "use")]
+        name: String,
+    },
+}
+
+/// Where the value of an environment variable of a task comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum EnvOrigin {
+    /// `env` of the task, including the one of the current OS
+    TaskEnv,
+    /// `env_file` of the task
+    TaskEnvFile,
+    /// Inherited from the given base task
+    BaseTask(String),
+    /// `env` of the config file
+    FileEnv,
+    /// `env_file` of the config file
+    FileEnvFile,
+    /// `path` of the config file, prepended to the PATH
+    FilePath,
+    /// Env files given with `--env-file`
+    Cli,
+}
+
+impl fmt::Display for EnvOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvOrigin::TaskEnv => write!(f, "task env"),
+            EnvOrigin::TaskEnvFile => write!(f, "task env_file"),
+            EnvOrigin::BaseTask(name) => write!(f, "base task {}", name),
+            EnvOrigin::FileEnv => write!(f, "file env"),
+            EnvOrigin::FileEnvFile => write!(f, "file env_file"),
+            EnvOrigin::FilePath => write!(f, "file path"),
+            EnvOrigin::Cli => write!(f, "--env-file"),
+        }
+    }
+}
 
 /// Represents a Task
 #[derive(Debug, Deserialize)]
@@ -68,10 +243,16 @@ pub struct Task {
     debug_config: Option<TaskDebugConfig>,
     /// Help of the task
     help: Option<String>,
+    /// One line summary of the task, displayed when listing the tasks
+    summary: Option<String>,
     /// Whether to automatically quote argument with spaces
     quote: Option<EscapeMode>,
     /// Script to run
     script: Option<String>,
+    /// Script file to run, relative to the config file directory
+    script_file: Option<String>,
+    /// Whether to render the script file with the task arguments, variables and functions
+    template: Option<bool>,
     /// Interpreter program to use
     script_runner: Option<String>,
     /// Extra arguments to pass to the script runner
@@ -79,18 +260,59 @@ pub struct Task {
     /// Script extension
     #[serde(alias = "script_extension")]
     script_ext: Option<String>,
-    /// A program to run
-    program: Option<String>,
+    /// A program to run, or a list of programs to pick the first one found
+    program: Option<Program>,
     /// Args to pass to a command
-    args: Option<Vec<String>>,
+    args: Option<Vec<TaskArg>>,
     /// Extends args from bases
     #[serde(alias = "args+")]
-    args_extend: Option<Vec<String>>,
+    args_extend: Option<Vec<TaskArg>>,
     /// If given, runs all those tasks at once
-    serial: Option<Vec<String>>,
+    serial: Option<Vec<SerialStep>>,
+    /// Exit codes that are considered successful, `[0]` by default
+    allowed_exit_codes: Option<Vec<i32>>,
+    /// Messages to display for specific exit codes
+    #[serde(default, deserialize_with = "deserialize_exit_code_map")]
+    exit_code_map: Option<HashMap<i32, String>>,
     /// Env variables for the task
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_scalar_map")]
     pub(crate) env: HashMap<String, String>,
+    /// Documentation of the environment variables the task uses
+    #[serde(default, deserialize_with = "deserialize_unique_map")]
+    env_docs: IndexMap<String, String>,
+    /// Types of the named arguments, which are validated and normalized before rendering
+    #[serde(default, deserialize_with = "deserialize_optional_unique_map")]
+    params: Option<IndexMap<String, ParamType>>,
+    /// Env variables merged over `env` if the OS is linux
+    #[serde(
+        default,
+        rename = "env.linux",
+        deserialize_with = "deserialize_scalar_map"
+    )]
+    env_linux: HashMap<String, String>,
+    /// Env variables merged over `env` if the OS is windows
+    #[serde(
+        default,
+        rename = "env.windows",
+        deserialize_with = "deserialize_scalar_map"
+    )]
+    env_windows: HashMap<String, String>,
+    /// Env variables merged over `env` if the OS is macos
+    #[serde(
+        default,
+        rename = "env.macos",
+        deserialize_with = "deserialize_scalar_map"
+    )]
+    env_macos: HashMap<String, String>,
+    /// Args that replace `args` if the OS is linux
+    #[serde(rename = "args.linux")]
+    args_linux: Option<Vec<TaskArg>>,
+    /// Args that replace `args` if the OS is windows
+    #[serde(rename = "args.windows")]
+    args_windows: Option<Vec<TaskArg>>,
+    /// Args that replace `args` if the OS is macos
+    #[serde(rename = "args.macos")]
+    args_macos: Option<Vec<TaskArg>>,
     /// Env file to read environment variables from
     env_file: Option<String>,
     /// Working dir
@@ -104,9 +326,57 @@ pub struct Task {
     /// Base task to inherit from
     #[serde(default)]
     pub(crate) bases: Vec<String>,
+    /// Tasks that run before this one, each only once per invocation
+    deps: Option<Vec<String>>,
     /// If private, it cannot be called
     #[serde(default = "default_false")]
     private: bool,
+    /// If true, the task runs every time it is referenced, even if it already ran with the
+    /// same inputs
+    run_always: Option<bool>,
+    /// If true, the task can run while another yamis process runs the same task of the same
+    /// config file
+    reentrant: Option<bool>,
+    /// If false, the program, arguments and environment of the task are not echoed, i.e. in dry
+    /// runs, as they might contain secrets
+    echo_command: Option<bool>,
+    /// If true, the arguments of the task and its name are exported to the environment of
+    /// its commands, as `YAMIS_ARG_<N>`, `YAMIS_KW_<NAME>` and `YAMIS_TASK`
+    export_args_as_env: Option<bool>,
+    /// If true, running the task fails if it is given arguments that are neither declared in
+    /// `params` nor used by its templates, or the ones of its serial tasks
+    strict_args: Option<bool>,
+    /// Restrictions applied to the commands of the task, i.e. `read_only` so that they cannot
+    /// modify the directory of the config file
+    sandbox: Option<SandboxMode>,
+    /// Lock file acquired before running the task, which can be shared across machines
+    lock: Option<TaskLock>,
+    /// Time the task is expected to take. The task fails, or a warning is printed, if it
+    /// finishes faster or takes longer
+    expect_duration: Option<ExpectDuration>,
+    /// Glob patterns of the files the task reads, relative to the config file directory
+    sources: Option<Vec<String>>,
+    /// Glob patterns of the files the task writes, relative to the config file directory
+    outputs: Option<Vec<String>>,
+    /// How the task decides whether it is up to date, given `sources`
+    fingerprint: Option<FingerprintMethod>,
+    /// Glob patterns of the files that run the task again when they change, with `--watch`,
+    /// relative to the config file directory. Defaults to `sources`
+    watch: Option<Vec<String>>,
+    /// If true, references to environment variables written for other shells in the script,
+    /// i.e. `%HOME%`, are translated to the syntax of the shell that runs it
+    translate_env_refs: Option<bool>,
+    /// If true, the rendered script is written to `.yamis/scripts/<task>.<ext>` in the config
+    /// file directory, instead of a temporal file named after its hash
+    stable_script_path: Option<bool>,
+    /// Names of the tasks inherited from, directly or through other bases, in order of
+    /// precedence
+    #[serde(skip)]
+    ancestors: Vec<String>,
+    /// Where the variables in `env` come from, as the ones of `env_file` and the base tasks
+    /// are merged into it
+    #[serde(skip)]
+    env_origins: HashMap<String, EnvOrigin>,
 }
 
 cfg_if::cfg_if! {
@@ -121,6 +391,7 @@ cfg_if::cfg_if! {
             Ok(OpenOptions::new()
             .create(true)
             .write(true)
+            .truncate(true)
             .mode(0o770)  // Create with appropriate permission
             .open(path)?)
         }
@@ -154,7 +425,8 @@ fn get_temp_script(
     // get md5 hash of the task_name, config_file_path and content
     let mut hasher = Md5::new();
     hasher.update(task_name.as_bytes());
-    hasher.update(config_file_path.to_str().unwrap().as_bytes());
+    // The path is not necessarily valid unicode, i.e. if it is under a non-unicode home dir
+    hasher.update(config_file_path.to_string_lossy().as_bytes());
     hasher.update(content.as_bytes());
     let hash = hasher.finalize();
 
@@ -171,6 +443,334 @@ fn get_temp_script(
     Ok(path)
 }
 
+/// Writes the script to the given path, so that it can be found at the same place between runs,
+/// i.e. by debuggers. The file is not written again if its content did not change.
+///
+/// # Arguments
+///
+/// * `content` - Content of the script file
+/// * `path` - Path to write the script to
+fn write_stable_script(content: &str, path: &Path) -> DynErrResult<()> {
+    if fs::read_to_string(path).is_ok_and(|current| current == content) {
+        return Ok(());
+    }
+    fs::create_dir_all(path.parent().unwrap())?;
+    // Replaced at once, so that a script that is still running is not modified
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(".{}.tmp", std::process::id()));
+    let mut file = create_script_file(&tmp_path)?;
+    file.write_all(content.as_bytes())?;
+    drop(file);
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Returns the name of the script file of a task, replacing the characters that are not valid
+/// in file names in some OSs
+///
+/// # Arguments
+///
+/// * `task_name` - Name of the task
+/// * `extension` - Extension of the script, with or without the leading dot
+fn get_stable_script_name(task_name: &str, extension: &str) -> String {
+    let name: String = task_name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    let extension = extension.trim_start_matches('.');
+    if extension.is_empty() {
+        name
+    } else {
+        format!("{}.{}", name, extension)
+    }
+}
+
+/// Script rendered from a task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct RenderedScript {
+    /// Content of the script
+    content: String,
+    /// Extension of the script file
+    extension: String,
+    /// Path the script is written to, if it is stable instead of a temporal file
+    #[serde(default)]
+    path: Option<PathBuf>,
+}
+
+/// Command rendered from a task, with the arguments, environment variables and working
+/// directory it runs with, but not spawned yet. It can be serialized to replay it later.
+/// Arguments that are not valid unicode are serialized lossily.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderedCommand {
+    /// Name of the task the command was rendered from
+    task_name: String,
+    /// Program to run, or script runner for scripts
+    program: String,
+    /// Arguments passed to the program. For scripts, these are the script runner arguments
+    /// and do not include the path of the script file.
+    #[serde(
+        serialize_with = "serialize_os_strings_lossy",
+        deserialize_with = "deserialize_os_strings"
+    )]
+    args: Vec<OsString>,
+    /// Rendered script, for script tasks
+    script: Option<RenderedScript>,
+    /// Environment variables of the task
+    env: HashMap<String, String>,
+    /// Working directory
+    wd: Option<PathBuf>,
+    /// Path of the config file the task was declared in
+    config_file_path: PathBuf,
+    /// Exit codes that are considered successful
+    allowed_exit_codes: Vec<i32>,
+    /// Messages to display for specific exit codes
+    exit_code_map: HashMap<i32, String>,
+    /// Whether the program, arguments and environment can be echoed
+    #[serde(default = "default_true")]
+    echo: bool,
+    /// Restrictions applied to the command
+    #[serde(default)]
+    sandbox: Option<SandboxMode>,
+    /// Dependencies that already ran in the config file when the command was rendered, which
+    /// yamis invocations in the command do not run again
+    #[serde(default)]
+    executed_deps: Vec<String>,
+}
+
+impl RenderedCommand {
+    /// Returns the name of the task the command was rendered from
+    pub fn task_name(&self) -> &str {
+        &self.task_name
+    }
+
+    /// Returns the program to run, or the script runner for scripts
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Returns the arguments passed to the program. For scripts, the path of the script
+    /// file is not included.
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// Returns the rendered script, for script tasks
+    pub fn script(&self) -> Option<&str> {
+        self.script.as_ref().map(|script| script.content.as_str())
+    }
+
+    /// Returns the extension of the script file, for script tasks
+    pub fn script_extension(&self) -> Option<&str> {
+        self.script.as_ref().map(|script| script.extension.as_str())
+    }
+
+    /// Returns the environment variables of the task
+    pub fn env(&self) -> &HashMap<String, String> {
+        &self.env
+    }
+
+    /// Returns the working directory, if any
+    pub fn wd(&self) -> Option<&Path> {
+        self.wd.as_deref()
+    }
+
+    /// Returns the path of the config file the task was declared in
+    pub fn config_file_path(&self) -> &Path {
+        &self.config_file_path
+    }
+
+    /// Returns whether the program, arguments and environment can be echoed
+    pub fn echo(&self) -> bool {
+        self.echo
+    }
+
+    /// Returns the exit codes that are considered successful
+    pub(crate) fn allowed_exit_codes(&self) -> &[i32] {
+        &self.allowed_exit_codes
+    }
+
+    /// Returns the messages to display for specific exit codes
+    pub(crate) fn exit_code_map(&self) -> &HashMap<i32, String> {
+        &self.exit_code_map
+    }
+
+    /// Checks the exit code of the command. Returns the message mapped to the exit code, if any,
+    /// when it is allowed, or an error otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Exit code of the command
+    ///
+    /// returns: Result<Option<&str>, Box<dyn Error, Global>>
+    pub fn check_exit_code(&self, code: i32) -> DynErrResult<Option<&str>> {
+        let message = self.exit_code_map.get(&code);
+        if self.allowed_exit_codes.contains(&code) {
+            return Ok(message.map(|message| message.as_str()));
+        }
+        let reason = match message {
+            Some(message) => format!("{} (exit code {})", message, code),
+            None => format!("Process terminated with exit code {}", code),
+        };
+        Err(TaskError::RuntimeError(self.task_name.clone(), reason).into())
+    }
+
+    /// Returns the restrictions applied to the command, if any
+    pub(crate) fn sandbox(&self) -> Option<SandboxMode> {
+        self.sandbox
+    }
+
+    /// Returns the built-in command to run instead of spawning a process, if the program of a
+    /// program task is one, i.e. `yamis::echo`
+    pub(crate) fn builtin(&self) -> Option<Builtin> {
+        if self.script.is_some() {
+            return None;
+        }
+        Builtin::from_program(&self.program).ok().flatten()
+    }
+
+    /// Returns the environment variables exported to the command, besides the ones of the task,
+    /// so that yamis invocations in it find the same config file: `YAMIS_FILE`, the absolute
+    /// path of the config file, `YAMIS_PROJECT_ROOT`, its directory, and `YAMIS_TASK`, the name
+    /// of the task. `YAMIS_DEPTH` and `YAMIS_EXECUTED_DEPS` let them know they are nested, and
+    /// which dependencies do not need to run again. The environment of the task takes
+    /// precedence over them.
+    pub(crate) fn get_exported_env(&self) -> HashMap<String, String> {
+        let config_file_path = std::path::absolute(&self.config_file_path)
+            .unwrap_or_else(|_| self.config_file_path.clone());
+        let project_root = config_file_path.parent().unwrap_or(Path::new(""));
+        HashMap::from([
+            (
+                String::from("YAMIS_FILE"),
+                config_file_path.to_string_lossy().into_owned(),
+            ),
+            (
+                String::from("YAMIS_PROJECT_ROOT"),
+                project_root.to_string_lossy().into_owned(),
+            ),
+            (String::from("YAMIS_TASK"), self.task_name.clone()),
+            (
+                String::from(nesting::DEPTH_ENV),
+                (nesting::get_depth() + 1).to_string(),
+            ),
+            (
+                String::from(nesting::EXECUTED_DEPS_ENV),
+                self.executed_deps.join("\n"),
+            ),
+        ])
+    }
+
+    /// Builds the command to spawn. For scripts, this writes the script to a temporal file,
+    /// which is passed as the last argument. If the task sets a sandbox, the command is run
+    /// inside it, or without it after a warning if it is not available.
+    pub(crate) fn to_command(&self) -> DynErrResult<Command> {
+        let mut command = match self.get_sandbox_prefix() {
+            Some(prefix) => {
+                let mut command = Command::new(&prefix[0]);
+                command.args(&prefix[1..]).arg(&self.program);
+                command
+            }
+            None => Command::new(&self.program),
+        };
+        command.args(&self.args);
+        if let Some(script) = &self.script {
+            let script_file = match &script.path {
+                Some(path) => {
+                    write_stable_script(&script.content, path)?;
+                    path.clone()
+                }
+                None => get_temp_script(
+                    &script.content,
+                    &script.extension,
+                    &self.task_name,
+                    &self.config_file_path,
+                )?,
+            };
+            command.arg(script_file);
+        }
+        command.envs(self.get_exported_env());
+        command.envs(&self.env);
+        if let Some(wd) = &self.wd {
+            command.current_dir(wd);
+        }
+        Ok(command)
+    }
+}
+
+impl RenderedCommand {
+    /// Returns the program and arguments that run the command inside its sandbox, if it has
+    /// one and it is available. Prints a warning if it is not available.
+    fn get_sandbox_prefix(&self) -> Option<Vec<OsString>> {
+        let mode = self.sandbox?;
+        let project_dir = self.config_file_path.parent().unwrap_or(Path::new("."));
+        let path_var = env::var_os("PATH").unwrap_or_default();
+        match get_sandbox_prefix(mode, project_dir, &path_var) {
+            Ok(prefix) => Some(prefix),
+            Err(reason) => {
+                let msg = format!(
+                    "Task {} runs without the {} sandbox, {}",
+                    self.task_name, mode, reason
+                );
+                eprintln!("{}", msg.yamis_warn());
+                None
+            }
+        }
+    }
+}
+
+/// Returns the environment variables that export the arguments and name of a task. Positional
+/// arguments are exported as `YAMIS_ARG_1` to `YAMIS_ARG_<N>`, like `$1` to `$N`, named
+/// arguments as `YAMIS_KW_<NAME>`, in uppercase and with `-` replaced by `_`, and the name
+/// of the task as `YAMIS_TASK`. Named arguments given multiple times are joined with newlines.
+///
+/// # Arguments
+///
+/// * `task_name`: Name of the task
+/// * `args`: Arguments given to the task
+///
+/// returns: HashMap<String, String, RandomState>
+fn get_args_env(task_name: &str, args: &TaskArgs) -> HashMap<String, String> {
+    let mut env = HashMap::from([(String::from("YAMIS_TASK"), String::from(task_name))]);
+    for (name, values) in args {
+        let values = values.iter().map(|value| value.to_string_lossy());
+        if name == "*" {
+            for (i, value) in values.enumerate() {
+                env.insert(format!("YAMIS_ARG_{}", i + 1), value.to_string());
+            }
+        } else {
+            let name = format!("YAMIS_KW_{}", name.to_uppercase().replace('-', "_"));
+            env.insert(name, values.collect::<Vec<_>>().join("\n"));
+        }
+    }
+    env
+}
+
+/// Returns whether the program exists. Programs with a path are checked directly, while
+/// programs without a path are looked up in the PATH of the task, or the current one.
+///
+/// # Arguments
+///
+/// * `program` - Program to check
+/// * `env` - Environment variables of the task
+fn program_exists(program: &str, env: &HashMap<String, String>) -> bool {
+    if matches!(Builtin::from_program(program), Ok(Some(_))) {
+        return true;
+    }
+    let path = Path::new(program);
+    if path.is_absolute() || path.components().count() > 1 {
+        return path.exists();
+    }
+    match env.get("PATH") {
+        Some(path_var) => is_in_path(program, path_var),
+        None => match std::env::var_os("PATH") {
+            Some(path_var) => is_in_path(program, &path_var),
+            None => false,
+        },
+    }
+}
+
 /// Shortcut to inherit values from the task
 macro_rules! inherit_value {
     ( $from_task:expr, $from_base:expr ) => {
@@ -192,10 +792,34 @@ impl Task {
     ///
     pub(crate) fn setup(&mut self, name: &str, base_path: &Path) -> DynErrResult<()> {
         self.name = String::from(name);
+        self.apply_os_overrides();
+        self.env_origins = self
+            .env
+            .keys()
+            .map(|key| (key.clone(), EnvOrigin::TaskEnv))
+            .collect();
         self.load_env_file(base_path)?;
         Ok(self.validate()?)
     }
 
+    /// Merges the env variables and replaces the args given for the current OS, i.e. with
+    /// `env.windows` and `args.windows`. The ones of other OSs are ignored.
+    fn apply_os_overrides(&mut self) {
+        let (env, args) = match std::env::consts::OS {
+            "linux" => (std::mem::take(&mut self.env_linux), self.args_linux.take()),
+            "windows" => (
+                std::mem::take(&mut self.env_windows),
+                self.args_windows.take(),
+            ),
+            "macos" => (std::mem::take(&mut self.env_macos), self.args_macos.take()),
+            _ => (HashMap::new(), None),
+        };
+        self.env.extend(env);
+        if args.is_some() {
+            self.args = args;
+        }
+    }
+
     /// Extends from the given task.
     ///
     /// # Arguments
@@ -205,36 +829,66 @@ impl Task {
     /// returns: ()
     ///
     pub(crate) fn extend_task(&mut self, base_task: &Task) {
-        if self.quote.is_none() && base_task.quote.is_some() {
-            self.quote = Some(base_task.quote.as_ref().unwrap().clone());
-        }
+        inherit_value!(self.quote, base_task.quote);
         inherit_value!(self.debug_config, base_task.debug_config);
         inherit_value!(self.help, base_task.help);
+        inherit_value!(self.summary, base_task.summary);
         inherit_value!(self.script, base_task.script);
+        inherit_value!(self.script_file, base_task.script_file);
+        inherit_value!(self.template, base_task.template);
         inherit_value!(self.script_runner, base_task.script_runner);
         inherit_value!(self.script_runner_args, base_task.script_runner_args);
         inherit_value!(self.script_ext, base_task.script_ext);
         inherit_value!(self.program, base_task.program);
         inherit_value!(self.args, base_task.args);
         inherit_value!(self.serial, base_task.serial);
+        inherit_value!(self.deps, base_task.deps);
+        inherit_value!(self.allowed_exit_codes, base_task.allowed_exit_codes);
+        inherit_value!(self.exit_code_map, base_task.exit_code_map);
         inherit_value!(self.env_file, base_task.env_file);
+        inherit_value!(self.params, base_task.params);
+        inherit_value!(self.run_always, base_task.run_always);
+        inherit_value!(self.reentrant, base_task.reentrant);
+        inherit_value!(self.echo_command, base_task.echo_command);
+        inherit_value!(self.export_args_as_env, base_task.export_args_as_env);
+        inherit_value!(self.strict_args, base_task.strict_args);
+        inherit_value!(self.sandbox, base_task.sandbox);
+        inherit_value!(self.lock, base_task.lock);
+        inherit_value!(self.expect_duration, base_task.expect_duration);
+        inherit_value!(self.sources, base_task.sources);
+        inherit_value!(self.outputs, base_task.outputs);
+        inherit_value!(self.fingerprint, base_task.fingerprint);
+        inherit_value!(self.watch, base_task.watch);
+        inherit_value!(self.translate_env_refs, base_task.translate_env_refs);
+        inherit_value!(self.stable_script_path, base_task.stable_script_path);
+
+        let ancestors = std::iter::once(&base_task.name).chain(&base_task.ancestors);
+        for ancestor in ancestors {
+            if !self.ancestors.contains(ancestor) {
+                self.ancestors.push(ancestor.clone());
+            }
+        }
 
-        // We merge the envs, so the base env is not overwritten
-        if !base_task.env.is_empty() {
-            let old_env = mem::replace(&mut self.env, base_task.env.clone());
-
-            for (key, val) in old_env {
-                self.env.insert(key, val);
+        // We merge the envs, so the base env is not overwritten. Only the missing
+        // values are cloned.
+        for (key, val) in &base_task.env {
+            if !self.env.contains_key(key) {
+                self.env.insert(key.clone(), val.clone());
+                let origin = match base_task.env_origins.get(key) {
+                    Some(EnvOrigin::BaseTask(name)) => EnvOrigin::BaseTask(name.clone()),
+                    _ => EnvOrigin::BaseTask(base_task.name.clone()),
+                };
+                self.env_origins.insert(key.clone(), origin);
+            }
+        }
+        for (key, val) in &base_task.env_docs {
+            if !self.env_docs.contains_key(key) {
+                self.env_docs.insert(key.clone(), val.clone());
             }
-        } else if self.env.is_empty() {
-            self.env.extend(base_task.env.clone());
         }
 
         if self.args_extend.is_some() {
-            let new_args = mem::replace(&mut self.args_extend, None).unwrap();
-            if self.args.is_none() {
-                self.args = mem::replace(&mut self.args, Some(Vec::<String>::new()));
-            }
+            let new_args = self.args_extend.take().unwrap();
             if let Some(args) = &mut self.args {
                 args.extend(new_args);
             } else {
@@ -261,6 +915,54 @@ impl Task {
         }
     }
 
+    /// Returns whether the task can run while another yamis process runs the same task
+    pub fn is_reentrant(&self) -> bool {
+        self.reentrant.unwrap_or(false)
+    }
+
+    /// Returns whether the program, arguments and environment of the task can be echoed
+    pub fn echoes_command(&self) -> bool {
+        self.echo_command.unwrap_or(true)
+    }
+
+    /// Returns the variables available to the templates of the task. The values printed with
+    /// `--trace-templates` are masked if the task does not echo its commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file` - Configuration file of the task
+    /// * `context` - State of the run
+    fn get_vars<'a>(&self, config_file: &'a ConfigFile, context: &'a RunContext) -> TaskVars<'a> {
+        config_file
+            .get_vars(context)
+            .with_masked_trace(!self.echoes_command())
+    }
+
+    /// Returns the one line summary of the task, or the first line of the help if not given
+    pub fn get_summary(&self) -> &str {
+        let summary = match self.summary {
+            Some(ref summary) => summary,
+            None => self.get_help(),
+        };
+        summary.trim().lines().next().unwrap_or_default()
+    }
+
+    /// Returns the names of the tasks inherited from, directly or through other bases, in
+    /// order of precedence
+    pub fn get_ancestors(&self) -> &[String] {
+        &self.ancestors
+    }
+
+    /// Returns the types of the named arguments, if declared
+    pub(crate) fn get_params(&self) -> Option<&IndexMap<String, ParamType>> {
+        self.params.as_ref()
+    }
+
+    /// Returns the documentation of the environment variables the task uses, by name
+    pub fn get_env_docs(&self) -> &IndexMap<String, String> {
+        &self.env_docs
+    }
+
     /// Loads the environment file contained between this task
     ///
     /// # Arguments
@@ -270,32 +972,134 @@ impl Task {
     /// returns: Result<(), Box<dyn Error, Global>>
     fn load_env_file(&mut self, base_path: &Path) -> DynErrResult<()> {
         // removes the env_file as we won't need it again
-        let env_file = mem::replace(&mut self.env_file, None);
+        let env_file = self.env_file.take();
         if let Some(env_file) = env_file {
             let env_file = get_path_relative_to_base(base_path, &env_file);
             let env_variables = read_env_file(env_file.as_path())?;
             for (key, val) in env_variables {
-                self.env.entry(key).or_insert(val);
+                if let Entry::Vacant(entry) = self.env.entry(key) {
+                    self.env_origins
+                        .insert(entry.key().clone(), EnvOrigin::TaskEnvFile);
+                    entry.insert(val);
+                }
             }
         }
         Ok(())
     }
 
     /// Returns the environment variables by merging the ones from the config file with
-    /// the ones from the task, where the task takes precedence.
+    /// the ones from the task, where the task takes precedence. Environment variables given
+    /// from the command line take precedence over both. The directories in the `path` of the
+    /// config file are prepended to the PATH.
     ///
     /// # Arguments
     ///
     /// * `config_file`: Config file to load extra environment variables from
+    /// * `context`: State of the run, with the environment variables given from the command
+    ///   line
     ///
     /// returns: HashMap<String, String, RandomState>
-    fn get_env(&self, config_file: &ConfigFile) -> HashMap<String, String> {
+    fn get_env(&self, config_file: &ConfigFile, context: &RunContext) -> HashMap<String, String> {
         let mut env = self.env.clone();
         if let Some(config_file_env) = &config_file.env {
             for (key, val) in config_file_env {
                 env.entry(key.clone()).or_insert_with(|| val.clone());
             }
         }
+        for (key, val) in &context.env_overrides {
+            env.insert(key.clone(), val.clone());
+        }
+        let current_path = match env.get("PATH") {
+            Some(path) => Some(OsString::from(path)),
+            None => std::env::var_os("PATH"),
+        };
+        if let Some(path) = config_file.get_path_env(current_path.as_deref()) {
+            env.insert(String::from("PATH"), path);
+        }
+        env
+    }
+
+    /// Returns where the value of the given environment variable of the task comes from,
+    /// following the same precedence as [Task::get_env]
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: Name of the environment variable
+    /// * `config_file`: Config file of the task
+    /// * `context`: State of the run
+    ///
+    /// returns: EnvOrigin
+    fn get_env_origin(
+        &self,
+        key: &str,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> EnvOrigin {
+        if key == "PATH" && config_file.get_path_env(None).is_some() {
+            EnvOrigin::FilePath
+        } else if context.env_overrides.contains_key(key) {
+            EnvOrigin::Cli
+        } else if self.env.contains_key(key) {
+            self.env_origins
+                .get(key)
+                .cloned()
+                .unwrap_or(EnvOrigin::TaskEnv)
+        } else if config_file.env_file_vars.contains(key) {
+            EnvOrigin::FileEnvFile
+        } else {
+            EnvOrigin::FileEnv
+        }
+    }
+
+    /// Returns the environment variables the task sets, sorted by name, along with where their
+    /// values come from. Variables inherited from the system are not included.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file`: Config file of the task
+    /// * `context`: State of the run, with the environment variables given from the command
+    ///   line
+    ///
+    /// returns: Vec<(String, String, EnvOrigin), Global>
+    pub(crate) fn get_env_with_origins(
+        &self,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> Vec<(String, String, EnvOrigin)> {
+        let mut env: Vec<(String, String, EnvOrigin)> = self
+            .get_env(config_file, context)
+            .into_iter()
+            .map(|(key, val)| {
+                let origin = self.get_env_origin(&key, config_file, context);
+                (key, val, origin)
+            })
+            .collect();
+        env.sort_by(|(key_a, _, _), (key_b, _, _)| key_a.cmp(key_b));
+        env
+    }
+
+    /// Returns the environment variables of the commands of the task, which also include its
+    /// arguments and name if `export_args_as_env` is true. These take precedence over the
+    /// other variables.
+    ///
+    /// # Arguments
+    ///
+    /// * `args`: Arguments given to the task
+    /// * `config_file`: Config file to load extra environment variables from
+    /// * `context`: State of the run
+    ///
+    /// returns: HashMap<String, String, RandomState>
+    fn get_command_env(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> HashMap<String, String> {
+        let mut env = self.get_env(config_file, context);
+        if self.export_args_as_env.unwrap_or(false) {
+            env.extend(get_args_env(&self.name, args));
+            context.used_args.lock().unwrap().use_all();
+        }
         env
     }
 
@@ -312,6 +1116,24 @@ impl Task {
             ));
         }
 
+        if self.script_file.is_some()
+            && (self.script.is_some() || self.program.is_some() || self.serial.is_some())
+        {
+            return Err(TaskError::ImproperlyConfigured(
+                self.name.clone(),
+                String::from(
+                    "Cannot specify `script_file` along with `script`, `program` or `serial`.",
+                ),
+            ));
+        }
+
+        if self.script_file.is_some() && self.args.is_some() {
+            return Err(TaskError::ImproperlyConfigured(
+                self.name.clone(),
+                String::from("Cannot specify `args` on script files."),
+            ));
+        }
+
         if self.script_runner.is_some() && self.script_runner.as_ref().unwrap().is_empty() {
             return Err(TaskError::ImproperlyConfigured(
                 self.name.clone(),
@@ -319,6 +1141,32 @@ impl Task {
             ));
         }
 
+        if let Some(Program::Fallbacks(programs)) = &self.program {
+            if programs.is_empty() {
+                return Err(TaskError::ImproperlyConfigured(
+                    self.name.clone(),
+                    String::from("`program` cannot be an empty list."),
+                ));
+            }
+        }
+
+        if let Some(expect_duration) = &self.expect_duration {
+            expect_duration
+                .validate()
+                .map_err(|reason| TaskError::ImproperlyConfigured(self.name.clone(), reason))?;
+        }
+
+        for (key, patterns) in [
+            ("sources", &self.sources),
+            ("outputs", &self.outputs),
+            ("watch", &self.watch),
+        ] {
+            if let Some(patterns) = patterns {
+                validate_patterns(key, patterns)
+                    .map_err(|reason| TaskError::ImproperlyConfigured(self.name.clone(), reason))?;
+            }
+        }
+
         if self.script.is_some() && self.serial.is_some() {
             return Err(TaskError::ImproperlyConfigured(
                 self.name.clone(),
@@ -340,6 +1188,17 @@ impl Task {
             ));
         }
 
+        if self.serial.is_some()
+            && (self.allowed_exit_codes.is_some() || self.exit_code_map.is_some())
+        {
+            return Err(TaskError::ImproperlyConfigured(
+                self.name.clone(),
+                String::from(
+                    "`allowed_exit_codes` and `exit_code_map` cannot be set on serial tasks.",
+                ),
+            ));
+        }
+
         if (self.program.is_some() | self.serial.is_some()) && self.quote.is_some() {
             return Err(TaskError::ImproperlyConfigured(
                 self.name.clone(),
@@ -349,112 +1208,261 @@ impl Task {
         Ok(())
     }
 
-    /// Sets common parameters for commands, like stdout, stderr, stdin, working directory and
-    /// environment variables.
+    /// Returns the working directory of the task, if set in the task or config file.
     ///
     /// # Arguments
     ///
-    /// * `command` - Command to set the parameters for
-    /// * `config_file` - Configuration file
-    fn set_command_basics(
-        &self,
-        command: &mut Command,
-        config_file: &ConfigFile,
-    ) -> DynErrResult<()> {
-        command.stdout(Stdio::inherit());
-        command.stderr(Stdio::inherit());
-        command.stdin(Stdio::inherit());
-
-        let config_file_folder = config_file.directory();
-
-        let wd = match &self.wd {
+    /// * `config_file` - Configuration file of the task
+    fn get_working_directory(&self, config_file: &ConfigFile) -> Option<PathBuf> {
+        match &self.wd {
             None => config_file.working_directory(),
-            Some(wd) => Some(get_path_relative_to_base(config_file_folder, wd)),
-        };
-
-        if let Some(wd) = wd {
-            command.current_dir(wd);
+            Some(wd) => Some(get_path_relative_to_base(config_file.directory(), wd)),
         }
-
-        Ok(())
     }
 
-    /// Spawns a command and waits for its execution.
+    /// Renders a program, which can contain tags and start with `~`.
     ///
     /// # Arguments
     ///
-    /// * `command` - Command to spawn
-    fn spawn_command(&self, command: &mut Command) -> DynErrResult<()> {
-        let mut child = match command.spawn() {
-            Ok(child) => child,
+    /// * `program` - Program to render
+    /// * `args` - Arguments to format the program with
+    /// * `env` - Environment variables of the task
+    /// * `config_file` - Configuration file of the task
+    /// * `context` - State of the run
+    fn render_program_value(
+        &self,
+        program: &str,
+        args: &TaskArgs,
+        env: &HashMap<String, String>,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> DynErrResult<String> {
+        let rendered = match parse_script(
+            program,
+            args,
+            env,
+            &self.get_vars(config_file, context),
+            &EscapeMode::Never,
+            &ShellKind::Other,
+            false,
+        ) {
+            Ok(rendered) => rendered,
             Err(e) => {
-                return Err(TaskError::RuntimeError(self.name.clone(), format!("{}", e)).into());
+                return Err(
+                    TaskError::ImproperlyConfigured(self.name.clone(), e.to_string()).into(),
+                );
             }
         };
+        if rendered.is_empty() {
+            return Err(TaskError::ImproperlyConfigured(
+                self.name.clone(),
+                String::from("`program` cannot render to an empty value."),
+            )
+            .into());
+        }
+        if let Err(e) = Builtin::from_program(&rendered) {
+            return Err(TaskError::ImproperlyConfigured(self.name.clone(), e).into());
+        }
+        Ok(shellexpand::tilde(&rendered).to_string())
+    }
 
-        // let child handle ctrl-c to prevent dropping the parent and leaving the child running
-        ctrlc::set_handler(move || {}).unwrap_or(());
-
-        let result = child.wait()?;
-        match result.success() {
-            true => Ok(()),
-            false => match result.code() {
-                None => Err(TaskError::RuntimeError(
-                    self.name.clone(),
-                    String::from("Process did not terminate correctly"),
-                )
-                .into()),
-                Some(code) => Err(TaskError::RuntimeError(
+    /// Renders the `program` field. Programs without a path separator are resolved via PATH when
+    /// spawned, while absolute paths must exist. If a list of programs is given, the first one
+    /// that exists, or is found in the PATH, is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments to format the program with
+    /// * `env` - Environment variables of the task
+    /// * `config_file` - Configuration file of the task
+    /// * `context` - State of the run
+    fn render_program_path(
+        &self,
+        args: &TaskArgs,
+        env: &HashMap<String, String>,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> DynErrResult<String> {
+        match self.program.as_ref().unwrap() {
+            Program::Single(program) => {
+                let rendered =
+                    self.render_program_value(program, args, env, config_file, context)?;
+                if Path::new(&rendered).is_absolute() && !Path::new(&rendered).exists() {
+                    return Err(TaskError::RuntimeError(
+                        self.name.clone(),
+                        format!("Program `{}` does not exist", rendered),
+                    )
+                    .into());
+                }
+                Ok(rendered)
+            }
+            Program::Fallbacks(programs) => {
+                let mut not_found = Vec::with_capacity(programs.len());
+                for program in programs {
+                    let rendered =
+                        self.render_program_value(program, args, env, config_file, context)?;
+                    if program_exists(&rendered, env) {
+                        return Ok(rendered);
+                    }
+                    not_found.push(format!("`{}`", rendered));
+                }
+                Err(TaskError::RuntimeError(
                     self.name.clone(),
-                    format!("Process terminated with exit code {}", code),
+                    format!("None of the programs were found: {}", not_found.join(", ")),
                 )
-                .into()),
-            },
+                .into())
+            }
         }
     }
 
-    /// Runs a program from a task.
+    /// Returns the arguments of a program task, with the snippets replaced by their arguments.
+    /// Tags are not rendered yet, so snippets can contain tags too.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file` - Configuration file of the task, where the snippets are declared
+    fn get_args(&self, config_file: &ConfigFile) -> DynErrResult<Option<Vec<String>>> {
+        let Some(task_args) = &self.args else {
+            return Ok(None);
+        };
+        let mut result = Vec::with_capacity(task_args.len());
+        for arg in task_args {
+            match arg {
+                TaskArg::Value(value) => result.push(value.clone()),
+                TaskArg::Snippet { name } => match config_file.snippets.get(name) {
+                    Some(snippet) => result.extend(snippet.iter().cloned()),
+                    None => {
+                        return Err(TaskError::ImproperlyConfigured(
+                            self.name.clone(),
+                            format!("Snippet `{}` not found", name),
+                        )
+                        .into());
+                    }
+                },
+            }
+        }
+        Ok(Some(result))
+    }
+
+    /// Renders the command of a program task.
     ///
     /// # Arguments
     ///
-    /// * `name` - Name of the task, displayed in errors.
     /// * `args` - Arguments to format the task args with
     /// * `config_file` - Configuration file of the task
-    fn run_program(&self, args: &TaskArgs, config_file: &ConfigFile) -> DynErrResult<()> {
-        let program = self.program.as_ref().unwrap();
-        let mut command = Command::new(program);
-        self.set_command_basics(&mut command, config_file)?;
-
-        let env = self.get_env(config_file);
-        command.envs(&env);
-
-        if let Some(task_args) = &self.args {
-            match parse_params(task_args, args, &env) {
-                Ok(task_args) => {
+    /// * `context` - State of the run
+    fn render_program(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> DynErrResult<RenderedCommand> {
+        let env = self.get_command_env(args, config_file, context);
+        let program = self.render_program_path(args, &env, config_file, context)?;
+
+        let program_args = match self.get_args(config_file)? {
+            None => vec![],
+            Some(task_args) => {
+                match parse_params(&task_args, args, &env, &self.get_vars(config_file, context)) {
                     // Programs need to exclude empty arguments, otherwise they might be passed as real parameters
-                    command.args(task_args.iter().filter(|val| !val.is_empty()));
-                }
-                Err(e) => {
-                    return Err(
-                        TaskError::ImproperlyConfigured(self.name.clone(), e.to_string()).into(),
-                    );
+                    Ok(task_args) => task_args
+                        .into_iter()
+                        .filter(|val| !val.is_empty())
+                        .collect(),
+                    Err(e) => {
+                        return Err(TaskError::ImproperlyConfigured(
+                            self.name.clone(),
+                            e.to_string(),
+                        )
+                        .into());
+                    }
                 }
             }
-        }
+        };
+
+        Ok(RenderedCommand {
+            task_name: self.name.clone(),
+            program,
+            args: program_args,
+            script: None,
+            wd: self.get_working_directory(config_file),
+            env,
+            config_file_path: config_file.filepath.clone(),
+            allowed_exit_codes: self.get_allowed_exit_codes(),
+            exit_code_map: self.exit_code_map.clone().unwrap_or_default(),
+            echo: self.echoes_command(),
+            sandbox: self.sandbox,
+            executed_deps: context.get_executed_deps(),
+        })
+    }
 
-        self.spawn_command(&mut command)
+    /// Returns the path the script of the task is written to, if it sets
+    /// `stable_script_path: true`
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - Extension of the script
+    /// * `config_file` - Configuration file of the task
+    fn get_stable_script_path(&self, extension: &str, config_file: &ConfigFile) -> Option<PathBuf> {
+        if !self.stable_script_path.unwrap_or(false) {
+            return None;
+        }
+        Some(
+            config_file
+                .directory()
+                .join(".yamis")
+                .join("scripts")
+                .join(get_stable_script_name(&self.name, extension)),
+        )
     }
 
-    /// Runs a script from a task.
+    /// Renders the command of a script task.
     ///
     /// # Arguments
     ///
-    /// * `name` - Name of the task, displayed in errors.
     /// * `args` - Arguments to format the task args with
     /// * `config_file` - Configuration file of the task
-    fn run_script(&self, args: &TaskArgs, config_file: &ConfigFile) -> DynErrResult<()> {
+    /// * `context` - State of the run
+    fn render_script(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> DynErrResult<RenderedCommand> {
         let script = self.script.as_ref().unwrap();
+        let (script_runner, runner_args) = self.get_script_runner();
+
+        let script_extension = match &self.script_ext {
+            Some(script_extension) => script_extension.as_str(),
+            None => DEFAULT_SCRIPT_EXTENSION,
+        };
+
+        let env = self.get_command_env(args, config_file, context);
+        let script =
+            self.render_script_content(script, script_extension, args, &env, config_file, context)?;
+
+        Ok(RenderedCommand {
+            task_name: self.name.clone(),
+            program: String::from(script_runner),
+            args: runner_args,
+            script: Some(RenderedScript {
+                path: self.get_stable_script_path(script_extension, config_file),
+                content: script,
+                extension: String::from(script_extension),
+            }),
+            wd: self.get_working_directory(config_file),
+            env,
+            config_file_path: config_file.filepath.clone(),
+            allowed_exit_codes: self.get_allowed_exit_codes(),
+            exit_code_map: self.exit_code_map.clone().unwrap_or_default(),
+            echo: self.echoes_command(),
+            sandbox: self.sandbox,
+            executed_deps: context.get_executed_deps(),
+        })
+    }
 
+    /// Returns the script runner and its arguments
+    fn get_script_runner(&self) -> (&str, Vec<OsString>) {
         // Interpreter is a list, because sometimes there is need to pass extra arguments to the
         // interpreter, such as the /C option in the batch case
         let script_runner = if let Some(script_runner) = &self.script_runner {
@@ -463,108 +1471,557 @@ impl Task {
             DEFAULT_INTERPRETER
         };
 
-        let default_script_extension = String::from(DEFAULT_SCRIPT_EXTENSION);
-        let script_extension = self
-            .script_ext
-            .as_ref()
-            .unwrap_or(&default_script_extension);
-
-        let mut command = Command::new(script_runner);
+        let runner_args = match &self.script_runner_args {
+            Some(script_runner_args) => script_runner_args.iter().map(OsString::from).collect(),
+            None => vec![],
+        };
+        (script_runner, runner_args)
+    }
 
-        if let Some(script_runner_args) = &self.script_runner_args {
-            command.args(script_runner_args);
+    /// Renders the content of a script, quoting the values according to the shell that runs it.
+    ///
+    /// # Arguments
+    ///
+    /// * `script` - Script to render
+    /// * `script_extension` - Extension of the script file
+    /// * `args` - Arguments to format the script with
+    /// * `env` - Environment variables of the task
+    /// * `config_file` - Configuration file of the task
+    /// * `context` - State of the run
+    fn render_script_content(
+        &self,
+        script: &str,
+        script_extension: &str,
+        args: &TaskArgs,
+        env: &HashMap<String, String>,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> DynErrResult<String> {
+        let (script_runner, _) = self.get_script_runner();
+        let quote = self.quote.as_ref().unwrap_or(&config_file.quote);
+        let shell_kind = ShellKind::from_script_runner(script_runner, script_extension);
+
+        match parse_script(
+            script,
+            args,
+            env,
+            &self.get_vars(config_file, context),
+            quote,
+            &shell_kind,
+            self.translate_env_refs.unwrap_or(false),
+        ) {
+            Ok(script) => Ok(script),
+            Err(e) => Err(TaskError::ImproperlyConfigured(self.name.clone(), e.to_string()).into()),
         }
+    }
 
-        let env = self.get_env(config_file);
-        command.envs(&env);
-
-        self.set_command_basics(&mut command, config_file)?;
+    /// Renders the command of a script file task. The file is passed to the script runner as it
+    /// is, followed by the positional arguments of the task, unless `template` is set, in which
+    /// case it is rendered like inline scripts.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments to format the task args with
+    /// * `config_file` - Configuration file of the task
+    /// * `context` - State of the run
+    fn render_script_file(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> DynErrResult<RenderedCommand> {
+        let script_file =
+            get_path_relative_to_base(config_file.directory(), self.script_file.as_ref().unwrap());
+        if !script_file.is_file() {
+            return Err(TaskError::RuntimeError(
+                self.name.clone(),
+                format!(
+                    "Script file `{}` does not exist",
+                    script_file.to_string_lossy()
+                ),
+            )
+            .into());
+        }
 
-        let quote = if self.quote.is_some() {
-            self.quote.as_ref().unwrap()
+        let (script_runner, mut runner_args) = self.get_script_runner();
+        let env = self.get_command_env(args, config_file, context);
+
+        let script = if self.template.unwrap_or(false) {
+            let script_extension = match &self.script_ext {
+                Some(script_extension) => script_extension.clone(),
+                None => match script_file.extension() {
+                    Some(extension) => extension.to_string_lossy().to_string(),
+                    None => String::from(DEFAULT_SCRIPT_EXTENSION),
+                },
+            };
+            let content = fs::read_to_string(&script_file)?;
+            let content = self.render_script_content(
+                &content,
+                &script_extension,
+                args,
+                &env,
+                config_file,
+                context,
+            )?;
+            Some(RenderedScript {
+                path: self.get_stable_script_path(&script_extension, config_file),
+                content,
+                extension: script_extension,
+            })
         } else {
-            &config_file.quote
+            runner_args.push(script_file.into_os_string());
+            if let Some(positional_args) = args.get("*") {
+                runner_args.extend(positional_args.iter().cloned());
+            }
+            None
         };
 
-        match parse_script(script, args, &env, quote) {
-            Ok(script) => {
-                let script_file = get_temp_script(
-                    &script,
-                    script_extension,
-                    &self.name,
-                    config_file.filepath.as_path(),
-                )?;
-                command.arg(script_file.to_str().unwrap());
-            }
-            Err(e) => {
-                return Err(
-                    TaskError::ImproperlyConfigured(self.name.clone(), e.to_string()).into(),
-                );
+        Ok(RenderedCommand {
+            task_name: self.name.clone(),
+            program: String::from(script_runner),
+            args: runner_args,
+            script,
+            wd: self.get_working_directory(config_file),
+            env,
+            config_file_path: config_file.filepath.clone(),
+            allowed_exit_codes: self.get_allowed_exit_codes(),
+            exit_code_map: self.exit_code_map.clone().unwrap_or_default(),
+            echo: self.echoes_command(),
+            sandbox: self.sandbox,
+            executed_deps: context.get_executed_deps(),
+        })
+    }
+
+    /// Returns the exit codes that are considered successful
+    fn get_allowed_exit_codes(&self) -> Vec<i32> {
+        match &self.allowed_exit_codes {
+            Some(allowed_exit_codes) => allowed_exit_codes.clone(),
+            None => vec![0],
+        }
+    }
+
+    /// Returns the tasks to run for a serial task, in order, along with their index in
+    /// `serial`. Steps restricted to other operating systems are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file` - Configuration file of the task
+    fn get_serial_tasks<'a>(
+        &self,
+        config_file: &'a ConfigFile,
+    ) -> DynErrResult<Vec<(usize, &'a Task)>> {
+        let serial = self.serial.as_ref().unwrap();
+        let mut tasks: Vec<(usize, &Task)> = Vec::with_capacity(serial.len());
+        for (index, step) in serial.iter().enumerate() {
+            let Some(task_name) = step.get_task_name() else {
+                continue;
+            };
+            if let Some(task) = config_file.get_task_ref(task_name) {
+                tasks.push((index, task));
+            } else {
+                return Err(TaskError::RuntimeError(
+                    self.name.clone(),
+                    format!("Task `{}` not found.", task_name),
+                )
+                .into());
             }
         }
+        Ok(tasks)
+    }
 
-        self.spawn_command(&mut command)
+    /// Returns the fingerprint used to skip the task if it is up to date, if it sets `sources`
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file` - Configuration file of the task
+    fn get_fingerprint<'a>(&'a self, config_file: &'a ConfigFile) -> Option<Fingerprint<'a>> {
+        let sources = self.sources.as_ref()?;
+        Some(Fingerprint::new(
+            &self.name,
+            &config_file.filepath,
+            sources,
+            self.outputs.as_deref().unwrap_or_default(),
+            self.fingerprint.unwrap_or_default(),
+        ))
     }
 
-    /// Runs a series of tasks from a task, in order.
+    /// Returns what running the task depends on besides its sources, so that it runs again if
+    /// it is given other inputs. That is the rendered command, which includes the arguments and
+    /// environment variables, or those, sorted, for tasks that do not run a command themselves.
     ///
     /// # Arguments
-    /// * `args` - Arguments to format the task args with
+    ///
+    /// * `args` - Arguments the task was given, after coercing them
     /// * `config_file` - Configuration file of the task
-    fn run_serial(&self, args: &TaskArgs, config_file: &ConfigFile) -> DynErrResult<()> {
-        let serial = self.serial.as_ref().unwrap();
-        let mut tasks: Vec<Arc<Task>> = Vec::new();
-        for task_name in serial {
-            if let Some(task) = config_file.get_task(task_name) {
+    /// * `context` - State of the run
+    /// * `command` - Rendered command of the task, if any
+    ///
+    /// returns: String
+    fn get_inputs(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+        context: &RunContext,
+        command: Option<&RenderedCommand>,
+    ) -> String {
+        if let Some(command) = command {
+            return command.to_string();
+        }
+        let mut args: Vec<_> = args.iter().collect();
+        args.sort();
+        let mut env: Vec<_> = self.get_env(config_file, context).into_iter().collect();
+        env.sort();
+        format!("{:?}\n{:?}", args, env)
+    }
+
+    /// Returns what identifies a run of the task, so that dependencies and shared tasks run only
+    /// once with the same inputs. It is its name followed by a checksum of its inputs, so that
+    /// the task runs again if it is given other inputs, i.e. in a nested invocation with other
+    /// `--var` values, while other tasks with the same command still run
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - Inputs of the task, returned by `get_inputs`
+    ///
+    /// returns: String
+    fn get_run_identity(&self, inputs: &str) -> String {
+        let mut hasher = Md5::new();
+        hasher.update(inputs.as_bytes());
+        format!("{}:{:x}", self.name, hasher.finalize())
+    }
+
+    /// Returns the glob patterns of the files that run the task again when they change, given
+    /// in `watch`, or `sources` if not given
+    #[cfg(feature = "watch")]
+    pub(crate) fn get_watch_patterns(&self) -> Option<&[String]> {
+        self.watch.as_deref().or(self.sources.as_deref())
+    }
+
+    /// Returns the inline script of the task, before rendering, if any
+    pub(crate) fn get_script(&self) -> Option<&str> {
+        self.script.as_deref()
+    }
+
+    /// Returns the names of the tasks that run before this one
+    pub(crate) fn get_deps(&self) -> &[String] {
+        self.deps.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the tasks in `deps`, in order
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file` - Configuration file of the task
+    fn get_dep_tasks<'a>(&self, config_file: &'a ConfigFile) -> DynErrResult<Vec<&'a Task>> {
+        let mut tasks: Vec<&Task> = Vec::with_capacity(self.get_deps().len());
+        for task_name in self.get_deps() {
+            if let Some(task) = config_file.get_task_ref(task_name) {
                 tasks.push(task);
             } else {
                 return Err(TaskError::RuntimeError(
                     self.name.clone(),
-                    format!("Task `{}` not found.", task_name),
+                    format!("Dependency `{}` not found.", task_name),
                 )
                 .into());
             }
         }
-        for task in tasks {
-            task.run(args, config_file)?;
+        Ok(tasks)
+    }
+
+    /// Returns the arguments with the values of the named arguments declared in `params`
+    /// validated and normalized, i.e. `--replicas +3` becomes `--replicas 3` for an `int`.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments given to the task
+    fn get_coerced_args<'a>(&self, args: &'a TaskArgs) -> DynErrResult<Cow<'a, TaskArgs>> {
+        match &self.params {
+            None => Ok(Cow::Borrowed(args)),
+            Some(params) => match coerce_args(params, args) {
+                Ok(args) => Ok(Cow::Owned(args)),
+                Err(e) => Err(TaskError::RuntimeError(self.name.clone(), e).into()),
+            },
         }
-        Ok(())
+    }
+
+    /// Renders the commands that running the task would spawn, in order, without running them.
+    /// The commands of the tasks in `deps` come first, without the ones rendered already.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments to format the task args with
+    /// * `config_file` - Configuration file of the task
+    ///
+    /// returns: Result<Vec<RenderedCommand, Global>, Box<dyn Error, Global>>
+    pub fn render(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+    ) -> DynErrResult<Vec<RenderedCommand>> {
+        self.render_in(args, config_file, &RunContext::new())
+    }
+
+    /// Renders the commands that running the task would spawn in the given run, in order,
+    /// without running them.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments to format the task args with
+    /// * `config_file` - Configuration file of the task
+    /// * `context` - State of the run
+    ///
+    /// returns: Result<Vec<RenderedCommand, Global>, Box<dyn Error, Global>>
+    pub(crate) fn render_in(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> DynErrResult<Vec<RenderedCommand>> {
+        let args = self.get_coerced_args(args)?;
+        let args = args.as_ref();
+        if let Some(params) = &self.params {
+            let mut used_args = context.used_args.lock().unwrap();
+            for name in params.keys() {
+                used_args.use_named(name);
+            }
+        }
+        let mut commands = Vec::new();
+        // Dependencies do not receive the arguments of the task
+        for task in self.get_dep_tasks(config_file)? {
+            for command in task.render_in(&TaskArgs::new(), config_file, context)? {
+                if !commands.contains(&command) {
+                    commands.push(command);
+                }
+            }
+        }
+        if self.script.is_some() {
+            commands.push(self.render_script(args, config_file, context)?);
+        } else if self.script_file.is_some() {
+            commands.push(self.render_script_file(args, config_file, context)?);
+        } else if self.program.is_some() {
+            commands.push(self.render_program(args, config_file, context)?);
+        } else if self.serial.is_some() {
+            for (index, task) in self.get_serial_tasks(config_file)? {
+                match task.render_in(args, config_file, context) {
+                    Ok(task_commands) => commands.extend(task_commands),
+                    Err(e) => {
+                        return Err(TaskError::SerialError(self.name.clone(), index, e).into())
+                    }
+                }
+            }
+        } else if self.deps.is_none() {
+            return Err(TaskError::ImproperlyConfigured(
+                self.name.clone(),
+                String::from("Nothing to run."),
+            )
+            .into());
+        }
+        Ok(commands)
+    }
+
+    /// Returns an error with the arguments given to the task that it does not use, if it sets
+    /// `strict_args: true`. The arguments used are found by rendering the task, so that the
+    /// ones used by its serial tasks are also taken into account.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments given to the task
+    /// * `config_file` - Configuration file of the task
+    /// * `context` - State of the run
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    pub(crate) fn check_strict_args(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+        context: &RunContext,
+    ) -> DynErrResult<()> {
+        if !self.strict_args.unwrap_or(false) {
+            return Ok(());
+        }
+        std::mem::take(&mut *context.used_args.lock().unwrap());
+        self.render_in(args, config_file, context)?;
+        let unused = context.used_args.lock().unwrap().get_unused_args(args);
+        if unused.is_empty() {
+            return Ok(());
+        }
+        let unused: Vec<String> = unused.iter().map(|arg| format!("`{}`", arg)).collect();
+        Err(TaskError::RuntimeError(
+            self.name.clone(),
+            format!(
+                "Unused arguments: {}. Declare them in `params`, or set `strict_args: false`",
+                unused.join(", ")
+            ),
+        )
+        .into())
     }
 
     /// Runs a task.
     ///
     /// # Arguments
     ///
-    /// * `name` - Name of the task, displayed in errors.
     /// * `args` - Arguments to format the task args with
     /// * `config_file` - Configuration file of the task
-    /// * `config_files` - global ConfigurationFiles instance
     pub fn run(&self, args: &TaskArgs, config_file: &ConfigFile) -> DynErrResult<()> {
+        self.run_with_backend(
+            args,
+            config_file,
+            &RunContext::new(),
+            &ProcessBackend::new(),
+        )
+    }
+
+    /// Runs a task, executing the rendered commands with the given backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments to format the task args with
+    /// * `config_file` - Configuration file of the task
+    /// * `context` - State of the run, which is not shared with other runs of the same config
+    ///   file
+    /// * `backend` - Backend that executes the commands
+    pub fn run_with_backend(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+        context: &RunContext,
+        backend: &dyn ExecutionBackend,
+    ) -> DynErrResult<()> {
+        self.run_as(args, config_file, context, backend, false)
+    }
+
+    /// Runs a task, executing the rendered commands with the given backend. Dependencies run
+    /// only once with the same inputs, even if several tasks depend on them, or they already
+    /// ran in a parent yamis invocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments to format the task args with
+    /// * `config_file` - Configuration file of the task
+    /// * `context` - State of the run
+    /// * `backend` - Backend that executes the commands
+    /// * `as_dep` - Whether the task runs as a dependency of another task
+    fn run_as(
+        &self,
+        args: &TaskArgs,
+        config_file: &ConfigFile,
+        context: &RunContext,
+        backend: &dyn ExecutionBackend,
+        as_dep: bool,
+    ) -> DynErrResult<()> {
+        let args = self.get_coerced_args(args)?;
+        let args = args.as_ref();
         let task_debug_config =
             ConcreteTaskDebugConfig::new(&self.debug_config, &config_file.debug_config);
 
+        // Dependencies do not receive the arguments of the task
+        for task in self.get_dep_tasks(config_file)? {
+            task.run_as(&TaskArgs::new(), config_file, context, backend, true)?;
+        }
+
+        let command = if self.script.is_some() {
+            Some(self.render_script(args, config_file, context)?)
+        } else if self.script_file.is_some() {
+            Some(self.render_script_file(args, config_file, context)?)
+        } else if self.program.is_some() {
+            Some(self.render_program(args, config_file, context)?)
+        } else {
+            None
+        };
+        let inputs = self.get_inputs(args, config_file, context, command.as_ref());
+        if as_dep && !context.mark_dep_executed(&self.get_run_identity(&inputs)) {
+            return Ok(());
+        }
+
         if task_debug_config.print_task_name {
             println!("{}", format!("Task: `{}`", self.name).yamis_info());
         }
 
-        return if self.script.is_some() {
-            self.run_script(args, config_file)
-        } else if self.program.is_some() {
-            self.run_program(args, config_file)
+        // Released once the task finishes. Acquired before the up-to-date check, so that it
+        // sees the outputs of other runs that held it. Dry runs do not need it, as nothing runs
+        let _lock = match &self.lock {
+            Some(lock) if !backend.is_dry_run() => {
+                lock.acquire(&self.name, config_file.directory(), &context.held_locks)?
+            }
+            _ => None,
+        };
+
+        let fingerprint = self.get_fingerprint(config_file);
+        let mut checksum = None;
+        if let Some(fingerprint) = &fingerprint {
+            let (up_to_date, new_checksum) = fingerprint
+                .check(&inputs)
+                .map_err(|e| TaskError::RuntimeError(self.name.clone(), e.to_string()))?;
+            if up_to_date {
+                if task_debug_config.print_task_name {
+                    let msg = format!("Skipping `{}`, it is up to date", self.name);
+                    println!("{}", msg.yamis_info());
+                }
+                return Ok(());
+            }
+            checksum = new_checksum;
+        }
+
+        let started = Instant::now();
+        if let Some(command) = command {
+            if task_debug_config.print_program && command.echo() {
+                println!(
+                    "{}",
+                    format!("Program: `{}`", command.program()).yamis_info()
+                );
+            }
+            // Tasks referenced multiple times, i.e. shared by serial tasks, run only once with
+            // the same inputs
+            if !self.run_always.unwrap_or(false)
+                && !context.mark_executed(&self.get_run_identity(&inputs))
+            {
+                if task_debug_config.print_task_name {
+                    let msg = format!(
+                        "Skipping `{}`, it already ran with the same inputs",
+                        self.name
+                    );
+                    println!("{}", msg.yamis_info());
+                }
+                return Ok(());
+            }
+            backend.execute(&command)?;
         } else if self.serial.is_some() {
-            self.run_serial(args, config_file)
-        } else {
-            Err(
-                TaskError::ImproperlyConfigured(self.name.clone(), String::from("Nothing to run."))
-                    .into(),
+            for (index, task) in self.get_serial_tasks(config_file)? {
+                if let Err(e) = task.run_with_backend(args, config_file, context, backend) {
+                    return Err(TaskError::SerialError(self.name.clone(), index, e).into());
+                }
+            }
+        } else if self.deps.is_none() {
+            return Err(TaskError::ImproperlyConfigured(
+                self.name.clone(),
+                String::from("Nothing to run."),
             )
-        };
+            .into());
+        }
+
+        // Dry runs take no time, so they would always be flagged
+        if let Some(expect_duration) = &self.expect_duration {
+            if !backend.is_dry_run() {
+                expect_duration.check(&self.name, started.elapsed())?;
+            }
+        }
+
+        // Saved only once the task succeeded, so that it runs again if it failed
+        if let (Some(fingerprint), Some(checksum)) = (&fingerprint, &checksum) {
+            if !backend.is_dry_run() {
+                fingerprint
+                    .save(checksum)
+                    .map_err(|e| TaskError::RuntimeError(self.name.clone(), e.to_string()))?;
+            }
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::RecordBackend;
     use crate::config_files::ConfigFile;
     use assert_fs::TempDir;
     use std::collections::HashMap;
@@ -612,7 +2069,7 @@ mod tests {
 
         let task = config_file.get_task("hello").unwrap();
 
-        let env = task.get_env(&config_file);
+        let env = task.get_env(&config_file, &RunContext::new());
         let expected = HashMap::from([
             ("greeting".to_string(), "hello world".to_string()),
             ("one_plus_one".to_string(), "2".to_string()),
@@ -620,6 +2077,435 @@ mod tests {
         assert_eq!(env, expected);
     }
 
+    #[test]
+    fn test_env_docs_inheritance() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+    [tasks.base.env_docs]
+    API_URL = "Endpoint to hit"
+    TOKEN = "Token to authenticate with"
+
+    [tasks.hello]
+    bases = ["base"]
+    script = "curl $API_URL"
+    env_docs = { TOKEN = "Overridden", DEBUG = "Prints debug messages" }
+    "#,
+            PathBuf::from("project.yamis.toml"),
+        )
+        .unwrap();
+
+        let task = config_file.get_task("hello").unwrap();
+        let env_docs: Vec<(&str, &str)> = task
+            .get_env_docs()
+            .iter()
+            .map(|(key, val)| (key.as_str(), val.as_str()))
+            .collect();
+        assert_eq!(
+            env_docs,
+            vec![
+                ("TOKEN", "Overridden"),
+                ("DEBUG", "Prints debug messages"),
+                ("API_URL", "Endpoint to hit"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_args_as_env() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+    [tasks.base]
+    export_args_as_env = true
+    env = { YAMIS_TASK = "overridden" }
+
+    [tasks.exported]
+    bases = ["base"]
+    script = "echo $YAMIS_ARG_1"
+
+    [tasks.not_exported]
+    program = "yamis::echo"
+    "#,
+            PathBuf::from("project.yamis.toml"),
+        )
+        .unwrap();
+
+        let args = TaskArgs::from([
+            (
+                String::from("*"),
+                vec![OsString::from("first"), OsString::from("--dry-run=yes")],
+            ),
+            (String::from("dry-run"), vec![OsString::from("yes")]),
+            (
+                String::from("tag"),
+                vec![OsString::from("a"), OsString::from("b")],
+            ),
+        ]);
+        let commands = config_file
+            .get_task("exported")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap();
+        let env = commands[0].env();
+        assert_eq!(env.get("YAMIS_TASK").unwrap(), "exported");
+        assert_eq!(env.get("YAMIS_ARG_1").unwrap(), "first");
+        assert_eq!(env.get("YAMIS_ARG_2").unwrap(), "--dry-run=yes");
+        assert!(!env.contains_key("YAMIS_ARG_3"));
+        assert_eq!(env.get("YAMIS_KW_DRY_RUN").unwrap(), "yes");
+        assert_eq!(env.get("YAMIS_KW_TAG").unwrap(), "a\nb");
+
+        let commands = config_file
+            .get_task("not_exported")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap();
+        assert!(!commands[0].env().contains_key("YAMIS_TASK"));
+    }
+
+    #[test]
+    fn test_location_env() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+    [tasks.build]
+    program = "yamis::echo"
+    "#,
+            PathBuf::from("project").join("project.yamis.toml"),
+        )
+        .unwrap();
+        let project_root = std::path::absolute("project").unwrap();
+
+        let commands = config_file
+            .get_task("build")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap();
+        // Not part of the environment of the task, as it depends on the machine
+        assert!(!commands[0].env().contains_key("YAMIS_FILE"));
+        let env = commands[0].get_exported_env();
+        assert_eq!(
+            env.get("YAMIS_FILE").unwrap(),
+            &project_root.join("project.yamis.toml").to_string_lossy()
+        );
+        assert_eq!(
+            env.get("YAMIS_PROJECT_ROOT").unwrap(),
+            &project_root.to_string_lossy()
+        );
+        assert_eq!(env.get("YAMIS_TASK").unwrap(), "build");
+    }
+
+    #[test]
+    fn test_strict_args() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+    [tasks.build]
+    strict_args = true
+    program = "cargo"
+    args = ["build", "{$1?}", "{join(\",\", features)}"]
+
+    [tasks.deploy]
+    strict_args = true
+    serial = ["build", "upload"]
+    params = { replicas = "int" }
+
+    [tasks.upload]
+    script = "upload {target?}"
+
+    [tasks.all_args]
+    strict_args = true
+    program = "echo"
+    args = ["{$@}"]
+
+    [tasks.lenient]
+    program = "echo"
+    "#,
+            PathBuf::from("project.yamis.toml"),
+        )
+        .unwrap();
+
+        let args = TaskArgs::from([
+            (
+                String::from("*"),
+                vec![
+                    OsString::from("release"),
+                    OsString::from("--features=a"),
+                    OsString::from("--relase"),
+                    OsString::from("yes"),
+                    OsString::from("extra"),
+                ],
+            ),
+            (String::from("features"), vec![OsString::from("a")]),
+            (String::from("relase"), vec![OsString::from("yes")]),
+        ]);
+        let err = config_file
+            .get_task("build")
+            .unwrap()
+            .check_strict_args(&args, &config_file, &RunContext::new())
+            .unwrap_err();
+        assert!(err.to_string().ends_with(
+            "Unused arguments: `--relase yes`, `extra`. Declare them in `params`, or set `strict_args: false`"
+        ));
+
+        // Arguments used by the serial tasks, or declared in `params`, are used
+        let args = TaskArgs::from([
+            (
+                String::from("*"),
+                vec![
+                    OsString::from("--target=prod"),
+                    OsString::from("--replicas=3"),
+                    OsString::from("--features=a"),
+                ],
+            ),
+            (String::from("target"), vec![OsString::from("prod")]),
+            (String::from("replicas"), vec![OsString::from("3")]),
+            (String::from("features"), vec![OsString::from("a")]),
+        ]);
+        let deploy = config_file.get_task("deploy").unwrap();
+        assert!(deploy
+            .check_strict_args(&args, &config_file, &RunContext::new())
+            .is_ok());
+
+        assert!(config_file
+            .get_task("all_args")
+            .unwrap()
+            .check_strict_args(&args, &config_file, &RunContext::new())
+            .is_ok());
+        assert!(config_file
+            .get_task("lenient")
+            .unwrap()
+            .check_strict_args(&args, &config_file, &RunContext::new())
+            .is_ok());
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_render_program_path() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+    [tasks.tagged]
+    program = "{$BIN_DIR}/{$1}"
+    env = { BIN_DIR = "/bin" }
+
+    [tasks.path]
+    program = "echo"
+
+    [tasks.home]
+    program = "~/yamis-non-existent-program"
+
+    [tasks.empty]
+    program = "{$1?}"
+
+    [tasks.fallbacks]
+    program = ["yamis-non-existent-program", "/bin/yamis-non-existent-program", "sh", "bash"]
+
+    [tasks.missing]
+    program = ["yamis-non-existent-program", "~/yamis-non-existent-program"]
+    "#,
+            PathBuf::from("project.yamis.toml"),
+        )
+        .unwrap();
+
+        let args = TaskArgs::from([(String::from("*"), vec![OsString::from("sh")])]);
+        let commands = config_file
+            .get_task("tagged")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap();
+        assert_eq!(commands[0].program(), "/bin/sh");
+
+        let args = TaskArgs::from([(
+            String::from("*"),
+            vec![OsString::from("yamis-non-existent-program")],
+        )]);
+        let err = config_file
+            .get_task("tagged")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Program `/bin/yamis-non-existent-program` does not exist"));
+
+        let commands = config_file
+            .get_task("path")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap();
+        assert_eq!(commands[0].program(), "echo");
+
+        let err = config_file
+            .get_task("home")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap_err()
+            .to_string();
+        assert!(!err.contains('~'));
+        assert!(err.ends_with("/yamis-non-existent-program` does not exist"));
+
+        let err = config_file
+            .get_task("empty")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("`program` cannot render to an empty value."));
+
+        let commands = config_file
+            .get_task("fallbacks")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap();
+        assert_eq!(commands[0].program(), "sh");
+
+        let err = config_file
+            .get_task("missing")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("None of the programs were found: `yamis-non-existent-program`, `/"));
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  base:
+    program: grep
+    allowed_exit_codes: [0, 1]
+    exit_code_map:
+      1: No matches found
+      2: Grep failed
+  search:
+    bases: [base]
+  plain:
+    program: grep
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let commands = config_file
+            .get_task("search")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap();
+        let command = &commands[0];
+        assert_eq!(command.check_exit_code(0).unwrap(), None);
+        assert_eq!(
+            command.check_exit_code(1).unwrap(),
+            Some("No matches found")
+        );
+        let err = command.check_exit_code(2).unwrap_err().to_string();
+        assert!(err.ends_with("Grep failed (exit code 2)"));
+        let err = command.check_exit_code(3).unwrap_err().to_string();
+        assert!(err.ends_with("Process terminated with exit code 3"));
+
+        let commands = config_file
+            .get_task("plain")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap();
+        assert!(commands[0].check_exit_code(1).is_err());
+
+        // TOML keys are always strings
+        let config_file = ConfigFile::load_from_str(
+            r#"
+    [tasks.search]
+    program = "grep"
+    exit_code_map = { 1 = "No matches found" }
+    "#,
+            PathBuf::from("project.yamis.toml"),
+        )
+        .unwrap();
+        let commands = config_file
+            .get_task("search")
+            .unwrap()
+            .render(&TaskArgs::new(), &config_file)
+            .unwrap();
+        let err = commands[0].check_exit_code(1).unwrap_err().to_string();
+        assert!(err.ends_with("No matches found (exit code 1)"));
+
+        let err = ConfigFile::load_from_str(
+            r#"
+    [tasks.search]
+    program = "grep"
+    exit_code_map = { one = "No matches found" }
+    "#,
+            PathBuf::from("project.yamis.toml"),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("invalid exit code `one`"));
+    }
+
+    #[test]
+    fn test_script_file() {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::create_dir(tmp_dir.path().join("scripts")).unwrap();
+        fs::write(
+            tmp_dir.path().join("scripts").join("greet.py"),
+            "print('hello {$1} {vars.NAME}')",
+        )
+        .unwrap();
+        let config_file = ConfigFile::load_from_str(
+            r#"
+vars:
+  NAME: John
+tasks:
+  greet:
+    script_runner: python
+    script_file: scripts/greet.py
+  greet_template:
+    bases: [greet]
+    template: true
+  missing:
+    script_file: scripts/missing.sh
+"#,
+            tmp_dir.path().join("project.yamis.yml"),
+        )
+        .unwrap();
+        let args = TaskArgs::from([(String::from("*"), vec![OsString::from("a b")])]);
+        let script_path = tmp_dir.path().join("scripts").join("greet.py");
+
+        let commands = config_file
+            .get_task("greet")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap();
+        assert_eq!(commands[0].program(), "python");
+        assert_eq!(
+            commands[0].args(),
+            &[script_path.into_os_string(), OsString::from("a b")]
+        );
+        assert_eq!(commands[0].script(), None);
+
+        let commands = config_file
+            .get_task("greet_template")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap();
+        assert_eq!(commands[0].args(), &[] as &[OsString]);
+        assert_eq!(
+            commands[0].script(),
+            Some("print('hello \"a b\" \"John\"')")
+        );
+
+        let err = config_file
+            .get_task("missing")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap_err();
+        assert!(err.to_string().ends_with("missing.sh` does not exist"));
+
+        let err = ConfigFile::load_from_str(
+            "tasks:\n  both:\n    script: echo\n    script_file: script.sh\n",
+            tmp_dir.path().join("project.yamis.yml"),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Cannot specify `script_file` along with `script`, `program` or `serial`."));
+    }
+
     #[test]
     fn test_quotes_inheritance() {
         let tmp_dir = TempDir::new().unwrap();
@@ -688,14 +2574,22 @@ mod tests {
         let task_ref = task.as_ref();
         assert_eq!(
             task_ref.args.as_ref().unwrap(),
-            &vec!["-c".to_string(), "echo".to_string(), "hello".to_string()]
+            &vec![
+                TaskArg::Value("-c".to_string()),
+                TaskArg::Value("echo".to_string()),
+                TaskArg::Value("hello".to_string())
+            ]
         );
 
         let task = config_file.get_task("hello_2").unwrap();
         let task_ref = task.as_ref();
         assert_eq!(
             task_ref.args.as_ref().unwrap(),
-            &vec!["-c".to_string(), "echo".to_string(), "hello".to_string()]
+            &vec![
+                TaskArg::Value("-c".to_string()),
+                TaskArg::Value("echo".to_string()),
+                TaskArg::Value("hello".to_string())
+            ]
         );
     }
 
@@ -756,6 +2650,50 @@ Second line
         assert_eq!(task_ref.get_help(), "First line\nSecond line");
     }
 
+    #[test]
+    fn test_get_task_summary_and_ancestors() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  base:
+    summary: Base task
+    help: Long help
+    program: bash
+  other_base:
+    help: |
+      First line
+      Second line
+  child:
+    bases: [base, other_base]
+  grandchild:
+    bases: [child]
+    summary: Grandchild task
+  no_summary:
+    bases: [other_base]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+
+        let task = config_file.get_task("child").unwrap();
+        assert_eq!(task.get_summary(), "Base task");
+        assert_eq!(task.get_help(), "Long help");
+        assert_eq!(task.get_ancestors(), &["base", "other_base"]);
+
+        let task = config_file.get_task("grandchild").unwrap();
+        assert_eq!(task.get_summary(), "Grandchild task");
+        assert_eq!(task.get_ancestors(), &["child", "base", "other_base"]);
+
+        // Falls back to the first line of the help
+        let task = config_file.get_task("no_summary").unwrap();
+        assert_eq!(task.get_summary(), "First line");
+        assert!(config_file
+            .get_task("base")
+            .unwrap()
+            .get_ancestors()
+            .is_empty());
+    }
+
     #[test]
     fn test_read_env() {
         let tmp_dir = TempDir::new().unwrap();
@@ -818,7 +2756,7 @@ Second line
         let config_file = ConfigFile::load(project_config_path).unwrap();
 
         let task = config_file.get_task("test").unwrap();
-        let env = task.get_env(&config_file);
+        let env = task.get_env(&config_file, &RunContext::new());
 
         let expected = HashMap::from([
             ("VAR1".to_string(), "VAL1".to_string()),
@@ -828,13 +2766,71 @@ Second line
         assert_eq!(env, expected);
 
         let task = config_file.get_task("test_2").unwrap();
-        let env = task.get_env(&config_file);
+        let env = task.get_env(&config_file, &RunContext::new());
         let expected = HashMap::from([
             ("VAR1".to_string(), "TASK_VAL1".to_string()),
             ("VAR2".to_string(), "OTHER_VAL2".to_string()),
             ("VAR3".to_string(), "VAL3".to_string()),
         ]);
         assert_eq!(env, expected);
+
+        let context = RunContext::new().with_env_overrides(HashMap::from([(
+            "VAR1".to_string(),
+            "CLI_VAL1".to_string(),
+        )]));
+        let env = task.get_env(&config_file, &context);
+        assert_eq!(env.get("VAR1").unwrap(), "CLI_VAL1");
+        assert_eq!(env.get("VAR2").unwrap(), "OTHER_VAL2");
+    }
+
+    #[test]
+    fn test_get_env_with_origins() {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::write(tmp_dir.join(".env"), "FILE_ENV_FILE=1\nFILE_ENV=ignored\n").unwrap();
+        fs::write(tmp_dir.join(".task_env"), "TASK_ENV_FILE=1\n").unwrap();
+        fs::write(
+            tmp_dir.join("project.yamis.yml"),
+            r#"
+env_file: .env
+env:
+  FILE_ENV: "1"
+  CLI: "1"
+tasks:
+  base:
+    env:
+      BASE: "1"
+  mid:
+    bases: [base]
+  child:
+    bases: [mid]
+    env_file: .task_env
+    env:
+      TASK_ENV: "1"
+"#,
+        )
+        .unwrap();
+        let config_file = ConfigFile::load(tmp_dir.join("project.yamis.yml")).unwrap();
+        let context = RunContext::new()
+            .with_env_overrides(HashMap::from([("CLI".to_string(), "2".to_string())]));
+
+        let task = config_file.get_task("child").unwrap();
+        let env: Vec<(String, String, String)> = task
+            .get_env_with_origins(&config_file, &context)
+            .into_iter()
+            .map(|(key, val, origin)| (key, val, origin.to_string()))
+            .collect();
+        let expected: Vec<(String, String, String)> = [
+            ("BASE", "1", "base task base"),
+            ("CLI", "2", "--env-file"),
+            ("FILE_ENV", "1", "file env"),
+            ("FILE_ENV_FILE", "1", "file env_file"),
+            ("TASK_ENV", "1", "task env"),
+            ("TASK_ENV_FILE", "1", "task env_file"),
+        ]
+        .iter()
+        .map(|(key, val, origin)| (key.to_string(), val.to_string(), origin.to_string()))
+        .collect();
+        assert_eq!(env, expected);
     }
 
     #[test]
@@ -927,6 +2923,366 @@ Second line
         assert_eq!(task.unwrap_err().to_string(), expected_error.to_string());
     }
 
+    #[test]
+    fn test_os_overrides() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  base:
+    program: echo
+    env:
+      A: base
+      B: base
+    env.linux: {A: linux}
+    env.windows: {A: windows}
+    env.macos: {A: macos}
+  hello:
+    bases: [base]
+    args: [default]
+    args.linux: [linux]
+    args.windows: [windows]
+    args.macos: [macos]
+    env.linux: {C: linux}
+    env.windows: {C: windows}
+    env.macos: {C: macos}
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+
+        let os = std::env::consts::OS;
+        let task = config_file.get_task("hello").unwrap();
+        let commands = task.render(&TaskArgs::new(), &config_file).unwrap();
+        assert_eq!(commands[0].args(), &[OsString::from(os)]);
+        assert_eq!(commands[0].env().get("A").unwrap(), os);
+        assert_eq!(commands[0].env().get("B").unwrap(), "base");
+        assert_eq!(commands[0].env().get("C").unwrap(), os);
+    }
+
+    #[test]
+    fn test_shared_tasks_run_once() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  build:
+    program: echo
+    args: [build, "{$1?}"]
+  migrate:
+    program: echo
+    args: [migrate]
+    run_always: true
+  deploy:
+    serial: [build, migrate, build, migrate]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let args = TaskArgs::new();
+        let backend = RecordBackend::new();
+        let task = config_file.get_task("deploy").unwrap();
+        task.run_with_backend(&args, &config_file, &RunContext::new(), &backend)
+            .unwrap();
+        // Running it again with other inputs runs the shared task again
+        let args = TaskArgs::from([(String::from("*"), vec!["release".into()])]);
+        config_file
+            .get_task("build")
+            .unwrap()
+            .run_with_backend(&args, &config_file, &RunContext::new(), &backend)
+            .unwrap();
+
+        let tasks: Vec<String> = backend
+            .into_commands()
+            .iter()
+            .map(|command| {
+                let args: Vec<_> = command.args().iter().map(|a| a.to_string_lossy()).collect();
+                args.join(" ")
+            })
+            .collect();
+        assert_eq!(tasks, vec!["build", "migrate", "migrate", "build release"]);
+    }
+
+    #[test]
+    fn test_distinct_tasks_with_the_same_script_run() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  lint:
+    program: cargo
+    args: [check]
+  typecheck:
+    program: cargo
+    args: [check]
+  ci:
+    serial: [lint, typecheck, lint]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let backend = RecordBackend::new();
+        let task = config_file.get_task("ci").unwrap();
+        task.run_with_backend(&TaskArgs::new(), &config_file, &RunContext::new(), &backend)
+            .unwrap();
+        // Each task runs once, even if they run the same command
+        assert_eq!(backend.into_commands().len(), 2);
+    }
+
+    #[test]
+    fn test_serial_os_steps() {
+        let other_os = if std::env::consts::OS == "windows" {
+            "linux"
+        } else {
+            "windows"
+        };
+        let config_file = ConfigFile::load_from_str(
+            &format!(
+                r#"
+tasks:
+  build:
+    program: echo
+    args: [build]
+  build.windows:
+    program: echo
+    args: [build windows]
+  notarize:
+    program: echo
+    args: [notarize]
+  release:
+    serial:
+      - build.windows
+      - task: notarize
+        os: [{}]
+      - task: build
+        os: [linux, macos, windows]
+"#,
+                other_os
+            ),
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let backend = RecordBackend::new();
+        let task = config_file.get_task("release").unwrap();
+        task.run_with_backend(&TaskArgs::new(), &config_file, &RunContext::new(), &backend)
+            .unwrap();
+        let tasks: Vec<String> = backend
+            .into_commands()
+            .iter()
+            .map(|command| command.args()[0].to_string_lossy().into_owned())
+            .collect();
+        let build = if other_os == "linux" {
+            "build windows"
+        } else {
+            "build"
+        };
+        assert_eq!(tasks, vec!["build windows", build]);
+
+        let config_file = ConfigFile::load_from_str(
+            "tasks:\n  release:\n    serial:\n      - task: build\n        os: [beos]\n",
+            PathBuf::from("project.yamis.yml"),
+        );
+        assert!(config_file.is_err());
+    }
+
+    #[test]
+    fn test_deps() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  build:
+    program: echo
+    args: [build]
+  test:
+    deps: [build]
+    program: echo
+    args: [test]
+  lint:
+    deps: [build]
+    program: echo
+    args: [lint, "{$@?}"]
+  check:
+    bases: [lint]
+  ci:
+    deps: [test, check]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let task = config_file.get_task("ci").unwrap();
+        let args = TaskArgs::from([(String::from("*"), vec![OsString::from("all")])]);
+        let backend = RecordBackend::new();
+        task.run_with_backend(&args, &config_file, &RunContext::new(), &backend)
+            .unwrap();
+        let commands: Vec<String> = backend
+            .into_commands()
+            .iter()
+            .map(|command| {
+                command
+                    .args()
+                    .join(std::ffi::OsStr::new(" "))
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        // build runs once, and the arguments are not passed to the deps
+        assert_eq!(commands, vec!["build", "test", "lint"]);
+
+        let commands: Vec<String> = task
+            .render(&args, &config_file)
+            .unwrap()
+            .iter()
+            .map(|command| command.args()[0].to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(commands, vec!["build", "test", "lint"]);
+
+        let config_file = ConfigFile::load_from_str(
+            "tasks:\n  a:\n    deps: [b]\n    script: echo a\n  b:\n    deps: [a]\n    script: echo b\n",
+            PathBuf::from("project.yamis.yml"),
+        );
+        assert!(config_file
+            .unwrap_err()
+            .to_string()
+            .starts_with("Found a cyclic dependency in the deps of Task:"));
+
+        let config_file = ConfigFile::load_from_str(
+            "tasks:\n  a:\n    deps: [missing]\n    script: echo a\n",
+            PathBuf::from("project.yamis.yml"),
+        );
+        assert_eq!(
+            config_file.unwrap_err().to_string(),
+            "Task a cannot depend on non-existing task missing."
+        );
+    }
+
+    #[test]
+    fn test_serial_error_chain() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+    [tasks.build]
+    script = "echo build"
+
+    [tasks.db_up]
+    script = "echo {$1}"
+
+    [tasks.migrate]
+    serial = ["db_up"]
+
+    [tasks.deploy]
+    serial = ["build", "migrate"]
+    "#,
+            PathBuf::from("project.yamis.toml"),
+        )
+        .unwrap();
+
+        let task = config_file.get_task("deploy").unwrap();
+        let err = task.render(&TaskArgs::new(), &config_file).unwrap_err();
+        assert!(err.to_string().starts_with(
+            "Improperly configured tasks.deploy.serial[1] → tasks.migrate.serial[0] → tasks.db_up:\n"
+        ));
+        assert!(err.source().unwrap().source().is_some());
+
+        let err = TaskError::SerialError(
+            String::from("deploy"),
+            0,
+            TaskError::RuntimeError(String::from("build"), String::from("exit code 2")).into(),
+        );
+        assert_eq!(
+            err.to_string(),
+            "Error running tasks.deploy.serial[0] → tasks.build:\nexit code 2"
+        );
+
+        let err = TaskError::SerialError(String::from("deploy"), 0, "Some error".into());
+        assert_eq!(
+            err.to_string(),
+            "Error running tasks.deploy.serial[0]:\nSome error"
+        );
+    }
+
+    #[test]
+    fn test_snippets() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+snippets:
+  compile_flags: ["-O2", "-Wall", "-o", "{out}"]
+tasks:
+  build:
+    program: gcc
+    args:
+      - use: compile_flags
+      - main.c
+  build_debug:
+    bases: [build]
+    args_extend: ["-g"]
+  unknown:
+    program: gcc
+    args: [{use: other}]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let args = TaskArgs::from([(String::from("out"), vec!["main".into()])]);
+        let commands = config_file
+            .get_task("build_debug")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap();
+        assert_eq!(
+            commands[0].args(),
+            &["-O2", "-Wall", "-o", "main", "main.c", "-g"]
+        );
+
+        let err = config_file
+            .get_task("unknown")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Improperly configured tasks.unknown:\nSnippet `other` not found"
+        );
+    }
+
+    #[test]
+    fn test_params() {
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  base:
+    private: true
+    params:
+      replicas: int
+      verbose: bool
+  scale:
+    bases: [base]
+    program: scale
+    args: ["{replicas}", "{verbose?}"]
+  all:
+    serial: [scale]
+"#,
+            PathBuf::from("project.yamis.yml"),
+        )
+        .unwrap();
+        let args = TaskArgs::from([
+            (String::from("replicas"), vec!["+3".into()]),
+            (String::from("verbose"), vec!["YES".into()]),
+        ]);
+        let commands = config_file
+            .get_task("all")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap();
+        assert_eq!(commands[0].args(), &["3", "true"]);
+
+        let args = TaskArgs::from([(String::from("replicas"), vec!["three".into()])]);
+        let err = config_file
+            .get_task("all")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error running tasks.all.serial[0] → tasks.scale:\nInvalid value `three` for `--replicas`, expected an integer"
+        );
+    }
+
     #[test]
     fn test_create_temp_script() {
         let tmp_dir = TempDir::new().unwrap();
@@ -959,4 +3315,54 @@ Second line
         let script_content = fs::read_to_string(script_path).unwrap();
         assert_eq!(script_content, script);
     }
+
+    #[test]
+    fn test_stable_script_path() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_file = ConfigFile::load_from_str(
+            r#"
+tasks:
+  db:reset:
+    script: echo {$1}
+    script_ext: sh
+    stable_script_path: true
+"#,
+            tmp_dir.join("project.yamis.yml"),
+        )
+        .unwrap();
+        let task = config_file.get_task("db:reset").unwrap();
+        let expected_path = tmp_dir.join(".yamis").join("scripts").join("db_reset.sh");
+        for arg in ["first", "second"] {
+            let args = TaskArgs::from([(String::from("*"), vec![OsString::from(arg)])]);
+            let commands = task.render(&args, &config_file).unwrap();
+            let command = commands[0].to_command().unwrap();
+            assert_eq!(
+                command.get_args().last().unwrap(),
+                expected_path.as_os_str()
+            );
+            assert_eq!(
+                fs::read_to_string(&expected_path).unwrap(),
+                format!("echo \"{}\"", arg)
+            );
+        }
+        assert_eq!(get_stable_script_name("build", ""), "build");
+        assert_eq!(
+            get_stable_script_name("build.windows", ".cmd"),
+            "build.windows.cmd"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_create_temp_script_non_unicode_path() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let project_config_path = tmp_dir
+            .join(std::ffi::OsStr::from_bytes(b"caf\xe9"))
+            .join("project.yamis.toml");
+        let script_path =
+            get_temp_script("echo hello", "sh", "sample", project_config_path.as_path()).unwrap();
+        assert_eq!(fs::read_to_string(script_path).unwrap(), "echo hello");
+    }
 }
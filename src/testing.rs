@@ -0,0 +1,297 @@
+//! Helpers to test tasks without going through the command line.
+//!
+//! Config files can be built from a string, and tasks can be either rendered, to assert on
+//! the commands they would spawn, or run with their output captured.
+use crate::backend::{ExecutionBackend, OutputSink, ProcessBackend};
+use crate::cli::TaskSubcommand;
+use crate::config_files::ConfigFile;
+use crate::run_context::RunContext;
+use crate::tasks::RenderedCommand;
+use crate::types::{DynErrResult, TaskArgs};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Output of a task run with [run_task]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskOutput {
+    /// Captured standard output of all the commands that were run
+    pub stdout: String,
+    /// Captured standard error of all the commands that were run, followed by the reason the
+    /// task failed, if it did
+    pub stderr: String,
+    /// Exit code of the last command that was run. None if it was terminated by a signal.
+    pub exit_code: Option<i32>,
+    /// Whether all the commands finished with an allowed exit code
+    success: bool,
+}
+
+impl TaskOutput {
+    /// Returns whether all the commands finished with an allowed exit code
+    pub fn success(&self) -> bool {
+        self.success
+    }
+}
+
+/// Builds a config file from the given contents. The path is only used to know the format of
+/// the contents, from its extension, and to resolve relative paths, so it does not need to exist.
+///
+/// # Arguments
+///
+/// * `contents`: Contents of the config file
+/// * `path`: Path the config file would have, i.e. `project.yamis.yml`
+///
+/// returns: Result<ConfigFile, Box<dyn Error, Global>>
+pub fn config_file_from_str<P: Into<PathBuf>>(contents: &str, path: P) -> DynErrResult<ConfigFile> {
+    ConfigFile::load_from_str(contents, path.into())
+}
+
+/// Parses the arguments as if they were given after the task name in `yamis run <TASK>`.
+///
+/// # Arguments
+///
+/// * `args`: Arguments given to the task
+///
+/// returns: HashMap<String, Vec<OsString, Global>, RandomState>
+pub fn parse_task_args<S: AsRef<str>>(args: &[S]) -> TaskArgs {
+    let args = args
+        .iter()
+        .map(|arg| OsString::from(arg.as_ref()))
+        .collect();
+    TaskSubcommand::get_task_args(args, true)
+}
+
+/// Renders the commands that running the task would spawn, without spawning them.
+///
+/// # Arguments
+///
+/// * `config_file`: Config file containing the task
+/// * `task_name`: Name of the task, which must not be private
+/// * `args`: Arguments given to the task, as they would be given in the command line
+///
+/// returns: Result<Vec<RenderedCommand, Global>, Box<dyn Error, Global>>
+pub fn render_task<S: AsRef<str>>(
+    config_file: &ConfigFile,
+    task_name: &str,
+    args: &[S],
+) -> DynErrResult<Vec<RenderedCommand>> {
+    match config_file.get_public_task(task_name) {
+        Some(task) => task.render(&parse_task_args(args), config_file),
+        None => Err(format!("Task {} not found", task_name).into()),
+    }
+}
+
+/// Collects the output of the commands run by [run_task]
+#[derive(Debug, Default)]
+struct CapturedOutput {
+    /// Captured standard output and error
+    output: Mutex<(String, String)>,
+    /// Whether a process was spawned
+    spawned: AtomicBool,
+}
+
+impl OutputSink for CapturedOutput {
+    fn write_line(&self, line: &str, is_stderr: bool) {
+        let mut output = self.output.lock().unwrap();
+        let output = if is_stderr {
+            &mut output.1
+        } else {
+            &mut output.0
+        };
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    fn set_pid(&self, pid: Option<u32>) {
+        if pid.is_some() {
+            self.spawned.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Runs the task, capturing its output. The task runs like it would from the command line, so
+/// its dependencies, lock and fingerprint are taken into account, and the commands run in order
+/// until one of them fails. Each call starts from scratch, so tasks that already ran in a
+/// previous call run again.
+///
+/// # Arguments
+///
+/// * `config_file`: Config file containing the task
+/// * `task_name`: Name of the task, which must not be private
+/// * `args`: Arguments given to the task, as they would be given in the command line
+/// * `env`: Extra environment variables for the spawned processes. Variables set by the task
+///   or config file take precedence.
+///
+/// returns: Result<TaskOutput, Box<dyn Error, Global>>
+pub fn run_task<S: AsRef<str>>(
+    config_file: &ConfigFile,
+    task_name: &str,
+    args: &[S],
+    env: &HashMap<String, String>,
+) -> DynErrResult<TaskOutput> {
+    let task = config_file
+        .get_public_task(task_name)
+        .ok_or_else(|| format!("Task {} not found", task_name))?;
+
+    let mut base_env: BTreeMap<String, String> = std::env::vars().collect();
+    base_env.extend(env.clone());
+    let captured = Arc::new(CapturedOutput::default());
+    let backend = ProcessBackend::with_env(base_env)
+        .with_output(Arc::clone(&captured) as Arc<dyn OutputSink>);
+    let result = task.run_with_backend(
+        &parse_task_args(args),
+        config_file,
+        &RunContext::new(),
+        &backend,
+    );
+
+    let exit_code = backend.last_exit_code();
+    let success = match result {
+        Ok(_) => true,
+        // The task ran but failed, so the reason is part of its output
+        Err(e) if captured.spawned.load(Ordering::Relaxed) || exit_code.is_some() => {
+            for line in e.to_string().lines() {
+                captured.write_line(line, true);
+            }
+            false
+        }
+        Err(e) => return Err(e),
+    };
+    let (stdout, stderr) = std::mem::take(&mut *captured.output.lock().unwrap());
+    Ok(TaskOutput {
+        stdout,
+        stderr,
+        exit_code: if success {
+            exit_code.or(Some(0))
+        } else {
+            exit_code
+        },
+        success,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_task() {
+        let config_file = config_file_from_str(
+            r#"
+tasks:
+  hello:
+    program: echo
+    args: ["hello", "{$1}", "{name?}"]
+    env:
+      GREETING: hi
+  bye:
+    script: echo bye {$@}
+    script_runner: bash
+  all:
+    serial: [hello, bye]
+  secret:
+    private: true
+    program: echo
+"#,
+            "project.yamis.yml",
+        )
+        .unwrap();
+
+        let commands = render_task(&config_file, "hello", &["world", "--name", "John"]).unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].task_name(), "hello");
+        assert_eq!(commands[0].program(), "echo");
+        assert_eq!(commands[0].args(), &["hello", "world", "John"]);
+        assert_eq!(commands[0].script(), None);
+        assert_eq!(commands[0].env().get("GREETING").unwrap(), "hi");
+
+        let commands = render_task(&config_file, "all", &["a b"]).unwrap();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].args(), &["hello", "a b"]);
+        assert_eq!(commands[1].program(), "bash");
+        assert_eq!(commands[1].script(), Some("echo bye \"a b\""));
+
+        let err = render_task(&config_file, "secret", &[] as &[&str]).unwrap_err();
+        assert_eq!(err.to_string(), "Task secret not found");
+    }
+
+    #[test]
+    fn test_named_args_order() {
+        let config_file = config_file_from_str(
+            r#"
+tasks:
+  build:
+    program: echo
+    args: ["{count(target)}", "{first(target)}", "{last(target)}", "{target}"]
+"#,
+            "project.yamis.yml",
+        )
+        .unwrap();
+
+        let args = ["--target", "a", "--target=b", "x", "-target", "c"];
+        let commands = render_task(&config_file, "build", &args).unwrap();
+        assert_eq!(commands[0].args(), &["3", "a", "c", "a", "b", "c"]);
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_run_task() {
+        let tmp_dir = assert_fs::TempDir::new().unwrap();
+        let config_file = config_file_from_str(
+            r#"
+[tasks.hello]
+script = "echo hello {$1} $NAME $GREETING"
+env = { GREETING = "hi" }
+
+[tasks.fail]
+script = "echo failing && exit 3"
+
+[tasks.allowed]
+script = "echo allowed && exit 3"
+allowed_exit_codes = [0, 3]
+
+[tasks.all]
+serial = ["hello", "fail", "hello"]
+
+[tasks.build]
+script = "echo building && echo built > $YAMIS_PROJECT_ROOT/out.txt"
+sources = ["src.txt"]
+outputs = ["out.txt"]
+fingerprint = "checksum"
+
+[tasks.deploy]
+script = "echo deploying"
+deps = ["build"]
+"#,
+            tmp_dir.path().join("project.yamis.toml"),
+        )
+        .unwrap();
+
+        let env = HashMap::from([
+            (String::from("NAME"), String::from("John")),
+            (String::from("GREETING"), String::from("hello")),
+        ]);
+        let output = run_task(&config_file, "hello", &["world"], &env).unwrap();
+        assert!(output.success());
+        assert_eq!(output.stdout, "hello world John hi\n");
+
+        let output = run_task(&config_file, "allowed", &[] as &[&str], &env).unwrap();
+        assert!(output.success());
+        assert_eq!(output.exit_code, Some(3));
+
+        let output = run_task(&config_file, "all", &["world"], &env).unwrap();
+        assert!(!output.success());
+        assert_eq!(output.exit_code, Some(3));
+        assert_eq!(output.stdout, "hello world John hi\nfailing\n");
+        assert!(output.stderr.contains("exit code 3"));
+
+        // Dependencies run, and are skipped once they are up to date
+        std::fs::write(tmp_dir.path().join("src.txt"), "source").unwrap();
+        let output = run_task(&config_file, "deploy", &[] as &[&str], &env).unwrap();
+        assert_eq!(output.stdout, "building\ndeploying\n");
+        let output = run_task(&config_file, "deploy", &[] as &[&str], &env).unwrap();
+        assert_eq!(output.stdout, "deploying\n");
+    }
+}
@@ -12,7 +12,6 @@ use assert_fs::TempDir;
 use directories::ProjectDirs;
 
 const LATEST_RELEASE_URL: &str = "https://github.com/adrianmrit/yamis/releases/latest/";
-const CHECK_INTERVAL: u64 = 60 * 60 * 24; // 1 day
 
 /// Represents the cache file used to store the last update check time and latest version
 /// available so that we don't check for updates too often.
@@ -88,10 +87,14 @@ impl UpdateCacheFile {
     }
 
     /// Whether the cache file is outdated.
-    fn outdated(&self) -> bool {
+    ///
+    /// # Arguments
+    ///
+    /// * `check_interval`: Seconds after which the cache file is outdated
+    fn outdated(&self, check_interval: u64) -> bool {
         let now = SystemTime::now();
         let now = now.duration_since(UNIX_EPOCH).unwrap().as_secs();
-        now - self.latest_update > CHECK_INTERVAL
+        now.saturating_sub(self.latest_update) > check_interval
     }
 
     /// Updates and writes the cache file to disk.
@@ -107,10 +110,16 @@ impl UpdateCacheFile {
 }
 
 /// Checks for updates and returns the message to be printed to the user.
-pub(crate) fn check_update_available() -> DynErrResult<Option<String>> {
+///
+/// # Arguments
+///
+/// * `check_interval`: Seconds between checks, the latest version found is cached meanwhile
+///
+/// returns: Result<Option<String>, Box<dyn Error, Global>>
+pub(crate) fn check_update_available(check_interval: u64) -> DynErrResult<Option<String>> {
     let mut cache_file = UpdateCacheFile::new();
 
-    if cache_file.outdated() {
+    if cache_file.outdated(check_interval) {
         #[cfg(not(test))]
         {
             let releases = self_update::backends::github::ReleaseList::configure()
@@ -200,11 +209,12 @@ mod tests {
     fn test_update_cache_file_outdated() {
         let mut cache_file = UpdateCacheFile::default();
         cache_file.latest_update = 0;
-        assert!(cache_file.outdated());
+        assert!(cache_file.outdated(60));
         let now = SystemTime::now();
         let now = now.duration_since(UNIX_EPOCH).unwrap().as_secs();
-        cache_file.latest_update = now;
-        assert!(!cache_file.outdated());
+        cache_file.latest_update = now - 120;
+        assert!(cache_file.outdated(60));
+        assert!(!cache_file.outdated(3600));
     }
 
     #[test]
@@ -229,7 +239,7 @@ mod tests {
 
     #[test]
     fn test_check_update_available() {
-        let msg = check_update_available().unwrap();
+        let msg = check_update_available(3600).unwrap();
         assert!(msg.is_some());
         assert!(msg.unwrap().contains("A new release of yamis is available"));
     }
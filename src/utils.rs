@@ -1,10 +1,22 @@
 use crate::tasks::Task;
 use crate::types::DynErrResult;
 use dotenv_parser::parse_dotenv;
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use md5::{Digest, Md5};
 use petgraph::graphmap::DiGraphMap;
+use regex::Regex;
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use std::borrow::Borrow;
+use std::cmp::max;
 use std::collections::{BTreeMap, HashMap};
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{env, fs};
 
 /// To uniquely identify the temporary folder. Constant so that the scripts are cached.
@@ -27,6 +39,57 @@ pub fn to_os_task_name(task_name: &str) -> String {
     format!("{}.{}", task_name, env::consts::OS)
 }
 
+cfg_if::cfg_if! {
+    if #[cfg(unix)] {
+        /// Returns an OS string from bytes obtained with `OsStr::as_encoded_bytes`.
+        pub fn os_string_from_bytes(bytes: &[u8]) -> OsString {
+            use std::os::unix::ffi::OsStrExt;
+            OsStr::from_bytes(bytes).to_os_string()
+        }
+    } else {
+        /// Returns an OS string from bytes obtained with `OsStr::as_encoded_bytes`.
+        ///
+        /// Arguments in windows are always valid unicode, unless they contain unpaired
+        /// surrogates, in which case these will be replaced.
+        pub fn os_string_from_bytes(bytes: &[u8]) -> OsString {
+            OsString::from(String::from_utf8_lossy(bytes).to_string())
+        }
+    }
+}
+
+/// Relation between tasks a dependency graph is built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskRelation {
+    /// Tasks inherit the options of the tasks in `bases`
+    Bases,
+    /// Tasks run the tasks in `deps` before themselves
+    Deps,
+}
+
+impl TaskRelation {
+    /// Returns the names of the tasks the given task is related to
+    fn get_related<'a>(&self, task: &'a Task) -> &'a [String] {
+        match self {
+            TaskRelation::Bases => &task.bases,
+            TaskRelation::Deps => task.get_deps(),
+        }
+    }
+
+    /// Returns the error raised when a task is related to a task that does not exist
+    fn not_found_error(&self, task_name: &str, related_name: &str) -> String {
+        match self {
+            TaskRelation::Bases => format!(
+                "Task {} cannot inherit from non-existing task {}.",
+                task_name, related_name
+            ),
+            TaskRelation::Deps => format!(
+                "Task {} cannot depend on non-existing task {}.",
+                task_name, related_name
+            ),
+        }
+    }
+}
+
 /// Returns a directed graph containing dependency relations dependency for the given tasks, where
 /// the nodes are the names of the tasks. The graph does not include tasks that do not depend, or
 /// are not dependencies of other tasks. It is also possible that the graph contains multiple
@@ -34,28 +97,30 @@ pub fn to_os_task_name(task_name: &str) -> String {
 ///
 /// # Arguments
 ///
-/// * `tasks`: Hashmap of name to task
+/// * `tasks`: Map of name to task
+/// * `relation`: Relation the graph is built from, `bases` or `deps`
 ///
 /// returns: Result<GraphMap<&str, (), Directed>, Box<dyn Error, Global>>
-pub fn get_task_dependency_graph<'a>(
-    tasks: &'a HashMap<String, Task>,
+pub fn get_task_dependency_graph<'a, T: Borrow<Task>>(
+    tasks: &'a IndexMap<String, T>,
+    relation: TaskRelation,
 ) -> DynErrResult<DiGraphMap<&'a str, ()>> {
     let mut graph: DiGraphMap<&'a str, ()> = DiGraphMap::new();
 
     let mut bases_stack: Vec<&str> = vec![];
     for (task_name, task) in tasks {
-        let mut current_task = task;
+        let mut current_task = task.borrow();
         let mut current_task_name: &str = task_name;
 
-        if current_task.bases.is_empty() {
+        if relation.get_related(current_task).is_empty() {
             continue;
         }
 
         loop {
-            for base_name in &current_task.bases {
+            for base_name in relation.get_related(current_task) {
                 let os_base_name = to_os_task_name(base_name);
                 let base_name = if tasks.contains_key(&os_base_name) {
-                    // os_base_name needs to be a reference to the string in the HashMap
+                    // os_base_name needs to be a reference to the string in the map
                     let (os_base_name, _) = tasks.get_key_value(&os_base_name).unwrap();
                     os_base_name
                 } else {
@@ -68,15 +133,9 @@ pub fn get_task_dependency_graph<'a>(
             }
             while let Some(base) = bases_stack.pop() {
                 match tasks.get(base) {
-                    None => {
-                        return Err(format!(
-                            "Task {} cannot inherit from non-existing task {}.",
-                            current_task_name, base
-                        )
-                        .into())
-                    }
+                    None => return Err(relation.not_found_error(current_task_name, base).into()),
                     Some(new_current_task) => {
-                        current_task = new_current_task;
+                        current_task = new_current_task.borrow();
                         current_task_name = base;
                     }
                 }
@@ -110,30 +169,613 @@ pub fn get_path_relative_to_base<B: AsRef<OsStr> + ?Sized, P: AsRef<OsStr> + ?Si
     path.to_path_buf()
 }
 
+/// Exit code given either as an integer or as a string, as keys of TOML tables are always strings
+#[derive(serde_derive::Deserialize, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+enum ExitCodeKey {
+    Int(i32),
+    Str(String),
+}
+
+impl fmt::Display for ExitCodeKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExitCodeKey::Int(code) => write!(f, "{}", code),
+            ExitCodeKey::Str(code) => write!(f, "{}", code),
+        }
+    }
+}
+
+/// Deserializes a map of exit codes to messages, where the exit codes can be given as integers
+/// or strings.
+pub(crate) fn deserialize_exit_code_map<'de, D>(
+    deserializer: D,
+) -> Result<Option<HashMap<i32, String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let map: Option<Vec<(ExitCodeKey, String)>> = deserialize_optional_unique_map(deserializer)?;
+    let map = match map {
+        None => return Ok(None),
+        Some(map) => map,
+    };
+    let mut result = HashMap::with_capacity(map.len());
+    for (key, message) in map {
+        let code = match key {
+            ExitCodeKey::Int(code) => code,
+            ExitCodeKey::Str(code) => code
+                .parse()
+                .map_err(|_| serde::de::Error::custom(format!("invalid exit code `{}`", code)))?,
+        };
+        if result.insert(code, message).is_some() {
+            return Err(serde::de::Error::custom(format!(
+                "duplicate key `{}`",
+                code
+            )));
+        }
+    }
+    Ok(Some(result))
+}
+
+/// Serializes OS strings as strings, replacing invalid unicode sequences.
+/// To be used with `#[serde(serialize_with = "serialize_os_strings_lossy")]`.
+pub(crate) fn serialize_os_strings_lossy<S>(
+    values: &[OsString],
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_seq(values.iter().map(|value| value.to_string_lossy()))
+}
+
+/// Deserializes a list of strings as OS strings.
+/// To be used with `#[serde(deserialize_with = "deserialize_os_strings")]`.
+pub(crate) fn deserialize_os_strings<'de, D>(deserializer: D) -> Result<Vec<OsString>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let values: Vec<String> = Deserialize::deserialize(deserializer)?;
+    Ok(values.into_iter().map(OsString::from).collect())
+}
+
+/// Returns whether a program with the given name can be found in the given PATH. On Windows,
+/// the extensions in `PATHEXT` are also tried.
+///
+/// # Arguments
+///
+/// * `program`: Name of the program, without a path
+/// * `path_var`: Value of the PATH environment variable
+///
+/// returns: bool
+pub(crate) fn is_in_path<S: AsRef<OsStr> + ?Sized>(program: &str, path_var: &S) -> bool {
+    find_in_path(program, path_var).is_some()
+}
+
+/// Returns the path of the first program with the given name found in the given PATH. On
+/// Windows, the extensions in `PATHEXT` are also tried.
+///
+/// # Arguments
+///
+/// * `program`: Name of the program, without a path
+/// * `path_var`: Value of the PATH environment variable
+///
+/// returns: Option<PathBuf>
+pub(crate) fn find_in_path<S: AsRef<OsStr> + ?Sized>(
+    program: &str,
+    path_var: &S,
+) -> Option<PathBuf> {
+    let extensions: Vec<String> = if cfg!(target_os = "windows") {
+        let path_ext = env::var("PATHEXT").unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"));
+        std::iter::once(String::new())
+            .chain(path_ext.split(';').map(String::from))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    env::split_paths(path_var).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", program, ext)))
+            .find(|path| path.is_file())
+    })
+}
+
 /// Reads the content of an environment file from the given path and returns a BTreeMap.
+/// Files are cached by their absolute path, so that each file is only parsed once, even if it
+/// is referenced by multiple tasks or config files. The file is parsed again if its content
+/// changed since it was cached, so that long-lived processes pick up the changes.
 ///
 /// # Arguments
 /// * `path`: Path of the environment file
 ///
 /// returns: DynErrResult<BTreeMap<String, String>>
 pub fn read_env_file<S: AsRef<OsStr> + ?Sized>(path: &S) -> DynErrResult<BTreeMap<String, String>> {
-    let path = Path::new(path);
-    let result = match fs::read_to_string(path) {
-        Ok(content) => parse_dotenv(&content),
+    read_cached_env_file(Path::new(path)).map(|envs| envs.as_ref().clone())
+}
+
+/// Cached env file, along with the checksum of its content when it was parsed
+type CachedEnvFile = (String, Arc<BTreeMap<String, String>>);
+
+/// Same as [read_env_file], but returns the cached variables, which are shared by every read of
+/// the file until it changes
+fn read_cached_env_file(path: &Path) -> DynErrResult<Arc<BTreeMap<String, String>>> {
+    lazy_static! {
+        static ref ENV_FILES_CACHE: Mutex<HashMap<PathBuf, CachedEnvFile>> =
+            Mutex::new(HashMap::new());
+    }
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
         Err(err) => {
             return Err(format!("Failed to read env file at {}: {}", path.display(), err).into())
         }
     };
+    // Files can be rewritten with the same size within the resolution of the modification
+    // time, so changes are found by the content instead
+    let mut hasher = Md5::new();
+    hasher.update(content.as_bytes());
+    let checksum = format!("{:x}", hasher.finalize());
+    let absolute_path = fs::canonicalize(path).ok();
+    if let Some(absolute_path) = &absolute_path {
+        if let Some((cached_checksum, envs)) = ENV_FILES_CACHE.lock().unwrap().get(absolute_path) {
+            if *cached_checksum == checksum {
+                return Ok(Arc::clone(envs));
+            }
+        }
+    }
+    let envs = match parse_dotenv(&content) {
+        Ok(envs) => Arc::new(envs),
+        Err(err) => {
+            return Err(format!("Failed to parse env file at {}: {}", path.display(), err).into())
+        }
+    };
+    if let Some(absolute_path) = absolute_path {
+        ENV_FILES_CACHE
+            .lock()
+            .unwrap()
+            .insert(absolute_path, (checksum, Arc::clone(&envs)));
+    }
+    Ok(envs)
+}
+
+/// Map that fails to deserialize if a key is repeated. YAML parsers usually keep the last value
+/// silently, which can lead to confusing configurations.
+struct UniqueMap<K, V>(IndexMap<K, V>);
+
+impl<'de, K, V> Deserialize<'de> for UniqueMap<K, V>
+where
+    K: Deserialize<'de> + Eq + Hash + fmt::Display,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct UniqueMapVisitor<K, V>(PhantomData<(K, V)>);
+
+        impl<'de, K, V> Visitor<'de> for UniqueMapVisitor<K, V>
+        where
+            K: Deserialize<'de> + Eq + Hash + fmt::Display,
+            V: Deserialize<'de>,
+        {
+            type Value = UniqueMap<K, V>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut map = IndexMap::with_capacity(access.size_hint().unwrap_or(0));
+                while let Some(key) = access.next_key::<K>()? {
+                    if map.contains_key(&key) {
+                        return Err(serde::de::Error::custom(format!("duplicate key `{}`", key)));
+                    }
+                    let value = access.next_value()?;
+                    map.insert(key, value);
+                }
+                Ok(UniqueMap(map))
+            }
+        }
 
-    match result {
-        Ok(envs) => Ok(envs),
-        Err(err) => Err(format!("Failed to parse env file at {}: {}", path.display(), err).into()),
+        deserializer.deserialize_map(UniqueMapVisitor(PhantomData))
+    }
+}
+
+/// Deserializes a map, returning an error if a key is declared more than once.
+/// To be used with `#[serde(deserialize_with = "deserialize_unique_map")]`.
+pub(crate) fn deserialize_unique_map<'de, D, K, V, M>(deserializer: D) -> Result<M, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de> + Eq + Hash + fmt::Display,
+    V: Deserialize<'de>,
+    M: FromIterator<(K, V)>,
+{
+    Ok(UniqueMap::deserialize(deserializer)?
+        .0
+        .into_iter()
+        .collect())
+}
+
+/// Same as [deserialize_unique_map], but for optional maps.
+pub(crate) fn deserialize_optional_unique_map<'de, D, K, V, M>(
+    deserializer: D,
+) -> Result<Option<M>, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de> + Eq + Hash + fmt::Display,
+    V: Deserialize<'de>,
+    M: FromIterator<(K, V)>,
+{
+    Ok(
+        Option::<UniqueMap<K, V>>::deserialize(deserializer)?
+            .map(|map| map.0.into_iter().collect()),
+    )
+}
+
+/// String that can also be given as a number or a boolean, i.e. `PORT: 8080`, which is
+/// converted to its text
+struct ScalarString(String);
+
+impl<'de> Deserialize<'de> for ScalarString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ScalarStringVisitor;
+
+        impl<'de> Visitor<'de> for ScalarStringVisitor {
+            type Value = ScalarString;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string, number or boolean")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(ScalarString(String::from(value)))
+            }
+
+            fn visit_string<E: serde::de::Error>(self, value: String) -> Result<Self::Value, E> {
+                Ok(ScalarString(value))
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, value: bool) -> Result<Self::Value, E> {
+                Ok(ScalarString(value.to_string()))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                Ok(ScalarString(value.to_string()))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(ScalarString(value.to_string()))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<Self::Value, E> {
+                // Debug keeps the decimal point of round numbers, i.e. `1.0` instead of `1`
+                Ok(ScalarString(format!("{:?}", value)))
+            }
+        }
+
+        deserializer.deserialize_any(ScalarStringVisitor)
+    }
+}
+
+/// Same as [deserialize_unique_map], but the values can also be given as numbers or booleans,
+/// which are converted to strings, i.e. `PORT: 8080` for environment variables.
+pub(crate) fn deserialize_scalar_map<'de, D, M>(deserializer: D) -> Result<M, D::Error>
+where
+    D: Deserializer<'de>,
+    M: FromIterator<(String, String)>,
+{
+    Ok(
+        UniqueMap::<String, ScalarString>::deserialize(deserializer)?
+            .0
+            .into_iter()
+            .map(|(key, value)| (key, value.0))
+            .collect(),
+    )
+}
+
+/// Same as [deserialize_scalar_map], but for optional maps.
+pub(crate) fn deserialize_optional_scalar_map<'de, D, M>(
+    deserializer: D,
+) -> Result<Option<M>, D::Error>
+where
+    D: Deserializer<'de>,
+    M: FromIterator<(String, String)>,
+{
+    Ok(
+        Option::<UniqueMap<String, ScalarString>>::deserialize(deserializer)?.map(|map| {
+            map.0
+                .into_iter()
+                .map(|(key, value)| (key, value.0))
+                .collect()
+        }),
+    )
+}
+
+/// Returns the edit distance between two strings, that is, the minimum number of single character
+/// insertions, deletions or substitutions needed to change one string into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution_cost = if a_char == *b_char { 0 } else { 1 };
+            current[j + 1] = (previous[j] + substitution_cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Returns whether the text matches the glob pattern, where `*` matches any sequence of
+/// characters and `?` matches a single character.
+///
+/// # Arguments
+///
+/// * `pattern`: Glob pattern, i.e. `build_*`
+/// * `text`: Text to match
+///
+/// returns: bool
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and of the text when it was found, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the `*` match one more character
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Returns whether the text loosely matches the pattern, that is, if the characters of the
+/// pattern appear in the text in the same order, i.e. `bld` matches `build`, or if the text
+/// is a typo away from the pattern. Case is ignored.
+///
+/// # Arguments
+///
+/// * `pattern`: Pattern to match
+/// * `text`: Text to match
+///
+/// returns: bool
+pub(crate) fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    let mut text_chars = text.chars();
+    if pattern.chars().all(|c| text_chars.any(|t| t == c)) {
+        return true;
+    }
+    // Allow roughly one typo every three characters, like suggestions for unknown fields
+    edit_distance(&pattern, &text) <= max(1, pattern.chars().count() / 3)
+}
+
+/// Replaces the list of expected fields in unknown field errors, i.e.
+/// "unknown field `scrpt`, expected one of `help`, `script`, ...", with a suggestion of the
+/// closest expected field, i.e. "unknown field `scrpt`, did you mean `script`?". The message
+/// is returned unchanged if there is no close enough field.
+///
+/// # Arguments
+///
+/// * `msg`: Error message
+///
+/// returns: String
+pub(crate) fn add_unknown_field_suggestion(msg: &str) -> String {
+    lazy_static! {
+        static ref UNKNOWN_FIELD_REGEX: Regex =
+            Regex::new(r"unknown field `(?P<field>[^`]*)`, (?P<expected>expected (?:one of )?`[^`]*`(?:(?:, | or )`[^`]*`)*)").unwrap();
+        static ref FIELD_REGEX: Regex = Regex::new(r"`([^`]*)`").unwrap();
+    }
+    let captures = match UNKNOWN_FIELD_REGEX.captures(msg) {
+        Some(captures) => captures,
+        None => return String::from(msg),
+    };
+    let field = &captures["field"];
+    let expected = captures.name("expected").unwrap();
+    // Allow roughly one typo every three characters
+    let max_distance = max(1, field.chars().count() / 3);
+    let suggestion = FIELD_REGEX
+        .captures_iter(expected.as_str())
+        .map(|expected_field| expected_field.get(1).unwrap().as_str())
+        .map(|expected_field| (edit_distance(field, expected_field), expected_field))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance);
+    match suggestion {
+        Some((_, suggestion)) => format!(
+            "{}did you mean `{}`?{}",
+            &msg[..expected.start()],
+            suggestion,
+            &msg[expected.end()..]
+        ),
+        None => String::from(msg),
+    }
+}
+
+/// Returns the time since the Unix epoch, or zero if the clock is set before it
+pub(crate) fn since_epoch() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Duration given either as a number of seconds or as a string with a unit
+#[derive(serde_derive::Deserialize)]
+#[serde(untagged)]
+enum RawDuration {
+    Secs(u64),
+    Str(String),
+}
+
+/// Parses a duration given as a number followed by `ms`, `s`, `m` or `h`, i.e. `10m`. Numbers
+/// without a unit are seconds.
+pub(crate) fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_start);
+    let number: u64 = match number.parse() {
+        Ok(number) => number,
+        Err(_) => return Err(format!("invalid duration `{}`", value)),
+    };
+    match unit.trim() {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        "h" => Ok(Duration::from_secs(number * 3600)),
+        _ => Err(format!(
+            "invalid duration `{}`, expected a number followed by `ms`, `s`, `m` or `h`",
+            value
+        )),
+    }
+}
+
+/// Deserializes an optional duration, given as a number of seconds or as a string, i.e. `10m`
+pub(crate) fn deserialize_optional_duration<'de, D>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<RawDuration>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(RawDuration::Secs(secs)) => Ok(Some(Duration::from_secs(secs))),
+        Some(RawDuration::Str(value)) => parse_duration(&value)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(
+            parse_duration("10d"),
+            Err(String::from(
+                "invalid duration `10d`, expected a number followed by `ms`, `s`, `m` or `h`"
+            ))
+        );
+        assert_eq!(
+            parse_duration("m"),
+            Err(String::from("invalid duration `m`"))
+        );
+    }
+
+    #[test]
+    fn test_deserialize_scalar_map() {
+        #[derive(serde_derive::Deserialize)]
+        struct Env {
+            #[serde(deserialize_with = "deserialize_scalar_map")]
+            env: IndexMap<String, String>,
+        }
+
+        let env: Env = serde_yaml::from_str(
+            "env:\n  PORT: 8080\n  DEBUG: true\n  RATIO: 1.0\n  OFFSET: -2\n  NAME: app",
+        )
+        .unwrap();
+        assert_eq!(
+            env.env,
+            IndexMap::from([
+                (String::from("PORT"), String::from("8080")),
+                (String::from("DEBUG"), String::from("true")),
+                (String::from("RATIO"), String::from("1.0")),
+                (String::from("OFFSET"), String::from("-2")),
+                (String::from("NAME"), String::from("app")),
+            ])
+        );
+
+        let env: Env = toml::from_str("[env]\nPORT = 8080\nDEBUG = false").unwrap();
+        assert_eq!(env.env["PORT"], "8080");
+        assert_eq!(env.env["DEBUG"], "false");
+
+        assert!(serde_yaml::from_str::<Env>("env:\n  LIST: [1, 2]").is_err());
+        assert!(serde_yaml::from_str::<Env>("env:\n  PORT: 1\n  PORT: 2").is_err());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("script", "script"), 0);
+        assert_eq!(edit_distance("scrpt", "script"), 1);
+        assert_eq!(edit_distance("", "env"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("build", "build"));
+        assert!(!glob_match("build", "build_release"));
+        assert!(glob_match("build_*", "build_release"));
+        assert!(glob_match("build_*", "build_"));
+        assert!(glob_match("*_release", "build_release"));
+        assert!(glob_match("b*d*e", "build_release"));
+        assert!(glob_match("te?t", "test"));
+        assert!(!glob_match("te?t", "tet"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*a", "bbb"));
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert!(fuzzy_match("bld", "build"));
+        assert!(fuzzy_match("BUILD", "build_release"));
+        assert!(fuzzy_match("buikd", "build"));
+        assert!(!fuzzy_match("deploy", "build"));
+        assert!(!fuzzy_match("dlb", "build"));
+    }
+
+    #[test]
+    fn test_add_unknown_field_suggestion() {
+        assert_eq!(
+            add_unknown_field_suggestion(
+                "tasks.hello: unknown field `scrpt`, expected one of `help`, `script`, `env` at line 3 column 5"
+            ),
+            "tasks.hello: unknown field `scrpt`, did you mean `script`? at line 3 column 5"
+        );
+        assert_eq!(
+            add_unknown_field_suggestion("unknown field `hlp`, expected `help` or `env`"),
+            "unknown field `hlp`, did you mean `help`?"
+        );
+        // Not close enough
+        let msg = "unknown field `other`, expected one of `help`, `script`, `env`";
+        assert_eq!(add_unknown_field_suggestion(msg), msg);
+        let msg = "invalid type: string \"hello\", expected a map";
+        assert_eq!(add_unknown_field_suggestion(msg), msg);
+    }
     use assert_fs::TempDir;
     use std::env;
     use std::fs::File;
@@ -185,6 +827,32 @@ mod tests {
         .unwrap();
         let env_map = read_env_file(&env_file_path).unwrap();
         assert_eq!(env_map.get("TEST_VAR"), Some(&"test_value".to_string()));
+
+        // The file is cached by its absolute path, even if referenced by a different path
+        let cached = read_cached_env_file(&env_file_path).unwrap();
+        let other_path = tmp_dir
+            .join("..")
+            .join(tmp_dir.file_name().unwrap())
+            .join(".env");
+        assert!(Arc::ptr_eq(
+            &cached,
+            &read_cached_env_file(&other_path).unwrap()
+        ));
+
+        // The file is parsed again after it changes, even if the size and modification time
+        // are the same
+        let metadata = fs::metadata(&env_file_path).unwrap();
+        fs::write(&env_file_path, "\n    TEST_VAR=test_other\n    ").unwrap();
+        assert_eq!(fs::metadata(&env_file_path).unwrap().len(), metadata.len());
+        File::options()
+            .write(true)
+            .open(&env_file_path)
+            .unwrap()
+            .set_modified(metadata.modified().unwrap())
+            .unwrap();
+        let env_map = read_cached_env_file(&other_path).unwrap();
+        assert!(!Arc::ptr_eq(&cached, &env_map));
+        assert_eq!(env_map.get("TEST_VAR"), Some(&"test_other".to_string()));
     }
 
     #[test]
@@ -0,0 +1,353 @@
+//! Versions of the config files format. All versions are handled by the same parser, but
+//! fields introduced in a later version can only be used if the config file declares it.
+use crate::print_utils::YamisOutput;
+use crate::types::DynErrResult;
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use serde::de::IgnoredAny;
+use serde_derive::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Versions of the config files format, in order
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub(crate) enum ConfigVersion {
+    #[default]
+    #[serde(rename = "1", alias = "v1")]
+    V1,
+    #[serde(rename = "2", alias = "v2")]
+    V2,
+}
+
+impl fmt::Display for ConfigVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigVersion::V1 => write!(f, "1"),
+            ConfigVersion::V2 => write!(f, "2"),
+        }
+    }
+}
+
+/// Field introduced after the first version
+pub(crate) struct VersionedField {
+    /// Name of the field
+    pub(crate) name: &'static str,
+    /// Version the field was introduced in
+    pub(crate) since: ConfigVersion,
+}
+
+/// Fields of config files introduced after the first version
+const CONFIG_FILE_FIELDS: &[VersionedField] = &[];
+
+/// Fields of tasks introduced after the first version
+const TASK_FIELDS: &[VersionedField] = &[];
+
+/// Key of the context available to templates, i.e. `invocation.cwd`
+pub(crate) struct ContextKey {
+    /// Key as written in templates. Keys named by the user are given as `<name>`
+    pub(crate) key: &'static str,
+    /// Version the key was introduced in
+    pub(crate) since: ConfigVersion,
+    /// If the key is slated for removal, what to use instead
+    pub(crate) deprecated: Option<&'static str>,
+}
+
+/// Shorthand to declare a key available since the first version
+const fn context_key(key: &'static str) -> ContextKey {
+    ContextKey {
+        key,
+        since: ConfigVersion::V1,
+        deprecated: None,
+    }
+}
+
+/// Keys of the context available to templates. Every key the template parser accepts must be
+/// declared here, and keys are only removed in a later version after being deprecated.
+pub(crate) const CONTEXT_KEYS: &[ContextKey] = &[
+    // Positional argument, i.e. `$1`
+    context_key("$<n>"),
+    // All the arguments given to the task
+    context_key("$@"),
+    // Named argument, i.e. `name` for `--name=value`
+    context_key("<name>"),
+    // Environment variable, i.e. `$HOME`
+    context_key("$<name>"),
+    // Variable of the config file, or of the global one
+    context_key("vars.<name>"),
+    // Variable of the config file
+    context_key("file.vars.<name>"),
+    // Constant of the config file
+    context_key("const.<name>"),
+    // Environment variable imported with `env_import_prefix`
+    context_key("cfg.<name>"),
+    // State directory of the project
+    context_key("dirs.state"),
+    // Cache directory of the project
+    context_key("dirs.cache"),
+    // Temporary directory of the project
+    context_key("dirs.tmp"),
+    // Directory yamis was called from
+    context_key("invocation.cwd"),
+    // Arguments yamis was called with
+    context_key("invocation.argv"),
+    // Whether the commands are only printed
+    context_key("invocation.dry_run"),
+    // Number of CPUs available
+    context_key("system.cpus"),
+    // Total memory in megabytes
+    context_key("system.memory_mb"),
+    // Hostname of the machine
+    context_key("system.hostname"),
+    // Members of the workspace
+    context_key("workspace.members"),
+];
+
+lazy_static! {
+    /// Deprecated context keys that were already warned about
+    static ref WARNED_CONTEXT_KEYS: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+}
+
+/// Returns an error if the context key does not exist or was introduced after the given
+/// version, and prints a warning the first time a deprecated key is used
+///
+/// # Arguments
+///
+/// * `key`: Key as written in templates, with user given names replaced, i.e. `vars.<name>`
+/// * `version`: Declared version of the config file
+/// * `context_keys`: Keys of the context available to templates
+///
+/// returns: Result<(), String>
+fn check_context_key_in(
+    key: &str,
+    version: ConfigVersion,
+    context_keys: &'static [ContextKey],
+) -> Result<(), String> {
+    let Some(context_key) = context_keys.iter().find(|k| k.key == key) else {
+        return Err(format!("`{}` is not available to templates", key));
+    };
+    if context_key.since > version {
+        return Err(format!(
+            "`{}` requires version >= {}, but the config file declares version {}. Set `version: \"{}\"` to use it.",
+            key, context_key.since, version, context_key.since
+        ));
+    }
+    if let Some(replacement) = context_key.deprecated {
+        if WARNED_CONTEXT_KEYS.lock().unwrap().insert(context_key.key) {
+            let msg = format!(
+                "`{}` is deprecated and will be removed in a later version, use {} instead",
+                key, replacement
+            );
+            eprintln!("{}", msg.yamis_warn());
+        }
+    }
+    Ok(())
+}
+
+/// Returns an error if the context key does not exist or was introduced after the given
+/// version, and prints a warning the first time a deprecated key is used
+///
+/// # Arguments
+///
+/// * `key`: Key as written in templates, with user given names replaced, i.e. `vars.<name>`
+/// * `version`: Declared version of the config file
+///
+/// returns: Result<(), String>
+pub(crate) fn check_context_key(key: &str, version: ConfigVersion) -> Result<(), String> {
+    check_context_key_in(key, version, CONTEXT_KEYS)
+}
+
+/// Lightweight representation of a task that only holds the names of its fields
+#[derive(Debug, Deserialize)]
+struct TaskKeys {
+    /// Task to run instead if the OS is linux
+    linux: Option<Box<TaskKeys>>,
+    /// Task to run instead if the OS is windows
+    windows: Option<Box<TaskKeys>>,
+    /// Task to run instead if the OS is macos
+    macos: Option<Box<TaskKeys>>,
+    /// Other fields of the task
+    #[serde(flatten)]
+    fields: IndexMap<String, IgnoredAny>,
+}
+
+/// Lightweight representation of a config file that only holds its version and the names of
+/// its fields and the fields of its tasks
+#[derive(Debug, Deserialize)]
+pub(crate) struct ConfigFileKeys {
+    /// Declared version of the config file
+    #[serde(default)]
+    version: ConfigVersion,
+    /// Tasks of the config file
+    #[serde(default)]
+    tasks: IndexMap<String, TaskKeys>,
+    /// Other fields of the config file
+    #[serde(flatten)]
+    fields: IndexMap<String, IgnoredAny>,
+}
+
+/// Returns an error if the field was introduced after the given version
+///
+/// # Arguments
+///
+/// * `field`: Name of the field
+/// * `version`: Declared version of the config file
+/// * `versioned_fields`: Fields introduced after the first version
+///
+/// returns: Result<(), String>
+fn check_field(
+    field: &str,
+    version: ConfigVersion,
+    versioned_fields: &[VersionedField],
+) -> Result<(), String> {
+    match versioned_fields.iter().find(|f| f.name == field) {
+        Some(versioned_field) if versioned_field.since > version => Err(format!(
+            "`{}` requires version >= {}, but the config file declares version {}. Set `version: \"{}\"` to use it.",
+            field, versioned_field.since, version, versioned_field.since
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns an error if the task, or its OS specific tasks, use a field introduced after the
+/// given version
+fn check_task(
+    task: &TaskKeys,
+    version: ConfigVersion,
+    versioned_fields: &[VersionedField],
+) -> Result<(), String> {
+    for field in task.fields.keys() {
+        check_field(field, version, versioned_fields)?;
+    }
+    for os_task in [&task.linux, &task.windows, &task.macos]
+        .into_iter()
+        .flatten()
+    {
+        check_task(os_task, version, versioned_fields)?;
+    }
+    Ok(())
+}
+
+impl ConfigFileKeys {
+    /// Returns an error if a field introduced after the declared version is used
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file_fields`: Fields of config files introduced after the first version
+    /// * `task_fields`: Fields of tasks introduced after the first version
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    fn check_fields(
+        &self,
+        config_file_fields: &[VersionedField],
+        task_fields: &[VersionedField],
+    ) -> DynErrResult<()> {
+        for field in self.fields.keys() {
+            check_field(field, self.version, config_file_fields)?;
+        }
+        for (name, task) in &self.tasks {
+            if let Err(e) = check_task(task, self.version, task_fields) {
+                return Err(format!("tasks.{}: {}", name, e).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns an error if a field introduced after the declared version is used
+    pub(crate) fn check_versions(&self) -> DynErrResult<()> {
+        self.check_fields(CONFIG_FILE_FIELDS, TASK_FIELDS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CONFIG_FILE_FIELDS: &[VersionedField] = &[VersionedField {
+        name: "profiles",
+        since: ConfigVersion::V2,
+    }];
+
+    const TEST_TASK_FIELDS: &[VersionedField] = &[VersionedField {
+        name: "depends_on",
+        since: ConfigVersion::V2,
+    }];
+
+    fn check(contents: &str) -> DynErrResult<()> {
+        let keys: ConfigFileKeys = serde_yaml::from_str(contents)?;
+        keys.check_fields(TEST_CONFIG_FILE_FIELDS, TEST_TASK_FIELDS)
+    }
+
+    #[test]
+    fn test_versioned_fields() {
+        assert!(check("tasks:\n  hello:\n    script: echo hello\n").is_ok());
+        assert!(check("version: '2'\nprofiles: {}\n").is_ok());
+        assert!(check("version: v2\ntasks:\n  build:\n    depends_on: [lint]\n").is_ok());
+
+        let err = check("profiles: {}\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`profiles` requires version >= 2, but the config file declares version 1. Set `version: \"2\"` to use it."
+        );
+
+        let err = check("version: '1'\ntasks:\n  build:\n    linux:\n      depends_on: [lint]\n")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("tasks.build: `depends_on` requires version >= 2"));
+
+        let keys: ConfigFileKeys = toml::from_str("[tasks.build]\ndepends_on = ['lint']").unwrap();
+        assert!(keys
+            .check_fields(TEST_CONFIG_FILE_FIELDS, TEST_TASK_FIELDS)
+            .is_err());
+
+        assert!(check("version: '3'\n").is_err());
+    }
+
+    const TEST_CONTEXT_KEYS: &[ContextKey] = &[
+        context_key("vars.<name>"),
+        ContextKey {
+            key: "task.name",
+            since: ConfigVersion::V2,
+            deprecated: None,
+        },
+        ContextKey {
+            key: "file.<name>",
+            since: ConfigVersion::V1,
+            deprecated: Some("`file.vars.<name>`"),
+        },
+    ];
+
+    #[test]
+    fn test_context_keys() {
+        assert!(check_context_key_in("vars.<name>", ConfigVersion::V1, TEST_CONTEXT_KEYS).is_ok());
+        assert!(check_context_key_in("task.name", ConfigVersion::V2, TEST_CONTEXT_KEYS).is_ok());
+
+        let err =
+            check_context_key_in("task.name", ConfigVersion::V1, TEST_CONTEXT_KEYS).unwrap_err();
+        assert_eq!(
+            err,
+            "`task.name` requires version >= 2, but the config file declares version 1. Set `version: \"2\"` to use it."
+        );
+
+        let err =
+            check_context_key_in("task.args", ConfigVersion::V2, TEST_CONTEXT_KEYS).unwrap_err();
+        assert_eq!(err, "`task.args` is not available to templates");
+
+        // Deprecated keys still work, but are warned about once
+        assert!(check_context_key_in("file.<name>", ConfigVersion::V1, TEST_CONTEXT_KEYS).is_ok());
+        assert!(WARNED_CONTEXT_KEYS.lock().unwrap().contains("file.<name>"));
+
+        // Keys available to templates are not deprecated nor need a later version
+        for context_key in CONTEXT_KEYS {
+            assert!(check_context_key(context_key.key, ConfigVersion::V1).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_config_version_order() {
+        assert!(ConfigVersion::V1 < ConfigVersion::V2);
+        assert_eq!(ConfigVersion::default(), ConfigVersion::V1);
+        assert_eq!(ConfigVersion::V2.to_string(), "2");
+    }
+}
@@ -0,0 +1,327 @@
+//! WASM plugins declared in config files with `plugins`, which provide functions to the tasks of
+//! the config file. Modules run sandboxed, without access to the host, and with limited memory
+//! and instructions.
+//!
+//! A module must export its `memory`, a `yamis_alloc(len: i32) -> i32` function that returns a
+//! pointer to `len` free bytes, and a `yamis_fn_<name>(ptr: i32, len: i32) -> i64` function for
+//! each function it provides. Functions receive the JSON array of their arguments, i.e.
+//! `["a", ["b", "c"]]`, and return the pointer and length of their JSON response, packed as
+//! `(ptr << 32) | len`. The response is either `{"result": "value"}`, `{"result": ["a", "b"]}` or
+//! `{"error": "message"}`.
+use crate::plugins::PluginValue;
+use crate::types::DynErrResult;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Prefix of the exported functions that are available to the tasks
+#[cfg(feature = "wasm")]
+const FUNCTION_PREFIX: &str = "yamis_fn_";
+
+/// Instructions a single function call can run before it is stopped
+#[cfg(feature = "wasm")]
+const FUEL_LIMIT: u64 = 100_000_000;
+
+/// Maximum size of the memory of a module, in bytes
+#[cfg(feature = "wasm")]
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Compiled module of a plugin
+#[cfg(feature = "wasm")]
+struct WasmModule {
+    /// Path of the module
+    path: PathBuf,
+    /// Compiled module
+    module: wasmi::Module,
+    /// Functions provided by the module, without the prefix
+    functions: Vec<String>,
+}
+
+/// WASM plugins of a config file. Modules are compiled the first time one of their functions
+/// is used.
+#[derive(Default)]
+pub(crate) struct WasmPlugins {
+    /// Paths of the modules, in the order they were declared
+    paths: Vec<PathBuf>,
+    /// Engine the modules are compiled with
+    #[cfg(feature = "wasm")]
+    engine: wasmi::Engine,
+    /// Compiled modules, or the error compiling them
+    #[cfg(feature = "wasm")]
+    modules: std::sync::OnceLock<Result<Vec<WasmModule>, String>>,
+}
+
+impl fmt::Debug for WasmPlugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmPlugins")
+            .field("paths", &self.paths)
+            .finish()
+    }
+}
+
+impl WasmPlugins {
+    /// Returns the plugins of the modules in the given paths, which must exist. The modules are
+    /// not compiled yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths`: Paths of the modules
+    ///
+    /// returns: Result<WasmPlugins, Box<dyn Error, Global>>
+    pub(crate) fn new(paths: Vec<PathBuf>) -> DynErrResult<WasmPlugins> {
+        if paths.is_empty() {
+            return Ok(WasmPlugins::default());
+        }
+        if cfg!(not(feature = "wasm")) {
+            return Err(
+                "WASM plugins are not supported, yamis was built without the `wasm` feature".into(),
+            );
+        }
+        if let Some(path) = paths.iter().find(|path| !path.is_file()) {
+            return Err(format!("Plugin {} not found", path.display()).into());
+        }
+        Ok(WasmPlugins {
+            paths,
+            #[cfg(feature = "wasm")]
+            engine: wasmi::Engine::new(wasmi::Config::default().consume_fuel(true)),
+            #[cfg(feature = "wasm")]
+            modules: std::sync::OnceLock::new(),
+        })
+    }
+
+    /// Returns whether a plugin provides the function with the given name, or an error if a
+    /// module cannot be compiled
+    #[cfg(feature = "wasm")]
+    pub(crate) fn has_function(&self, name: &str) -> DynErrResult<bool> {
+        if self.paths.is_empty() {
+            return Ok(false);
+        }
+        Ok(self.find_module(name)?.is_some())
+    }
+
+    /// Returns false, as WASM plugins are not supported
+    #[cfg(not(feature = "wasm"))]
+    pub(crate) fn has_function(&self, _name: &str) -> DynErrResult<bool> {
+        Ok(false)
+    }
+
+    /// Calls the function with the given name, from the first plugin that provides it
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: Name of the function
+    /// * `args`: Arguments of the function
+    ///
+    /// returns: Result<PluginValue, Box<dyn Error, Global>>
+    #[cfg(feature = "wasm")]
+    pub(crate) fn call(&self, name: &str, args: &[PluginValue]) -> DynErrResult<PluginValue> {
+        let Some(module) = self.find_module(name)? else {
+            return Err(format!("Undefined function `{}`", name).into());
+        };
+        match self.call_module(module, name, args) {
+            Ok(value) => Ok(value),
+            Err(e) => Err(format!("Plugin {}: {}", module.path.display(), e).into()),
+        }
+    }
+
+    /// Returns an error, as WASM plugins are not supported
+    #[cfg(not(feature = "wasm"))]
+    pub(crate) fn call(&self, name: &str, _args: &[PluginValue]) -> DynErrResult<PluginValue> {
+        Err(format!("Undefined function `{}`", name).into())
+    }
+
+    /// Returns the first module that provides the given function, compiling the modules if
+    /// they were not compiled yet
+    #[cfg(feature = "wasm")]
+    fn find_module(&self, name: &str) -> DynErrResult<Option<&WasmModule>> {
+        let modules = self.modules.get_or_init(|| {
+            self.paths
+                .iter()
+                .map(|path| self.compile(path.clone()))
+                .collect()
+        });
+        match modules {
+            Ok(modules) => Ok(modules
+                .iter()
+                .find(|module| module.functions.iter().any(|function| function == name))),
+            Err(e) => Err(e.clone().into()),
+        }
+    }
+
+    /// Compiles the module in the given path. Modules cannot import anything from the host.
+    #[cfg(feature = "wasm")]
+    fn compile(&self, path: PathBuf) -> Result<WasmModule, String> {
+        let module = std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|wasm| wasmi::Module::new(&self.engine, &wasm).map_err(|e| e.to_string()));
+        let module = match module {
+            Ok(module) => module,
+            Err(e) => return Err(format!("Invalid plugin {}: {}", path.display(), e)),
+        };
+        if let Some(import) = module.imports().next() {
+            return Err(format!(
+                "Invalid plugin {}: plugins cannot import anything, but it imports `{}::{}`",
+                path.display(),
+                import.module(),
+                import.name()
+            ));
+        }
+        let functions = module
+            .exports()
+            .filter_map(|export| export.name().strip_prefix(FUNCTION_PREFIX))
+            .map(String::from)
+            .collect();
+        Ok(WasmModule {
+            path,
+            module,
+            functions,
+        })
+    }
+
+    /// Calls the function of the given module, in a new instance so that calls do not share
+    /// their state
+    #[cfg(feature = "wasm")]
+    fn call_module(
+        &self,
+        module: &WasmModule,
+        name: &str,
+        args: &[PluginValue],
+    ) -> DynErrResult<PluginValue> {
+        use crate::plugins::FunctionResponse;
+        use wasmi::{Linker, Store, StoreLimits, StoreLimitsBuilder};
+
+        let limits = StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build();
+        let mut store: Store<StoreLimits> = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL_LIMIT).map_err(|e| e.to_string())?;
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &module.module)?
+            .ensure_no_start(&mut store)?;
+        let Some(memory) = instance.get_memory(&store, "memory") else {
+            return Err("it does not export its `memory`".into());
+        };
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "yamis_alloc")?;
+        let function = instance
+            .get_typed_func::<(i32, i32), i64>(&store, &format!("{}{}", FUNCTION_PREFIX, name))?;
+
+        let request = serde_json::to_vec(args)?;
+        let request_len = i32::try_from(request.len())?;
+        let request_ptr = alloc.call(&mut store, request_len)?;
+        memory
+            .write(&mut store, request_ptr as u32 as usize, &request)
+            .map_err(|e| e.to_string())?;
+        let packed = function.call(&mut store, (request_ptr, request_len))? as u64;
+
+        let (response_ptr, response_len) = ((packed >> 32) as usize, (packed as u32) as usize);
+        let Some(response) = memory
+            .data(&store)
+            .get(response_ptr..response_ptr.saturating_add(response_len))
+        else {
+            return Err(format!("the response of `{}` is out of bounds", name).into());
+        };
+        match serde_json::from_slice::<FunctionResponse>(response) {
+            Ok(response) => response.into_result(),
+            Err(e) => Err(format!("invalid response of `{}`: {}", name, e).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "wasm")]
+mod tests {
+    use super::*;
+    use crate::config_files::ConfigFile;
+    use crate::types::TaskArgs;
+    use assert_fs::TempDir;
+
+    /// Module with an `upper` function that returns its arguments in upper case, a `fail`
+    /// function that returns an error, and a `spin` function that never returns
+    const MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (data (i32.const 0) "{\"result\":")
+  (data (i32.const 16) "{\"error\":\"boom\"}")
+  (func $alloc (export "yamis_alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "yamis_fn_upper") (param $ptr i32) (param $len i32) (result i64)
+    (local $out i32) (local $i i32) (local $c i32)
+    (local.set $out (call $alloc (i32.add (local.get $len) (i32.const 11))))
+    (memory.copy (local.get $out) (i32.const 0) (i32.const 10))
+    (block $done
+      (loop $next_char
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (local.set $c (i32.load8_u (i32.add (local.get $ptr) (local.get $i))))
+        (if (i32.and (i32.ge_u (local.get $c) (i32.const 97)) (i32.le_u (local.get $c) (i32.const 122)))
+          (then (local.set $c (i32.sub (local.get $c) (i32.const 32)))))
+        (i32.store8 (i32.add (i32.add (local.get $out) (i32.const 10)) (local.get $i)) (local.get $c))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next_char)))
+    (i32.store8 (i32.add (i32.add (local.get $out) (i32.const 10)) (local.get $len)) (i32.const 125))
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+      (i64.extend_i32_u (i32.add (local.get $len) (i32.const 11)))))
+  (func (export "yamis_fn_fail") (param i32 i32) (result i64)
+    (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 16)))
+  (func (export "yamis_fn_spin") (param i32 i32) (result i64)
+    (loop $forever (br $forever))
+    (unreachable)))
+"#;
+
+    #[test]
+    fn test_wasm_plugins() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("casing.wasm");
+        std::fs::write(&path, wat::parse_str(MODULE).unwrap()).unwrap();
+
+        let plugins = WasmPlugins::new(vec![path]).unwrap();
+        assert!(plugins.has_function("upper").unwrap());
+        assert!(!plugins.has_function("lower").unwrap());
+        let args = [
+            PluginValue::String(String::from("hello")),
+            PluginValue::String(String::from("world")),
+        ];
+        assert_eq!(
+            plugins.call("upper", &args).unwrap(),
+            PluginValue::List(vec![String::from("HELLO"), String::from("WORLD")])
+        );
+        assert!(plugins
+            .call("fail", &[])
+            .unwrap_err()
+            .to_string()
+            .ends_with("casing.wasm: boom"));
+        // Runs out of fuel instead of hanging
+        assert!(plugins.call("spin", &[]).is_err());
+
+        let missing = tmp_dir.path().join("missing.wasm");
+        assert!(WasmPlugins::new(vec![missing]).is_err());
+
+        // Functions are available to the tasks of the config file that declares the plugin
+        let config_file = ConfigFile::load_from_str(
+            "plugins: [casing.wasm]\ntasks:\n  hello:\n    program: echo\n    args: ['{upper($1, $2)}', '{trim(\" x \")}']\n",
+            tmp_dir.path().join("project.yamis.yml"),
+        )
+        .unwrap();
+        let args = TaskArgs::from([(String::from("*"), vec!["a".into(), "b".into()])]);
+        let commands = config_file
+            .get_task("hello")
+            .unwrap()
+            .render(&args, &config_file)
+            .unwrap();
+        assert_eq!(commands[0].args(), &["A", "B", "x"]);
+    }
+
+    #[test]
+    fn test_wasm_plugins_cannot_import() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("importer.wasm");
+        let module = r#"(module (import "env" "exit" (func)) (func (export "yamis_fn_x")))"#;
+        std::fs::write(&path, wat::parse_str(module).unwrap()).unwrap();
+
+        let plugins = WasmPlugins::new(vec![path]).unwrap();
+        let error = plugins.has_function("x").unwrap_err().to_string();
+        assert!(error.contains("plugins cannot import anything, but it imports `env::exit`"));
+    }
+}
@@ -0,0 +1,219 @@
+//! Watches the files of a task, given in its `watch` option, so that `--watch` runs the task
+//! again whenever they change.
+use crate::types::DynErrResult;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// Time without changes to wait for before running the task again, so that saving several
+/// files at once runs it only once
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Time between checks of whether the watch should stop
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Watches the files matching some glob patterns
+pub(crate) struct FileWatcher {
+    /// Notifier of the changes, which stops watching when dropped
+    _watcher: RecommendedWatcher,
+    /// Events sent by the notifier
+    events: Receiver<notify::Result<Event>>,
+    /// Directory relative patterns are resolved against
+    base_dir: PathBuf,
+    /// Patterns of the files to watch
+    patterns: Vec<glob::Pattern>,
+    /// Files that are watched even if they do not match the patterns, i.e. the config file
+    extra_files: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    /// Starts watching the files in the directory that match the given patterns
+    ///
+    /// # Arguments
+    ///
+    /// * `base_dir`: Directory to watch, which relative patterns are resolved against
+    /// * `patterns`: Glob patterns of the files to watch
+    /// * `extra_files`: Files in the directory to watch as well
+    ///
+    /// returns: Result<FileWatcher, Box<dyn Error, Global>>
+    pub(crate) fn new(
+        base_dir: &Path,
+        patterns: &[String],
+        extra_files: &[PathBuf],
+    ) -> DynErrResult<Self> {
+        // Notifiers might report the canonical paths, i.e. in macOS
+        let base_dir = base_dir.canonicalize()?;
+        let mut compiled = Vec::with_capacity(patterns.len());
+        for pattern in patterns {
+            match glob::Pattern::new(pattern) {
+                Ok(pattern) => compiled.push(pattern),
+                Err(e) => {
+                    return Err(format!("Invalid pattern `{}` in `watch`: {}", pattern, e).into())
+                }
+            }
+        }
+        let extra_files = extra_files
+            .iter()
+            .map(|path| path.canonicalize().unwrap_or_else(|_| path.clone()))
+            .collect();
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&base_dir, RecursiveMode::Recursive)?;
+        Ok(FileWatcher {
+            _watcher: watcher,
+            events,
+            base_dir,
+            patterns: compiled,
+            extra_files,
+        })
+    }
+
+    /// Starts watching only the given files, i.e. the config files while they cannot be loaded.
+    /// Their directories are watched without recursing, as they might be far apart.
+    ///
+    /// # Arguments
+    ///
+    /// * `files`: Files to watch
+    ///
+    /// returns: Result<FileWatcher, Box<dyn Error, Global>>
+    pub(crate) fn files(files: &[PathBuf]) -> DynErrResult<Self> {
+        let files: Vec<PathBuf> = files
+            .iter()
+            .map(|path| path.canonicalize().unwrap_or_else(|_| path.clone()))
+            .collect();
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let mut base_dir = PathBuf::new();
+        for file in &files {
+            if let Some(dir) = file.parent() {
+                watcher.watch(dir, RecursiveMode::NonRecursive)?;
+                base_dir = dir.to_path_buf();
+            }
+        }
+        Ok(FileWatcher {
+            _watcher: watcher,
+            events,
+            base_dir,
+            patterns: Vec::new(),
+            extra_files: files,
+        })
+    }
+
+    /// Returns whether the given path is watched
+    fn is_watched(&self, path: &Path) -> bool {
+        if self.extra_files.iter().any(|file| file == path) {
+            return true;
+        }
+        let relative = path.strip_prefix(&self.base_dir).unwrap_or(path);
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches_path(relative) || pattern.matches_path(path))
+    }
+
+    /// Returns whether the event is a change of a watched file
+    fn is_watched_change(&self, event: &notify::Result<Event>) -> bool {
+        match event {
+            Ok(event) => {
+                matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                ) && event.paths.iter().any(|path| self.is_watched(path))
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Blocks until a watched file changes, and no other change happens for a moment.
+    ///
+    /// # Arguments
+    ///
+    /// * `stop`: Flag that stops waiting once set, i.e. by ctrl-c
+    ///
+    /// returns: bool, whether a file changed, or false if it stopped before
+    pub(crate) fn wait_for_change(&self, stop: &AtomicBool) -> bool {
+        loop {
+            if stop.load(Ordering::SeqCst) {
+                return false;
+            }
+            match self.events.recv_timeout(STOP_CHECK_INTERVAL) {
+                Ok(event) if self.is_watched_change(&event) => break,
+                Ok(_) | Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+        // Other events that come right after are part of the same change
+        loop {
+            match self.events.recv_timeout(DEBOUNCE) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => return !stop.load(Ordering::SeqCst),
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_wait_for_change() {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::create_dir(tmp_dir.path().join("src")).unwrap();
+        let watcher = FileWatcher::new(
+            tmp_dir.path(),
+            &[String::from("src/**/*.rs")],
+            &[tmp_dir.path().join("project.yamis.yml")],
+        )
+        .unwrap();
+        let base_dir = tmp_dir.path().canonicalize().unwrap();
+        assert!(watcher.is_watched(&base_dir.join("src").join("main.rs")));
+        assert!(watcher.is_watched(&base_dir.join("src").join("a").join("b.rs")));
+        assert!(watcher.is_watched(&base_dir.join("project.yamis.yml")));
+        assert!(!watcher.is_watched(&base_dir.join("target").join("main.rs")));
+        assert!(!watcher.is_watched(&base_dir.join("src").join("notes.txt")));
+
+        let path = tmp_dir.path().join("src").join("main.rs");
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            fs::write(path, "fn main() {}").unwrap();
+        });
+        assert!(watcher.wait_for_change(&AtomicBool::new(false)));
+        writer.join().unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopper = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                stop.store(true, Ordering::SeqCst);
+            })
+        };
+        assert!(!watcher.wait_for_change(&stop));
+        stopper.join().unwrap();
+    }
+
+    #[test]
+    fn test_watch_files() {
+        let tmp_dir = TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("project.yamis.yml");
+        fs::write(&config_path, "tasks: {").unwrap();
+        let watcher = FileWatcher::files(std::slice::from_ref(&config_path)).unwrap();
+        let base_dir = tmp_dir.path().canonicalize().unwrap();
+        assert!(watcher.is_watched(&base_dir.join("project.yamis.yml")));
+        assert!(!watcher.is_watched(&base_dir.join("main.rs")));
+
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            fs::write(config_path, "tasks: {}").unwrap();
+        });
+        assert!(watcher.wait_for_change(&AtomicBool::new(false)));
+        writer.join().unwrap();
+    }
+}
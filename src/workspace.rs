@@ -0,0 +1,129 @@
+//! Workspaces group the projects of a monorepo, so that a task can be run in all of them with
+//! `yamis ws run <TASK>`.
+use crate::types::DynErrResult;
+use indexmap::IndexMap;
+use serde_derive::Deserialize;
+
+/// Members of a workspace, declared in the `workspace` section of a config file
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Workspace {
+    /// Directories of the members, relative to the config file directory
+    pub(crate) members: Vec<String>,
+    /// Members that need to run before the given member
+    #[serde(default)]
+    depends_on: IndexMap<String, Vec<String>>,
+}
+
+impl Workspace {
+    /// Checks that the dependencies only reference members of the workspace
+    pub(crate) fn validate(&self) -> DynErrResult<()> {
+        for (member, dependencies) in &self.depends_on {
+            for name in std::iter::once(member).chain(dependencies) {
+                if !self.members.contains(name) {
+                    return Err(format!("Unknown workspace member `{}`", name).into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the members in the order they should run. Members run after the ones they
+    /// depend on, and otherwise in the order they were declared.
+    ///
+    /// # Arguments
+    ///
+    /// * `only`: If not empty, only these members are returned, still in the order they
+    ///   should run. Their dependencies are not included.
+    ///
+    /// returns: Result<Vec<&str, Global>, Box<dyn Error, Global>>
+    pub(crate) fn ordered_members(&self, only: &[&str]) -> DynErrResult<Vec<&str>> {
+        self.validate()?;
+        for name in only {
+            if !self.members.iter().any(|member| member == name) {
+                return Err(format!("Unknown workspace member `{}`", name).into());
+            }
+        }
+
+        let mut ordered: Vec<&str> = Vec::with_capacity(self.members.len());
+        while ordered.len() < self.members.len() {
+            // The first member, in declaration order, whose dependencies already run
+            let next = self.members.iter().find(|member| {
+                !ordered.contains(&member.as_str())
+                    && self.depends_on.get(*member).is_none_or(|dependencies| {
+                        dependencies
+                            .iter()
+                            .all(|dependency| ordered.contains(&dependency.as_str()))
+                    })
+            });
+            match next {
+                Some(member) => ordered.push(member),
+                None => {
+                    let pending: Vec<&str> = self
+                        .members
+                        .iter()
+                        .map(String::as_str)
+                        .filter(|member| !ordered.contains(member))
+                        .collect();
+                    return Err(format!(
+                        "Found a cyclic dependency between the workspace members: {}",
+                        pending.join(", ")
+                    )
+                    .into());
+                }
+            }
+        }
+
+        if !only.is_empty() {
+            ordered.retain(|member| only.contains(member));
+        }
+        Ok(ordered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered_members() {
+        let workspace: Workspace = serde_yaml::from_str(
+            r#"
+members: [app, cli, lib, utils]
+depends_on:
+  app: [lib]
+  lib: [utils]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            workspace.ordered_members(&[]).unwrap(),
+            vec!["cli", "utils", "lib", "app"]
+        );
+        assert_eq!(
+            workspace.ordered_members(&["app", "utils"]).unwrap(),
+            vec!["utils", "app"]
+        );
+        assert_eq!(
+            workspace
+                .ordered_members(&["other"])
+                .unwrap_err()
+                .to_string(),
+            "Unknown workspace member `other`"
+        );
+
+        let workspace: Workspace =
+            serde_yaml::from_str("members: [a, b]\ndepends_on: {a: [c]}").unwrap();
+        assert_eq!(
+            workspace.validate().unwrap_err().to_string(),
+            "Unknown workspace member `c`"
+        );
+
+        let workspace: Workspace =
+            serde_yaml::from_str("members: [a, b, c]\ndepends_on: {a: [b], b: [a]}").unwrap();
+        assert_eq!(
+            workspace.ordered_members(&[]).unwrap_err().to_string(),
+            "Found a cyclic dependency between the workspace members: a, b"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use assert_cmd::cargo::cargo_bin;
 use assert_cmd::prelude::*;
 use assert_fs::TempDir;
 use predicates::prelude::*;
@@ -13,7 +14,7 @@ fn test_no_config_file_discovered() {
     cmd.arg("echo");
     cmd.assert()
         .failure()
-        .stderr(predicate::str::contains("[YAMIS] Task echo not found"));
+        .stderr(predicate::str::contains("[YAMIS] No config file found"));
 }
 
 #[test]
@@ -112,8 +113,29 @@ fn test_escape_on_space_windows() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-// TODO: Test escaping in Unix
-//   Not critical since we already test the script formatter
+#[test]
+#[cfg(unix)]
+fn test_escape_always_unix() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.toml"))?;
+    file.write_all(
+        r#"
+    [tasks.say_price]
+    script = "echo {$1} {$2}"
+    "#
+        .as_bytes(),
+    )?;
+
+    // Quoted values used to be expanded by the shell
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["say_price", "$HOME costs $5", r#"`date` "now""#]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"$HOME costs $5 `date` "now""#));
+    Ok(())
+}
 
 #[test]
 fn test_escape_never() -> Result<(), Box<dyn std::error::Error>> {
@@ -452,3 +474,2096 @@ tasks:
 
     Ok(())
 }
+
+#[test]
+fn test_run_subcommand() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+tasks:
+  hello:
+    quote: never
+    script: "echo args={$@?} key={key?}"
+
+  run:
+    script: "echo task named run"
+"#
+        .as_bytes(),
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["run", "hello", "--key=val", "world"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("args=--key=val world key=val"));
+
+    // named arguments are not parsed after `--`
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["run", "hello", "--", "--key=val"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("args=--key=val key=\n"));
+
+    // tasks named like subcommands can still be run
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["run", "run"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("task named run"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["hello", "--key=val"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("args=--key=val key=val"));
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_program_non_unicode_args() -> Result<(), Box<dyn std::error::Error>> {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let tmp_dir = TempDir::new().unwrap();
+    let file_name = OsString::from_vec(b"file_\xff.txt".to_vec());
+    File::create(tmp_dir.join(&file_name))?;
+
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+tasks:
+  list_file:
+    program: "ls"
+    args: ["{$1}"]
+
+  list_file_kwarg:
+    program: "ls"
+    args: ["{file}"]
+"#
+        .as_bytes(),
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.arg("list_file");
+    cmd.arg(&file_name);
+    // ls fails if the file name was mangled
+    cmd.assert().success();
+
+    let mut kwarg = OsString::from("--file=");
+    kwarg.push(&file_name);
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.arg("list_file_kwarg");
+    cmd.arg(kwarg);
+    cmd.assert().success();
+    Ok(())
+}
+
+#[test]
+#[cfg(windows)]
+fn test_kwargs_with_spaces_windows() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.toml"))?;
+    file.write_all(
+        r#"
+    [tasks.greet]
+    quote = "spaces"
+    script = "echo {name} {$@}"
+    "#
+        .as_bytes(),
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["greet", "--name", "John Doe"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"John Doe\" --name \"John Doe\""));
+    Ok(())
+}
+
+#[test]
+#[cfg(windows)]
+fn test_kwargs_with_quotes_windows() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.toml"))?;
+    file.write_all(
+        r#"
+    [tasks.greet]
+    quote = "never"
+    script = "echo {name}"
+    "#
+        .as_bytes(),
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["greet", "--name=say \"hi\" there"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("say \"hi\" there"));
+    Ok(())
+}
+
+#[test]
+fn test_files_without_task_are_not_loaded() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+tasks:
+  hello:
+    script: "echo hello project"
+"#
+        .as_bytes(),
+    )?;
+
+    // Has an invalid task, but it does not define the task to run
+    let mut file = File::create(tmp_dir.join("local.yamis.yml"))?;
+    file.write_all(
+        r#"
+tasks:
+  bye:
+    unknown_field: "echo bye"
+"#
+        .as_bytes(),
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.arg("hello");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello project"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.arg("bye");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown field `unknown_field`"));
+    Ok(())
+}
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn test_invocation_metadata() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+tasks:
+  hello:
+    quote: never
+    script: "echo {invocation.dry_run} {invocation.argv[1:]} {invocation.cwd}"
+"#
+        .as_bytes(),
+    )?;
+
+    let cwd = tmp_dir.path().canonicalize()?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&cwd);
+    cmd.args(["hello", "--name", "John"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "false hello --name John {}",
+            cwd.to_string_lossy()
+        )));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&cwd);
+    cmd.args(["--execution-backend", "record", "hello"]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "echo true --execution-backend record hello",
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_env_check() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+env_file: .env
+tasks:
+  hello:
+    env_file: envs/.env.local
+    script: echo hello
+"#
+        .as_bytes(),
+    )?;
+    std::fs::create_dir(tmp_dir.join("envs"))?;
+    std::fs::write(tmp_dir.join(".env"), "A=1\nB=2\n")?;
+    std::fs::write(tmp_dir.join(".env.example"), "A=\nB=\n")?;
+    std::fs::write(tmp_dir.join("envs/.env.local"), "A=1\n")?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["env", "check"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(".env: OK"))
+        .stderr(predicate::str::contains("example file"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["env", "check", "--example", ".env.example"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "Missing keys declared in .env.example: B",
+        ))
+        .stderr(predicate::str::contains(
+            "Some env files do not match their example",
+        ));
+    Ok(())
+}
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn test_env_file_fan_out() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+env:
+  TARGET: local
+  REGION: eu
+tasks:
+  deploy:
+    quote: never
+    script: test "$TARGET" != broken && echo "deploying to $TARGET in $REGION"
+"#
+        .as_bytes(),
+    )?;
+    std::fs::write(tmp_dir.join(".env.staging"), "TARGET=staging\n")?;
+    std::fs::write(tmp_dir.join(".env.prod"), "TARGET=prod\nREGION=us\n")?;
+    std::fs::write(tmp_dir.join(".env.broken"), "TARGET=broken\n")?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args([
+        "--env-file",
+        ".env.prod",
+        "--env-file",
+        ".env.staging",
+        "deploy",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("deploying to staging in us"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args([
+        "--env-file",
+        ".env.staging",
+        "--env-file",
+        ".env.broken",
+        "--env-file",
+        ".env.prod",
+        "--fan-out",
+        "deploy",
+    ]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("deploying to staging in eu"))
+        .stdout(predicate::str::contains("deploying to prod in us"))
+        .stdout(predicate::str::contains("2 of 3 environments succeeded"))
+        .stderr(predicate::str::contains(
+            "Task failed for the environments: .env.broken",
+        ));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["--fan-out", "deploy"]);
+    cmd.assert().failure();
+    Ok(())
+}
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn test_run_serial_error_chain() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+tasks:
+  build:
+    script: echo building
+  migrate:
+    script: exit 2
+  deploy:
+    serial: [build, migrate]
+"#
+        .as_bytes(),
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.arg("deploy");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("building"))
+        .stderr(predicate::str::contains("project.yamis.yml"))
+        .stderr(predicate::str::contains(
+            "Error running tasks.deploy.serial[1] → tasks.migrate:",
+        ))
+        .stderr(predicate::str::contains(
+            "Process terminated with exit code 2",
+        ));
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_path_dirs() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+path: [tools/bin]
+tasks:
+  greet:
+    program: greet
+  greet_script:
+    script: greet from script
+"#
+        .as_bytes(),
+    )?;
+    std::fs::create_dir_all(tmp_dir.join("tools/bin"))?;
+    let program_path = tmp_dir.join("tools/bin/greet");
+    std::fs::write(&program_path, "#!/bin/sh\necho \"greetings $*\"\n")?;
+    std::fs::set_permissions(&program_path, std::fs::Permissions::from_mode(0o755))?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.arg("greet");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("greetings"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.arg("greet_script");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("greetings from script"));
+    Ok(())
+}
+
+#[test]
+fn test_workspace_run() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+workspace:
+  members: [app, docs, lib]
+  depends_on:
+    app: [lib]
+tasks:
+  members:
+    script: echo {workspace.members}
+"#
+        .as_bytes(),
+    )?;
+    for member in ["app", "docs", "lib"] {
+        std::fs::create_dir_all(tmp_dir.join(member))?;
+    }
+    for member in ["app", "lib"] {
+        std::fs::write(
+            tmp_dir.join(member).join("yamis.yml"),
+            format!("tasks:\n  build:\n    script: echo building {}\n", member),
+        )?;
+    }
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["ws", "run", "build"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match(
+            r"(?s)Member: lib.*building lib.*Member: app.*building app",
+        )?)
+        .stdout(predicate::str::contains("docs").not());
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.join("lib"));
+    cmd.args(["ws", "run", "--member", "app", "build"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("building app"))
+        .stdout(predicate::str::contains("building lib").not());
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["ws", "run", "test"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Task test not found in any workspace member",
+    ));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.arg("members");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("app docs lib"));
+    Ok(())
+}
+
+#[test]
+fn test_output_theme() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let home_dir = tmp_dir.join("home");
+    std::fs::create_dir_all(home_dir.join(".yamis"))?;
+    std::fs::write(
+        home_dir.join(".yamis").join("user.yamis.yml"),
+        r#"
+settings:
+  update_check: "off"
+  output:
+    prefix: "[tasks]"
+    messages:
+      dry_run: Simulation
+      error_running: Fehler bei
+"#,
+    )?;
+    let mut file = File::create(tmp_dir.join("project.yamis.yml"))?;
+    file.write_all(
+        r#"
+tasks:
+  hello:
+    script: echo hello
+  broken:
+    serial: [missing]
+"#
+        .as_bytes(),
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", &home_dir);
+    cmd.args(["--execution-backend", "record", "hello"]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[tasks] Simulation"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", &home_dir);
+    cmd.arg("broken");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[tasks] Fehler bei tasks.broken"));
+    Ok(())
+}
+
+#[test]
+fn test_no_config_file_found() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let home_dir = tmp_dir.join("home");
+    let project_dir = tmp_dir.join("project");
+    std::fs::create_dir_all(&home_dir)?;
+    std::fs::create_dir_all(&project_dir)?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", &home_dir);
+    cmd.arg("hello");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "No config file found. Searched in:",
+        ))
+        .stderr(predicate::str::contains(
+            project_dir.to_string_lossy().as_ref(),
+        ))
+        .stderr(predicate::str::contains(
+            home_dir.join(".yamis").to_string_lossy().as_ref(),
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn test_file_option_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let backend_dir = tmp_dir.join("backend");
+    std::fs::create_dir_all(backend_dir.join("src"))?;
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "tasks:\n  hello:\n    script: echo hello from root\n",
+    )?;
+    std::fs::write(
+        backend_dir.join("yamis.yml"),
+        "tasks:\n  build:\n    script: echo building backend\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["-f", "backend", "build"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("building backend"));
+
+    // Only the given directory is searched, unless `--walk-up` is given
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["-f", "backend", "hello"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Task hello not found"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["-f", "backend", "--walk-up", "hello"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello from root"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["-f", "backend/src", "build"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "No config file found. Searched in:",
+        ))
+        .stderr(predicate::str::contains("backend/src"));
+    Ok(())
+}
+
+#[test]
+fn test_multiple_file_options() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("shared.yml"),
+        "tasks:\n  lint:\n    script: echo shared lint\n  fmt:\n    script: echo shared fmt\n",
+    )?;
+    std::fs::write(
+        tmp_dir.join("project.yml"),
+        "tasks:\n  build:\n    script: echo project build\n  fmt:\n    script: echo project fmt\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["-f", "project.yml", "-f", "shared.yml", "lint"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("shared lint"));
+
+    // The first file that defines the task is used
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["-f", "project.yml", "-f", "shared.yml", "fmt"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("project fmt"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["-f", "project.yml", "-f", "shared.yml", "--list-tasks"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("project.yml"))
+        .stdout(predicate::str::contains("shared.yml"))
+        .stdout(predicate::str::contains("build"))
+        .stdout(predicate::str::contains("lint"));
+    Ok(())
+}
+
+#[test]
+fn test_current() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let project_dir = tmp_dir.join("project");
+    std::fs::create_dir(&project_dir)?;
+    std::fs::write(
+        project_dir.join("project.yamis.yml"),
+        "tasks:\n  build:\n    script: echo build\n  test:\n    script: echo test\n  base:\n    private: true\n",
+    )?;
+
+    // The second run uses the task index
+    for _ in 0..2 {
+        let mut cmd = Command::cargo_bin("yamis")?;
+        cmd.current_dir(&project_dir);
+        cmd.env("HOME", tmp_dir.path());
+        cmd.arg("current");
+        cmd.assert().success().stdout("yamis: 2 tasks\n");
+    }
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["current", "--format", "{name} {tasks}"]);
+    cmd.assert().success().stdout("project.yamis.yml 2\n");
+
+    // Nothing is printed without a config file
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("current");
+    cmd.assert().success().stdout("");
+    Ok(())
+}
+
+#[test]
+fn test_global_tasks() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let home_dir = tmp_dir.join("home");
+    let project_dir = tmp_dir.join("project");
+    std::fs::create_dir_all(home_dir.join(".yamis"))?;
+    std::fs::create_dir_all(&project_dir)?;
+    let global_config_path = home_dir.join(".yamis").join("user.yamis.yml");
+    std::fs::write(
+        &global_config_path,
+        "tasks:\n  helper:\n    program: yamis::echo\n    args: [global helper]\n",
+    )?;
+    std::fs::write(
+        project_dir.join("project.yamis.yml"),
+        "tasks:\n  build:\n    program: yamis::echo\n    args: [build]\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", &home_dir);
+    cmd.arg("helper");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("global helper\n"))
+        .stderr(predicate::str::contains(format!(
+            "Task helper not found in the project, running it from {}",
+            global_config_path.to_string_lossy()
+        )));
+
+    // Project tasks do not print the notice
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", &home_dir);
+    cmd.arg("build");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("not found in the project").not());
+
+    let settings = "settings:\n  global_tasks: silent\n";
+    let tasks = std::fs::read_to_string(&global_config_path)?;
+    std::fs::write(&global_config_path, format!("{}{}", settings, tasks))?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", &home_dir);
+    cmd.arg("helper");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("global helper\n"))
+        .stderr(predicate::str::contains("not found in the project").not());
+
+    std::fs::write(
+        &global_config_path,
+        format!("settings:\n  global_tasks: 'off'\n{}", tasks),
+    )?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", &home_dir);
+    cmd.arg("helper");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Task helper not found"));
+    Ok(())
+}
+
+#[test]
+fn test_shadowed_tasks() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let home_dir = tmp_dir.join("home");
+    let project_dir = tmp_dir.join("project");
+    let sub_dir = project_dir.join("sub");
+    std::fs::create_dir_all(home_dir.join(".yamis"))?;
+    std::fs::create_dir_all(&sub_dir)?;
+    let global_config_path = home_dir.join(".yamis").join("user.yamis.yml");
+    std::fs::write(
+        &global_config_path,
+        "tasks:\n  hello:\n    program: yamis::echo\n    args: [global]\n",
+    )?;
+    let project_config_path = project_dir.join("project.yamis.yml");
+    std::fs::write(
+        &project_config_path,
+        "tasks:\n  hello:\n    program: yamis::echo\n    args: [project]\n  same:\n    program: yamis::echo\n",
+    )?;
+    std::fs::write(
+        sub_dir.join("yamis.yml"),
+        "tasks:\n  hello:\n    program: yamis::echo\n    args: [sub]\n  same:\n    program: yamis::echo\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&sub_dir);
+    cmd.env("HOME", &home_dir);
+    cmd.arg("hello");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("sub\n"))
+        .stderr(predicate::str::contains(format!(
+            "Task hello shadows the one in {}",
+            project_config_path.to_string_lossy()
+        )))
+        .stderr(predicate::str::contains(format!(
+            "Task hello shadows the one in {}",
+            global_config_path.to_string_lossy()
+        )));
+
+    // Tasks with the same definition are not reported
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&sub_dir);
+    cmd.env("HOME", &home_dir);
+    cmd.arg("same");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("shadows").not());
+
+    let tasks = std::fs::read_to_string(&global_config_path)?;
+    std::fs::write(
+        &global_config_path,
+        format!("settings:\n  warn_shadowed_tasks: false\n{}", tasks),
+    )?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&sub_dir);
+    cmd.env("HOME", &home_dir);
+    cmd.arg("hello");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("shadows").not());
+    Ok(())
+}
+
+#[test]
+fn test_no_script_echo() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "tasks:\n  hello:\n    script: \"echo one\\necho two\"\n",
+    )?;
+
+    // The output is not colored when it is not a terminal
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["--execution-backend", "record", "hello"]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "  script:\n    echo one\n    echo two\n",
+    ));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["--execution-backend", "record", "--no-script-echo", "hello"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("  script: <2 lines>\n"))
+        .stdout(predicate::str::contains("echo one").not());
+    Ok(())
+}
+
+#[test]
+fn test_task_summary_and_info() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  base:
+    private: true
+    summary: Deploys the app
+    help: |
+      Deploys the `app`.
+
+      - Builds it
+  deploy:
+    bases: [base]
+    program: echo
+    params:
+      replicas: int
+  plain:
+    program: echo
+"#,
+    )?;
+
+    // The second run uses the task index
+    for _ in 0..2 {
+        let mut cmd = Command::cargo_bin("yamis")?;
+        cmd.current_dir(tmp_dir.path());
+        cmd.env("HOME", tmp_dir.path());
+        cmd.arg("--list-tasks");
+        cmd.assert().success().stdout(predicate::str::contains(
+            " - deploy: Deploys the app\n - plain\n",
+        ));
+    }
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--task-info", "deploy"]);
+    cmd.assert().success().stdout(predicate::str::ends_with(
+        " - deploy\n     Deploys the app.\n     \n     • Builds it\n     Bases: base\n     Parameters:\n       --replicas: an integer\n",
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_task_info_patterns() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let home_dir = tmp_dir.join("home");
+    let project_dir = tmp_dir.join("project");
+    let sub_dir = project_dir.join("sub");
+    std::fs::create_dir_all(&home_dir)?;
+    std::fs::create_dir_all(&sub_dir)?;
+    std::fs::write(
+        project_dir.join("project.yamis.yml"),
+        "tasks:\n  build:\n    help: Parent build\n    program: echo\n  build_release:\n    program: echo\n",
+    )?;
+    std::fs::write(
+        sub_dir.join("yamis.yml"),
+        "tasks:\n  build:\n    help: Sub build\n    program: echo\n  test:\n    program: echo\n",
+    )?;
+
+    let task_info = |pattern: &str| {
+        let mut cmd = Command::cargo_bin("yamis").unwrap();
+        cmd.current_dir(&sub_dir);
+        cmd.env("HOME", &home_dir);
+        cmd.args(["--task-info", pattern]);
+        cmd.assert()
+    };
+
+    // Exact matches are printed for every config file
+    task_info("build").success().stdout(
+        predicate::str::contains(" - build\n     Sub build\n")
+            .and(predicate::str::contains(" - build\n     Parent build\n"))
+            .and(predicate::str::contains("build_release").not()),
+    );
+
+    task_info("build*").success().stdout(
+        predicate::str::contains(" - build\n     Sub build\n")
+            .and(predicate::str::contains(" - build\n     Parent build\n"))
+            .and(predicate::str::contains(" - build_release\n"))
+            .and(predicate::str::contains(" - test").not()),
+    );
+
+    task_info("bld_rel")
+        .success()
+        .stderr(predicate::str::contains(
+            "Task bld_rel not found, showing similar tasks",
+        ))
+        .stdout(
+            predicate::str::contains(" - build_release\n")
+                .and(predicate::str::contains(" - build\n").not()),
+        );
+
+    task_info("deploy")
+        .failure()
+        .stderr(predicate::str::contains("Task deploy not found"));
+    task_info("deploy*")
+        .failure()
+        .stderr(predicate::str::contains("Task deploy* not found"));
+    Ok(())
+}
+
+#[test]
+fn test_timestamps() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "tasks:\n  hello:\n    program: yamis::echo\n    args: [hello]\n  both:\n    script: echo out && echo err 1>&2\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--timestamps", "hello"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^\[ +\d+\.\d{3}s\] hello$")?);
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--timestamps=wall", "both"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match(
+            r"(?m)^\[\d{2}:\d{2}:\d{2}\.\d{3}\] out\s*$",
+        )?)
+        .stderr(predicate::str::is_match(
+            r"(?m)^\[\d{2}:\d{2}:\d{2}\.\d{3}\] err\s*$",
+        )?);
+    Ok(())
+}
+
+#[test]
+fn test_failure_hints() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "tasks:\n  serve:\n    script: echo 'listen EADDRINUSE 3000' 1>&2 && exit 1\n  db:\n    script: echo 'port 5432 refused' && exit 1\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--hints", "serve"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("EADDRINUSE"))
+        .stderr(predicate::str::contains("Hint: The port is already in use"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("serve");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Hint:").not());
+
+    std::fs::create_dir_all(tmp_dir.join(".yamis"))?;
+    std::fs::write(
+        tmp_dir.join(".yamis").join("user.yamis.yml"),
+        "settings:\n  hints:\n    enabled: true\n    rules:\n      - pattern: port 5432\n        hint: Start the database first\n",
+    )?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("db");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("port 5432 refused"))
+        .stderr(predicate::str::contains("Hint: Start the database first"));
+    Ok(())
+}
+
+#[test]
+fn test_expect_duration() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  test:
+    program: yamis::echo
+    args: [done]
+    expect_duration:
+      min: 10s
+  test_warn:
+    bases: [test]
+    expect_duration:
+      min: 10s
+      action: warn
+  build:
+    program: yamis::echo
+    args: [done]
+    expect_duration:
+      max: 1h
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("test");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("done"))
+        .stderr(predicate::str::contains("expected to take at least 10.0s"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("test_warn");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("expected to take at least 10.0s"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("build");
+    cmd.assert().success();
+    Ok(())
+}
+
+#[test]
+fn test_sources_checksum() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  migrate:
+    program: yamis::echo
+    args: [migrated]
+    sources: ["migrations/*.sql"]
+    fingerprint: checksum
+"#,
+    )?;
+    std::fs::create_dir(tmp_dir.join("migrations"))?;
+    std::fs::write(
+        tmp_dir.join("migrations").join("001.sql"),
+        "CREATE TABLE a;",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("migrate");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("migrated"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("migrate");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Skipping `migrate`, it is up to date",
+        ))
+        .stdout(predicate::str::contains("migrated").not());
+
+    std::fs::write(
+        tmp_dir.join("migrations").join("002.sql"),
+        "CREATE TABLE b;",
+    )?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("migrate");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("migrated"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_yamis_file() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let other_dir = tmp_dir.join("other");
+    std::fs::create_dir(&other_dir)?;
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  outer:
+    script: cd other && "$YAMIS_BIN" inner
+  inner:
+    script: echo "inner of $YAMIS_TASK from $(basename $YAMIS_PROJECT_ROOT)"
+"#,
+    )?;
+    std::fs::write(
+        other_dir.join("project.yamis.yml"),
+        "tasks:\n  inner:\n    script: echo wrong inner\n",
+    )?;
+    let project_name = tmp_dir.path().file_name().unwrap().to_string_lossy();
+
+    // Nested invocations find the config file of the task that runs them
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("YAMIS_BIN", cargo_bin("yamis"));
+    cmd.arg("outer");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "inner of inner from {}",
+            project_name
+        )));
+
+    // `-f` takes precedence over `YAMIS_FILE`
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&other_dir);
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("YAMIS_FILE", tmp_dir.join("project.yamis.yml"));
+    cmd.arg("inner");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("inner of inner"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("YAMIS_FILE", tmp_dir.join("project.yamis.yml"));
+    cmd.args(["-f", "other/project.yamis.yml", "inner"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("wrong inner"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_nested_invocations() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+vars:
+  mode: debug
+tasks:
+  build:
+    script: echo building {vars.mode}
+  test:
+    deps: [build]
+    script: echo testing
+  ci:
+    deps: [build]
+    script: '"$YAMIS_BIN" test'
+  release:
+    deps: [build]
+    script: '"$YAMIS_BIN" --var mode=release test'
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("YAMIS_BIN", cargo_bin("yamis"));
+    cmd.arg("ci");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("building").count(1))
+        .stdout(predicate::str::contains(
+            "\n  [YAMIS] Task: `test`\n  testing\n",
+        ));
+
+    // Dependencies rendered differently in the nested invocation run again
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("YAMIS_BIN", cargo_bin("yamis"));
+    cmd.arg("release");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("building debug").count(1))
+        .stdout(predicate::str::contains("\n  building release\n"));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "watch")]
+fn test_watch_without_patterns() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  hello:
+    program: yamis::echo
+    args: [hello]
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--watch", "hello"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Task hello cannot be watched, as it does not set `watch` or `sources`",
+        ))
+        .stdout(predicate::str::contains("hello").not());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_lint_scripts() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let home_dir = tmp_dir.join("home");
+    std::fs::create_dir_all(home_dir.join(".yamis"))?;
+    // Reports each line that calls rm, like shellcheck would with `--format=gcc`
+    std::fs::write(
+        home_dir.join(".yamis").join("user.yamis.yml"),
+        r#"
+settings:
+  update_check: "off"
+  linters:
+    sh:
+      program: sh
+      args: ["-c", 'grep -n rm "$1" | cut -d: -f1 | sed "s|^|$1:|; s|$|:1: warning: rm in $0|"', "{shell}"]
+"#,
+    )?;
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  clean:
+    script_runner: sh
+    script: |
+      cd build
+      rm -rf {$1?}
+  build:
+    script: cargo build
+  deploy:
+    script: echo {$1}
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", &home_dir);
+    cmd.arg("lint-scripts");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("clean:2:1: warning: rm in sh"))
+        .stdout(predicate::str::contains("rm -rf {$1?}"))
+        .stdout(predicate::str::contains("deploy ... skipped"))
+        .stdout(predicate::str::contains(
+            "2 scripts linted, 1 problems found",
+        ))
+        .stderr(predicate::str::contains("Found 1 problems in the scripts"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", &home_dir);
+    cmd.args(["lint-scripts", "deploy", "--", "prod"]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "1 scripts linted, 0 problems found",
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_config_tests() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  greet:
+    program: yamis::echo
+    args: ["{name}"]
+tests:
+  greet_john:
+    task: greet
+    args: [--name, John]
+    commands:
+      - args: [John]
+  greet_jane:
+    task: greet
+    args: [--name, Jane]
+    commands:
+      - args: [John]
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["test", "greet_john"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("greet_john ... ok"))
+        .stdout(predicate::str::contains("1 passed, 0 failed"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("test");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("greet_jane ... FAILED"))
+        .stdout(predicate::str::contains(
+            r#"commands[0].args: expected ["John"], got ["Jane"]"#,
+        ))
+        .stdout(predicate::str::contains("1 passed, 1 failed"));
+
+    // A task named `test` takes precedence
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "tasks:\n  test:\n    program: yamis::echo\n    args: [testing]\n",
+    )?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("test");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("testing"));
+    Ok(())
+}
+
+#[test]
+fn test_scalar_env_and_vars() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+env:
+  DEBUG: true
+vars:
+  replicas: 3
+tasks:
+  serve:
+    script: echo "$DEBUG $PORT {vars.replicas}"
+    env:
+      PORT: 8080
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("serve");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("true 8080 3\n"));
+    Ok(())
+}
+
+#[test]
+fn test_no_update_check() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "tasks:\n  hello:\n    program: yamis::echo\n    args: [hello]\n",
+    )?;
+
+    // Accepted by every build, even without the updater feature
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--no-update-check", "hello"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("hello\n"));
+    Ok(())
+}
+
+#[test]
+fn test_var_overrides() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "vars:\n  name: World\n  greeting: Hello\ntasks:\n  hello:\n    program: yamis::echo\n    args: [\"{vars.greeting}\", \"{vars.name}\"]\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--var", "name=John", "--var", "name=Joe=Doe", "hello"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("Hello Joe=Doe\n"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--var", "user.name=joe", "hello"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Invalid variable name `user.name`, variables cannot be nested",
+    ));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--var", "name", "hello"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Invalid variable `name`, expected KEY=VALUE",
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_inline_config_file() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let config = "tasks:\n  hello:\n    program: yamis::echo\n    args: [Hello, \"{$1}\"]\n";
+
+    let mut cmd = assert_cmd::Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["-f", "-", "hello", "stdin"]);
+    cmd.write_stdin(config);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("Hello stdin\n"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args([
+        "--inline",
+        "tasks: {hi: {program: 'yamis::echo', args: [hi]}}",
+        "hi",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("hi\n"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--inline", "tasks: {hi: {program: 'yamis::echo'}}", "bye"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Task bye not found"));
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_inline_config_file_is_removed() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let inline_dir = tmp_dir.join("adrianmrit.yamis").join("inline");
+
+    // The file exists while the tasks run, so that nested invocations can load it
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("TMPDIR", tmp_dir.path());
+    cmd.args([
+        "--inline",
+        "tasks: {hi: {script: 'test -f \"$YAMIS_FILE\" && echo exists'}}",
+        "hi",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("exists\n"));
+    assert_eq!(std::fs::read_dir(&inline_dir)?.count(), 0);
+
+    let mut cmd = assert_cmd::Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("TMPDIR", tmp_dir.path());
+    cmd.args(["-f", "-", "bye"]);
+    cmd.write_stdin("tasks:\n  hi:\n    program: yamis::echo\n");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Task bye not found"));
+    assert_eq!(std::fs::read_dir(&inline_dir)?.count(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_trace_templates() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "vars:\n  name: World\ntasks:\n  hello:\n    program: yamis::echo\n    args: [\"{vars.name}\", \"{$API_TOKEN}\", \"{$@?}\"]\n    env:\n      API_TOKEN: abc123\n  secret:\n    program: yamis::echo\n    args: [\"{vars.name}\"]\n    echo_command: false\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--trace-templates", "hello", "a b", "c"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("World abc123 a b c\n"))
+        .stderr(predicate::str::contains("{vars.name} = \"World\""))
+        .stderr(predicate::str::contains("{$API_TOKEN} = ******"))
+        .stderr(predicate::str::contains("{$@?} = [\"a b\", \"c\"]"))
+        .stderr(predicate::str::contains("abc123").not());
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--trace-templates", "secret"]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("{vars.name} = ******"));
+
+    // Nothing is traced by default
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["hello"]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("{vars.name}").not());
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_reentrancy() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let config_path = tmp_dir.join("project.yamis.yml");
+    std::fs::write(
+        &config_path,
+        "tasks:\n  deploy:\n    program: yamis::echo\n    args: [deployed]\n  watch:\n    program: yamis::echo\n    args: [watching]\n    reentrant: true\n",
+    )?;
+
+    // Fakes other yamis processes running the tasks
+    let mut other = std::process::Command::new("sleep").arg("10").spawn()?;
+    let runs_dir = tmp_dir.join(".cache").join("yamis").join("runs");
+    std::fs::create_dir_all(&runs_dir)?;
+    for task in ["deploy", "watch"] {
+        std::fs::write(
+            runs_dir.join(format!("{}.json", other.id())),
+            format!(
+                r#"{{"id": "{}", "process": {}, "pid": null, "task": "{}", "dir": "/", "started": 0, "config_file": {:?}}}"#,
+                other.id(),
+                other.id(),
+                task,
+                config_path.to_string_lossy()
+            ),
+        )?;
+
+        let mut cmd = Command::cargo_bin("yamis")?;
+        cmd.current_dir(tmp_dir.path());
+        cmd.env("HOME", tmp_dir.path());
+        cmd.env_remove("XDG_CACHE_HOME");
+        cmd.arg(task);
+        if task == "deploy" {
+            cmd.assert()
+                .failure()
+                .stderr(predicate::str::contains(format!(
+                    "Task deploy is already running with id {}",
+                    other.id()
+                )));
+        } else {
+            cmd.assert().success();
+        }
+    }
+
+    std::fs::write(
+        runs_dir.join(format!("{}.json", other.id())),
+        format!(
+            r#"{{"id": "{}", "process": {}, "pid": null, "task": "deploy", "dir": "/", "started": 0, "config_file": {:?}}}"#,
+            other.id(),
+            other.id(),
+            config_path.to_string_lossy()
+        ),
+    )?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env_remove("XDG_CACHE_HOME");
+    cmd.args(["--force", "deploy"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("deployed\n"));
+
+    other.kill()?;
+    other.wait()?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env_remove("XDG_CACHE_HOME");
+    cmd.arg("deploy");
+    cmd.assert().success();
+    Ok(())
+}
+
+#[test]
+fn test_cwd() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let project_dir = tmp_dir.join("project");
+    std::fs::create_dir(&project_dir)?;
+    std::fs::write(
+        project_dir.join("project.yamis.yml"),
+        "tasks:\n  where:\n    program: yamis::echo\n    args: ['{invocation.cwd}']\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--cwd", "project", "where"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with(format!(
+            "{}\n",
+            project_dir.canonicalize()?.to_string_lossy()
+        )));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["--cwd", "missing", "where"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Could not change the working directory to missing",
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_list_config_files() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let home_dir = tmp_dir.join("home");
+    let project_dir = tmp_dir.join("project");
+    std::fs::create_dir_all(home_dir.join(".yamis"))?;
+    std::fs::create_dir_all(&project_dir)?;
+    let global_path = home_dir.join(".yamis").join("user.yamis.yml");
+    std::fs::write(&global_path, "tasks:\n  helper:\n    program: echo\n")?;
+    let project_path = project_dir.join("project.yamis.yml");
+    std::fs::write(&project_path, "tasks:\n  build:\n    program: echo\n")?;
+    let local_path = project_dir.join("local.yamis.yml");
+    std::fs::write(&local_path, "tasks:\n  base:\n    private: true\n")?;
+    let shared_path = tmp_dir.join("shared.yamis.yml");
+    std::fs::write(&shared_path, "tasks:\n  lint:\n    program: echo\n")?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", &home_dir);
+    cmd.arg("--list");
+    cmd.assert().success().stdout(format!(
+        "Discovered from {}:\n  {}\n  {}\nGlobal:\n  {}\n",
+        project_dir.to_string_lossy(),
+        local_path.to_string_lossy(),
+        project_path.to_string_lossy(),
+        global_path.to_string_lossy()
+    ));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", &home_dir);
+    cmd.args(["-f", "shared.yamis.yml", "--list"]);
+    cmd.assert().success().stdout(format!(
+        "Given with -f:\n  shared.yamis.yml\nDiscovered from {}:\n  No config files found\nGlobal:\n  {}\n",
+        tmp_dir.path().to_string_lossy(),
+        global_path.to_string_lossy()
+    ));
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_snapshot_env_and_replay() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(tmp_dir.join(".env"), "GREETING=hello\n")?;
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "env_file: .env\ntasks:\n  greet:\n    script: echo \"$GREETING $SNAPSHOT_VAR {$1}\"\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("SNAPSHOT_VAR", "before");
+    cmd.args(["--snapshot-env", "snapshot.json", "greet", "world"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello before world"));
+
+    // The replay uses the same environment, even if the env file and variables changed
+    std::fs::write(tmp_dir.join(".env"), "GREETING=bye\n")?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("SNAPSHOT_VAR", "after");
+    cmd.args(["replay", "snapshot.json"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello before world"))
+        .stderr(predicate::str::contains("changed since the snapshot").not());
+
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "tasks:\n  greet:\n    script: echo changed\n",
+    )?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["replay", "snapshot.json"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello before world"))
+        .stderr(predicate::str::contains(
+            "project.yamis.yml changed since the snapshot was taken",
+        ));
+    Ok(())
+}
+
+#[test]
+fn test_builtin_commands() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  wait:
+    program: yamis::sleep
+    args: ["0.1"]
+  greet:
+    program: yamis::echo
+    args: [hello, "{$1}"]
+  boom:
+    program: yamis::fail
+    args: [not ready]
+  all:
+    serial: [wait, greet, boom, greet]
+  unknown:
+    program: yamis::other
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["all", "world"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "hello world\n[YAMIS] Task: `boom`\n",
+        ))
+        .stdout(predicate::str::ends_with("[YAMIS] Task: `boom`\n"))
+        .stderr(predicate::str::contains("not ready"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.arg("unknown");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Unknown built-in command `yamis::other`",
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_chained_tasks() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  greet:
+    program: yamis::echo
+    args: ["hello", "{$@?}"]
+  boom:
+    program: yamis::fail
+    args: [not ready]
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["greet", "a", "&&", "greet", "b", "||", "greet", "c"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello a\n"))
+        .stdout(predicate::str::contains("hello b\n"))
+        .stdout(predicate::str::contains("hello c").not());
+
+    // The failure is reported, and the task after `||` runs instead of the one after `&&`
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["boom", "&&", "greet", "a", "||", "greet", "b"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello a").not())
+        .stdout(predicate::str::contains("hello b\n"))
+        .stderr(predicate::str::contains("not ready"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["greet", "&&", "boom", "&&", "greet", "a"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("hello a").not())
+        .stderr(predicate::str::contains("not ready"));
+
+    // Operators after `--` are passed as arguments
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["run", "greet", "--", "&&", "greet", "&&", "greet", "b"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello && greet && greet b\n"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.args(["greet", "&&"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("No task was given after `&&`."));
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_plugins() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp_dir = TempDir::new().unwrap();
+    let bin_dir = tmp_dir.join("bin");
+    std::fs::create_dir(&bin_dir)?;
+    let plugin_path = bin_dir.join("yamis-greeter");
+    std::fs::write(
+        &plugin_path,
+        r#"#!/bin/sh
+if [ "$1" = "--yamis-function" ]; then
+    read request
+    case "$request" in
+        *'"function":"greet"'*) echo '{"result": ["hello", "plugin"]}' ;;
+        *) echo '{"error": "unknown function"}' ;;
+    esac
+    exit 0
+fi
+echo "greeter args: $*"
+"#,
+    )?;
+    std::fs::set_permissions(&plugin_path, std::fs::Permissions::from_mode(0o755))?;
+    let path_var = std::env::join_paths(
+        std::iter::once(bin_dir.to_path_buf())
+            .chain(std::env::split_paths(&std::env::var_os("PATH").unwrap())),
+    )?;
+
+    let project_dir = tmp_dir.join("project");
+    std::fs::create_dir(&project_dir)?;
+    std::fs::write(
+        project_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  hello:
+    program: yamis::echo
+    args: ["{plugin('greeter', 'greet', $@?)}"]
+  broken:
+    program: yamis::echo
+    args: ["{plugin('greeter', 'other')}"]
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path()).env("PATH", &path_var);
+    cmd.args(["greeter", "a", "--b"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("greeter args: a --b"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path()).env("PATH", &path_var);
+    cmd.args(["hello", "world"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello plugin\n"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path()).env("PATH", &path_var);
+    cmd.arg("broken");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown function"));
+
+    // Tasks take precedence over plugins
+    std::fs::write(
+        project_dir.join("project.yamis.yml"),
+        "tasks:\n  greeter:\n    program: yamis::echo\n    args: [task]\n",
+    )?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path()).env("PATH", &path_var);
+    cmd.arg("greeter");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("task\n"))
+        .stdout(predicate::str::contains("greeter args").not());
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_export_script() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::create_dir(tmp_dir.join("sub"))?;
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  greet:
+    wd: sub
+    env:
+      GREETING: Hello
+    program: sh
+    args: [-c, 'echo "$GREETING $0"; basename "$PWD"', "{$1}"]
+  script:
+    script: echo from script
+    script_runner: sh
+    script_ext: sh
+  failing:
+    program: yamis::fail
+    args: [not ready]
+  all:
+    serial: [greet, script, failing, greet]
+"#,
+    )?;
+    let script_path = tmp_dir.join("run.sh");
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["export-script", "all", "-o", "run.sh", "--", "World"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Exported task all to run.sh"));
+
+    // The script does not need yamis, and stops at the first failure
+    let output = std::process::Command::new(&script_path)
+        .current_dir(tmp_dir.path())
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "Hello World\nsub\nfrom script\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        "not ready\nTask failing failed with exit code 1\n"
+    );
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["export-script", "missing", "-o", "run.sh"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Task missing not found"));
+    Ok(())
+}
+
+#[test]
+fn test_strict_args() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        tmp_dir.join("project.yamis.yml"),
+        "tasks:\n  build:\n    strict_args: true\n    program: yamis::echo\n    args: [building, \"{release?}\"]\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["build", "--release=yes"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("building yes\n"));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(tmp_dir.path());
+    cmd.env("HOME", tmp_dir.path());
+    cmd.args(["build", "--relase=yes"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("building").not())
+        .stderr(predicate::str::contains("Unused arguments: `--relase=yes`"));
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_read_only_sandbox() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp_dir = TempDir::new().unwrap();
+    let project_dir = tmp_dir.join("project");
+    let bin_dir = tmp_dir.join("bin");
+    std::fs::create_dir(&project_dir)?;
+    std::fs::create_dir(&bin_dir)?;
+    std::fs::write(
+        project_dir.join("project.yamis.yml"),
+        "tasks:\n  lint:\n    sandbox: read_only\n    program: /bin/sh\n    args: [-c, echo linting]\n",
+    )?;
+    // Fakes bubblewrap, printing its arguments and running the command
+    let bwrap = bin_dir.join("bwrap");
+    std::fs::write(
+        &bwrap,
+        "#!/bin/sh\necho \"bwrap $*\"\nwhile [ \"$1\" != \"--\" ]; do shift; done\nshift\nexec \"$@\"\n",
+    )?;
+    std::fs::set_permissions(&bwrap, std::fs::Permissions::from_mode(0o755))?;
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("PATH", &bin_dir);
+    cmd.arg("lint");
+    let ro_bind = format!(
+        "--ro-bind {} {}",
+        project_dir.to_string_lossy(),
+        project_dir.to_string_lossy()
+    );
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(ro_bind))
+        .stdout(predicate::str::ends_with("linting\n"));
+
+    // Runs without the sandbox if bubblewrap is not available
+    std::fs::remove_file(&bwrap)?;
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("PATH", &bin_dir);
+    cmd.arg("lint");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("bwrap").not())
+        .stdout(predicate::str::ends_with("linting\n"))
+        .stderr(predicate::str::contains(
+            "Task lint runs without the read_only sandbox, bubblewrap (bwrap) was not found in the PATH",
+        ));
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_status_file() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new().unwrap();
+    let project_dir = tmp_dir.join("project");
+    std::fs::create_dir(&project_dir)?;
+    std::fs::write(
+        project_dir.join("project.yamis.yml"),
+        r#"
+tasks:
+  first:
+    program: yamis::echo
+    args: [first]
+  show:
+    program: /bin/sh
+    args: [-c, 'cat "$0/status.json"', "{dirs.state}"]
+  all:
+    serial: [first, show]
+  where:
+    program: yamis::echo
+    args: ["{dirs.state}"]
+"#,
+    )?;
+
+    // The status is updated while the task runs
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("all");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#""task": "all""#))
+        .stdout(predicate::str::contains(r#""current_step": {"#))
+        .stdout(predicate::str::contains(r#""exit_code": 0"#))
+        .stdout(predicate::str::contains(r#""finished_at": null"#));
+
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path());
+    cmd.arg("where");
+    let output = cmd.output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    let state_dir = stdout.lines().last().unwrap();
+    let status = std::fs::read_to_string(std::path::Path::new(state_dir).join("status.json"))?;
+    // Finalized once the run ends
+    assert!(status.contains(r#""task": "where""#));
+    assert!(status.contains(r#""success": true"#));
+    assert!(status.contains(r#""current_step": null"#));
+    assert!(!status.contains(r#""finished_at": null"#));
+
+    // Nested invocations leave the status of the outermost run alone
+    let mut cmd = Command::cargo_bin("yamis")?;
+    cmd.current_dir(&project_dir);
+    cmd.env("HOME", tmp_dir.path());
+    cmd.env("YAMIS_DEPTH", "1");
+    cmd.arg("first");
+    cmd.assert().success();
+    let nested_status =
+        std::fs::read_to_string(std::path::Path::new(state_dir).join("status.json"))?;
+    assert_eq!(nested_status, status);
+    Ok(())
+}